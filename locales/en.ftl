@@ -0,0 +1,69 @@
+# English messages sent by Mostro to users, `{ $name }` is replaced by the
+# value of the argument `name`
+cant-do = You can't do that!
+maintenance-mode = Mostro is in maintenance, new orders can't be published or taken for now, orders in progress go on
+purge-user-busy = The data of this user can't be purged yet, it has { $open_items } orders in progress or payouts pending
+order-expired = Your order has expired without being taken and it was removed
+escrow-canceled = The hold invoice of this order was canceled outside Mostro, the order was canceled as it has no escrow anymore and no sats were moved
+cooperative-cancel-expired = The cooperative cancel request has expired, the order goes on
+hold-invoice-not-paid-canceled = The seller didn't pay the hold invoice on time, the order was canceled
+hold-invoice-not-paid-republished = The seller didn't pay the hold invoice on time, the order was published again
+release-reminder = Reminder #{ $reminder }: the buyer reported the fiat sent { $elapsed } minutes ago, please check it and release the sats. A dispute will be opened in { $minutes } minutes
+release-reminder-no-dispute = Reminder #{ $reminder }: the buyer reported the fiat sent { $elapsed } minutes ago, please check it and release the sats or open a dispute
+release-final-warning = Final warning: if you don't release the sats or open a dispute in { $minutes } minutes a dispute will be opened automatically
+stalled-reminder = Reminder #{ $reminder }: the order is { $status } for { $elapsed } minutes and waiting for you
+stalled-final-warning = Final warning: the order is { $status } and waiting for you, Mostro will step in in { $minutes } minutes
+stalled-order-alert = Order { $order_id } is { $status } for { $elapsed } minutes and needs attention
+inconsistent-order-alert = Order { $order_id } was found { $status } with { $reason } after a restart and needs attention
+maintenance-failed-alert = Database { $task } failed: { $error }
+daily-summary = Daily summary: { $new_orders } new orders, { $trades } trades completed for { $volume } sats, { $fees } sats earned in fees, { $disputes } disputes pending and { $failed_payouts } payouts failed. { $liquidity }
+node-liquidity = Node: { $active } channels active and { $inactive } inactive, { $outbound } sats to send and { $inbound } sats to receive
+node-unreachable = Node: LND could not be reached, { $error }
+status-forced = An admin moved this order to { $status }: { $reason }
+routing-fee-shortfall = We couldn't pay your invoice, routing the payment costs more than our fee limit. Send a new invoice for { $amount } sats and the difference will pay for the routing, or wait and we will try again every { $minutes } minutes
+price-not-available = We couldn't get the market price for this currency, try again later
+market-order-invoice-amount = Market price orders need an invoice without amount
+market-quote-sats = This order is denominated in sats: { $amount } sats are { $fiat_amount } { $fiat_code } at the market price
+market-quote-fiat = This order is denominated in fiat: { $fiat_amount } { $fiat_code } are { $amount } sats at the market price
+sats-conversion = { $fiat_amount } { $fiat_code } at { $price } { $fiat_code }/BTC with a { $premium }% premium are { $exact } sats, rounded ({ $rounding }) to a multiple of { $granularity } sats: { $amount } sats
+invalid-amounts = Orders need a sats amount, a fiat amount or both
+buyer-fee-breakdown = Order amount: { $amount } sats - Mostro fee: { $fee } sats - You receive: { $total } sats
+no-limit = no limit
+order-out-of-limits = Order out of limits - Amount: min { $min_amount } max { $max_amount } sats - Fiat amount: min { $min_fiat_amount } max { $max_fiat_amount } { $fiat_code }
+new-user-limit = Users with less than { $trades } completed trades can only trade up to { $max } sats for now, the limit grows with every completed trade
+daily-volume-limit = Users can trade up to { $limit } sats every 24 hours, this order would go over the limit
+bond-invoice-description = { $mostro } - Buyer bond Order #{ $order_id }: { $amount } sats - It WILL FREEZE IN WALLET. It will return once the trade is over. It will be lost if you abandon the trade
+bond-compensation = Your counterpart abandoned the trade and lost the bond, send me an invoice of { $amount } sats to receive it as compensation
+dispute-fee-bond-refund = You lost the dispute and a fee of { $fee } sats was taken from your bond, send me an invoice of { $amount } sats to get the rest back
+invalid-split = The buyer percentage must be between 1 and 100
+dispute-taken = A solver is looking into your dispute, tag your messages with the dispute id to talk to the solver
+dispute-assigned-to-other = This dispute is being handled by another solver
+dispute-split = The dispute was resolved with a partial settlement, send me an invoice of { $amount } sats to receive your part
+dispute-won-fee = The dispute was resolved in your favor, the dispute fee of { $fee } sats charged to the seller was taken from the escrow, send me an invoice of { $amount } sats to receive your sats
+payout-paid = Payment of { $amount } sats sent
+split-payout-disabled = Payouts can't be split across several invoices
+invalid-split-payout = Only payouts of at least { $min } sats can be split, in up to { $max } invoices
+split-payout-amount = Every invoice must have an amount and together they must add up to { $amount } sats
+payout-part-failed = The payment of part { $part } of your payout, { $amount } sats, failed and the rest was put on hold, please contact the admins
+invalid-recurring = A recurring order can be published again from 1 to { $max } times
+cant-edit-order-identity = The kind and the currency of an order can't be edited
+release-confirmation = Send Release again within { $minutes } minutes to confirm, the sats will be sent to the buyer and this can't be undone
+buyer-invoice-not-received-canceled = The buyer didn't send an invoice in time, the order was canceled and the hold invoice returned to the seller
+buyer-invoice-not-received-republished = The buyer didn't send an invoice in time, the hold invoice was returned to the seller and the order published again
+cant-take-own-order = You can't take your own order
+unsupported-payment-method = Payment method { $payment_method } is not accepted for this currency, use one or more of: { $methods }
+blocked-payment-method = This Mostro doesn't accept { $payment_method }, its operator doesn't allow: { $methods }
+invalid-region = Region { $region } is not valid, use an ISO 3166 country code optionally followed by /<city>
+invalid-trade-index = Trade index { $index } is not valid, it must be a number higher than the last index you used
+invalid-description = The order description can't be empty or longer than { $max } characters
+user-banned = You are banned from this Mostro
+invalid-rating = Rating must be between { $min } and { $max }
+already-rated = You already rated this trade
+counterpart-reputation = Counterpart reputation: { $rating }/5 from { $total } ratings - { $trades } trades completed ({ $completion }% of started) - releases in { $release_minutes } minutes on average - { $days } days active
+unsupported-currency = Currency { $fiat_code } is not supported, supported currencies: { $currencies }
+blocked-currency = This Mostro doesn't accept orders in { $fiat_code }, its operator doesn't allow: { $currencies }
+invalid-range = Range orders must have a minimum lower than the maximum and a market price amount
+out-of-range-amount = The amount must be between { $min } and { $max } { $fiat_code }
+hold-invoice-description = { $mostro } - Escrow Order #{ $order_id }: SELL { $amount } sats for { $fiat_code } { $fiat_amount } - Premium: { $premium }% - Mostro fee: { $fee } sats - You pay: { $total } sats - It WILL FREEZE IN WALLET. It will release once you release. It will return if buyer does not confirm the payment
+wrong-status = The order is { $status }, you can only: { $actions }
+no-actions = nothing, the order is closed
@@ -0,0 +1,69 @@
+# Mensajes en español enviados por Mostro a los usuarios, `{ $name }` se
+# reemplaza por el valor del argumento `name`
+cant-do = ¡No puedes hacer eso!
+maintenance-mode = Mostro está en mantenimiento, por ahora no se pueden publicar ni tomar órdenes, las órdenes en curso continúan
+purge-user-busy = Los datos de este usuario no se pueden borrar todavía, tiene { $open_items } órdenes en curso o pagos pendientes
+order-expired = Tu orden expiró sin ser tomada y fue eliminada
+escrow-canceled = La factura retenida de esta orden fue cancelada fuera de Mostro, la orden fue cancelada porque ya no tiene garantía y no se movieron sats
+cooperative-cancel-expired = La solicitud de cancelación cooperativa expiró, la orden continúa
+hold-invoice-not-paid-canceled = El vendedor no pagó la hold invoice a tiempo, la orden fue cancelada
+hold-invoice-not-paid-republished = El vendedor no pagó la hold invoice a tiempo, la orden fue publicada de nuevo
+release-reminder = Recordatorio #{ $reminder }: el comprador reportó el envío del fiat hace { $elapsed } minutos, por favor verifícalo y libera los sats. Se abrirá una disputa en { $minutes } minutos
+release-reminder-no-dispute = Recordatorio #{ $reminder }: el comprador reportó el envío del fiat hace { $elapsed } minutos, por favor verifícalo y libera los sats o abre una disputa
+release-final-warning = Último aviso: si no liberas los sats o abres una disputa en { $minutes } minutos se abrirá una disputa automáticamente
+stalled-reminder = Recordatorio #{ $reminder }: la orden está en { $status } hace { $elapsed } minutos y te está esperando
+stalled-final-warning = Último aviso: la orden está en { $status } y te está esperando, Mostro intervendrá en { $minutes } minutos
+stalled-order-alert = La orden { $order_id } está en { $status } hace { $elapsed } minutos y necesita atención
+inconsistent-order-alert = La orden { $order_id } quedó en { $status } con { $reason } después de un reinicio y necesita atención
+maintenance-failed-alert = Falló { $task } de la base de datos: { $error }
+daily-summary = Resumen diario: { $new_orders } órdenes nuevas, { $trades } intercambios completados por { $volume } sats, { $fees } sats ganados en comisiones, { $disputes } disputas pendientes y { $failed_payouts } pagos fallidos. { $liquidity }
+node-liquidity = Nodo: { $active } canales activos y { $inactive } inactivos, { $outbound } sats para enviar y { $inbound } sats para recibir
+node-unreachable = Nodo: no se pudo conectar con LND, { $error }
+status-forced = Un administrador movió esta orden a { $status }: { $reason }
+routing-fee-shortfall = No pudimos pagar tu factura, enrutar el pago cuesta más que nuestro límite de comisión. Envía una nueva factura por { $amount } sats y la diferencia pagará el enrutamiento, o espera y lo intentaremos de nuevo cada { $minutes } minutos
+price-not-available = No pudimos obtener el precio de mercado para esta moneda, intenta más tarde
+market-order-invoice-amount = Las órdenes a precio de mercado necesitan una factura sin monto
+market-quote-sats = Esta orden está denominada en sats: { $amount } sats son { $fiat_amount } { $fiat_code } al precio de mercado
+market-quote-fiat = Esta orden está denominada en fiat: { $fiat_amount } { $fiat_code } son { $amount } sats al precio de mercado
+sats-conversion = { $fiat_amount } { $fiat_code } a { $price } { $fiat_code }/BTC con una prima de { $premium }% son { $exact } sats, redondeados ({ $rounding }) a un múltiplo de { $granularity } sats: { $amount } sats
+invalid-amounts = Las órdenes necesitan un monto en sats, un monto fiat o ambos
+buyer-fee-breakdown = Monto de la orden: { $amount } sats - Comisión de Mostro: { $fee } sats - Recibes: { $total } sats
+no-limit = sin límite
+order-out-of-limits = Orden fuera de los límites - Monto: mín { $min_amount } máx { $max_amount } sats - Monto fiat: mín { $min_fiat_amount } máx { $max_fiat_amount } { $fiat_code }
+new-user-limit = Los usuarios con menos de { $trades } intercambios completados solo pueden intercambiar hasta { $max } sats por ahora, el límite crece con cada intercambio completado
+daily-volume-limit = Los usuarios pueden intercambiar hasta { $limit } sats cada 24 horas, esta orden superaría el límite
+bond-invoice-description = { $mostro } - Fianza del comprador Orden #{ $order_id }: { $amount } sats - SE CONGELARÁ EN TU BILLETERA. Se devolverá cuando termine el intercambio. Se perderá si abandonas el intercambio
+bond-compensation = Tu contraparte abandonó el intercambio y perdió la fianza, envíame una factura de { $amount } sats para recibirla como compensación
+dispute-fee-bond-refund = Perdiste la disputa y se tomó una comisión de { $fee } sats de tu fianza, envíame una factura de { $amount } sats para recuperar el resto
+invalid-split = El porcentaje del comprador debe estar entre 1 y 100
+dispute-taken = Un solver está revisando tu disputa, etiqueta tus mensajes con el id de la disputa para comunicarte con el solver
+dispute-assigned-to-other = Otro solver está atendiendo esta disputa
+dispute-split = La disputa se resolvió con un acuerdo parcial, envíame una factura de { $amount } sats para recibir tu parte
+dispute-won-fee = La disputa se resolvió a tu favor, la comisión de disputa de { $fee } sats cobrada al vendedor se tomó del depósito, envíame una factura de { $amount } sats para recibir tus sats
+payout-paid = Pago de { $amount } sats enviado
+split-payout-disabled = Los pagos no se pueden dividir en varias facturas
+invalid-split-payout = Solo los pagos de al menos { $min } sats se pueden dividir, en hasta { $max } facturas
+split-payout-amount = Cada factura debe tener monto y entre todas deben sumar { $amount } sats
+payout-part-failed = El pago de la parte { $part } de tu pago, { $amount } sats, falló y el resto quedó en espera, por favor contacta a los administradores
+invalid-recurring = Una orden recurrente puede publicarse de nuevo de 1 a { $max } veces
+cant-edit-order-identity = El tipo y la moneda de una orden no se pueden editar
+release-confirmation = Envía Release de nuevo en los próximos { $minutes } minutos para confirmar, los sats se enviarán al comprador y no se puede deshacer
+buyer-invoice-not-received-canceled = El comprador no envió una factura a tiempo, la orden fue cancelada y la hold invoice devuelta al vendedor
+buyer-invoice-not-received-republished = El comprador no envió una factura a tiempo, la hold invoice fue devuelta al vendedor y la orden publicada de nuevo
+cant-take-own-order = No puedes tomar tu propia orden
+unsupported-payment-method = El método de pago { $payment_method } no se acepta para esta moneda, usa uno o más de: { $methods }
+blocked-payment-method = Este Mostro no acepta { $payment_method }, su operador no permite: { $methods }
+invalid-region = La región { $region } no es válida, usa un código de país ISO 3166 seguido opcionalmente de /<ciudad>
+invalid-trade-index = El índice de intercambio { $index } no es válido, debe ser un número mayor que el último índice que usaste
+invalid-description = La descripción de la orden no puede estar vacía ni tener más de { $max } caracteres
+user-banned = Estás bloqueado en este Mostro
+invalid-rating = La calificación debe estar entre { $min } y { $max }
+already-rated = Ya calificaste este intercambio
+counterpart-reputation = Reputación de tu contraparte: { $rating }/5 de { $total } calificaciones - { $trades } intercambios completados ({ $completion }% de los iniciados) - libera en { $release_minutes } minutos en promedio - { $days } días activo
+unsupported-currency = La moneda { $fiat_code } no está soportada, monedas soportadas: { $currencies }
+blocked-currency = Este Mostro no acepta órdenes en { $fiat_code }, su operador no permite: { $currencies }
+invalid-range = Las órdenes con rango deben tener un mínimo menor que el máximo y monto a precio de mercado
+out-of-range-amount = El monto debe estar entre { $min } y { $max } { $fiat_code }
+hold-invoice-description = { $mostro } - Garantía Orden #{ $order_id }: VENTA de { $amount } sats por { $fiat_code } { $fiat_amount } - Prima: { $premium }% - Comisión de Mostro: { $fee } sats - Pagas: { $total } sats - SE CONGELARÁ EN TU BILLETERA. Se liberará cuando liberes. Se devolverá si el comprador no confirma el pago
+wrong-status = La orden está en { $status }, solo puedes: { $actions }
+no-actions = nada, la orden está cerrada
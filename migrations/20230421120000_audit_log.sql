@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS audit_log (
+  id integer primary key autoincrement,
+  pubkey char(64) not null,
+  action varchar(50) not null,
+  order_id varchar(36),
+  details text,
+  created_at integer not null
+);
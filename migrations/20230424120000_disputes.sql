@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS disputes (
+  id varchar(36) primary key not null,
+  order_id varchar(36) not null,
+  status varchar(10) not null,
+  initiator_pubkey char(64) not null,
+  created_at integer not null
+);
+
+CREATE TABLE IF NOT EXISTS dispute_messages (
+  id integer primary key autoincrement,
+  dispute_id varchar(36) not null,
+  sender_pubkey char(64) not null,
+  content text not null,
+  created_at integer not null
+);
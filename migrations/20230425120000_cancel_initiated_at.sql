@@ -0,0 +1 @@
+ALTER TABLE orders ADD COLUMN cancel_initiated_at integer default 0;
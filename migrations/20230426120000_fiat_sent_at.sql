@@ -0,0 +1,2 @@
+ALTER TABLE orders ADD COLUMN fiat_sent_at integer default 0;
+ALTER TABLE orders ADD COLUMN fiat_sent_reminders integer not null default 0;
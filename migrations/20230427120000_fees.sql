@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS fees (
+  id integer primary key autoincrement,
+  order_id char(36) not null,
+  amount integer not null,
+  paid_by varchar(10) not null,
+  created_at integer not null
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS ratings (
+  id integer primary key autoincrement,
+  order_id char(36) not null,
+  rater_pubkey char(64) not null,
+  rated_pubkey char(64) not null,
+  rating integer not null,
+  created_at integer not null,
+  UNIQUE(order_id, rater_pubkey)
+);
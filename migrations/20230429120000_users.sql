@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS users (
+  pubkey char(64) primary key not null,
+  trades_completed integer not null default 0,
+  volume integer not null default 0,
+  disputes_opened integer not null default 0,
+  disputes_lost integer not null default 0,
+  is_banned integer not null default 0,
+  created_at integer not null
+);
@@ -0,0 +1 @@
+ALTER TABLE orders ADD COLUMN release_requested_at integer not null default 0;
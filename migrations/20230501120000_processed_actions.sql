@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS processed_actions (
+  event_id char(64) primary key not null,
+  order_id char(36),
+  action varchar(50) not null,
+  pubkey char(64) not null,
+  created_at integer not null
+);
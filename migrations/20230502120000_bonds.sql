@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS bonds (
+  id char(36) primary key not null,
+  order_id char(36) not null,
+  pubkey char(64) not null,
+  amount integer not null,
+  hash char(64) not null,
+  preimage char(64) not null,
+  status varchar(10) not null,
+  created_at integer not null
+);
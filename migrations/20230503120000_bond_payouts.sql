@@ -0,0 +1,2 @@
+ALTER TABLE bonds ADD COLUMN payee_pubkey char(64);
+ALTER TABLE bonds ADD COLUMN payout_amount integer not null default 0;
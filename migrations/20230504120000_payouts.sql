@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS payouts (
+  id char(36) primary key not null,
+  order_id char(36) not null,
+  pubkey char(64) not null,
+  amount integer not null,
+  status varchar(10) not null,
+  created_at integer not null
+);
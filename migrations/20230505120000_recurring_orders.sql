@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS recurring_orders (
+  order_id char(36) primary key not null,
+  remaining integer not null,
+  max_amount integer not null default 0,
+  created_at integer not null
+);
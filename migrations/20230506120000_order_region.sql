@@ -0,0 +1 @@
+ALTER TABLE orders ADD COLUMN region varchar(64);
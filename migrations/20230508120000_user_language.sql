@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN language varchar(5) not null default 'en';
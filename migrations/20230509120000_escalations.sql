@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS escalations (
+  order_id char(36) not null,
+  status varchar(20) not null,
+  started_at integer not null,
+  reminders integer not null default 0,
+  escalated integer not null default 0,
+  primary key (order_id, status)
+);
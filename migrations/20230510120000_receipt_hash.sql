@@ -0,0 +1 @@
+ALTER TABLE orders ADD COLUMN receipt_hash char(64);
@@ -0,0 +1,2 @@
+ALTER TABLE orders ADD COLUMN denomination varchar(5);
+UPDATE orders SET denomination = 'fiat' WHERE price_from_api = 1;
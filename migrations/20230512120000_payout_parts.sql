@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS payout_parts (
+  id char(36) primary key not null,
+  order_id char(36) not null,
+  part integer not null,
+  payment_request text not null,
+  amount integer not null,
+  status varchar(10) not null,
+  payment_hash char(64),
+  created_at integer not null
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS trade_indexes (
+  order_id char(36) not null,
+  pubkey char(64) not null,
+  trade_index integer not null,
+  created_at integer not null,
+  primary key (pubkey, trade_index)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS order_messages (
+  id integer primary key autoincrement,
+  order_id varchar(36) not null,
+  pubkey char(64) not null,
+  action varchar(50) not null,
+  direction varchar(3) not null,
+  payload_hash char(64) not null,
+  created_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS order_messages_order_id ON order_messages (order_id);
@@ -0,0 +1,3 @@
+ALTER TABLE orders ADD COLUMN payment_failed_at integer not null default 0;
+ALTER TABLE orders ADD COLUMN payment_attempts integer not null default 0;
+ALTER TABLE orders ADD COLUMN routing_compensation integer not null default 0;
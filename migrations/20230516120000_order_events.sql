@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS order_events (
+  id integer primary key autoincrement,
+  order_id varchar(36) not null,
+  from_status varchar(20),
+  to_status varchar(20) not null,
+  trigger varchar(10) not null,
+  created_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS order_events_order_id ON order_events (order_id);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS payments (
+  id integer primary key autoincrement,
+  order_id varchar(36) not null,
+  pubkey char(64) not null,
+  payment_hash char(64) not null,
+  amount integer not null,
+  fee_paid integer not null default 0,
+  status varchar(10) not null,
+  failure_reason varchar(50),
+  created_at integer not null,
+  updated_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS payments_order_id ON payments (order_id);
+CREATE INDEX IF NOT EXISTS payments_payment_hash ON payments (payment_hash);
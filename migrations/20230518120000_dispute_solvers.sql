@@ -0,0 +1,4 @@
+ALTER TABLE disputes ADD COLUMN solver_pubkey char(64);
+ALTER TABLE disputes ADD COLUMN resolution text;
+ALTER TABLE disputes ADD COLUMN taken_at integer not null default 0;
+ALTER TABLE disputes ADD COLUMN resolved_at integer not null default 0;
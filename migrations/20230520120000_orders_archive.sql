@@ -0,0 +1,11 @@
+-- Same columns as orders, any column added to orders must be added here too
+CREATE TABLE IF NOT EXISTS orders_archive AS SELECT * FROM orders WHERE 0;
+
+CREATE INDEX IF NOT EXISTS orders_archive_id ON orders_archive (id);
+CREATE INDEX IF NOT EXISTS orders_archive_buyer_pubkey ON orders_archive (buyer_pubkey);
+CREATE INDEX IF NOT EXISTS orders_archive_seller_pubkey ON orders_archive (seller_pubkey);
+
+CREATE VIEW IF NOT EXISTS all_orders AS
+  SELECT * FROM orders
+  UNION ALL
+  SELECT * FROM orders_archive;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS outbox (
+  id integer primary key autoincrement,
+  event_id char(64) not null,
+  receiver_pubkey char(64) not null,
+  event text not null,
+  status varchar(10) not null,
+  attempts integer not null default 0,
+  last_error text,
+  created_at integer not null,
+  updated_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS outbox_status ON outbox (status);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS fees_created_at;
+ALTER TABLE fees DROP COLUMN fiat_amount;
+ALTER TABLE fees DROP COLUMN fiat_code;
@@ -0,0 +1,13 @@
+ALTER TABLE fees ADD COLUMN fiat_code varchar(5);
+ALTER TABLE fees ADD COLUMN fiat_amount real;
+
+-- Fees recorded before the ledger take the currency of their order
+UPDATE fees
+SET fiat_code = (SELECT fiat_code FROM all_orders WHERE all_orders.id = fees.order_id),
+fiat_amount = (
+  SELECT fees.amount * all_orders.fiat_amount * 1.0 / all_orders.amount
+  FROM all_orders
+  WHERE all_orders.id = fees.order_id AND all_orders.amount > 0
+);
+
+CREATE INDEX IF NOT EXISTS fees_created_at ON fees (created_at);
@@ -0,0 +1,5 @@
+DROP VIEW IF EXISTS user_reputations;
+DROP TRIGGER IF EXISTS ratings_aggregate;
+DROP INDEX IF EXISTS ratings_rated_pubkey;
+ALTER TABLE users DROP COLUMN rating_sum;
+ALTER TABLE users DROP COLUMN total_ratings;
@@ -0,0 +1,32 @@
+ALTER TABLE users ADD COLUMN total_ratings integer not null default 0;
+ALTER TABLE users ADD COLUMN rating_sum integer not null default 0;
+
+CREATE INDEX IF NOT EXISTS ratings_rated_pubkey ON ratings (rated_pubkey);
+
+-- Users rated before the aggregates existed
+INSERT OR IGNORE INTO users (pubkey, created_at)
+SELECT rated_pubkey, MIN(created_at) FROM ratings GROUP BY rated_pubkey;
+
+UPDATE users
+SET total_ratings = (SELECT COUNT(*) FROM ratings WHERE rated_pubkey = users.pubkey),
+rating_sum = (SELECT COALESCE(SUM(rating), 0) FROM ratings WHERE rated_pubkey = users.pubkey);
+
+-- Every rating saved updates the aggregates of the rated user
+CREATE TRIGGER IF NOT EXISTS ratings_aggregate AFTER INSERT ON ratings
+BEGIN
+  INSERT OR IGNORE INTO users (pubkey, created_at) VALUES (NEW.rated_pubkey, NEW.created_at);
+  UPDATE users
+  SET total_ratings = total_ratings + 1,
+  rating_sum = rating_sum + NEW.rating
+  WHERE pubkey = NEW.rated_pubkey;
+END;
+
+-- Reputation of a user as it is published and shown to counterparts
+CREATE VIEW IF NOT EXISTS user_reputations AS
+SELECT
+  pubkey,
+  total_ratings,
+  CASE WHEN total_ratings > 0 THEN rating_sum * 1.0 / total_ratings ELSE 0.0 END AS average_rating,
+  (CAST(strftime('%s', 'now') AS integer) - created_at) / 86400 AS days_active,
+  trades_completed
+FROM users;
@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS orders_search_update;
+DROP TRIGGER IF EXISTS orders_search_insert;
+DROP TABLE IF EXISTS orders_search;
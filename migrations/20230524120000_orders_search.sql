@@ -0,0 +1,36 @@
+-- Full text index of the orders for admins, archived orders stay indexed
+CREATE VIRTUAL TABLE IF NOT EXISTS orders_search USING fts5(
+  order_id UNINDEXED,
+  description,
+  payment_method,
+  pubkeys
+);
+
+INSERT INTO orders_search (order_id, description, payment_method, pubkeys)
+SELECT id, COALESCE(description, ''), payment_method,
+  creator_pubkey || ' ' || COALESCE(buyer_pubkey, '') || ' ' || COALESCE(seller_pubkey, '')
+FROM all_orders;
+
+CREATE TRIGGER IF NOT EXISTS orders_search_insert AFTER INSERT ON orders
+BEGIN
+  INSERT INTO orders_search (order_id, description, payment_method, pubkeys)
+  VALUES (
+    NEW.id,
+    COALESCE(NEW.description, ''),
+    NEW.payment_method,
+    NEW.creator_pubkey || ' ' || COALESCE(NEW.buyer_pubkey, '') || ' ' || COALESCE(NEW.seller_pubkey, '')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS orders_search_update
+AFTER UPDATE OF description, payment_method, creator_pubkey, buyer_pubkey, seller_pubkey ON orders
+BEGIN
+  DELETE FROM orders_search WHERE order_id = OLD.id;
+  INSERT INTO orders_search (order_id, description, payment_method, pubkeys)
+  VALUES (
+    NEW.id,
+    COALESCE(NEW.description, ''),
+    NEW.payment_method,
+    NEW.creator_pubkey || ' ' || COALESCE(NEW.buyer_pubkey, '') || ' ' || COALESCE(NEW.seller_pubkey, '')
+  );
+END;
@@ -0,0 +1,10 @@
+-- The view is created again once both tables have the same columns
+DROP VIEW IF EXISTS all_orders;
+DROP TRIGGER IF EXISTS orders_version;
+ALTER TABLE orders DROP COLUMN version;
+ALTER TABLE orders_archive DROP COLUMN version;
+
+CREATE VIEW IF NOT EXISTS all_orders AS
+  SELECT * FROM orders
+  UNION ALL
+  SELECT * FROM orders_archive;
@@ -0,0 +1,10 @@
+-- Every write to an order bumps its version, status updates only go through
+-- when the version is still the one read before
+ALTER TABLE orders ADD COLUMN version integer not null default 0;
+ALTER TABLE orders_archive ADD COLUMN version integer not null default 0;
+
+CREATE TRIGGER IF NOT EXISTS orders_version AFTER UPDATE ON orders
+WHEN NEW.version = OLD.version
+BEGIN
+  UPDATE orders SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
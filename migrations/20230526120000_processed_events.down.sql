@@ -0,0 +1,13 @@
+-- The action and the order of the events were not kept
+CREATE TABLE IF NOT EXISTS processed_actions (
+  event_id char(64) primary key not null,
+  order_id char(36),
+  action varchar(50) not null,
+  pubkey char(64) not null,
+  created_at integer not null
+);
+
+INSERT OR IGNORE INTO processed_actions (event_id, action, pubkey, created_at)
+SELECT event_id, '', pubkey, created_at FROM processed_events;
+
+DROP TABLE IF EXISTS processed_events;
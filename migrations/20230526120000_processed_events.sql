@@ -0,0 +1,14 @@
+-- Ids of every handled event, not only order actions, kept for
+-- PROCESSED_EVENTS_MAX_AGE days
+CREATE TABLE IF NOT EXISTS processed_events (
+  event_id char(64) primary key not null,
+  pubkey char(64) not null,
+  created_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS processed_events_created_at ON processed_events (created_at);
+
+INSERT OR IGNORE INTO processed_events (event_id, pubkey, created_at)
+SELECT event_id, pubkey, created_at FROM processed_actions;
+
+DROP TABLE IF EXISTS processed_actions;
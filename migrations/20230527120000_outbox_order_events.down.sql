@@ -0,0 +1,4 @@
+-- Order events still in the outbox are lost, they are published again with
+-- the next change of their order
+DELETE FROM outbox WHERE kind = 'order';
+ALTER TABLE outbox DROP COLUMN kind;
@@ -0,0 +1,3 @@
+-- Order events are saved in the outbox with their new status, receiver_pubkey
+-- is empty for them
+ALTER TABLE outbox ADD COLUMN kind varchar(10) not null default 'dm';
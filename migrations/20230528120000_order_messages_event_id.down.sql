@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS order_messages_order_id_pubkey;
+ALTER TABLE order_messages DROP COLUMN event_id;
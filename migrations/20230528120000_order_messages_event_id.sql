@@ -0,0 +1,5 @@
+-- Messages point to the nostr event that carried them, the log itself keeps
+-- only the action, the parties, the direction and the time
+ALTER TABLE order_messages ADD COLUMN event_id char(64);
+
+CREATE INDEX IF NOT EXISTS order_messages_order_id_pubkey ON order_messages (order_id, pubkey);
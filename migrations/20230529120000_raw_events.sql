@@ -0,0 +1,11 @@
+-- Incoming events as received, still encrypted, kept only when
+-- RAW_EVENTS_RETENTION_DAYS is set
+CREATE TABLE IF NOT EXISTS raw_events (
+  event_id char(64) primary key not null,
+  pubkey char(64) not null,
+  event text not null,
+  received_at integer not null
+);
+
+CREATE INDEX IF NOT EXISTS raw_events_received_at ON raw_events (received_at);
+CREATE INDEX IF NOT EXISTS raw_events_pubkey ON raw_events (pubkey);
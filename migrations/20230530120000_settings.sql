@@ -0,0 +1,7 @@
+-- Settings changed by the admins at runtime, they override the environment
+CREATE TABLE IF NOT EXISTS settings (
+  name varchar(50) primary key not null,
+  value text not null,
+  updated_by char(64) not null,
+  updated_at integer not null
+);
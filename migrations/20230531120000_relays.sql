@@ -0,0 +1,11 @@
+-- Relays Mostro connects to, seeded from RELAYS on the first start and
+-- edited by the admins afterwards, with the status seen by the relay checks
+CREATE TABLE IF NOT EXISTS relays (
+  url varchar(500) primary key not null,
+  enabled integer not null default 1,
+  status varchar(20) not null default 'Unknown',
+  latency integer,
+  checked_at integer,
+  updated_by char(64) not null,
+  created_at integer not null
+);
@@ -0,0 +1,15 @@
+DROP VIEW IF EXISTS user_reputations;
+DROP TRIGGER IF EXISTS user_stats_release;
+DROP TRIGGER IF EXISTS user_stats_started;
+ALTER TABLE users DROP COLUMN release_time_sum;
+ALTER TABLE users DROP COLUMN releases;
+ALTER TABLE users DROP COLUMN trades_started;
+
+CREATE VIEW IF NOT EXISTS user_reputations AS
+SELECT
+  pubkey,
+  total_ratings,
+  CASE WHEN total_ratings > 0 THEN rating_sum * 1.0 / total_ratings ELSE 0.0 END AS average_rating,
+  (CAST(strftime('%s', 'now') AS integer) - created_at) / 86400 AS days_active,
+  trades_completed
+FROM users;
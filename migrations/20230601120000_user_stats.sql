@@ -0,0 +1,81 @@
+-- Trades of a user that got both parties committed, and the releases of its
+-- sells with the seconds from the fiat sent to the release, so the
+-- completion rate and the average release time are read without going
+-- through the orders
+ALTER TABLE users ADD COLUMN trades_started integer not null default 0;
+ALTER TABLE users ADD COLUMN releases integer not null default 0;
+ALTER TABLE users ADD COLUMN release_time_sum integer not null default 0;
+
+INSERT OR IGNORE INTO users (pubkey, created_at)
+SELECT buyer_pubkey, MIN(created_at) FROM all_orders
+WHERE buyer_pubkey IS NOT NULL AND taken_at > 0 GROUP BY buyer_pubkey;
+
+INSERT OR IGNORE INTO users (pubkey, created_at)
+SELECT seller_pubkey, MIN(created_at) FROM all_orders
+WHERE seller_pubkey IS NOT NULL AND taken_at > 0 GROUP BY seller_pubkey;
+
+UPDATE users
+SET trades_started = (
+  SELECT COUNT(DISTINCT o.id)
+  FROM order_events e JOIN all_orders o ON o.id = e.order_id
+  WHERE e.to_status = 'Active'
+  AND e.from_status IN ('WaitingPayment', 'WaitingBuyerInvoice')
+  AND users.pubkey IN (o.buyer_pubkey, o.seller_pubkey)
+),
+releases = (
+  SELECT COUNT(*)
+  FROM order_events e JOIN all_orders o ON o.id = e.order_id
+  WHERE e.from_status = 'FiatSent' AND e.to_status = 'SettledHoldInvoice'
+  AND o.fiat_sent_at > 0 AND o.seller_pubkey = users.pubkey
+),
+release_time_sum = (
+  SELECT COALESCE(SUM(e.created_at - o.fiat_sent_at), 0)
+  FROM order_events e JOIN all_orders o ON o.id = e.order_id
+  WHERE e.from_status = 'FiatSent' AND e.to_status = 'SettledHoldInvoice'
+  AND o.fiat_sent_at > 0 AND o.seller_pubkey = users.pubkey
+);
+
+-- Every status change updates the statistics of the parties of the order
+CREATE TRIGGER IF NOT EXISTS user_stats_started AFTER INSERT ON order_events
+WHEN NEW.to_status = 'Active' AND NEW.from_status IN ('WaitingPayment', 'WaitingBuyerInvoice')
+BEGIN
+  INSERT OR IGNORE INTO users (pubkey, created_at)
+  SELECT buyer_pubkey, NEW.created_at FROM orders WHERE id = NEW.order_id AND buyer_pubkey IS NOT NULL;
+  INSERT OR IGNORE INTO users (pubkey, created_at)
+  SELECT seller_pubkey, NEW.created_at FROM orders WHERE id = NEW.order_id AND seller_pubkey IS NOT NULL;
+  UPDATE users
+  SET trades_started = trades_started + 1
+  WHERE pubkey IN (
+    SELECT buyer_pubkey FROM orders WHERE id = NEW.order_id
+    UNION
+    SELECT seller_pubkey FROM orders WHERE id = NEW.order_id
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS user_stats_release AFTER INSERT ON order_events
+WHEN NEW.from_status = 'FiatSent' AND NEW.to_status = 'SettledHoldInvoice'
+BEGIN
+  UPDATE users
+  SET releases = releases + 1,
+  release_time_sum = release_time_sum + NEW.created_at - (
+    SELECT fiat_sent_at FROM orders WHERE id = NEW.order_id
+  )
+  WHERE pubkey = (
+    SELECT seller_pubkey FROM orders WHERE id = NEW.order_id AND fiat_sent_at > 0
+  );
+END;
+
+DROP VIEW IF EXISTS user_reputations;
+
+-- Reputation of a user as it is published and shown to counterparts
+CREATE VIEW IF NOT EXISTS user_reputations AS
+SELECT
+  pubkey,
+  total_ratings,
+  CASE WHEN total_ratings > 0 THEN rating_sum * 1.0 / total_ratings ELSE 0.0 END AS average_rating,
+  (CAST(strftime('%s', 'now') AS integer) - created_at) / 86400 AS days_active,
+  trades_completed,
+  volume,
+  CASE WHEN trades_started > 0 THEN MIN(trades_completed * 1.0 / trades_started, 1.0) ELSE 0.0 END AS completion_rate,
+  CASE WHEN releases > 0 THEN release_time_sum / releases ELSE 0 END AS average_release_time
+FROM users;
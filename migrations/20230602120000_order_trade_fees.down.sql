@@ -0,0 +1,11 @@
+-- The view is created again once both tables have the same columns
+DROP VIEW IF EXISTS all_orders;
+ALTER TABLE orders DROP COLUMN seller_fee;
+ALTER TABLE orders DROP COLUMN buyer_fee;
+ALTER TABLE orders_archive DROP COLUMN seller_fee;
+ALTER TABLE orders_archive DROP COLUMN buyer_fee;
+
+CREATE VIEW IF NOT EXISTS all_orders AS
+  SELECT * FROM orders
+  UNION ALL
+  SELECT * FROM orders_archive;
@@ -0,0 +1,6 @@
+-- Fee paid by each party, saved when the hold invoice is created so the
+-- payout doesn't move if MAKER_FEE_SHARE changes during the trade
+ALTER TABLE orders ADD COLUMN seller_fee integer;
+ALTER TABLE orders ADD COLUMN buyer_fee integer;
+ALTER TABLE orders_archive ADD COLUMN seller_fee integer;
+ALTER TABLE orders_archive ADD COLUMN buyer_fee integer;
@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS orders_version;
+
+CREATE TRIGGER IF NOT EXISTS orders_version AFTER UPDATE ON orders
+WHEN NEW.version = OLD.version
+BEGIN
+  UPDATE orders SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
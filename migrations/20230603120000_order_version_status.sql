@@ -0,0 +1,10 @@
+-- The version counts the status changes of an order, a handler writing other
+-- columns of the order doesn't make its own status update stale while a
+-- status changed by someone else since it read the order does
+DROP TRIGGER IF EXISTS orders_version;
+
+CREATE TRIGGER IF NOT EXISTS orders_version AFTER UPDATE OF status ON orders
+WHEN NEW.status != OLD.status AND NEW.version = OLD.version
+BEGIN
+  UPDATE orders SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
@@ -0,0 +1 @@
+-- The saved times are kept, the requests expire from them
@@ -0,0 +1,4 @@
+-- Cooperative cancel requests made before their time was saved start
+-- counting now instead of expiring on the first run
+UPDATE orders SET cancel_initiated_at = strftime('%s', 'now')
+WHERE cancel_initiator_pubkey IS NOT NULL AND cancel_initiated_at = 0;
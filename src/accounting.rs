@@ -0,0 +1,164 @@
+use crate::db;
+use crate::models::{Fee, Trade};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use sqlx::SqlitePool;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Unix time of a date filter given as a unix time or as a `YYYY-MM-DD` UTC
+/// day, the end of a range takes the whole day
+pub fn parse_date(date: &str, end: bool) -> Option<i64> {
+    if let Ok(timestamp) = date.parse::<i64>() {
+        return Some(timestamp);
+    }
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let day = if end { day.succ_opt()? } else { day };
+
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+}
+
+/// CSV field quoted when it has separators, quotes or line breaks
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+pub fn trades_csv(trades: &[Trade]) -> String {
+    let mut lines = vec![csv_line(&[
+        "order_id".to_string(),
+        "kind".to_string(),
+        "amount".to_string(),
+        "fee".to_string(),
+        "fiat_code".to_string(),
+        "fiat_amount".to_string(),
+        "payment_method".to_string(),
+        "premium".to_string(),
+        "created_at".to_string(),
+        "completed_at".to_string(),
+    ])];
+    for trade in trades {
+        lines.push(csv_line(&[
+            trade.id.to_string(),
+            trade.kind.clone(),
+            trade.amount.to_string(),
+            trade.fee.to_string(),
+            trade.fiat_code.clone(),
+            trade.fiat_amount.to_string(),
+            trade.payment_method.clone(),
+            trade.premium.to_string(),
+            trade.created_at.to_string(),
+            trade.completed_at.to_string(),
+        ]));
+    }
+
+    lines.join("\n")
+}
+
+pub fn fees_csv(fees: &[Fee]) -> String {
+    let mut lines = vec![csv_line(&[
+        "order_id".to_string(),
+        "amount".to_string(),
+        "paid_by".to_string(),
+        "fiat_code".to_string(),
+        "fiat_amount".to_string(),
+        "created_at".to_string(),
+    ])];
+    for fee in fees {
+        lines.push(csv_line(&[
+            fee.order_id.to_string(),
+            fee.amount.to_string(),
+            fee.paid_by.clone(),
+            fee.fiat_code.clone().unwrap_or_default(),
+            fee.fiat_amount
+                .map(|amount| amount.to_string())
+                .unwrap_or_default(),
+            fee.created_at.to_string(),
+        ]));
+    }
+
+    lines.join("\n")
+}
+
+/// Completed trades or fees earned between two dates as CSV or JSON lines,
+/// `args` are the format and the optional from and to dates
+pub async fn export(pool: &SqlitePool, history: &str, args: &[String]) -> Result<String> {
+    let format = args
+        .first()
+        .and_then(|format| ExportFormat::from_str(format).ok())
+        .ok_or_else(|| anyhow!("Export format must be csv or json"))?;
+    let from = match args.get(1) {
+        Some(date) => parse_date(date, false).ok_or_else(|| anyhow!("Wrong date {date}"))?,
+        None => 0,
+    };
+    let to = match args.get(2) {
+        Some(date) => parse_date(date, true).ok_or_else(|| anyhow!("Wrong date {date}"))?,
+        None => i64::MAX,
+    };
+    let export = match (history, format) {
+        ("trades", ExportFormat::Csv) => trades_csv(&db::find_trades(pool, from, to).await?),
+        ("trades", ExportFormat::Json) => json_lines(&db::find_trades(pool, from, to).await?)?,
+        (_, ExportFormat::Csv) => fees_csv(&db::find_fees(pool, from, to).await?),
+        (_, ExportFormat::Json) => json_lines(&db::find_fees(pool, from, to).await?)?,
+    };
+
+    Ok(export)
+}
+
+fn json_lines<T: serde::Serialize>(rows: &[T]) -> Result<String> {
+    let lines = rows
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, parse_date};
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(Some(1684000000), parse_date("1684000000", false));
+        assert_eq!(Some(1682899200), parse_date("2023-05-01", false));
+        // The last day of a range is included
+        assert_eq!(Some(1685577600), parse_date("2023-05-31", true));
+        assert_eq!(None, parse_date("31/05/2023", false));
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!("Sell", csv_field("Sell"));
+        assert_eq!("\"bank, cash\"", csv_field("bank, cash"));
+        assert_eq!("\"say \"\"hi\"\"\"", csv_field("say \"hi\""));
+    }
+}
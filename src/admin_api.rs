@@ -0,0 +1,420 @@
+use crate::accounting::parse_date;
+use crate::app::admin_cancel::cancel_dispute;
+use crate::app::admin_force::{force_status, ForceStatus};
+use crate::app::admin_settle::settle_dispute;
+use crate::config::settings;
+use crate::db;
+use crate::lightning::LndConnector;
+use crate::settings::var;
+use crate::state::transition;
+use crate::stats::get_stats;
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use mostro_core::{Action, Status};
+use nostr_sdk::prelude::{Client, FromBech32, Keys, ToBech32, Url, XOnlyPublicKey};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Items a list answers with when no limit is given and the most it can
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+/// Admin written in the audit log for the changes made through the API
+const API_ADMIN: &str = "admin-api";
+
+/// Requests the admin API answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    Orders,
+    Order(Uuid),
+    Disputes,
+    ForceStatus(Uuid),
+    ResolveDispute(Uuid),
+    Ban(String, bool),
+    Stats,
+}
+
+impl Route {
+    pub fn parse(method: &Method, path: &str) -> Option<Self> {
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        match (method, segments.as_slice()) {
+            (&Method::GET, ["orders"]) => Some(Self::Orders),
+            (&Method::GET, ["orders", order_id]) => Uuid::parse_str(order_id).ok().map(Self::Order),
+            (&Method::GET, ["disputes"]) => Some(Self::Disputes),
+            (&Method::POST, ["orders", order_id, "status"]) => {
+                Uuid::parse_str(order_id).ok().map(Self::ForceStatus)
+            }
+            (&Method::POST, ["disputes", order_id, "resolve"]) => {
+                Uuid::parse_str(order_id).ok().map(Self::ResolveDispute)
+            }
+            (&Method::POST, ["users", pubkey, "ban"]) => Some(Self::Ban(pubkey.to_string(), true)),
+            (&Method::DELETE, ["users", pubkey, "ban"]) => {
+                Some(Self::Ban(pubkey.to_string(), false))
+            }
+            (&Method::GET, ["stats"]) => Some(Self::Stats),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the Authorization header has the API token, compared in constant
+/// time
+pub fn is_authorized(header: Option<&str>, token: &str) -> bool {
+    let given = match header.and_then(|header| header.strip_prefix("Bearer ")) {
+        Some(given) => given.as_bytes(),
+        None => return false,
+    };
+    let token = token.as_bytes();
+
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Address and token of the admin API, ADMIN_API_ADDR and ADMIN_API_TOKEN.
+/// It is off unless both are set
+pub fn get_admin_api_config() -> Option<(SocketAddr, String)> {
+    let addr = var("ADMIN_API_ADDR").ok()?;
+    let addr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(_) => {
+            warn!("ADMIN_API_ADDR {addr} is not a valid address, the admin API is off");
+            return None;
+        }
+    };
+    match var("ADMIN_API_TOKEN") {
+        Ok(token) if !token.trim().is_empty() => Some((addr, token.trim().to_string())),
+        _ => {
+            warn!("ADMIN_API_TOKEN is not set, the admin API is off");
+            None
+        }
+    }
+}
+
+/// State the requests are handled with
+#[derive(Clone)]
+struct Api {
+    token: String,
+    pool: SqlitePool,
+    client: Client,
+    my_keys: Keys,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForceStatusBody {
+    status: String,
+    reason: String,
+}
+
+/// How a dispute is resolved, `settle` pays the buyer the `buyer_percentage`
+/// of the sats (all of them by default) and `cancel` returns them to the
+/// seller
+#[derive(Debug, Deserialize)]
+struct ResolveDisputeBody {
+    resolution: String,
+    buyer_percentage: Option<i64>,
+}
+
+fn bad_request(error: &str) -> (StatusCode, Value) {
+    (StatusCode::BAD_REQUEST, json!({ "error": error }))
+}
+
+fn not_found(error: &str) -> (StatusCode, Value) {
+    (StatusCode::NOT_FOUND, json!({ "error": error }))
+}
+
+fn conflict(error: &str) -> (StatusCode, Value) {
+    (StatusCode::CONFLICT, json!({ "error": error }))
+}
+
+/// Settle or cancel the hold invoice of an order in dispute, the API can't
+/// resolve a dispute a solver has taken
+async fn resolve(api: &Api, order_id: Uuid, body: &[u8]) -> Result<(StatusCode, Value)> {
+    let body = match serde_json::from_slice::<ResolveDisputeBody>(body) {
+        Ok(body) => body,
+        Err(_) => return Ok(bad_request("The body needs a resolution")),
+    };
+    let (action, buyer_percentage) = match (body.resolution.as_str(), body.buyer_percentage) {
+        ("settle", None) => (Action::AdminSettle, 100),
+        ("settle", Some(percentage)) if (1..=100).contains(&percentage) => {
+            (Action::AdminSettle, percentage)
+        }
+        ("settle", Some(_)) => return Ok(bad_request("The buyer percentage goes from 1 to 100")),
+        ("cancel", None) => (Action::AdminCancel, 0),
+        _ => return Ok(bad_request("The resolution is settle or cancel")),
+    };
+    let (order, version) = match db::find_order_with_version(&api.pool, order_id).await? {
+        Some(order) => order,
+        None => return Ok(not_found("Order not found")),
+    };
+    if transition(&order, &action).is_err() {
+        return Ok(conflict(&format!(
+            "An order in status {} can't be resolved",
+            order.status
+        )));
+    }
+    if let Some(solver) = db::find_open_dispute(&api.pool, order.id)
+        .await?
+        .and_then(|dispute| dispute.solver_pubkey)
+    {
+        return Ok(conflict(&format!("The dispute is taken by {solver}")));
+    }
+    let mut ln_client = LndConnector::try_connect(&settings().lightning).await?;
+    match action {
+        Action::AdminSettle => {
+            settle_dispute(
+                &api.pool,
+                &api.client,
+                &api.my_keys,
+                &mut ln_client,
+                order,
+                version,
+                buyer_percentage,
+                API_ADMIN,
+                None,
+            )
+            .await?
+        }
+        _ => {
+            cancel_dispute(
+                &api.pool,
+                &api.client,
+                &api.my_keys,
+                &mut ln_client,
+                &order,
+                version,
+                API_ADMIN,
+                None,
+            )
+            .await?
+        }
+    }
+    info!("Order Id {order_id}: dispute resolved by the admin API");
+
+    Ok((
+        StatusCode::OK,
+        json!({ "order_id": order_id, "resolution": body.resolution }),
+    ))
+}
+
+/// Limit and offset of a list, `limit` and `offset` in the query
+fn page(query: &HashMap<String, String>) -> (i64, i64) {
+    let limit = query
+        .get("limit")
+        .and_then(|limit| limit.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_LIMIT)
+        .clamp(1, MAX_LIMIT);
+    let offset = query
+        .get("offset")
+        .and_then(|offset| offset.parse::<i64>().ok())
+        .unwrap_or(0)
+        .max(0);
+
+    (limit, offset)
+}
+
+async fn answer(
+    api: &Api,
+    route: Route,
+    query: HashMap<String, String>,
+    body: &[u8],
+) -> Result<(StatusCode, Value)> {
+    let (limit, offset) = page(&query);
+    let status = query.get("status").map(|status| status.as_str());
+    Ok(match route {
+        Route::Orders => {
+            if matches!(status.map(Status::from_str), Some(Err(_))) {
+                return Ok(bad_request("Unknown status"));
+            }
+            let orders = db::find_order_details(&api.pool, status, limit, offset).await?;
+            (StatusCode::OK, json!(orders))
+        }
+        Route::Order(order_id) => match db::find_order_detail(&api.pool, order_id).await? {
+            Some(order) => {
+                let events = db::find_order_events(&api.pool, order_id).await?;
+                let dispute = db::find_open_dispute(&api.pool, order_id).await?;
+                (
+                    StatusCode::OK,
+                    json!({ "order": order, "events": events, "dispute": dispute }),
+                )
+            }
+            None => not_found("Order not found"),
+        },
+        Route::Disputes => {
+            let disputes = db::find_disputes(&api.pool, status, limit, offset).await?;
+            (StatusCode::OK, json!(disputes))
+        }
+        Route::ForceStatus(order_id) => {
+            let body = match serde_json::from_slice::<ForceStatusBody>(body) {
+                Ok(body) => body,
+                Err(_) => return Ok(bad_request("The body needs a status and a reason")),
+            };
+            let values = [order_id.to_string(), body.status, body.reason];
+            let force = match ForceStatus::parse(&values) {
+                Some(force) => force,
+                None => return Ok(bad_request("Unknown status or empty reason")),
+            };
+            match force_status(
+                &api.pool,
+                &api.client,
+                &api.my_keys,
+                API_ADMIN,
+                &force,
+                None,
+            )
+            .await?
+            {
+                true => (
+                    StatusCode::OK,
+                    json!({ "order_id": order_id, "status": force.status.to_string() }),
+                ),
+                false => not_found("Order not found"),
+            }
+        }
+        Route::ResolveDispute(order_id) => resolve(api, order_id, body).await?,
+        Route::Ban(pubkey, is_banned) => {
+            let pubkey = XOnlyPublicKey::from_bech32(&pubkey)
+                .ok()
+                .or_else(|| XOnlyPublicKey::from_str(&pubkey).ok());
+            let pubkey = match pubkey {
+                Some(pubkey) => pubkey.to_bech32()?,
+                None => return Ok(bad_request("Invalid pubkey")),
+            };
+            db::update_user_banned(&api.pool, &pubkey, is_banned).await?;
+            let action = if is_banned { "BanUser" } else { "UnbanUser" };
+            db::add_audit_log(&api.pool, API_ADMIN, action, None, &pubkey).await?;
+            info!("{action}: {pubkey} by the admin API");
+            (
+                StatusCode::OK,
+                json!(db::find_user(&api.pool, &pubkey).await?),
+            )
+        }
+        Route::Stats => {
+            let since = match query.get("since") {
+                Some(since) => parse_date(since, false),
+                None => Some(0),
+            };
+            match since {
+                Some(since) => (StatusCode::OK, json!(get_stats(since).await?)),
+                None => bad_request("Invalid since date"),
+            }
+        }
+    })
+}
+
+async fn handle(api: Api, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let authorization = request
+        .headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok());
+    let route = Route::parse(request.method(), request.uri().path());
+    let query = Url::parse(&format!("http://localhost{}", request.uri()))
+        .map(|url| url.query_pairs().into_owned().collect::<HashMap<_, _>>())
+        .unwrap_or_default();
+    let (status, body) = if !is_authorized(authorization, &api.token) {
+        (StatusCode::UNAUTHORIZED, json!({ "error": "Unauthorized" }))
+    } else if let Some(route) = route {
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .unwrap_or_default();
+        match answer(&api, route, query, &body).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                error!("Admin API request failed: {e}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({ "error": e.to_string() }),
+                )
+            }
+        }
+    } else {
+        not_found("Not found")
+    };
+    let response = Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Response is valid");
+
+    Ok(response)
+}
+
+/// Serve the admin API in the background, every request needs the token
+pub fn serve(
+    addr: SocketAddr,
+    token: String,
+    pool: SqlitePool,
+    client: Client,
+    my_keys: Keys,
+) -> Result<()> {
+    let api = Api {
+        token,
+        pool,
+        client,
+        my_keys,
+    };
+    let make_service = make_service_fn(move |_| {
+        let api = api.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(api.clone(), request))) }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    info!("Admin API listening on {addr}");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Admin API stopped: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_authorized, Route};
+    use hyper::Method;
+
+    #[test]
+    fn test_route_parse() {
+        let order_id = "6ceda69d-99e4-4263-84cd-157a673aa307";
+        assert_eq!(Some(Route::Orders), Route::parse(&Method::GET, "/orders"));
+        assert_eq!(
+            Some(Route::ForceStatus(uuid::Uuid::parse_str(order_id).unwrap())),
+            Route::parse(&Method::POST, &format!("/orders/{order_id}/status"))
+        );
+        assert_eq!(
+            Some(Route::Ban("npub1abc".to_string(), false)),
+            Route::parse(&Method::DELETE, "/users/npub1abc/ban")
+        );
+        assert_eq!(
+            Some(Route::Order(uuid::Uuid::parse_str(order_id).unwrap())),
+            Route::parse(&Method::GET, &format!("/orders/{order_id}"))
+        );
+        assert_eq!(
+            Some(Route::ResolveDispute(
+                uuid::Uuid::parse_str(order_id).unwrap()
+            )),
+            Route::parse(&Method::POST, &format!("/disputes/{order_id}/resolve"))
+        );
+        assert_eq!(None, Route::parse(&Method::POST, "/orders/1/status"));
+        assert_eq!(None, Route::parse(&Method::DELETE, "/orders"));
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer secreT"), "secret"));
+        assert!(!is_authorized(Some("secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+    }
+}
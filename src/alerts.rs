@@ -0,0 +1,300 @@
+use crate::config::{self, AlertSettings};
+use crate::db;
+use crate::lightning::LndConnector;
+use crate::summary::{get_liquidity, Liquidity};
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tonic_openssl_lnd::lnrpc::invoice::InvoiceState;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Seconds Telegram or Matrix have to take an alert
+const ALERT_TIMEOUT: u64 = 10;
+
+/// Events that need someone to look at them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Alert {
+    DisputeOpened,
+    CltvExpiry,
+    LowLiquidity,
+}
+
+pub const ALERTS: [Alert; 3] = [Alert::DisputeOpened, Alert::CltvExpiry, Alert::LowLiquidity];
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DisputeOpened => write!(f, "dispute_opened"),
+            Self::CltvExpiry => write!(f, "cltv_expiry"),
+            Self::LowLiquidity => write!(f, "low_liquidity"),
+        }
+    }
+}
+
+impl FromStr for Alert {
+    type Err = ();
+
+    fn from_str(alert: &str) -> Result<Self, Self::Err> {
+        ALERTS
+            .into_iter()
+            .find(|known| known.to_string() == alert)
+            .ok_or(())
+    }
+}
+
+/// Alerts sent set on ALERT_EVENTS, separated by commas. None when one of
+/// them is unknown
+pub fn parse_alert_events(events: &str) -> Option<Vec<Alert>> {
+    events
+        .split(',')
+        .map(|event| event.trim())
+        .filter(|event| !event.is_empty())
+        .map(|event| Alert::from_str(event).ok())
+        .collect()
+}
+
+/// Where the alerts go
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Channel {
+    Telegram {
+        token: String,
+        chat_id: String,
+    },
+    Matrix {
+        homeserver: String,
+        access_token: String,
+        room_id: String,
+    },
+}
+
+/// Telegram gets the alerts with TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID
+/// set, Matrix with MATRIX_HOMESERVER, MATRIX_ACCESS_TOKEN and
+/// MATRIX_ROOM_ID
+fn get_channels(alerts: &AlertSettings) -> Vec<Channel> {
+    let mut channels = vec![];
+    if let (Some(token), Some(chat_id)) = (&alerts.telegram_bot_token, &alerts.telegram_chat_id) {
+        channels.push(Channel::Telegram {
+            token: token.clone(),
+            chat_id: chat_id.clone(),
+        });
+    }
+    if let (Some(homeserver), Some(access_token), Some(room_id)) = (
+        &alerts.matrix_homeserver,
+        &alerts.matrix_access_token,
+        &alerts.matrix_room_id,
+    ) {
+        channels.push(Channel::Matrix {
+            homeserver: homeserver.clone(),
+            access_token: access_token.clone(),
+            room_id: room_id.clone(),
+        });
+    }
+
+    channels
+}
+
+pub fn is_configured(alerts: &AlertSettings) -> bool {
+    !get_channels(alerts).is_empty()
+}
+
+async fn post(client: &reqwest::Client, channel: &Channel, text: &str) -> Result<()> {
+    let request = match channel {
+        Channel::Telegram { token, chat_id } => client
+            .post(format!("https://api.telegram.org/bot{token}/sendMessage"))
+            .json(&json!({ "chat_id": chat_id, "text": text })),
+        Channel::Matrix {
+            homeserver,
+            access_token,
+            room_id,
+        } => {
+            // Matrix drops a message sent again with the same transaction id
+            let txn_id = Uuid::new_v4().to_string();
+            let mut url = Url::parse(homeserver)?;
+            url.path_segments_mut()
+                .map_err(|_| anyhow!("MATRIX_HOMESERVER is not a base URL"))?
+                .pop_if_empty()
+                .extend([
+                    "_matrix",
+                    "client",
+                    "v3",
+                    "rooms",
+                    room_id.as_str(),
+                    "send",
+                    "m.room.message",
+                    txn_id.as_str(),
+                ]);
+            client
+                .put(url)
+                .bearer_auth(access_token)
+                .json(&json!({ "msgtype": "m.text", "body": text }))
+        }
+    };
+    request.send().await?.error_for_status()?;
+
+    Ok(())
+}
+
+/// Push an alert to Telegram and Matrix unless ALERT_EVENTS leaves it out
+pub async fn send(alert: Alert, text: &str) {
+    let alerts = config::mostro().alerts.clone();
+    let channels = get_channels(&alerts);
+    if channels.is_empty() || !alerts.alert_events.contains(&alert) {
+        return;
+    }
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(ALERT_TIMEOUT))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Alerts couldn't be sent: {e}");
+            return;
+        }
+    };
+    for channel in channels {
+        if let Err(e) = post(&client, &channel, text).await {
+            let name = match channel {
+                Channel::Telegram { .. } => "Telegram",
+                Channel::Matrix { .. } => "Matrix",
+            };
+            error!("{alert} alert couldn't be sent to {name}: {e}");
+        }
+    }
+}
+
+/// Send an alert without waiting for it
+pub fn spawn_send(alert: Alert, text: String) {
+    tokio::spawn(async move { send(alert, &text).await });
+}
+
+/// Orders already alerted about their hold invoice expiring
+static EXPIRING: Mutex<Option<HashSet<Uuid>>> = Mutex::new(None);
+/// Whether the node had enough liquidity the last time it was checked
+static LIQUID: AtomicBool = AtomicBool::new(true);
+
+/// Whether the node can't pay or receive the usual amounts
+fn is_low_liquidity(liquidity: &Liquidity, min_sats: u64) -> bool {
+    liquidity.active_channels == 0 || liquidity.outbound < min_sats || liquidity.inbound < min_sats
+}
+
+/// Alert once about every hold invoice whose HTLC expires in less than
+/// ALERT_CLTV_BLOCKS blocks, the sats go back to the seller when it does
+async fn check_cltv_expiry(pool: &SqlitePool, margin: i64) -> Result<()> {
+    let mut lnd = LndConnector::try_connect(&config::settings().lightning).await?;
+    let block_height = lnd.get_info().await?.block_height as i64;
+    let orders = db::find_orders_in_escrow(pool).await?;
+    let mut expiring = vec![];
+    for order in orders.iter() {
+        let hash = match order.hash.as_ref() {
+            Some(hash) => hash,
+            None => continue,
+        };
+        let invoice = lnd.lookup_invoice(hash).await?;
+        if InvoiceState::from_i32(invoice.state) != Some(InvoiceState::Accepted) {
+            continue;
+        }
+        let expiry_height = invoice
+            .htlcs
+            .iter()
+            .map(|htlc| htlc.expiry_height as i64)
+            .min();
+        if let Some(expiry_height) = expiry_height {
+            let blocks_left = expiry_height - block_height;
+            if blocks_left < margin {
+                expiring.push((order.id, order.status.clone(), blocks_left));
+            }
+        }
+    }
+    let expiring = {
+        let mut alerted = EXPIRING.lock().unwrap();
+        let alerted = alerted.get_or_insert_with(HashSet::new);
+        // Orders out of escrow won't expire anymore
+        alerted.retain(|order_id| orders.iter().any(|order| order.id == *order_id));
+        expiring
+            .into_iter()
+            .filter(|(order_id, _, _)| alerted.insert(*order_id))
+            .collect::<Vec<_>>()
+    };
+    for (order_id, status, blocks_left) in expiring {
+        let text = format!(
+            "Order {order_id} is {status} and the HTLC of its hold invoice expires in {blocks_left} blocks, the sats go back to the seller when it does"
+        );
+        warn!("{text}");
+        send(Alert::CltvExpiry, &text).await;
+    }
+
+    Ok(())
+}
+
+/// Alert when the node can't pay or receive ALERT_MIN_LIQUIDITY_SATS, once
+/// until it can again
+async fn check_liquidity(min_sats: u64) -> Result<()> {
+    let liquidity = get_liquidity().await?;
+    let low = is_low_liquidity(&liquidity, min_sats);
+    if low && LIQUID.swap(false, Ordering::SeqCst) {
+        let text = format!(
+            "The node is out of liquidity: {} channels active, {} sats to send and {} sats to receive",
+            liquidity.active_channels, liquidity.outbound, liquidity.inbound
+        );
+        warn!("{text}");
+        send(Alert::LowLiquidity, &text).await;
+    } else if !low {
+        LIQUID.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+/// Check the hold invoices and the liquidity of the node, run by the
+/// scheduler when Telegram or Matrix are set
+pub async fn check_node(pool: &SqlitePool, alerts: &AlertSettings) -> Result<()> {
+    if alerts.alert_events.contains(&Alert::CltvExpiry) {
+        check_cltv_expiry(pool, alerts.alert_cltv_blocks).await?;
+    }
+    if alerts.alert_events.contains(&Alert::LowLiquidity) {
+        check_liquidity(alerts.alert_min_liquidity_sats).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_low_liquidity, parse_alert_events, Alert};
+    use crate::summary::Liquidity;
+
+    #[test]
+    fn test_parse_alert_events() {
+        assert_eq!(
+            Some(vec![Alert::DisputeOpened, Alert::LowLiquidity]),
+            parse_alert_events("dispute_opened, low_liquidity")
+        );
+        assert_eq!(None, parse_alert_events("dispute_opened,unknown"));
+        assert_eq!(Some(vec![]), parse_alert_events(""));
+    }
+
+    #[test]
+    fn test_is_low_liquidity() {
+        let liquidity = Liquidity {
+            active_channels: 2,
+            inactive_channels: 0,
+            outbound: 500_000,
+            inbound: 80_000,
+        };
+        assert!(is_low_liquidity(&liquidity, 100_000));
+        assert!(!is_low_liquidity(&liquidity, 50_000));
+        let offline = Liquidity {
+            active_channels: 0,
+            ..liquidity
+        };
+        assert!(is_low_liquidity(&offline, 50_000));
+    }
+}
@@ -1,24 +1,92 @@
 pub mod add_invoice;
+pub mod admin_cancel;
+pub mod admin_fees;
+pub mod admin_force;
+pub mod admin_purge;
+pub mod admin_relays;
+pub mod admin_reload;
+pub mod admin_search;
+pub mod admin_settings;
+pub mod admin_settle;
+pub mod admin_stats;
+pub mod admin_user;
 pub mod cancel;
+pub mod dispute;
+pub mod edit_order;
 pub mod fiat_sent;
+pub mod get_order;
+pub mod list_orders;
 pub mod order;
+pub mod rate_user;
 pub mod release;
 pub mod take_buy;
 pub mod take_sell;
 
 use crate::app::add_invoice::add_invoice_action;
+use crate::app::admin_cancel::admin_cancel_action;
+use crate::app::admin_fees::{admin_fees_action, get_fees_query};
+use crate::app::admin_force::{admin_force_status_action, get_force_status_query};
+use crate::app::admin_purge::{admin_purge_action, get_purge_query};
+use crate::app::admin_relays::{admin_relay_action, get_relay_query};
+use crate::app::admin_reload::{admin_reload_action, is_reload_query};
+use crate::app::admin_search::{admin_search_action, get_search_query};
+use crate::app::admin_settings::{admin_setting_action, get_setting_query};
+use crate::app::admin_settle::admin_settle_action;
+use crate::app::admin_stats::{admin_stats_action, get_stats_query};
+use crate::app::admin_user::{admin_user_action, get_user_query};
 use crate::app::cancel::cancel_action;
+use crate::app::dispute::{
+    dispute_action, dispute_message_action, dispute_timeline_action, get_dispute_id,
+    get_take_dispute_query, get_timeline_query, take_dispute_action,
+};
+use crate::app::edit_order::edit_order_action;
 use crate::app::fiat_sent::fiat_sent_action;
+use crate::app::get_order::{get_events_query, get_order_action, order_events_action};
+use crate::app::list_orders::{get_orders_query, list_orders_action};
 use crate::app::order::order_action;
+use crate::app::rate_user::rate_user_action;
 use crate::app::release::release_action;
 use crate::app::take_buy::take_buy_action;
 use crate::app::take_sell::take_sell_action;
+use crate::archive::{archive_message, Direction};
+use crate::db::{add_audit_log, update_user_language};
+use crate::dedup::is_new_event;
+use crate::i18n::get_lang_tag;
 use crate::lightning::LndConnector;
+use crate::messages;
+use crate::metrics::record_action;
+use crate::raw_events::store_event;
+use crate::reporting;
+use crate::shutdown;
+use crate::util::{get_user_lang, send_cant_do_msg};
+use crate::webhook;
 use anyhow::Result;
 use mostro_core::{Action, Message};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
+use std::time::Instant;
+use tracing::{error, info, info_span, Instrument};
+use uuid::Uuid;
 
+/// Every processed action and admin command goes to the audit log with its
+/// outcome, the error of the action is returned once it is logged
+async fn audit_action(
+    pool: &Pool<Sqlite>,
+    pubkey: &XOnlyPublicKey,
+    action: &str,
+    order_id: Option<Uuid>,
+    result: Result<()>,
+) -> Result<()> {
+    let outcome = match &result {
+        Ok(()) => "ok".to_string(),
+        Err(e) => format!("error: {e}"),
+    };
+    add_audit_log(pool, &pubkey.to_bech32()?, action, order_id, &outcome).await?;
+
+    result
+}
+
+/// Handle the DMs sent to Mostro until a shutdown is requested
 pub async fn run(
     my_keys: Keys,
     client: Client,
@@ -28,58 +96,323 @@ pub async fn run(
     loop {
         let mut notifications = client.notifications();
 
-        while let Ok(notification) = notifications.recv().await {
+        // On shutdown no new event is taken, the one being handled is
+        // finished first
+        while let Some(Ok(notification)) = tokio::select! {
+            notification = notifications.recv() => Some(notification),
+            _ = shutdown::requested() => None,
+        } {
             if let RelayPoolNotification::Event(_, event) = notification {
                 if let Kind::EncryptedDirectMessage = event.kind {
+                    // Kept encrypted for investigations when the retention is on
+                    if let Err(e) = store_event(&pool, &event).await {
+                        error!("Event Id {} could not be stored: {e}", event.id);
+                    }
                     let message = decrypt(
                         &my_keys.secret_key().unwrap(),
                         &event.pubkey,
                         &event.content,
                     );
                     if let Ok(m) = message {
+                        // Relays can deliver the same event more than once, an
+                        // event already recorded was already handled
+                        if !is_new_event(&pool, &event).await? {
+                            info!("Event Id {} already processed", event.id);
+                            continue;
+                        }
+                        // Clients declare the language of the user in any message
+                        if let Some(lang) = get_lang_tag(&event) {
+                            update_user_language(&pool, &event.pubkey.to_bech32()?, lang).await?;
+                        }
+                        // Messages tagged with a user pubkey are admin queries
+                        if let Some((pubkey, command)) = get_user_query(&event) {
+                            let result = admin_user_action(
+                                &pubkey,
+                                command.as_deref(),
+                                &event,
+                                &my_keys,
+                                &client,
+                                &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "UserQuery", None, result).await?;
+                            continue;
+                        }
+                        // Admins ask for the fee ledger
+                        if let Some(month) = get_fees_query(&event) {
+                            let result = admin_fees_action(
+                                month.as_deref(),
+                                &event,
+                                &my_keys,
+                                &client,
+                                &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "FeeLedger", None, result).await?;
+                            continue;
+                        }
+                        // Admins search orders by description, payment method or pubkey
+                        if let Some((terms, page)) = get_search_query(&event) {
+                            let result =
+                                admin_search_action(&terms, page, &event, &my_keys, &client, &pool)
+                                    .await;
+                            audit_action(&pool, &event.pubkey, "OrderSearch", None, result).await?;
+                            continue;
+                        }
+                        // Admins ask for the statistics of the orders
+                        if let Some(since) = get_stats_query(&event) {
+                            let result = admin_stats_action(
+                                since.as_deref(),
+                                &event,
+                                &my_keys,
+                                &client,
+                                &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "Stats", None, result).await?;
+                            continue;
+                        }
+                        // Admins change settings at runtime
+                        if let Some((name, value)) = get_setting_query(&event) {
+                            let result = admin_setting_action(
+                                name.as_deref(),
+                                value.as_deref(),
+                                &event,
+                                &my_keys,
+                                &client,
+                                &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "Setting", None, result).await?;
+                            continue;
+                        }
+                        // Admins edit the relays Mostro connects to
+                        if let Some(command) = get_relay_query(&event) {
+                            let command = command
+                                .as_ref()
+                                .map(|(url, command)| (url.as_str(), command.as_str()));
+                            let result =
+                                admin_relay_action(command, &event, &my_keys, &client, &pool).await;
+                            audit_action(&pool, &event.pubkey, "Relay", None, result).await?;
+                            continue;
+                        }
+                        // Admins apply the settings edited while Mostro runs
+                        if is_reload_query(&event) {
+                            let result =
+                                admin_reload_action(&event, &my_keys, &client, &pool).await;
+                            audit_action(&pool, &event.pubkey, "Reload", None, result).await?;
+                            continue;
+                        }
+                        // Admins purge the data of a user on request
+                        if let Some((pubkey, dry_run)) = get_purge_query(&event) {
+                            let result = admin_purge_action(
+                                &pubkey, dry_run, &event, &my_keys, &client, &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "Purge", None, result).await?;
+                            continue;
+                        }
+                        // Admins can force the status of an order
+                        if let Some(values) = get_force_status_query(&event) {
+                            let order_id = values.first().and_then(|id| Uuid::parse_str(id).ok());
+                            let result = admin_force_status_action(
+                                &values, &event, &my_keys, &client, &pool,
+                            )
+                            .await;
+                            audit_action(
+                                &pool,
+                                &event.pubkey,
+                                "ForceStatusQuery",
+                                order_id,
+                                result,
+                            )
+                            .await?;
+                            continue;
+                        }
+                        // Admins ask for the lifecycle of an order
+                        if let Some(order_id) = get_events_query(&event) {
+                            let result =
+                                order_events_action(order_id, &event, &my_keys, &client, &pool)
+                                    .await;
+                            audit_action(
+                                &pool,
+                                &event.pubkey,
+                                "OrderEvents",
+                                Some(order_id),
+                                result,
+                            )
+                            .await?;
+                            continue;
+                        }
+                        // Users ask for their own orders with an orders tag
+                        if let Some(page) = get_orders_query(&event) {
+                            let result =
+                                list_orders_action(page, &event, &my_keys, &client, &pool).await;
+                            audit_action(&pool, &event.pubkey, "OrdersList", None, result).await?;
+                            continue;
+                        }
+                        // Solvers take a dispute before resolving it
+                        if let Some(dispute_id) = get_take_dispute_query(&event) {
+                            let result =
+                                take_dispute_action(dispute_id, &event, &my_keys, &client, &pool)
+                                    .await;
+                            audit_action(&pool, &event.pubkey, "TakeDispute", None, result).await?;
+                            continue;
+                        }
+                        // Solvers ask for the timeline of a disputed order
+                        if let Some(dispute_id) = get_timeline_query(&event) {
+                            let result = dispute_timeline_action(
+                                dispute_id, &event, &my_keys, &client, &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "DisputeTimeline", None, result)
+                                .await?;
+                            continue;
+                        }
+                        // Messages tagged with a dispute id go to the dispute log
+                        if let Some(dispute_id) = get_dispute_id(&event) {
+                            let result = dispute_message_action(
+                                dispute_id, &m, &event, &my_keys, &client, &pool,
+                            )
+                            .await;
+                            audit_action(&pool, &event.pubkey, "DisputeMessage", None, result)
+                                .await?;
+                            continue;
+                        }
                         let message = Message::from_json(&m);
                         if let Ok(msg) = message {
                             if msg.verify() {
-                                match msg.action {
-                                    Action::Order => {
-                                        order_action(msg, &event, &my_keys, &client, &pool).await?
-                                    }
-                                    Action::TakeSell => {
-                                        take_sell_action(msg, &event, &my_keys, &client, &pool)
-                                            .await?
-                                    }
-                                    Action::TakeBuy => {
-                                        take_buy_action(msg, &event, &my_keys, &client, &pool)
-                                            .await?
-                                    }
-                                    Action::FiatSent => {
-                                        fiat_sent_action(msg, &event, &my_keys, &client, &pool)
-                                            .await?
-                                    }
-                                    Action::Release => {
-                                        release_action(
-                                            msg, &event, &my_keys, &client, &pool, ln_client,
-                                        )
-                                        .await?
-                                    }
-                                    Action::Cancel => {
-                                        cancel_action(
-                                            msg, &event, &my_keys, &client, &pool, ln_client,
-                                        )
-                                        .await?
-                                    }
-                                    Action::AddInvoice => {
-                                        add_invoice_action(msg, &event, &my_keys, &client, &pool)
-                                            .await?
+                                let action = format!("{:?}", msg.action);
+                                if let Some(order_id) = msg.order_id {
+                                    archive_message(
+                                        &pool,
+                                        order_id,
+                                        &event.pubkey,
+                                        &action,
+                                        Direction::In,
+                                        &m,
+                                        &event.id,
+                                    )
+                                    .await?;
+                                }
+                                let order_id = msg.order_id;
+                                // In maintenance mode the trades in progress go on
+                                // but nothing new starts
+                                if crate::config::mostro().maintenance_mode
+                                    && matches!(
+                                        msg.action,
+                                        Action::Order | Action::TakeSell | Action::TakeBuy
+                                    )
+                                {
+                                    send_cant_do_msg(
+                                        &client,
+                                        &my_keys,
+                                        order_id,
+                                        messages::maintenance_mode(
+                                            get_user_lang(&pool, &event.pubkey).await,
+                                        ),
+                                        &event.pubkey,
+                                    )
+                                    .await?;
+                                    continue;
+                                }
+                                // Logs of the action carry its name and order
+                                let span = info_span!(
+                                    "action",
+                                    action = %action,
+                                    order_id = ?order_id,
+                                    pubkey = %event.pubkey
+                                );
+                                let started = Instant::now();
+                                let context = reporting::Context {
+                                    action: action.clone(),
+                                    order_id,
+                                };
+                                let result = async {
+                                    match msg.action {
+                                        Action::Order => {
+                                            order_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::EditOrder => {
+                                            edit_order_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::TakeSell => {
+                                            take_sell_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::TakeBuy => {
+                                            take_buy_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::FiatSent => {
+                                            fiat_sent_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::Release => {
+                                            release_action(
+                                                msg, &event, &my_keys, &client, &pool, ln_client,
+                                            )
+                                            .await
+                                        }
+                                        Action::Cancel => {
+                                            cancel_action(
+                                                msg, &event, &my_keys, &client, &pool, ln_client,
+                                            )
+                                            .await
+                                        }
+                                        Action::AddInvoice => {
+                                            add_invoice_action(
+                                                msg, &event, &my_keys, &client, &pool,
+                                            )
+                                            .await
+                                        }
+                                        Action::AdminCancel => {
+                                            admin_cancel_action(
+                                                msg, &event, &my_keys, &client, &pool, ln_client,
+                                            )
+                                            .await
+                                        }
+                                        Action::AdminSettle => {
+                                            admin_settle_action(
+                                                msg, &event, &my_keys, &client, &pool, ln_client,
+                                            )
+                                            .await
+                                        }
+                                        Action::Dispute => {
+                                            dispute_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::RateUser => {
+                                            rate_user_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::GetOrder => {
+                                            get_order_action(msg, &event, &my_keys, &client, &pool)
+                                                .await
+                                        }
+                                        Action::PayInvoice => todo!(),
+                                        _ => todo!(),
                                     }
-                                    Action::PayInvoice => todo!(),
-                                    _ => todo!(),
                                 }
+                                .instrument(span);
+                                let result = reporting::with_context(context.clone(), result).await;
+                                record_action(&action, started.elapsed(), result.is_ok());
+                                if let Err(e) = &result {
+                                    webhook::notify_database_error(&action, e);
+                                    reporting::capture_error(e, &context);
+                                }
+                                audit_action(&pool, &event.pubkey, &action, order_id, result)
+                                    .await?;
                             }
                         }
                     };
                 }
             }
         }
+        if shutdown::is_shutting_down() {
+            return Ok(());
+        }
     }
 }
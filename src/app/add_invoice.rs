@@ -1,15 +1,107 @@
+use crate::app::release::do_payment;
+use crate::auth::authorize;
+use crate::config::{self, FeeSettings, PayoutSettings};
+use crate::db::{
+    add_payout_parts, edit_buyer_invoice_order, find_order_with_version, find_payment_failure,
+    find_pending_payout, take_failed_payment, update_routing_compensation,
+};
 use crate::error::MostroError;
+use crate::fee::{buyer_payout_amount, find_trade_fees, get_routing_compensation};
+use crate::i18n::Lang;
+use crate::lifecycle::Trigger;
 use crate::lightning::invoice::is_valid_invoice;
-use crate::util::send_dm;
+use crate::messages;
+use crate::payout::{pay_payout, split_payment_request};
+use crate::state::{order_status, transition};
+use crate::util::{get_user_lang, send_cant_do_msg, send_dm, send_wrong_status_msg};
 
 use anyhow::Result;
-use log::error;
 use mostro_core::order::Order;
 use mostro_core::{order::SmallOrder, Action, Content, Message, Status};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
 use sqlx_crud::Crud;
-use std::str::FromStr;
+use tracing::{error, info};
+
+/// Invoices of a payout split by the buyer with their amounts, or the
+/// reason to reject them. Every invoice needs an amount and together they
+/// must add up to the payout
+fn check_payout_parts(
+    invoices: &[String],
+    payout: &PayoutSettings,
+    payout_amount: i64,
+    lang: Lang,
+) -> std::result::Result<Vec<(String, i64)>, String> {
+    let min_amount = payout.split_payout_min_amount;
+    let max_parts = payout.max_payout_parts;
+    if min_amount == 0 || payout_amount < min_amount || invoices.len() > max_parts {
+        return Err(messages::invalid_split_payout(lang, min_amount, max_parts));
+    }
+    let mut parts = vec![];
+    for invoice in invoices {
+        let amount = match is_valid_invoice(invoice, None) {
+            Ok(parsed) => parsed.amount_milli_satoshis().unwrap_or(0) as i64 / 1000,
+            Err(e) => return Err(e.to_string()),
+        };
+        if amount == 0 {
+            return Err(messages::split_payout_amount(lang, payout_amount));
+        }
+        parts.push((invoice.to_string(), amount));
+    }
+    if parts.iter().map(|(_, amount)| amount).sum::<i64>() != payout_amount {
+        return Err(messages::split_payout_amount(lang, payout_amount));
+    }
+
+    Ok(parts)
+}
+
+/// The buyer accepted a smaller payout after its payment failed for the
+/// routing fee, the new invoice is paid with the difference added to the
+/// routing budget
+async fn reduced_payout_action(
+    pool: &Pool<Sqlite>,
+    client: &Client,
+    my_keys: &Keys,
+    fees: &FeeSettings,
+    order: Order,
+    pr: String,
+    buyer_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    let lang = get_user_lang(pool, buyer_pubkey).await;
+    let (_, attempts, _) = find_payment_failure(pool, order.id).await?;
+    let payout_amount = buyer_payout_amount(&order, &find_trade_fees(pool, fees, &order).await?);
+    let compensation = get_routing_compensation(fees, payout_amount);
+    if attempts == 0 {
+        send_wrong_status_msg(pool, client, my_keys, &order, buyer_pubkey).await?;
+        return Ok(());
+    }
+    if let Err(e) = is_valid_invoice(&pr, Some((payout_amount - compensation) as u64)) {
+        send_cant_do_msg(client, my_keys, Some(order.id), e.to_string(), buyer_pubkey).await?;
+        return Ok(());
+    }
+    // The payment can't be in flight, the invoice would be paid twice
+    if !take_failed_payment(pool, order.id).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::cant_do(lang),
+            buyer_pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    edit_buyer_invoice_order(pool, order.id, &pr).await?;
+    update_routing_compensation(pool, order.id, compensation).await?;
+    info!(
+        "AddInvoice: Order Id {}: buyer accepted {compensation} sats less for routing",
+        order.id
+    );
+    match Order::by_id(pool, order.id).await? {
+        Some(order) => do_payment(order).await,
+        None => Ok(()),
+    }
+}
 
 pub async fn add_invoice_action(
     msg: Message,
@@ -20,24 +112,100 @@ pub async fn add_invoice_action(
 ) -> Result<()> {
     // Safe unwrap as we verified the message
     let order_id = msg.order_id.unwrap();
-    let order = match Order::by_id(pool, order_id).await? {
+    let settings = config::mostro();
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
         Some(order) => order,
         None => {
             error!("AddInvoice: Order Id {order_id} not found!");
             return Ok(());
         }
     };
-    if order.kind != "Buy" {
-        error!("AddInvoice: Order Id {order_id} wrong kind");
+    let buyer_pubkey = event.pubkey;
+    // The invoice could be for a payout owed out of the trade flow, like a
+    // forfeited bond or a part of a dispute
+    if let Some(payout) = find_pending_payout(pool, order.id, &buyer_pubkey.to_bech32()?).await? {
+        if let Some(pr) = msg.get_payment_request() {
+            pay_payout(
+                pool,
+                client,
+                my_keys,
+                &settings.fees,
+                payout,
+                pr,
+                &buyer_pubkey,
+            )
+            .await?;
+        }
         return Ok(());
     }
-    let buyer_pubkey = event.pubkey;
-    let pr: String;
-    // If a buyer sent me a lightning invoice we look on db an order with
-    // that order id and save the buyer pubkey and invoice fields
-    if let Some(payment_request) = msg.get_payment_request() {
+    // Only the buyer of this order can send the invoice
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::AddInvoice,
+        &buyer_pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    // A buyer whose payment failed can send an invoice for a smaller payout
+    if order_status(&order) == Ok(Status::SettledHoldInvoice) {
+        if let Some(pr) = msg.get_payment_request() {
+            reduced_payout_action(
+                pool,
+                client,
+                my_keys,
+                &settings.fees,
+                order,
+                pr,
+                &buyer_pubkey,
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+    // Mostro only waits for an invoice after the seller paid the hold invoice
+    if transition(&order, &Action::AddInvoice).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &buyer_pubkey).await?;
+        return Ok(());
+    }
+    let pr = match msg.get_payment_request() {
+        Some(pr) => pr,
+        None => {
+            error!("AddInvoice: Order Id {order_id} wrong get_payment_request");
+            return Ok(());
+        }
+    };
+    // The buyer invoice is paid with the order amount minus the buyer fee
+    let trade_fees = find_trade_fees(pool, &settings.fees, &order).await?;
+    let payout_amount = buyer_payout_amount(&order, &trade_fees);
+    // Large payouts can be split across several invoices, the first one is
+    // kept as the buyer invoice of the order
+    let invoices = split_payment_request(&pr);
+    let pr = if invoices.len() > 1 {
+        let lang = get_user_lang(pool, &buyer_pubkey).await;
+        match check_payout_parts(&invoices, &settings.payout, payout_amount, lang) {
+            Ok(parts) => {
+                add_payout_parts(pool, order.id, &parts).await?;
+                info!(
+                    "AddInvoice: Order Id {}: payout split in {} invoices",
+                    order.id,
+                    parts.len()
+                );
+                invoices[0].clone()
+            }
+            Err(text_message) => {
+                send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey)
+                    .await?;
+                return Ok(());
+            }
+        }
+    } else {
         // Verify if invoice is valid
-        match is_valid_invoice(&payment_request, Some(order.amount as u64)) {
+        match is_valid_invoice(&pr, Some(payout_amount as u64)) {
             Ok(_) => {}
             Err(e) => match e {
                 MostroError::ParsingInvoiceError
@@ -45,59 +213,31 @@ pub async fn add_invoice_action(
                 | MostroError::MinExpirationTimeError
                 | MostroError::WrongAmountError
                 | MostroError::MinAmountError => {
-                    // We create a Message
-                    let message = Message::new(
-                        0,
+                    send_cant_do_msg(
+                        client,
+                        my_keys,
                         Some(order.id),
-                        Action::CantDo,
-                        Some(Content::TextMessage(e.to_string())),
-                    );
-                    let message = message.as_json()?;
-                    send_dm(client, my_keys, &buyer_pubkey, message).await?;
+                        e.to_string(),
+                        &buyer_pubkey,
+                    )
+                    .await?;
                     error!("{e}");
                     return Ok(());
                 }
                 _ => {}
             },
         }
-        pr = payment_request;
-    } else {
-        error!("AddInvoice: Order Id {order_id} wrong get_payment_request");
-        return Ok(());
-    }
-
-    let order_status = match Status::from_str(&order.status) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("AddInvoice: Order Id {order_id} wrong status: {e:?}");
-            return Ok(());
-        }
+        pr
     };
-    // Buyer can take pending orders only
-    match order_status {
-        Status::WaitingBuyerInvoice => {}
-        _ => {
-            // We create a Message
-            let message = Message::new(
-                0,
-                Some(order.id),
-                Action::CantDo,
-                Some(Content::TextMessage(format!(
-                    "Order Id {order_id} status must be WaitingBuyerInvoice!"
-                ))),
-            );
-            let message = message.as_json()?;
-            send_dm(client, my_keys, &buyer_pubkey, message).await?;
-            return Ok(());
-        }
-    }
+    edit_buyer_invoice_order(pool, order.id, &pr).await?;
+    info!("AddInvoice: Order Id {}: buyer invoice saved", order.id);
     // We send this data related to the order to the parties
     let order_data = SmallOrder::new(
         order.id,
-        order.amount,
+        payout_amount,
         order.fiat_code.clone(),
         order.fiat_amount,
-        pr,
+        order.payment_method.clone(),
         order.premium,
         order.buyer_pubkey.as_ref().cloned(),
         order.seller_pubkey.as_ref().cloned(),
@@ -109,9 +249,17 @@ pub async fn add_invoice_action(
         Action::BuyerTookOrder,
         Some(Content::SmallOrder(order_data.clone())),
     );
-    let message = message.as_json().unwrap();
-    let seller_pubkey = order.seller_pubkey.as_ref().cloned().unwrap();
-    let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey)?;
+    let message = message.as_json()?;
+    let seller_pubkey = match order.seller_pubkey.as_ref() {
+        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
+        None => {
+            error!(
+                "AddInvoice: Seller pubkey not found for order {}!",
+                order.id
+            );
+            return Ok(());
+        }
+    };
     send_dm(client, my_keys, &seller_pubkey, message).await?;
     // We send a message to buyer saying seller paid
     let message = Message::new(
@@ -120,15 +268,22 @@ pub async fn add_invoice_action(
         Action::HoldInvoicePaymentAccepted,
         Some(Content::SmallOrder(order_data)),
     );
-    let message = message.as_json().unwrap();
-    send_dm(client, my_keys, &buyer_pubkey, message)
-        .await
-        .unwrap();
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &buyer_pubkey, message).await?;
 
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    crate::util::update_order_event(pool, client, my_keys, Status::Active, &order, None)
-        .await
-        .unwrap();
+    crate::util::update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::Active,
+        &order,
+        version,
+        None,
+        Trigger::User,
+    )
+    .await?;
+
     Ok(())
 }
@@ -0,0 +1,143 @@
+use crate::app::dispute::can_resolve_dispute;
+use crate::auth::authorize;
+use crate::bond::charge_dispute_fee;
+use crate::config;
+use crate::db::{add_audit_log, find_order_with_version, resolve_dispute, update_user_disputes};
+use crate::lifecycle::Trigger;
+use crate::lightning::LndConnector;
+use crate::messages;
+use crate::state::transition;
+use crate::util::{
+    get_user_lang, send_cant_do_msg, send_dm, send_wrong_status_msg, update_order_event,
+};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use tracing::{error, info};
+
+pub async fn admin_cancel_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+    ln_client: &mut LndConnector,
+) -> Result<()> {
+    let order_id = msg.order_id.unwrap();
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("AdminCancel: Order Id {order_id} not found!");
+            return Ok(());
+        }
+    };
+    // Only admins/solvers can cancel an order
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::AdminCancel,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if transition(&order, &Action::AdminCancel).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    // A dispute taken by a solver is resolved by that solver
+    if !can_resolve_dispute(pool, order.id, &event.pubkey).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::dispute_assigned_to_other(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let admin_pubkey = event.pubkey.to_bech32()?;
+    cancel_dispute(
+        pool,
+        client,
+        my_keys,
+        ln_client,
+        &order,
+        version,
+        &admin_pubkey,
+        Some(&event.pubkey),
+    )
+    .await
+}
+
+/// Cancel the hold invoice of an order in dispute, returning the sats to the
+/// seller. Both parties are told, and `admin` too when the request came by DM
+#[allow(clippy::too_many_arguments)]
+pub async fn cancel_dispute(
+    pool: &Pool<Sqlite>,
+    client: &Client,
+    my_keys: &Keys,
+    ln_client: &mut LndConnector,
+    order: &Order,
+    version: i64,
+    admin_pubkey: &str,
+    admin: Option<&XOnlyPublicKey>,
+) -> Result<()> {
+    // The buyer lost the dispute and pays the dispute fee with its bond,
+    // before the bond is released with the order
+    let dispute_fee = config::mostro().fees.dispute_fee;
+    let dispute_fee = charge_dispute_fee(pool, client, my_keys, order.id, dispute_fee).await?;
+    // We update the status before canceling the invoice, this way the
+    // invoice subscription knows this cancelation was done on purpose
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::CanceledByAdmin,
+        order,
+        version,
+        None,
+        Trigger::Admin,
+    )
+    .await?;
+    if let Some(hash) = order.hash.as_ref() {
+        // We return funds to seller
+        ln_client.cancel_hold_invoice(hash).await?;
+        info!(
+            "AdminCancel: Order Id {}: Funds returned to seller",
+            &order.id
+        );
+    }
+    // The buyer lost the dispute
+    if let Some(buyer_pubkey) = order.buyer_pubkey.as_ref() {
+        update_user_disputes(pool, buyer_pubkey, 0, 1).await?;
+    }
+    let details = format!(
+        "Dispute resolved in favor of the seller, hold invoice canceled, {dispute_fee} sats dispute fee"
+    );
+    add_audit_log(pool, admin_pubkey, "AdminCancel", Some(order.id), &details).await?;
+    resolve_dispute(pool, order.id, "SellerRefunded", admin_pubkey, &details).await?;
+    // We send a message to the admin and to both parties
+    let message = Message::new(0, Some(order.id), Action::AdminCancel, None);
+    let message = message.as_json()?;
+    if let Some(admin) = admin {
+        send_dm(client, my_keys, admin, message.clone()).await?;
+    }
+    if let Some(seller_pubkey) = order.seller_pubkey.as_ref() {
+        let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey)?;
+        send_dm(client, my_keys, &seller_pubkey, message.clone()).await?;
+    }
+    if let Some(buyer_pubkey) = order.buyer_pubkey.as_ref() {
+        let buyer_pubkey = XOnlyPublicKey::from_bech32(buyer_pubkey)?;
+        send_dm(client, my_keys, &buyer_pubkey, message).await?;
+    }
+
+    Ok(())
+}
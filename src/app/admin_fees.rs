@@ -0,0 +1,80 @@
+use crate::db::{find_fees, find_monthly_fees};
+use crate::messages;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use nostr_sdk::prelude::*;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+
+/// Admins ask for the fee ledger sending a DM tagged with `["fees"]` for the
+/// monthly totals or `["fees", "<YYYY-MM>"]` for the fees of a month
+pub fn get_fees_query(event: &Event) -> Option<Option<String>> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "fees" => {
+            Some(values.first().cloned())
+        }
+        _ => None,
+    })
+}
+
+/// Unix timestamps of the start of a `YYYY-MM` month and of the next one, UTC
+pub fn month_range(month: &str) -> Option<(i64, i64)> {
+    let start = NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").ok()?;
+    let end = match start.month() {
+        12 => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)?,
+        month => NaiveDate::from_ymd_opt(start.year(), month + 1, 1)?,
+    };
+
+    Some((
+        start.and_hms_opt(0, 0, 0)?.and_utc().timestamp(),
+        end.and_hms_opt(0, 0, 0)?.and_utc().timestamp(),
+    ))
+}
+
+pub async fn admin_fees_action(
+    month: Option<&str>,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let range = month.map(month_range);
+    if !is_admin(&event.pubkey) || matches!(range, Some(None)) {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let ledger = match range.flatten() {
+        Some((from, to)) => json!({
+            "month": month,
+            "totals": find_monthly_fees(pool, from, to).await?,
+            "fees": find_fees(pool, from, to).await?,
+        }),
+        None => json!({ "totals": find_monthly_fees(pool, 0, i64::MAX).await? }),
+    };
+    send_dm(client, my_keys, &event.pubkey, ledger.to_string()).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::month_range;
+
+    #[test]
+    fn test_month_range() {
+        assert_eq!(Some((1682899200, 1685577600)), month_range("2023-05"));
+        // December ends with the next year
+        assert_eq!(Some((1701388800, 1704067200)), month_range("2023-12"));
+        assert_eq!(None, month_range("2023-13"));
+        assert_eq!(None, month_range("may"));
+    }
+}
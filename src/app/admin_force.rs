@@ -0,0 +1,177 @@
+use crate::db::{add_audit_log, find_order_with_version};
+use crate::lifecycle::Trigger;
+use crate::messages;
+use crate::state::order_status;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm, update_order_event};
+
+use anyhow::Result;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use std::str::FromStr;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Request of an admin to put an order in a status by hand
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForceStatus {
+    pub order_id: Uuid,
+    pub status: Status,
+    pub reason: String,
+}
+
+impl ForceStatus {
+    pub fn parse(values: &[String]) -> Option<Self> {
+        let order_id = Uuid::parse_str(values.first()?).ok()?;
+        let status = Status::from_str(values.get(1)?).ok()?;
+        let reason = values.get(2)?.trim().to_string();
+        if reason.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            order_id,
+            status,
+            reason,
+        })
+    }
+}
+
+/// Admins force the status of an order sending a DM tagged with
+/// `["force_status", <order id>, <status>, <reason>]`, the reason is mandatory
+pub fn get_force_status_query(event: &Event) -> Option<Vec<String>> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "force_status" => {
+            Some(values.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Move an order to any status skipping the state machine, to recover from
+/// cases Mostro can't handle by itself. Hold invoices and payouts are not
+/// touched, the admin takes care of them
+pub async fn admin_force_status_action(
+    values: &[String],
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let lang = get_user_lang(pool, &event.pubkey).await;
+    let force = match ForceStatus::parse(values) {
+        Some(force) if is_admin(&event.pubkey) => force,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(lang),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let admin_pubkey = event.pubkey.to_bech32()?;
+    force_status(
+        pool,
+        client,
+        my_keys,
+        &admin_pubkey,
+        &force,
+        Some(&event.pubkey),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Put an order in the status of `force`, the audit log keeps who did it and
+/// why. Both parties are told, and `admin` too when the request came by DM.
+/// False when the order doesn't exist
+pub async fn force_status(
+    pool: &Pool<Sqlite>,
+    client: &Client,
+    my_keys: &Keys,
+    admin_pubkey: &str,
+    force: &ForceStatus,
+    admin: Option<&XOnlyPublicKey>,
+) -> Result<bool> {
+    let (order, version) = match find_order_with_version(pool, force.order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("ForceStatus: Order Id {} not found!", force.order_id);
+            return Ok(false);
+        }
+    };
+    let previous_status = order_status(&order)?;
+    // We publish a new replaceable kind nostr event with the status updated
+    // and update on local database the status and new event id
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        force.status,
+        &order,
+        version,
+        None,
+        Trigger::Admin,
+    )
+    .await?;
+    let details = format!(
+        "Status forced from {previous_status} to {}: {}",
+        force.status, force.reason
+    );
+    add_audit_log(pool, admin_pubkey, "ForceStatus", Some(order.id), &details).await?;
+    info!("Order Id {}: {details} by admin {admin_pubkey}", order.id);
+    // The admin and both parties are told the new status and why
+    let mut pubkeys = admin.into_iter().copied().collect::<Vec<_>>();
+    let parties = [order.buyer_pubkey.as_ref(), order.seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        pubkeys.push(XOnlyPublicKey::from_bech32(pubkey)?);
+    }
+    for pubkey in pubkeys {
+        let text_message = messages::status_forced(
+            get_user_lang(pool, &pubkey).await,
+            &force.status.to_string(),
+            &force.reason,
+        );
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::GetOrder,
+            Some(Content::TextMessage(text_message)),
+        );
+        let message = message.as_json()?;
+        send_dm(client, my_keys, &pubkey, message).await?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ForceStatus;
+    use mostro_core::Status;
+
+    #[test]
+    fn test_parse_force_status() {
+        let order_id = "6ceda69d-99e4-4263-84cd-157a673aa307".to_string();
+        let values = [
+            order_id.clone(),
+            "Canceled".to_string(),
+            "Hold invoice lost".to_string(),
+        ];
+        let force = ForceStatus::parse(&values).unwrap();
+        assert_eq!(Status::Canceled, force.status);
+        assert_eq!("Hold invoice lost", force.reason);
+        // The reason is mandatory
+        let values = [order_id.clone(), "Canceled".to_string(), " ".to_string()];
+        assert_eq!(None, ForceStatus::parse(&values));
+        let values = [order_id, "Canceled".to_string()];
+        assert_eq!(None, ForceStatus::parse(&values));
+        let values = ["1".to_string(), "Canceled".to_string(), "x".to_string()];
+        assert_eq!(None, ForceStatus::parse(&values));
+    }
+}
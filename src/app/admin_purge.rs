@@ -0,0 +1,91 @@
+use crate::db::{add_audit_log, count_user_open_items, purge_user_data};
+use crate::messages;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use std::str::FromStr;
+use tracing::info;
+
+/// Admins purge the data of a user who asked for it sending a DM tagged with
+/// `["purge", <pubkey>, "confirm"]`, without `confirm` it is a dry run and
+/// Mostro only answers with what would be removed
+pub fn get_purge_query(event: &Event) -> Option<(String, bool)> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "purge" => {
+            values.first().map(|pubkey| {
+                let confirmed = values.get(1).map(|value| value.as_str()) == Some("confirm");
+                (pubkey.to_string(), !confirmed)
+            })
+        }
+        _ => None,
+    })
+}
+
+pub async fn admin_purge_action(
+    pubkey: &str,
+    dry_run: bool,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let user_pubkey = XOnlyPublicKey::from_bech32(pubkey)
+        .ok()
+        .or_else(|| XOnlyPublicKey::from_str(pubkey).ok());
+    let user_pubkey = match user_pubkey {
+        Some(pk) if is_admin(&event.pubkey) => pk,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let lang = get_user_lang(pool, &event.pubkey).await;
+    let bech32_pubkey = user_pubkey.to_bech32()?;
+    // Trades in progress and sats owed need the pubkey to be finished
+    let open_items = count_user_open_items(pool, &bech32_pubkey).await?;
+    if open_items > 0 {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::purge_user_busy(lang, open_items),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    // The records kept get a random pubkey, a valid one so the code reading
+    // old orders keeps working, that links them to nobody
+    let anonymous_pubkey = Keys::generate().public_key().to_bech32()?;
+    let report = purge_user_data(
+        pool,
+        &bech32_pubkey,
+        &user_pubkey.to_string(),
+        &anonymous_pubkey,
+        dry_run,
+    )
+    .await?;
+    if !dry_run {
+        let admin_pubkey = event.pubkey.to_bech32()?;
+        let details = format!(
+            "{bech32_pubkey}: {} orders, {} messages, {} ratings, {} events, {} payments",
+            report.orders, report.messages, report.ratings, report.events, report.payments
+        );
+        add_audit_log(pool, &admin_pubkey, "PurgeUser", None, &details).await?;
+        info!("PurgeUser: {details} by admin {admin_pubkey}");
+    }
+    // We answer the admin with what was purged or would be
+    let report = serde_json::to_string(&report)?;
+    send_dm(client, my_keys, &event.pubkey, report).await?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use crate::db::{add_audit_log, add_relay, delete_relay, find_relays, update_relay_enabled};
+use crate::messages;
+use crate::relays::{follow, is_valid_url, reload};
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use tracing::info;
+
+/// Admins edit the relay registry sending a DM tagged with
+/// `["relay", <url>, <command>]`, the command is `add`, `remove`, `enable`
+/// or `disable`. With the tag alone Mostro answers with the relays and their
+/// last status
+pub fn get_relay_query(event: &Event) -> Option<Option<(String, String)>> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "relay" => {
+            Some(match (values.first(), values.get(1)) {
+                (Some(url), Some(command)) => Some((url.to_string(), command.to_string())),
+                _ => None,
+            })
+        }
+        _ => None,
+    })
+}
+
+pub async fn admin_relay_action(
+    command: Option<(&str, &str)>,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let is_valid = match command {
+        Some((url, "add")) => is_valid_url(url),
+        Some((_, "remove" | "enable" | "disable")) | None => true,
+        Some(_) => false,
+    };
+    if !is_admin(&event.pubkey) || !is_valid {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    if let Some((url, command)) = command {
+        let admin_pubkey = event.pubkey.to_bech32()?;
+        let changed = match command {
+            "add" => add_relay(pool, url, &admin_pubkey).await?,
+            "remove" => delete_relay(pool, url).await?,
+            command => update_relay_enabled(pool, url, command == "enable", &admin_pubkey).await?,
+        };
+        if changed {
+            reload(pool).await?;
+            // The running client follows the registry right away
+            follow(client, my_keys, url, matches!(command, "add" | "enable")).await?;
+            let details = format!("{command} {url}");
+            add_audit_log(pool, &admin_pubkey, "EditRelay", None, &details).await?;
+            info!("EditRelay: {details} by admin {admin_pubkey}");
+        }
+    }
+    // We answer the admin with the registry
+    let relays = serde_json::to_string(&find_relays(pool).await?)?;
+    send_dm(client, my_keys, &event.pubkey, relays).await?;
+
+    Ok(())
+}
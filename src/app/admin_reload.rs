@@ -0,0 +1,46 @@
+use crate::db::add_audit_log;
+use crate::messages;
+use crate::settings::reload;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use tracing::info;
+
+/// Admins reload the settings without restarting Mostro sending a DM tagged
+/// with `["reload"]`, as a SIGHUP does
+pub fn is_reload_query(event: &Event) -> bool {
+    event.tags.iter().any(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), _) => kind == "reload",
+        _ => false,
+    })
+}
+
+pub async fn admin_reload_action(
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    if !is_admin(&event.pubkey) {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let report = reload(pool, client, my_keys).await?;
+    let admin_pubkey = event.pubkey.to_bech32()?;
+    let details = serde_json::to_string(&report)?;
+    add_audit_log(pool, &admin_pubkey, "ReloadSettings", None, &details).await?;
+    info!("ReloadSettings: {details} by admin {admin_pubkey}");
+    // We answer the admin with what changed
+    send_dm(client, my_keys, &event.pubkey, details).await?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use crate::app::list_orders::ORDERS_PAGE_SIZE;
+use crate::db::{count_search_orders, search_orders};
+use crate::messages;
+use crate::models::OrderPage;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use std::str::FromStr;
+
+/// Admins search orders by description, payment method or pubkey sending a
+/// DM tagged with `["search", <terms>]`, an optional page number can follow
+pub fn get_search_query(event: &Event) -> Option<(String, i64)> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "search" => {
+            values.first().map(|terms| {
+                let page = values
+                    .get(1)
+                    .and_then(|page| page.parse::<i64>().ok())
+                    .unwrap_or(1)
+                    .max(1);
+                (terms.to_string(), page)
+            })
+        }
+        _ => None,
+    })
+}
+
+/// FTS5 query matching orders with all the terms, every term is quoted so
+/// user input can't break the query syntax, a trailing `*` searches by prefix.
+/// Hex pubkeys are searched as npub, the way orders keep them
+pub fn fts_query(terms: &str) -> String {
+    terms
+        .split_whitespace()
+        .filter_map(|term| {
+            let (term, prefix) = match term.strip_suffix('*') {
+                Some("") => return None,
+                Some(term) => (term, "*"),
+                None => (term, ""),
+            };
+            let term = match XOnlyPublicKey::from_str(term) {
+                Ok(pubkey) => pubkey.to_bech32().unwrap_or_else(|_| term.to_string()),
+                Err(_) => term.to_string(),
+            };
+            Some(format!("\"{}\"{prefix}", term.replace('"', "\"\"")))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Send an admin one page of the orders matching the search, newest first
+pub async fn admin_search_action(
+    terms: &str,
+    page: i64,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let query = fts_query(terms);
+    if !is_admin(&event.pubkey) || query.is_empty() {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let orders = search_orders(
+        pool,
+        &query,
+        ORDERS_PAGE_SIZE,
+        (page - 1) * ORDERS_PAGE_SIZE,
+    )
+    .await?;
+    let order_page = OrderPage {
+        page,
+        page_size: ORDERS_PAGE_SIZE,
+        total: count_search_orders(pool, &query).await?,
+        orders,
+    };
+    let order_page = serde_json::to_string(&order_page)?;
+    send_dm(client, my_keys, &event.pubkey, order_page).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fts_query;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!("\"revolut\" \"sepa\"", fts_query(" revolut  sepa "));
+        assert_eq!("\"npub1abc\"*", fts_query("npub1abc*"));
+        // Quotes and operators are searched as text
+        assert_eq!("\"say\"\"hi\"\"\"", fts_query("say\"hi\""));
+        assert_eq!("\"OR\"", fts_query("OR"));
+        assert_eq!("", fts_query(" * "));
+    }
+}
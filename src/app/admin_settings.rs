@@ -0,0 +1,77 @@
+use crate::config;
+use crate::db::add_audit_log;
+use crate::messages;
+use crate::settings::{is_valid_value, set, RUNTIME_SETTINGS};
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use tracing::info;
+
+/// Admins change a setting at runtime sending a DM tagged with
+/// `["setting", <name>, <value>]`, `default` as value goes back to the
+/// environment value. Without value Mostro answers with the current one and
+/// without name with all the runtime settings
+pub fn get_setting_query(event: &Event) -> Option<(Option<String>, Option<String>)> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "setting" => {
+            Some((values.first().cloned(), values.get(1).cloned()))
+        }
+        _ => None,
+    })
+}
+
+pub async fn admin_setting_action(
+    name: Option<&str>,
+    value: Option<&str>,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let is_valid = match (name, value) {
+        (Some(name), Some("default")) | (Some(name), None) => RUNTIME_SETTINGS.contains(&name),
+        (Some(name), Some(value)) => is_valid_value(name, value),
+        (None, _) => true,
+    };
+    if !is_admin(&event.pubkey) || !is_valid {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            None,
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    if let (Some(name), Some(value)) = (name, value) {
+        let admin_pubkey = event.pubkey.to_bech32()?;
+        let old_value = config::mostro().runtime_value(name).unwrap_or_default();
+        let new_value = match value {
+            "default" => None,
+            value => Some(value),
+        };
+        set(pool, name, new_value, &admin_pubkey).await?;
+        let new_value = config::mostro().runtime_value(name).unwrap_or_default();
+        let details = format!("{name}: {old_value} -> {new_value}");
+        add_audit_log(pool, &admin_pubkey, "SetSetting", None, &details).await?;
+        info!("SetSetting: {details} by admin {admin_pubkey}");
+    }
+    // We answer the admin with the values in use
+    let names = match name {
+        Some(name) => vec![name],
+        None => RUNTIME_SETTINGS.to_vec(),
+    };
+    let mostro = config::mostro();
+    let settings = names
+        .into_iter()
+        .map(|name| (name.to_string(), json!(mostro.runtime_value(name))))
+        .collect::<serde_json::Map<String, serde_json::Value>>();
+    let settings = serde_json::to_string(&settings)?;
+    send_dm(client, my_keys, &event.pubkey, settings).await?;
+
+    Ok(())
+}
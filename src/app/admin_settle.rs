@@ -0,0 +1,233 @@
+use crate::app::dispute::can_resolve_dispute;
+use crate::app::release::{do_payment, record_fee};
+use crate::auth::authorize;
+use crate::config;
+use crate::crypto::decrypt_secret;
+use crate::db::{
+    add_audit_log, add_fee, find_order_with_version, get_user_language, resolve_dispute,
+    update_user_disputes,
+};
+use crate::fee::{buyer_payout_amount, cap_dispute_fee, find_trade_fees};
+use crate::lifecycle::Trigger;
+use crate::lightning::LndConnector;
+use crate::messages;
+use crate::payout::{request_payout, split_amount};
+use crate::state::transition;
+use crate::util::{
+    get_user_lang, send_cant_do_msg, send_dm, send_wrong_status_msg, update_order_event,
+};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use tracing::{error, info};
+
+pub async fn admin_settle_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+    ln_client: &mut LndConnector,
+) -> Result<()> {
+    let order_id = msg.order_id.unwrap();
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("AdminSettle: Order Id {order_id} not found!");
+            return Ok(());
+        }
+    };
+    // Only admins/solvers can settle an order
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::AdminSettle,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if transition(&order, &Action::AdminSettle).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    // A dispute taken by a solver is resolved by that solver
+    if !can_resolve_dispute(pool, order.id, &event.pubkey).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::dispute_assigned_to_other(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    // Solvers can split the funds sending the percentage for the buyer,
+    // without it everything goes to the buyer
+    let buyer_percentage = match msg.content {
+        Some(Content::Amount(percentage)) => percentage,
+        _ => 100,
+    };
+    if !(1..=100).contains(&buyer_percentage) {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::invalid_split(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let admin_pubkey = event.pubkey.to_bech32()?;
+    settle_dispute(
+        pool,
+        client,
+        my_keys,
+        ln_client,
+        order,
+        version,
+        buyer_percentage,
+        &admin_pubkey,
+        Some(&event.pubkey),
+    )
+    .await
+}
+
+/// Settle the hold invoice of an order in dispute, paying the buyer the
+/// `buyer_percentage` of the sats and the seller the rest. Both parties are
+/// told, and `admin` too when the request came by DM
+#[allow(clippy::too_many_arguments)]
+pub async fn settle_dispute(
+    pool: &Pool<Sqlite>,
+    client: &Client,
+    my_keys: &Keys,
+    ln_client: &mut LndConnector,
+    mut order: Order,
+    version: i64,
+    buyer_percentage: i64,
+    admin_pubkey: &str,
+    admin: Option<&XOnlyPublicKey>,
+) -> Result<()> {
+    let order_id = order.id;
+    let fees = config::mostro().fees.clone();
+    let preimage = match order.preimage.as_ref() {
+        Some(preimage) => decrypt_secret(preimage)?,
+        None => {
+            error!("AdminSettle: Order Id {order_id} has no preimage!");
+            return Ok(());
+        }
+    };
+    // We update the status before settling the invoice, this way the
+    // invoice subscription knows this settlement was done on purpose
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::CompletedByAdmin,
+        &order,
+        version,
+        None,
+        Trigger::Admin,
+    )
+    .await?;
+    record_fee(pool, &fees, &order).await?;
+    ln_client.settle_hold_invoice(&preimage).await?;
+    info!("AdminSettle: Order Id {}: Released sats", &order.id);
+    order.status = Status::CompletedByAdmin.to_string();
+    // The seller lost the dispute unless the funds were split
+    if buyer_percentage == 100 {
+        if let Some(seller_pubkey) = order.seller_pubkey.as_ref() {
+            update_user_disputes(pool, seller_pubkey, 0, 1).await?;
+        }
+    }
+    let details = if buyer_percentage == 100 {
+        "Dispute resolved in favor of the buyer, hold invoice settled".to_string()
+    } else {
+        format!(
+            "Dispute resolved with a split, {buyer_percentage}% for the buyer and {}% for the seller",
+            100 - buyer_percentage
+        )
+    };
+    add_audit_log(pool, admin_pubkey, "AdminSettle", Some(order.id), &details).await?;
+    resolve_dispute(pool, order.id, "Settled", admin_pubkey, &details).await?;
+    // We send a message to the admin and to both parties
+    let message = Message::new(0, Some(order.id), Action::AdminSettle, None);
+    let message = message.as_json()?;
+    if let Some(admin) = admin {
+        send_dm(client, my_keys, admin, message.clone()).await?;
+    }
+    if let Some(seller_pubkey) = order.seller_pubkey.as_ref() {
+        let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey)?;
+        send_dm(client, my_keys, &seller_pubkey, message.clone()).await?;
+    }
+    if let Some(buyer_pubkey) = order.buyer_pubkey.as_ref() {
+        let buyer_pubkey = XOnlyPublicKey::from_bech32(buyer_pubkey)?;
+        send_dm(client, my_keys, &buyer_pubkey, message).await?;
+    }
+    let dispute_fee = fees.dispute_fee;
+    if buyer_percentage == 100 && dispute_fee == 0 {
+        // Finally we try to pay buyer's invoice
+        return do_payment(order).await;
+    }
+    // Each party is paid its part to a new invoice
+    let trade_fees = find_trade_fees(pool, &fees, &order).await?;
+    let (mut buyer_amount, mut seller_amount) =
+        split_amount(buyer_payout_amount(&order, &trade_fees), buyer_percentage);
+    // The party with the smaller part lost the dispute and pays the dispute
+    // fee out of its part, a seller losing the whole escrow pays it out of
+    // the escrow before the buyer is paid
+    let dispute_fee = if buyer_percentage < 50 || buyer_percentage == 100 {
+        let charged = cap_dispute_fee(buyer_amount, dispute_fee);
+        buyer_amount -= charged;
+        charged
+    } else if buyer_percentage > 50 {
+        let charged = cap_dispute_fee(seller_amount, dispute_fee);
+        seller_amount -= charged;
+        charged
+    } else {
+        0
+    };
+    if dispute_fee > 0 {
+        add_fee(pool, order.id, dispute_fee, "dispute").await?;
+        info!(
+            "AdminSettle: Order Id {}: {dispute_fee} sats dispute fee",
+            order.id
+        );
+    }
+    let parties = [
+        (order.buyer_pubkey.as_ref(), buyer_amount),
+        (order.seller_pubkey.as_ref(), seller_amount),
+    ];
+    for (pubkey, amount) in parties {
+        if let Some(pubkey) = pubkey {
+            if amount > 0 {
+                let lang = get_user_language(pool, pubkey).await?;
+                let text_message = if buyer_percentage == 100 {
+                    messages::dispute_won_fee(lang, dispute_fee, amount)
+                } else {
+                    messages::dispute_split(lang, amount)
+                };
+                request_payout(
+                    pool,
+                    client,
+                    my_keys,
+                    order.id,
+                    pubkey,
+                    amount,
+                    text_message,
+                )
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::accounting::parse_date;
+use crate::messages;
+use crate::stats::get_stats;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+
+/// Admins ask for the statistics of the orders sending a DM tagged with
+/// `["stats"]`, completed trades can be counted since a date or unix time
+/// (`["stats", "2023-05-01"]`)
+pub fn get_stats_query(event: &Event) -> Option<Option<String>> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "stats" => {
+            Some(values.first().cloned())
+        }
+        _ => None,
+    })
+}
+
+pub async fn admin_stats_action(
+    since: Option<&str>,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let since = match since {
+        Some(since) => parse_date(since, false),
+        None => Some(0),
+    };
+    let since = match since {
+        Some(since) if is_admin(&event.pubkey) => since,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let stats = serde_json::to_string(&get_stats(since).await?)?;
+    send_dm(client, my_keys, &event.pubkey, stats).await?;
+
+    Ok(())
+}
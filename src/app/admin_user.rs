@@ -0,0 +1,72 @@
+use crate::db::{add_audit_log, find_user, update_user_banned};
+use crate::messages;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use std::str::FromStr;
+use tracing::info;
+
+/// Admins query a user sending a DM tagged with `["user", <pubkey>]`, adding
+/// `ban` or `unban` after the pubkey changes the ban status of the user
+pub fn get_user_query(event: &Event) -> Option<(String, Option<String>)> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "user" => values
+            .first()
+            .map(|pubkey| (pubkey.to_string(), values.get(1).cloned())),
+        _ => None,
+    })
+}
+
+pub async fn admin_user_action(
+    pubkey: &str,
+    command: Option<&str>,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let user_pubkey = XOnlyPublicKey::from_bech32(pubkey)
+        .ok()
+        .or_else(|| XOnlyPublicKey::from_str(pubkey).ok());
+    let user_pubkey = match user_pubkey {
+        Some(pk) if is_admin(&event.pubkey) => pk.to_bech32()?,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let admin_pubkey = event.pubkey.to_bech32()?;
+    let is_banned = match command {
+        Some("ban") => Some(true),
+        Some("unban") => Some(false),
+        _ => None,
+    };
+    if let Some(is_banned) = is_banned {
+        update_user_banned(pool, &user_pubkey, is_banned).await?;
+        let action = if is_banned { "BanUser" } else { "UnbanUser" };
+        add_audit_log(pool, &admin_pubkey, action, None, &user_pubkey).await?;
+        info!("{action}: {user_pubkey} by admin {admin_pubkey}");
+    }
+    // We answer the admin with the user record
+    let user = find_user(pool, &user_pubkey).await?;
+    let message = Message::new(
+        0,
+        None,
+        Action::CantDo,
+        Some(Content::TextMessage(serde_json::to_string(&user)?)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
@@ -1,17 +1,21 @@
+use crate::auth::authorize;
 use crate::db::{
-    edit_buyer_pubkey_order, edit_seller_pubkey_order, init_cancel_order,
+    edit_buyer_pubkey_order, edit_seller_pubkey_order, find_order_with_version, init_cancel_order,
     update_order_to_initial_state,
 };
+use crate::lifecycle::Trigger;
 use crate::lightning::LndConnector;
-use crate::messages;
-use crate::util::{send_dm, update_order_event};
+use crate::state::{is_repeated, order_status, transition};
+use crate::util::{
+    delete_order_event, reset_market_amounts, send_dm, send_wrong_status_msg, update_order_event,
+};
 use anyhow::Result;
-use log::{error, info};
 use mostro_core::order::Order;
-use mostro_core::{Action, Content, Message, Status};
+use mostro_core::{Action, Message, Status};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
 use sqlx_crud::Crud;
+use tracing::{error, info};
 
 pub async fn cancel_action(
     msg: Message,
@@ -22,47 +26,82 @@ pub async fn cancel_action(
     ln_client: &mut LndConnector,
 ) -> Result<()> {
     let order_id = msg.order_id.unwrap();
-    let mut order = match Order::by_id(pool, order_id).await? {
+    let (mut order, version) = match find_order_with_version(pool, order_id).await? {
         Some(order) => order,
         None => {
             error!("Cancel: Order Id {order_id} not found!");
             return Ok(());
         }
     };
-    if order.status == "Pending" {
+    // A repeated Cancel from a party gets the same answer without canceling again
+    if is_repeated(&order, &Action::Cancel) {
         let user_pubkey = event.pubkey.to_bech32()?;
-        // Validates if this user is the order creator
-        if user_pubkey != order.creator_pubkey {
-            let text_message = messages::cant_do();
-            // We create a Message
-            let message = Message::new(
-                0,
-                Some(order.id),
-                Action::CantDo,
-                Some(Content::TextMessage(text_message)),
-            );
+        let parties = [
+            Some(&order.creator_pubkey),
+            order.buyer_pubkey.as_ref(),
+            order.seller_pubkey.as_ref(),
+        ];
+        if parties.contains(&Some(&user_pubkey)) {
+            let message = Message::new(0, Some(order.id), Action::Cancel, None);
             let message = message.as_json()?;
             send_dm(client, my_keys, &event.pubkey, message).await?;
             return Ok(());
         }
+    }
+    if transition(&order, &Action::Cancel).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    // Only the maker can cancel a pending order, after it is taken only the
+    // buyer or the seller
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::Cancel,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    let status = order_status(&order)?;
+    if status == Status::Pending {
         // We publish a new replaceable kind nostr event with the status updated
         // and update on local database the status and new event id
-        update_order_event(pool, client, my_keys, Status::Canceled, &order, None).await?;
+        update_order_event(
+            pool,
+            client,
+            my_keys,
+            Status::Canceled,
+            &order,
+            version,
+            None,
+            Trigger::User,
+        )
+        .await?;
+        // The offer is pulled from the book, relays can drop its event
+        if let Some(canceled) = Order::by_id(pool, order.id).await? {
+            delete_order_event(client, my_keys, &canceled.event_id).await?;
+        }
+        info!("Cancel: Order Id {order_id} canceled by the maker");
         // We create a Message for cancel
         let message = Message::new(0, Some(order.id), Action::Cancel, None);
         let message = message.as_json()?;
         send_dm(client, my_keys, &event.pubkey, message).await?;
     }
 
-    if order.kind == "Sell" && order.status == "WaitingBuyerInvoice" {
-        cancel_add_invoice(ln_client, &mut order, event, pool, client, my_keys).await?;
+    if status == Status::WaitingBuyerInvoice {
+        cancel_add_invoice(ln_client, &mut order, version, event, pool, client, my_keys).await?;
     }
 
-    if order.kind == "Buy" && order.status == "WaitingPayment" {
-        cancel_pay_hold_invoice(ln_client, &mut order, event, pool, client, my_keys).await?;
+    if status == Status::WaitingPayment {
+        cancel_pay_hold_invoice(ln_client, &mut order, version, event, pool, client, my_keys)
+            .await?;
     }
 
-    if order.status == "Active" || order.status == "FiatSent" || order.status == "Dispute" {
+    if matches!(status, Status::Active | Status::FiatSent | Status::Dispute) {
         let user_pubkey = event.pubkey.to_bech32()?;
         let buyer_pubkey_bech32 = order.buyer_pubkey.as_ref().unwrap();
         let seller_pubkey_bech32 = order.seller_pubkey.as_ref().unwrap();
@@ -78,34 +117,33 @@ pub async fn cancel_action(
         match order.cancel_initiator_pubkey {
             Some(ref initiator_pubkey) => {
                 if initiator_pubkey == &user_pubkey {
-                    let text_message = messages::cant_do();
-                    // We create a Message
+                    // A repeated cancel from the initiator gets the same answer
                     let message = Message::new(
                         0,
                         Some(order.id),
-                        Action::CantDo,
-                        Some(Content::TextMessage(text_message)),
+                        Action::CooperativeCancelInitiatedByYou,
+                        None,
                     );
                     let message = message.as_json()?;
                     send_dm(client, my_keys, &event.pubkey, message).await?;
 
                     return Ok(());
                 } else {
-                    if order.hash.is_some() {
-                        // We return funds to seller
-                        let hash = order.hash.as_ref().unwrap();
-                        ln_client.cancel_hold_invoice(hash).await?;
-                        info!(
-                            "Cooperative cancel: Order Id {}: Funds returned to seller",
-                            &order.id
-                        );
-                    }
                     init_cancel_order(pool, &order).await?;
-                    order.status = "Canceled".to_string();
                     // We publish a new replaceable kind nostr event with the status updated
                     // and update on local database the status and new event id
-                    update_order_event(pool, client, my_keys, Status::Canceled, &order, None)
-                        .await?;
+                    update_order_event(
+                        pool,
+                        client,
+                        my_keys,
+                        Status::Canceled,
+                        &order,
+                        version,
+                        None,
+                        Trigger::User,
+                    )
+                    .await?;
+                    return_funds(ln_client, &order).await?;
                     // We create a Message for an accepted cooperative cancel and send it to both parties
                     let message =
                         Message::new(0, Some(order.id), Action::CooperativeCancelAccepted, None);
@@ -147,40 +185,30 @@ pub async fn cancel_action(
 pub async fn cancel_add_invoice(
     ln_client: &mut LndConnector,
     order: &mut Order,
+    version: i64,
     event: &Event,
     pool: &Pool<Sqlite>,
     client: &Client,
     my_keys: &Keys,
 ) -> Result<()> {
-    if order.hash.is_some() {
-        // We return funds to seller
-        let hash = order.hash.as_ref().unwrap();
-        ln_client.cancel_hold_invoice(hash).await?;
-        info!("Cancel: Order Id {}: Funds returned to seller", &order.id);
-    }
-    let user_pubkey = event.pubkey.to_bech32()?;
     let buyer_pubkey_bech32 = order.buyer_pubkey.as_ref().unwrap();
     let seller_pubkey = order.seller_pubkey.as_ref().cloned().unwrap();
     let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey)?;
-    if buyer_pubkey_bech32 != &user_pubkey {
-        let text_message = messages::cant_do();
-        // We create a Message
-        let message = Message::new(
-            0,
-            Some(order.id),
-            Action::CantDo,
-            Some(Content::TextMessage(text_message)),
-        );
-        let message = message.as_json()?;
-        send_dm(client, my_keys, &event.pubkey, message).await?;
-
-        return Ok(());
-    }
-
     if &order.creator_pubkey == buyer_pubkey_bech32 {
         // We publish a new replaceable kind nostr event with the status updated
         // and update on local database the status and new event id
-        update_order_event(pool, client, my_keys, Status::Canceled, order, None).await?;
+        update_order_event(
+            pool,
+            client,
+            my_keys,
+            Status::Canceled,
+            order,
+            version,
+            None,
+            Trigger::User,
+        )
+        .await?;
+        return_funds(ln_client, order).await?;
         // We create a Message for cancel
         let message = Message::new(0, Some(order.id), Action::Cancel, None);
         let message = message.as_json()?;
@@ -190,13 +218,24 @@ pub async fn cancel_add_invoice(
     } else {
         // We re-publish the event with Pending status
         // and update on local database
-        if order.price_from_api {
-            order.amount = 0;
-            order.fee = 0;
-        }
+        reset_market_amounts(pool, order).await?;
         edit_buyer_pubkey_order(pool, order.id, None).await?;
-        update_order_to_initial_state(pool, order.id, order.amount, order.fee).await?;
-        update_order_event(pool, client, my_keys, Status::Pending, order, None).await?;
+        update_order_to_initial_state(pool, order.id, order.amount, order.fee, Trigger::User)
+            .await?;
+        // Back to Pending, one status change after the version read
+        order.status = Status::Pending.to_string();
+        update_order_event(
+            pool,
+            client,
+            my_keys,
+            Status::Pending,
+            order,
+            version + 1,
+            None,
+            Trigger::User,
+        )
+        .await?;
+        return_funds(ln_client, order).await?;
         info!(
             "Buyer: {}: Canceled order Id {} republishing order",
             buyer_pubkey_bech32, order.id
@@ -208,40 +247,30 @@ pub async fn cancel_add_invoice(
 pub async fn cancel_pay_hold_invoice(
     ln_client: &mut LndConnector,
     order: &mut Order,
+    version: i64,
     event: &Event,
     pool: &Pool<Sqlite>,
     client: &Client,
     my_keys: &Keys,
 ) -> Result<()> {
-    if order.hash.is_some() {
-        // We return funds to seller
-        let hash = order.hash.as_ref().unwrap();
-        ln_client.cancel_hold_invoice(hash).await?;
-        info!("Cancel: Order Id {}: Funds returned to seller", &order.id);
-    }
-    let user_pubkey = event.pubkey.to_bech32()?;
     let buyer_pubkey_bech32 = order.buyer_pubkey.as_ref().unwrap();
     let seller_pubkey_bech32 = order.seller_pubkey.as_ref().unwrap();
     let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey_bech32)?;
-    if seller_pubkey_bech32 != &user_pubkey {
-        let text_message = messages::cant_do();
-        // We create a Message
-        let message = Message::new(
-            0,
-            Some(order.id),
-            Action::CantDo,
-            Some(Content::TextMessage(text_message)),
-        );
-        let message = message.as_json()?;
-        send_dm(client, my_keys, &event.pubkey, message).await?;
-
-        return Ok(());
-    }
-
     if &order.creator_pubkey == seller_pubkey_bech32 {
         // We publish a new replaceable kind nostr event with the status updated
         // and update on local database the status and new event id
-        update_order_event(pool, client, my_keys, Status::Canceled, order, None).await?;
+        update_order_event(
+            pool,
+            client,
+            my_keys,
+            Status::Canceled,
+            order,
+            version,
+            None,
+            Trigger::User,
+        )
+        .await?;
+        return_funds(ln_client, order).await?;
         // We create a Message for cancel
         let message = Message::new(0, Some(order.id), Action::Cancel, None);
         let message = message.as_json()?;
@@ -251,13 +280,24 @@ pub async fn cancel_pay_hold_invoice(
     } else {
         // We re-publish the event with Pending status
         // and update on local database
-        if order.price_from_api {
-            order.amount = 0;
-            order.fee = 0;
-        }
+        reset_market_amounts(pool, order).await?;
         edit_seller_pubkey_order(pool, order.id, None).await?;
-        update_order_to_initial_state(pool, order.id, order.amount, order.fee).await?;
-        update_order_event(pool, client, my_keys, Status::Pending, order, None).await?;
+        update_order_to_initial_state(pool, order.id, order.amount, order.fee, Trigger::User)
+            .await?;
+        // Back to Pending, one status change after the version read
+        order.status = Status::Pending.to_string();
+        update_order_event(
+            pool,
+            client,
+            my_keys,
+            Status::Pending,
+            order,
+            version + 1,
+            None,
+            Trigger::User,
+        )
+        .await?;
+        return_funds(ln_client, order).await?;
         info!(
             "Seller: {}: Canceled order Id {} republishing order",
             buyer_pubkey_bech32, order.id
@@ -265,3 +305,16 @@ pub async fn cancel_pay_hold_invoice(
         Ok(())
     }
 }
+
+/// Cancel the hold invoice of the seller, called once the new status is
+/// saved so the invoice subscription knows this cancelation was done on
+/// purpose
+async fn return_funds(ln_client: &mut LndConnector, order: &Order) -> Result<()> {
+    if let Some(hash) = order.hash.as_ref() {
+        // We return funds to seller
+        ln_client.cancel_hold_invoice(hash).await?;
+        info!("Cancel: Order Id {}: Funds returned to seller", &order.id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,356 @@
+use crate::alerts::{self, Alert};
+use crate::auth::authorize;
+use crate::db::{
+    add_dispute, add_dispute_message, find_dispute_by_id, find_dispute_messages, find_open_dispute,
+    find_order_messages, find_order_with_version, take_dispute, update_order_dispute,
+    update_user_disputes,
+};
+use crate::lifecycle::Trigger;
+use crate::messages;
+use crate::models::Dispute;
+use crate::state::{order_status, transition};
+use crate::util::{
+    get_admin_pubkeys, get_user_lang, is_admin, send_cant_do_msg, send_dm, send_wrong_status_msg,
+    update_order_event,
+};
+use crate::webhook::{self, WebhookEvent};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::*;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use sqlx_crud::Crud;
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub async fn dispute_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let order_id = msg.order_id.unwrap();
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("Dispute: Order Id {order_id} not found!");
+            return Ok(());
+        }
+    };
+    let user_pubkey = event.pubkey.to_bech32()?;
+    // Only buyer or seller can open a dispute on an active order
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::Dispute,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if transition(&order, &Action::Dispute).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    open_dispute(
+        pool,
+        client,
+        my_keys,
+        &order,
+        version,
+        &user_pubkey,
+        Trigger::User,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Move an order to Dispute status and let both parties and solvers know
+/// the dispute id they must use to tag their messages, `trigger` tells a
+/// dispute of a party from one opened by the scheduler
+pub async fn open_dispute(
+    pool: &Pool<Sqlite>,
+    client: &Client,
+    my_keys: &Keys,
+    order: &Order,
+    version: i64,
+    initiator_pubkey: &str,
+    trigger: Trigger,
+) -> Result<Dispute> {
+    let buyer_dispute = order.buyer_pubkey.as_deref() == Some(initiator_pubkey);
+    let seller_dispute = order.seller_pubkey.as_deref() == Some(initiator_pubkey);
+    update_order_dispute(pool, order.id, buyer_dispute, seller_dispute).await?;
+    // We publish a new replaceable kind nostr event with the status updated
+    // and update on local database the status and new event id
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::Dispute,
+        order,
+        version,
+        None,
+        trigger,
+    )
+    .await?;
+    let dispute = add_dispute(pool, order.id, initiator_pubkey).await?;
+    update_user_disputes(pool, initiator_pubkey, 1, 0).await?;
+    let dispute_id = dispute.id.to_string();
+    info!("Order Id {}: Dispute {dispute_id} opened", order.id);
+    webhook::notify(
+        WebhookEvent::DisputeOpened,
+        json!({
+            "order_id": order.id,
+            "dispute_id": dispute.id,
+            "initiator_pubkey": initiator_pubkey,
+            "trigger": trigger.to_string(),
+        }),
+    );
+    alerts::spawn_send(
+        Alert::DisputeOpened,
+        format!("Dispute {dispute_id} opened on order {}", order.id),
+    );
+
+    let parties = [order.buyer_pubkey.as_ref(), order.seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        let action = if pubkey == initiator_pubkey {
+            Action::DisputeInitiatedByYou
+        } else {
+            Action::DisputeInitiatedByPeer
+        };
+        // We send the dispute id to both parties
+        let message = Message::new(
+            0,
+            Some(order.id),
+            action,
+            Some(Content::TextMessage(dispute_id.clone())),
+        );
+        let message = message.as_json()?;
+        let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+        send_dm(client, my_keys, &pubkey, message).await?;
+    }
+    // We let the solvers know there is a new dispute
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::Dispute,
+        Some(Content::TextMessage(dispute_id)),
+    );
+    let message = message.as_json()?;
+    for admin_pubkey in get_admin_pubkeys().iter() {
+        send_dm(client, my_keys, admin_pubkey, message.clone()).await?;
+    }
+
+    Ok(dispute)
+}
+
+/// Get the dispute id from a `dispute` tag on the event, if any
+pub fn get_dispute_id(event: &Event) -> Option<Uuid> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "dispute" => {
+            values.first().and_then(|id| Uuid::parse_str(id).ok())
+        }
+        _ => None,
+    })
+}
+
+/// Solvers take a dispute tagging a DM with `["take_dispute", <dispute id>]`
+pub fn get_take_dispute_query(event: &Event) -> Option<Uuid> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "take_dispute" => {
+            values.first().and_then(|id| Uuid::parse_str(id).ok())
+        }
+        _ => None,
+    })
+}
+
+/// Assign a dispute to the solver who asked for it, the solver gets the
+/// dispute and both parties are told someone is looking into it
+pub async fn take_dispute_action(
+    dispute_id: Uuid,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let solver_pubkey = event.pubkey.to_bech32()?;
+    let taken = is_admin(&event.pubkey) && take_dispute(pool, dispute_id, &solver_pubkey).await?;
+    let dispute = match find_dispute_by_id(pool, dispute_id).await {
+        Ok(dispute) if taken => dispute,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "Order Id {}: Dispute {dispute_id} taken by {solver_pubkey}",
+        dispute.order_id
+    );
+    let message = Message::new(
+        0,
+        Some(dispute.order_id),
+        Action::Dispute,
+        Some(Content::TextMessage(serde_json::to_string(&dispute)?)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+    if let Some(order) = Order::by_id(pool, dispute.order_id).await? {
+        let parties = [order.buyer_pubkey.as_ref(), order.seller_pubkey.as_ref()];
+        for pubkey in parties.into_iter().flatten() {
+            let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+            let message = Message::new(
+                0,
+                Some(order.id),
+                Action::Dispute,
+                Some(Content::TextMessage(messages::dispute_taken(
+                    get_user_lang(pool, &pubkey).await,
+                ))),
+            );
+            let message = message.as_json()?;
+            send_dm(client, my_keys, &pubkey, message).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A dispute taken by a solver can only be resolved by that solver, anyone
+/// of the admins can resolve it before it is taken
+pub async fn can_resolve_dispute(
+    pool: &Pool<Sqlite>,
+    order_id: Uuid,
+    solver_pubkey: &XOnlyPublicKey,
+) -> Result<bool> {
+    let solver_pubkey = solver_pubkey.to_bech32()?;
+    let can_resolve = match find_open_dispute(pool, order_id).await? {
+        Some(Dispute {
+            solver_pubkey: Some(solver),
+            ..
+        }) => solver == solver_pubkey,
+        _ => true,
+    };
+
+    Ok(can_resolve)
+}
+
+/// Solvers ask for every message exchanged about a disputed order tagging a
+/// DM with `["timeline", <dispute id>]`
+pub fn get_timeline_query(event: &Event) -> Option<Uuid> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "timeline" => {
+            values.first().and_then(|id| Uuid::parse_str(id).ok())
+        }
+        _ => None,
+    })
+}
+
+/// Answer a solver with the timeline of the order in dispute, the action,
+/// direction, time and payload hash of every message about the order
+pub async fn dispute_timeline_action(
+    dispute_id: Uuid,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let dispute = match find_dispute_by_id(pool, dispute_id).await {
+        Ok(dispute) if is_admin(&event.pubkey) => dispute,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let timeline = find_order_messages(pool, dispute.order_id).await?;
+    let timeline = serde_json::to_string(&timeline)?;
+    let message = Message::new(
+        0,
+        Some(dispute.order_id),
+        Action::Dispute,
+        Some(Content::TextMessage(timeline)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
+
+/// Messages tagged with a dispute id are saved as evidence when they come
+/// from the parties, when a solver sends one we answer with the whole log
+pub async fn dispute_message_action(
+    dispute_id: Uuid,
+    content: &str,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let dispute = match find_dispute_by_id(pool, dispute_id).await {
+        Ok(dispute) => dispute,
+        Err(_) => {
+            error!("Dispute Id {dispute_id} not found!");
+            return Ok(());
+        }
+    };
+    if is_admin(&event.pubkey) {
+        let dispute_messages = find_dispute_messages(pool, dispute.id).await?;
+        let log = serde_json::to_string(&dispute_messages)?;
+        let message = Message::new(
+            0,
+            Some(dispute.order_id),
+            Action::Dispute,
+            Some(Content::TextMessage(log)),
+        );
+        let message = message.as_json()?;
+        send_dm(client, my_keys, &event.pubkey, message).await?;
+        return Ok(());
+    }
+    let order = match Order::by_id(pool, dispute.order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("Dispute: Order Id {} not found!", dispute.order_id);
+            return Ok(());
+        }
+    };
+    let sender_pubkey = event.pubkey.to_bech32()?;
+    let is_party = order.buyer_pubkey.as_ref() == Some(&sender_pubkey)
+        || order.seller_pubkey.as_ref() == Some(&sender_pubkey);
+    // Evidence is only accepted from the parties while the dispute is open
+    if !is_party || order_status(&order) != Ok(Status::Dispute) {
+        let text_message = messages::cant_do(get_user_lang(pool, &event.pubkey).await);
+        // We create a Message
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::CantDo,
+            Some(Content::TextMessage(text_message)),
+        );
+        let message = message.as_json()?;
+        send_dm(client, my_keys, &event.pubkey, message).await?;
+        return Ok(());
+    }
+    add_dispute_message(pool, dispute.id, &sender_pubkey, content).await?;
+    info!("Dispute Id {dispute_id}: New message from {sender_pubkey}");
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::app::order::validate_order_terms;
+use crate::auth::authorize;
+use crate::config;
+use crate::db::{edit_order_terms, find_order_with_version};
+use crate::error::MostroError;
+use crate::lifecycle::Trigger;
+use crate::messages;
+use crate::state::transition;
+use crate::util::{
+    get_user_lang, send_cant_do_msg, send_dm, send_wrong_status_msg, update_order_event,
+};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use sqlx_crud::Crud;
+use tracing::{error, info};
+
+/// Makers change the amounts, premium or payment method of a pending order,
+/// the order keeps its id and its event is replaced
+pub async fn edit_order_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let order_id = match msg.order_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let order = match Order::by_id(pool, order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("EditOrder: Order Id {order_id} not found!");
+            return Ok(());
+        }
+    };
+    // Only the maker can edit an order and only before it is taken
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::EditOrder,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if transition(&order, &Action::EditOrder).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    let new_terms = match msg.get_order() {
+        Some(new_terms) => new_terms,
+        None => {
+            error!("EditOrder: Order Id {order_id} without new terms");
+            return Ok(());
+        }
+    };
+    // Kind and currency are part of the order identity and can't be edited
+    if new_terms.kind.to_string() != order.kind || new_terms.fiat_code != order.fiat_code {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::cant_edit_order_identity(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let lang = get_user_lang(pool, &event.pubkey).await;
+    if let Some(text_message) = validate_order_terms(new_terms, &config::mostro().limits, lang) {
+        send_cant_do_msg(client, my_keys, Some(order.id), text_message, &event.pubkey).await?;
+        return Ok(());
+    }
+    // Only a pending order is edited, if it was taken meanwhile nothing changes
+    if !edit_order_terms(pool, order.id, new_terms).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            MostroError::AlreadyTaken.to_string(),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    info!("Order Id {}: terms edited by the maker", order.id);
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
+        Some(order) => order,
+        None => return Ok(()),
+    };
+    // We publish the order again replacing the previous event
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::Pending,
+        &order,
+        version,
+        None,
+        Trigger::User,
+    )
+    .await?;
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::EditOrder,
+        Some(Content::Order(order.as_new_order())),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
@@ -1,13 +1,14 @@
-use crate::messages;
-use crate::util::{send_dm, update_order_event};
+use crate::auth::authorize;
+use crate::db::{find_order_with_version, update_order_fiat_sent};
+use crate::lifecycle::Trigger;
+use crate::state::{is_repeated, transition};
+use crate::util::{send_dm, send_wrong_status_msg, update_order_event};
 
 use anyhow::Result;
-use log::error;
-use mostro_core::order::Order;
 use mostro_core::{Action, Content, Message, Peer, Status};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
-use sqlx_crud::Crud;
+use tracing::error;
 
 pub async fn fiat_sent_action(
     msg: Message,
@@ -17,35 +18,63 @@ pub async fn fiat_sent_action(
     pool: &Pool<Sqlite>,
 ) -> Result<()> {
     let order_id = msg.order_id.unwrap();
-    let order = match Order::by_id(pool, order_id).await? {
+    let (order, version) = match find_order_with_version(pool, order_id).await? {
         Some(order) => order,
         None => {
             error!("FiatSent: Order Id {order_id} not found!");
             return Ok(());
         }
     };
-    // TODO: send to user a DM with the error
-    if order.status != "Active" {
-        error!("FiatSent: Order Id {order_id} wrong status");
+    // A repeated FiatSent gets the same answer without notifying the seller again
+    if is_repeated(&order, &Action::FiatSent)
+        && Some(event.pubkey.to_bech32()?) == order.buyer_pubkey
+    {
+        if let Some(seller_pubkey) = order.seller_pubkey.as_ref() {
+            let peer = Peer::new(seller_pubkey.to_string());
+            let message = Message::new(
+                0,
+                Some(order.id),
+                Action::FiatSent,
+                Some(Content::Peer(peer)),
+            );
+            let message = message.as_json()?;
+            send_dm(client, my_keys, &event.pubkey, message).await?;
+        }
+        return Ok(());
+    }
+    // Only the buyer can tell the fiat was sent
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::FiatSent,
+        &event.pubkey,
+    )
+    .await?
+    {
         return Ok(());
     }
-    // Check if the pubkey is the buyer
-    if Some(event.pubkey.to_bech32()?) != order.buyer_pubkey {
-        let text_message = messages::cant_do();
-        // We create a Message
-        let message = Message::new(
-            0,
-            Some(order.id),
-            Action::CantDo,
-            Some(Content::TextMessage(text_message)),
-        );
-        let message = message.as_json()?;
-        send_dm(client, my_keys, &event.pubkey, message).await?;
+    if transition(&order, &Action::FiatSent).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
     }
 
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    update_order_event(pool, client, my_keys, Status::FiatSent, &order, None).await?;
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::FiatSent,
+        &order,
+        version,
+        None,
+        Trigger::User,
+    )
+    .await?;
+    // We start the clock for the seller to release
+    update_order_fiat_sent(pool, order.id, Timestamp::now().as_i64()).await?;
 
     let seller_pubkey = match order.seller_pubkey.as_ref() {
         Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
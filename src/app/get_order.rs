@@ -0,0 +1,127 @@
+use crate::auth::authorize;
+use crate::db::{
+    find_archived_order, find_order_detail, find_order_events, find_payments, find_payout_parts,
+    find_payouts, find_user_order_messages,
+};
+use crate::messages;
+use crate::models::OrderRecord;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg, send_dm};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use sqlx_crud::Crud;
+use tracing::error;
+use uuid::Uuid;
+
+pub async fn get_order_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let order_id = match msg.order_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    // Finished orders can be in the archive
+    let order = match Order::by_id(pool, order_id).await? {
+        Some(order) => order,
+        None => match find_archived_order(pool, order_id).await? {
+            Some(order) => order,
+            None => {
+                error!("GetOrder: Order Id {order_id} not found!");
+                return Ok(());
+            }
+        },
+    };
+    // Only the buyer and the seller can see the whole record
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::GetOrder,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    let detail = match find_order_detail(pool, order.id).await? {
+        Some(detail) => detail,
+        None => return Ok(()),
+    };
+    let pubkey = event.pubkey.to_bech32()?;
+    // The parts of a split payout are only shown to the buyer who sent them
+    let payout_parts = if order.buyer_pubkey.as_ref() == Some(&pubkey) {
+        find_payout_parts(pool, order.id).await?
+    } else {
+        vec![]
+    };
+    let record = OrderRecord {
+        order: detail,
+        payouts: find_payouts(pool, order.id, &pubkey).await?,
+        payout_parts,
+        payments: find_payments(pool, order.id, &pubkey).await?,
+        events: find_order_events(pool, order.id).await?,
+        messages: find_user_order_messages(pool, order.id, &pubkey).await?,
+    };
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::GetOrder,
+        Some(Content::TextMessage(serde_json::to_string(&record)?)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
+
+/// Admins ask for the status transitions of any order tagging a DM with
+/// `["events", <order id>]`
+pub fn get_events_query(event: &Event) -> Option<Uuid> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "events" => {
+            values.first().and_then(|id| Uuid::parse_str(id).ok())
+        }
+        _ => None,
+    })
+}
+
+/// Answer an admin with every status transition of an order, when it
+/// happened and what triggered it
+pub async fn order_events_action(
+    order_id: Uuid,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    if !is_admin(&event.pubkey) {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order_id),
+            messages::cant_do(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    let events = find_order_events(pool, order_id).await?;
+    let message = Message::new(
+        0,
+        Some(order_id),
+        Action::GetOrder,
+        Some(Content::TextMessage(serde_json::to_string(&events)?)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::db::{count_user_orders, find_user_orders};
+use crate::models::OrderPage;
+use crate::util::send_dm;
+
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+
+/// Number of orders sent on every page
+pub const ORDERS_PAGE_SIZE: i64 = 20;
+
+/// Users ask for their orders sending a DM tagged with `["orders"]`, an
+/// optional page number can follow, the first page is 1
+pub fn get_orders_query(event: &Event) -> Option<i64> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "orders" => Some(
+            values
+                .first()
+                .and_then(|page| page.parse::<i64>().ok())
+                .unwrap_or(1)
+                .max(1),
+        ),
+        _ => None,
+    })
+}
+
+/// Send a user one page of the orders it made or took, newest first
+pub async fn list_orders_action(
+    page: i64,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    let pubkey = event.pubkey.to_bech32()?;
+    let orders = find_user_orders(
+        pool,
+        &pubkey,
+        ORDERS_PAGE_SIZE,
+        (page - 1) * ORDERS_PAGE_SIZE,
+    )
+    .await?;
+    let order_page = OrderPage {
+        page,
+        page_size: ORDERS_PAGE_SIZE,
+        total: count_user_orders(pool, &pubkey).await?,
+        orders,
+    };
+    let order_page = serde_json::to_string(&order_page)?;
+    send_dm(client, my_keys, &event.pubkey, order_page).await?;
+
+    Ok(())
+}
@@ -1,11 +1,110 @@
-use crate::util::publish_order;
+use crate::config::{self, LimitSettings};
+use crate::currency::{get_blocked_currencies, get_supported_currencies, is_supported_currency};
+use crate::db::{
+    add_recurring_order, add_trade_index, add_user_if_not_exists, count_active_orders,
+    is_user_banned,
+};
+use crate::denomination::Denomination;
+use crate::description::{get_description_tag, sanitize_description, MAX_DESCRIPTION_LENGTH};
+use crate::error::MostroError;
+use crate::i18n::Lang;
+use crate::limits::{exceeded_new_user_limit, exceeds_daily_volume, OrderLimits};
+use crate::messages;
+use crate::models::OrderExtras;
+use crate::payment_method::{
+    get_blocked_methods, methods_for_currency, normalize, normalize_payment_methods,
+};
+use crate::recurring::{get_max_repeats, get_recurring_tag};
+use crate::region::{get_region_tag, Region};
+use crate::trade_index::{get_trade_index_tag, next_trade_index};
+use crate::util::{get_user_lang, publish_order, send_cant_do_msg};
 
 use anyhow::Result;
+use mostro_core::order::NewOrder;
 use mostro_core::Message;
 use nostr_sdk::prelude::ToBech32;
 use nostr_sdk::{Client, Event, Keys};
 use sqlx::{Pool, Sqlite};
 
+/// Check the terms of a new or edited order, returns the reason to reject it
+pub fn validate_order_terms(
+    order: &NewOrder,
+    limits: &LimitSettings,
+    lang: Lang,
+) -> Option<String> {
+    // Operators can refuse some currencies and payment methods
+    let blocked_currencies = get_blocked_currencies();
+    if blocked_currencies.contains(&order.fiat_code.to_uppercase()) {
+        return Some(messages::blocked_currency(
+            lang,
+            &order.fiat_code,
+            &blocked_currencies,
+        ));
+    }
+    let blocked_methods = get_blocked_methods();
+    if let Some(method) = order
+        .payment_method
+        .split(',')
+        .find(|method| matches!(normalize(method), Some(name) if blocked_methods.contains(&name)))
+    {
+        return Some(messages::blocked_payment_method(
+            lang,
+            method.trim(),
+            &blocked_methods,
+        ));
+    }
+    // Only ISO 4217 currencies allowed by the operator are accepted
+    if !is_supported_currency(&order.fiat_code) {
+        return Some(messages::unsupported_currency(
+            lang,
+            &order.fiat_code,
+            &get_supported_currencies(),
+        ));
+    }
+    // Payment methods must be known and available for the currency
+    if normalize_payment_methods(&order.payment_method, &order.fiat_code).is_none() {
+        return Some(messages::unsupported_payment_method(
+            lang,
+            &order.payment_method,
+            &methods_for_currency(&order.fiat_code),
+        ));
+    }
+    // Range orders need a valid range and are always market price orders
+    let valid_range = match (order.min_amount, order.max_amount) {
+        (None, None) => true,
+        (Some(min), Some(max)) => min > 0 && min < max && order.amount == 0,
+        _ => false,
+    };
+    if !valid_range {
+        return Some(messages::invalid_range(lang));
+    }
+    // Makers fix the sats amount, the fiat amount or both
+    if order.amount == 0 && order.fiat_amount == 0 && order.max_amount.is_none() {
+        return Some(messages::invalid_amounts(lang));
+    }
+    // Order size must be inside the operator limits, both ends of a range
+    let limits = OrderLimits::from_settings(limits);
+    // Sats denominated orders have no fiat amount until they are taken
+    let fiat_amounts = match (order.min_amount, order.max_amount) {
+        (Some(min), Some(max)) => vec![min, max],
+        _ if order.fiat_amount == 0 => vec![],
+        _ => vec![order.fiat_amount],
+    };
+    if !limits.is_valid_amount(order.amount)
+        || !fiat_amounts
+            .iter()
+            .all(|fiat_amount| limits.is_valid_fiat_amount(*fiat_amount))
+    {
+        return Some(messages::order_out_of_limits(
+            lang,
+            &limits,
+            &order.fiat_code,
+        ));
+    }
+
+    None
+}
+
 pub async fn order_action(
     msg: Message,
     event: &Event,
@@ -14,9 +113,120 @@ pub async fn order_action(
     pool: &Pool<Sqlite>,
 ) -> Result<()> {
     if let Some(order) = msg.get_order() {
+        let lang = get_user_lang(pool, &event.pubkey).await;
+        let limits = config::mostro().limits.clone();
+        if let Some(text_message) = validate_order_terms(order, &limits, lang) {
+            send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+            return Ok(());
+        }
+        // Recurring orders are published again up to MAX_ORDER_REPEATS times
+        let repeats = match get_recurring_tag(event).map(|times| times.parse::<i64>()) {
+            None => 0,
+            Some(Ok(times)) if (1..=get_max_repeats()).contains(&times) => times,
+            Some(_) => {
+                let text_message = messages::invalid_recurring(lang, get_max_repeats());
+                send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+                return Ok(());
+            }
+        };
+        // Face-to-face orders can be discovered by region
+        let region = match get_region_tag(event) {
+            Some(region) => match Region::parse(&region) {
+                Some(region) => Some(region),
+                None => {
+                    let text_message = messages::invalid_region(lang, &region);
+                    send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        // Makers can add their own terms, sanitized before they are published
+        let description = match get_description_tag(event) {
+            Some(text) => match sanitize_description(&text) {
+                Some(description) => Some(description),
+                None => {
+                    let text_message = messages::invalid_description(lang, MAX_DESCRIPTION_LENGTH);
+                    send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
         let initiator_pubkey = event.pubkey.to_bech32()?;
+        // Keys derived per order can't be reused, their index always grows
+        let trade_index = match get_trade_index_tag(event) {
+            Some(index) => match next_trade_index(pool, &initiator_pubkey, &index).await? {
+                Some(index) => Some(index),
+                None => {
+                    let text_message = messages::invalid_trade_index(lang, &index);
+                    send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        let extras = OrderExtras {
+            region: region.map(|region| region.to_string()),
+            description,
+            denomination: Denomination::from_amounts(order.amount, order.fiat_amount)
+                .map(|denomination| denomination.to_string()),
+        };
+        add_user_if_not_exists(pool, &initiator_pubkey).await?;
+        if is_user_banned(pool, &initiator_pubkey).await? {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                None,
+                messages::user_banned(lang),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+        // New users can't publish orders above their limit
+        if let Some(max_amount) =
+            exceeded_new_user_limit(pool, &limits, &[initiator_pubkey.as_str()], order.amount)
+                .await?
+        {
+            let text_message = messages::new_user_limit(lang, max_amount, limits.new_user_trades);
+            send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+            return Ok(());
+        }
+        if exceeds_daily_volume(
+            pool,
+            &limits,
+            &[initiator_pubkey.as_str()],
+            order.amount,
+            None,
+        )
+        .await?
+        {
+            let text_message = messages::daily_volume_limit(lang, limits.daily_volume_limit);
+            send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+            return Ok(());
+        }
+        // A user can't have more than MAX_ACTIVE_ORDERS open at the same time
+        let max_active_orders = limits.max_active_orders;
+        if max_active_orders > 0
+            && count_active_orders(pool, &initiator_pubkey).await? >= max_active_orders
+        {
+            let text_message = MostroError::TooManyOrders.to_string();
+            send_cant_do_msg(client, my_keys, None, text_message, &event.pubkey).await?;
+            return Ok(());
+        }
 
-        publish_order(pool, client, my_keys, order, &initiator_pubkey).await?;
+        let new_order =
+            publish_order(pool, client, my_keys, order, &initiator_pubkey, &extras).await?;
+        if let Some(order_id) = new_order.id {
+            if let Some(index) = trade_index {
+                add_trade_index(pool, order_id, &initiator_pubkey, index).await?;
+            }
+            if repeats > 0 {
+                let max_amount = order.max_amount.unwrap_or(0);
+                add_recurring_order(pool, order_id, repeats, max_amount).await?;
+            }
+        }
     }
     Ok(())
 }
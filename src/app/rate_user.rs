@@ -0,0 +1,115 @@
+use crate::auth::authorize;
+use crate::db::{add_rating, has_rated};
+use crate::messages;
+use crate::state::transition;
+use crate::util::{
+    get_user_lang, publish_reputation, send_cant_do_msg, send_dm, send_wrong_status_msg,
+};
+
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use sqlx::{Pool, Sqlite};
+use sqlx_crud::Crud;
+use tracing::{error, info};
+
+pub const MIN_RATING: u8 = 1;
+pub const MAX_RATING: u8 = 5;
+
+pub async fn rate_user_action(
+    msg: Message,
+    event: &Event,
+    my_keys: &Keys,
+    client: &Client,
+    pool: &Pool<Sqlite>,
+) -> Result<()> {
+    // Safe unwrap as we verified the message
+    let order_id = msg.order_id.unwrap();
+    let order = match Order::by_id(pool, order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("RateUser: Order Id {order_id} not found!");
+            return Ok(());
+        }
+    };
+    // Only the parties of a successful trade can rate each other
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::RateUser,
+        &event.pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    if transition(&order, &Action::RateUser).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &event.pubkey).await?;
+        return Ok(());
+    }
+    let rater_pubkey = event.pubkey.to_bech32()?;
+    let rated_pubkey = if Some(&rater_pubkey) == order.buyer_pubkey.as_ref() {
+        order.seller_pubkey.clone()
+    } else {
+        order.buyer_pubkey.clone()
+    };
+    let rated_pubkey = match rated_pubkey {
+        Some(pk) => pk,
+        None => {
+            error!("RateUser: Order Id {order_id} has no counterpart!");
+            return Ok(());
+        }
+    };
+    let rating = match msg.content {
+        Some(Content::RatingUser(rating)) if (MIN_RATING..=MAX_RATING).contains(&rating) => rating,
+        _ => {
+            send_cant_do_msg(
+                client,
+                my_keys,
+                Some(order.id),
+                messages::invalid_rating(
+                    get_user_lang(pool, &event.pubkey).await,
+                    MIN_RATING,
+                    MAX_RATING,
+                ),
+                &event.pubkey,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    if has_rated(pool, order.id, &rater_pubkey).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::already_rated(get_user_lang(pool, &event.pubkey).await),
+            &event.pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    add_rating(pool, order.id, &rater_pubkey, &rated_pubkey, rating as i64).await?;
+    info!(
+        "RateUser: Order Id {}: {rated_pubkey} rated {rating}",
+        order.id
+    );
+    // The new reputation of the rated user is public
+    if let Err(e) = publish_reputation(pool, client, my_keys, &rated_pubkey).await {
+        error!("RateUser: reputation of {rated_pubkey} not published: {e}");
+    }
+    // We confirm the rating to the rater
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::RateUser,
+        Some(Content::RatingUser(rating)),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, &event.pubkey, message).await?;
+
+    Ok(())
+}
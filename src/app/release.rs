@@ -1,18 +1,32 @@
+use crate::auth::authorize;
+use crate::config::{self, FeeSettings};
+use crate::crypto::decrypt_secret;
 use crate::db::{self};
+use crate::fee::{
+    buyer_payout_amount, find_trade_fees, get_routing_compensation, get_routing_fee_limit,
+};
+use crate::lifecycle::Trigger;
 use crate::lightning::LndConnector;
 use crate::messages;
-use crate::util::{connect_nostr, get_keys};
+use crate::models::PayoutPart;
+use crate::payout::record_payment;
+use crate::receipt::send_receipt;
+use crate::shutdown;
+use crate::state::{is_repeated, order_status, transition};
+use crate::util::{
+    connect_nostr, get_keys, get_user_lang, send_cant_do_msg, send_wrong_status_msg,
+};
 use crate::util::{send_dm, update_order_event};
 
 use anyhow::Result;
-use log::{error, info};
 use mostro_core::order::Order;
 use mostro_core::{Action, Content, Message, Status};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
-use sqlx_crud::Crud;
 use tokio::sync::mpsc::channel;
 use tonic_openssl_lnd::lnrpc::payment::PaymentStatus;
+use tonic_openssl_lnd::lnrpc::PaymentFailureReason;
+use tracing::{error, info};
 
 pub async fn release_action(
     msg: Message,
@@ -23,7 +37,8 @@ pub async fn release_action(
     ln_client: &mut LndConnector,
 ) -> Result<()> {
     let order_id = msg.order_id.unwrap();
-    let order = match Order::by_id(pool, order_id).await? {
+    let settings = config::mostro();
+    let (mut order, version) = match db::find_order_with_version(pool, order_id).await? {
         Some(order) => order,
         None => {
             error!("Release: Order Id {order_id} not found!");
@@ -31,83 +46,375 @@ pub async fn release_action(
         }
     };
     let seller_pubkey = event.pubkey;
-    if Some(seller_pubkey.to_bech32()?) != order.seller_pubkey {
-        let text_message = messages::cant_do();
-        // We create a Message
-        let message = Message::new(
-            0,
-            Some(order.id),
-            Action::CantDo,
-            Some(Content::TextMessage(text_message)),
-        );
+    // Only the seller of an Active or FiatSent order can release
+    if !authorize(
+        pool,
+        client,
+        my_keys,
+        &order,
+        &Action::Release,
+        &seller_pubkey,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    // A repeated Release gets the same answer without settling again
+    if is_repeated(&order, &Action::Release) {
+        let message = Message::new(0, Some(order.id), Action::HoldInvoicePaymentSettled, None);
         let message = message.as_json()?;
-        send_dm(client, my_keys, &event.pubkey, message).await?;
+        send_dm(client, my_keys, &seller_pubkey, message).await?;
+        return Ok(());
+    }
+    if transition(&order, &Action::Release).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &seller_pubkey).await?;
+        return Ok(());
+    }
+    // If configured the seller has to send Release twice, the second one
+    // within RELEASE_CONFIRMATION_TIMEOUT minutes
+    if settings.expiration.release_confirmation {
+        let timeout = settings.expiration.release_confirmation_timeout * 60;
+        let now = Timestamp::now().as_i64();
+        let requested_at = db::find_release_requested_at(pool, order.id).await?;
+        if requested_at == 0 || now - requested_at > timeout {
+            db::update_order_release_requested(pool, order.id, now).await?;
+            let message = Message::new(
+                0,
+                Some(order.id),
+                Action::Release,
+                Some(Content::TextMessage(messages::release_confirmation(
+                    get_user_lang(pool, &event.pubkey).await,
+                    timeout / 60,
+                ))),
+            );
+            let message = message.as_json()?;
+            send_dm(client, my_keys, &seller_pubkey, message).await?;
+            return Ok(());
+        }
+        db::update_order_release_requested(pool, order.id, 0).await?;
     }
 
     if order.preimage.is_none() {
         return Ok(());
     }
-    let preimage = order.preimage.as_ref().unwrap();
-    ln_client.settle_hold_invoice(preimage).await?;
+    let preimage = decrypt_secret(order.preimage.as_ref().unwrap())?;
+    ln_client.settle_hold_invoice(&preimage).await?;
     info!("Release: Order Id {}: Released sats", &order.id);
+    record_fee(pool, &settings.fees, &order).await?;
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    update_order_event(
+    if let Err(e) = update_order_event(
         pool,
         client,
         my_keys,
         Status::SettledHoldInvoice,
         &order,
+        version,
         None,
+        Trigger::User,
     )
-    .await?;
+    .await
+    {
+        // The invoice subscription can save SettledHoldInvoice first, the
+        // sats are settled anyway so the buyer is paid
+        order = match db::find_order_with_version(pool, order.id).await? {
+            Some((order, _)) if order_status(&order) == Ok(Status::SettledHoldInvoice) => order,
+            _ => return Err(e),
+        };
+        info!(
+            "Release: Order Id {}: status already saved, paying the buyer",
+            order.id
+        );
+    }
 
     // Finally we try to pay buyer's invoice
-    let payment_request = order.buyer_invoice.as_ref().unwrap().to_string();
+    do_payment(order).await
+}
+
+pub async fn do_payment(order: Order) -> Result<()> {
+    // A payout split by the buyer is paid part by part
+    let pool = db::connect().await?;
+    let fees = config::mostro().fees.clone();
+    let parts = db::find_payout_parts(&pool, order.id).await?;
+    if !parts.is_empty() {
+        shutdown::spawn_payment(pay_payout_parts(order, parts));
+        return Ok(());
+    }
+    let payment_request = match order.buyer_invoice.as_ref() {
+        Some(pr) => decrypt_secret(pr)?,
+        None => {
+            error!("Order Id {}: buyer invoice not found!", order.id);
+            return Ok(());
+        }
+    };
+    // The buyer is paid the order amount minus the buyer fee, when the buyer
+    // accepted a smaller payout the difference goes to routing fees
+    let (_, _, compensation) = db::find_payment_failure(&pool, order.id).await?;
+    let trade_fees = find_trade_fees(&pool, &fees, &order).await?;
+    let payout_amount = buyer_payout_amount(&order, &trade_fees) - compensation;
+    let fee_limit = get_routing_fee_limit(&fees, payout_amount) + compensation;
     let mut ln_client_payment = LndConnector::new().await;
     let (tx, mut rx) = channel(100);
     let payment_task = {
         async move {
             ln_client_payment
-                .send_payment(&payment_request, order.amount, tx)
+                .send_payment(&payment_request, payout_amount, fee_limit, tx)
                 .await;
         }
     };
     tokio::spawn(payment_task);
+    let buyer_pubkey = order.buyer_pubkey.clone().unwrap_or_default();
     let payment = {
         async move {
-            // We redeclare vars to use inside this block
-            let client = connect_nostr().await.unwrap();
-            let my_keys = get_keys().unwrap();
-            let buyer_pubkey =
-                XOnlyPublicKey::from_bech32(order.buyer_pubkey.as_ref().unwrap()).unwrap();
-            let pool = db::connect().await.unwrap();
             // Receiving msgs from send_payment()
             while let Some(msg) = rx.recv().await {
+                if let Err(e) = record_payment(&pool, order.id, &buyer_pubkey, &msg.payment).await {
+                    error!("Order Id {}: error saving payment: {e}", order.id);
+                }
                 if let Some(status) = PaymentStatus::from_i32(msg.payment.status) {
                     if status == PaymentStatus::Succeeded {
                         info!(
-                            "Release: Order Id {}: Invoice with hash: {} paid!",
+                            "Order Id {}: Invoice with hash: {} paid!",
                             order.id, msg.payment.payment_hash
                         );
-                        // Purchase completed message to buyer
-                        let message =
-                            Message::new(0, Some(order.id), Action::PurchaseCompleted, None);
-                        let message = message.as_json().unwrap();
-                        send_dm(&client, &my_keys, &buyer_pubkey, message)
-                            .await
-                            .unwrap();
-                        let status = Status::Success;
-                        // We publish a new replaceable kind nostr event with the status updated
-                        // and update on local database the status and new event id
-                        update_order_event(&pool, &client, &my_keys, status, &order, None)
-                            .await
-                            .unwrap();
+                        if let Err(e) = payment_completed(&order, &msg.payment.payment_hash).await {
+                            error!("Order Id {}: error completing order: {e}", order.id);
+                        }
+                    } else if status == PaymentStatus::Failed {
+                        if let Err(e) = payment_failed(&order, msg.payment.failure_reason).await {
+                            error!("Order Id {}: error saving failed payment: {e}", order.id);
+                        }
                     }
                 }
             }
         }
     };
-    tokio::spawn(payment);
+    shutdown::spawn_payment(payment);
+    Ok(())
+}
+
+/// Pay the parts of a split payout one after the other, a failed part puts
+/// the rest on hold and the buyer is told to contact the admins
+async fn pay_payout_parts(order: Order, parts: Vec<PayoutPart>) {
+    let pool = match db::connect().await {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("Order Id {}: {e}", order.id);
+            return;
+        }
+    };
+    let fees = config::mostro().fees.clone();
+    let mut payment_hashes = vec![];
+    for part in parts {
+        // Parts paid before a restart are not paid again
+        if part.status == "Paid" {
+            payment_hashes.push(part.payment_hash.unwrap_or_default());
+            continue;
+        }
+        match pay_part(&pool, &fees, &order, &part).await {
+            Some(payment_hash) => {
+                info!(
+                    "Order Id {}: part {} of {} sats paid",
+                    order.id, part.part, part.amount
+                );
+                if let Err(e) =
+                    db::update_payout_part_status(&pool, part.id, "Paid", Some(&payment_hash)).await
+                {
+                    error!(
+                        "Order Id {}: error saving part {}: {e}",
+                        order.id, part.part
+                    );
+                }
+                payment_hashes.push(payment_hash);
+            }
+            None => {
+                error!(
+                    "Order Id {}: part {} of {} sats failed",
+                    order.id, part.part, part.amount
+                );
+                if let Err(e) = part_failed(&pool, &order, &part).await {
+                    error!("Order Id {}: {e}", order.id);
+                }
+                return;
+            }
+        }
+    }
+    if let Err(e) = payment_completed(&order, &payment_hashes.join(",")).await {
+        error!("Order Id {}: error completing order: {e}", order.id);
+    }
+}
+
+/// Pay a part of a split payout waiting for the result, returns the payment
+/// hash once it is paid
+async fn pay_part(
+    pool: &Pool<Sqlite>,
+    fees: &FeeSettings,
+    order: &Order,
+    part: &PayoutPart,
+) -> Option<String> {
+    let mut ln_client_payment = LndConnector::new().await;
+    let (tx, mut rx) = channel(100);
+    let payment_request = match decrypt_secret(&part.payment_request) {
+        Ok(payment_request) => payment_request,
+        Err(e) => {
+            error!(
+                "Order Id {}: payout part {} invoice: {e}",
+                order.id, part.part
+            );
+            return None;
+        }
+    };
+    let amount = part.amount;
+    tokio::spawn(async move {
+        ln_client_payment
+            .send_payment(
+                &payment_request,
+                amount,
+                get_routing_fee_limit(fees, amount),
+                tx,
+            )
+            .await;
+    });
+    let buyer_pubkey = order.buyer_pubkey.clone().unwrap_or_default();
+    while let Some(msg) = rx.recv().await {
+        if let Err(e) = record_payment(pool, order.id, &buyer_pubkey, &msg.payment).await {
+            error!("Order Id {}: error saving payment: {e}", order.id);
+        }
+        match PaymentStatus::from_i32(msg.payment.status) {
+            Some(PaymentStatus::Succeeded) => return Some(msg.payment.payment_hash),
+            Some(PaymentStatus::Failed) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+async fn part_failed(pool: &Pool<Sqlite>, order: &Order, part: &PayoutPart) -> Result<()> {
+    db::update_payout_part_status(pool, part.id, "Failed", None).await?;
+    let buyer_pubkey = match order.buyer_pubkey.as_ref() {
+        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
+        None => return Ok(()),
+    };
+    let text_message = messages::payout_part_failed(
+        get_user_lang(pool, &buyer_pubkey).await,
+        part.part,
+        part.amount,
+    );
+    let client = connect_nostr().await?;
+    send_cant_do_msg(
+        &client,
+        &get_keys()?,
+        Some(order.id),
+        text_message,
+        &buyer_pubkey,
+    )
+    .await
+}
+
+/// The buyer payment failed, it is tried again later. When routing costs
+/// more than the limit the buyer is offered a smaller payout with the
+/// difference added to the routing budget
+async fn payment_failed(order: &Order, failure_reason: i32) -> Result<()> {
+    let pool = db::connect().await?;
+    let attempts = db::update_payment_failed(&pool, order.id, Timestamp::now().as_i64()).await?;
+    error!(
+        "Order Id {}: buyer payment failed {attempts} times, reason {failure_reason}",
+        order.id
+    );
+    let (_, _, compensation) = db::find_payment_failure(&pool, order.id).await?;
+    // The offer is made once, on the first payment failed for lack of route
+    if attempts > 1
+        || compensation > 0
+        || failure_reason != PaymentFailureReason::FailureReasonNoRoute as i32
+    {
+        return Ok(());
+    }
+    let buyer_pubkey = match order.buyer_pubkey.as_ref() {
+        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
+        None => return Ok(()),
+    };
+    let settings = config::mostro();
+    let trade_fees = find_trade_fees(&pool, &settings.fees, order).await?;
+    let payout_amount = buyer_payout_amount(order, &trade_fees);
+    let text_message = messages::routing_fee_shortfall(
+        get_user_lang(&pool, &buyer_pubkey).await,
+        payout_amount - get_routing_compensation(&settings.fees, payout_amount),
+        settings.payout.payment_retry_interval,
+    );
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::AddInvoice,
+        Some(Content::TextMessage(text_message)),
+    );
+    let message = message.as_json()?;
+    let client = connect_nostr().await?;
+    send_dm(&client, &get_keys()?, &buyer_pubkey, message).await
+}
+
+/// The buyer was paid, the trade is over
+async fn payment_completed(order: &Order, payment_hash: &str) -> Result<()> {
+    // We redeclare vars to use inside this block
+    let client = connect_nostr().await?;
+    let my_keys = get_keys()?;
+    let pool = db::connect().await?;
+    // The order changed since the payment started, the status is written
+    // from the order as it is now
+    let (order, version) = match db::find_order_with_version(&pool, order.id).await? {
+        Some(order) => order,
+        None => return Ok(()),
+    };
+    let order = &order;
+    let buyer_pubkey = match order.buyer_pubkey.as_ref() {
+        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
+        None => return Ok(()),
+    };
+    // Purchase completed message to buyer
+    let message = Message::new(0, Some(order.id), Action::PurchaseCompleted, None);
+    let message = message.as_json()?;
+    send_dm(&client, &my_keys, &buyer_pubkey, message).await?;
+    // Both parties add a completed trade to their statistics
+    let parties = [order.buyer_pubkey.as_ref(), order.seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        if let Err(e) = db::update_user_trade(&pool, pubkey, order.amount).await {
+            error!("Order Id {}: error updating user {pubkey}: {e}", order.id);
+        }
+    }
+    let status = Status::Success;
+    // We publish a new replaceable kind nostr event with the status updated
+    // and update on local database the status and new event id
+    update_order_event(
+        &pool,
+        &client,
+        &my_keys,
+        status,
+        order,
+        version,
+        None,
+        Trigger::Lightning,
+    )
+    .await?;
+    // Both parties get a signed receipt as proof of the trade
+    if let Err(e) = send_receipt(&pool, &client, &my_keys, order, payment_hash).await {
+        error!("Order Id {}: error sending receipt: {e}", order.id);
+    }
+
+    Ok(())
+}
+
+/// Once the hold invoice is settled the Mostro fee was collected
+pub async fn record_fee(pool: &Pool<Sqlite>, fees: &FeeSettings, order: &Order) -> Result<()> {
+    let trade_fees = find_trade_fees(pool, fees, order).await?;
+    if trade_fees.seller_fee > 0 {
+        db::add_fee(pool, order.id, trade_fees.seller_fee, "seller").await?;
+    }
+    if trade_fees.buyer_fee > 0 {
+        db::add_fee(pool, order.id, trade_fees.buyer_fee, "buyer").await?;
+    }
+    if order.fee > 0 {
+        info!("Order Id {}: {} sats fee collected", order.id, order.fee);
+    }
+
     Ok(())
 }
@@ -1,16 +1,26 @@
-use crate::db::edit_buyer_pubkey_order;
+use crate::bond::request_bond;
+use crate::config;
+use crate::db::{
+    add_trade_index, add_user_if_not_exists, find_order_with_version, is_user_banned, take_order,
+};
+use crate::denomination::Denomination;
 use crate::error::MostroError;
+use crate::fee::{buyer_payout_amount, get_fee, get_trade_fees};
 use crate::lightning::invoice::is_valid_invoice;
-use crate::util::{send_dm, set_market_order_sats_amount, show_hold_invoice};
+use crate::limits::{exceeded_new_user_limit, exceeds_daily_volume, OrderLimits};
+use crate::messages;
+use crate::state::transition;
+use crate::trade_index::{get_trade_index_tag, next_trade_index};
+use crate::util::{
+    get_user_lang, release_taken_order, send_cant_do_msg, send_counterpart_reputation,
+    send_wrong_status_msg, set_market_order_amounts, show_hold_invoice, take_range_order,
+};
 
 use anyhow::Result;
-use log::error;
-use mostro_core::order::Order;
-use mostro_core::{Message, Status};
+use mostro_core::{Action, Content, Message, Status};
 use nostr_sdk::prelude::*;
 use sqlx::{Pool, Sqlite};
-use sqlx_crud::Crud;
-use std::str::FromStr;
+use tracing::error;
 
 pub async fn take_sell_action(
     msg: Message,
@@ -21,31 +31,88 @@ pub async fn take_sell_action(
 ) -> Result<()> {
     // Safe unwrap as we verified the message
     let order_id = msg.order_id.unwrap();
+    let settings = config::mostro();
 
-    let mut order = match Order::by_id(pool, order_id).await? {
+    let (mut order, mut version) = match find_order_with_version(pool, order_id).await? {
         Some(order) => order,
         None => {
             error!("TakeSell: Order Id {order_id} not found!");
             return Ok(());
         }
     };
-    if order.kind != "Sell" {
-        error!("TakeSell: Order Id {order_id} wrong kind");
+    let buyer_pubkey = event.pubkey;
+    // Buyer can take pending sell orders only
+    if transition(&order, &Action::TakeSell).is_err() {
+        send_wrong_status_msg(pool, client, my_keys, &order, &buyer_pubkey).await?;
         return Ok(());
     }
-    let buyer_pubkey = event.pubkey;
+    // Banned users can't take orders and the maker can't take its own order
+    let taker_pubkey = buyer_pubkey.to_bech32()?;
+    add_user_if_not_exists(pool, &taker_pubkey).await?;
+    if is_user_banned(pool, &taker_pubkey).await? {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::user_banned(get_user_lang(pool, &event.pubkey).await),
+            &buyer_pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    if order.creator_pubkey == taker_pubkey {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(order.id),
+            messages::cant_take_own_order(get_user_lang(pool, &event.pubkey).await),
+            &buyer_pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    // Keys derived per order can't be reused, their index always grows
+    let trade_index = match get_trade_index_tag(event) {
+        Some(index) => match next_trade_index(pool, &taker_pubkey, &index).await? {
+            Some(index) => Some(index),
+            None => {
+                let text_message =
+                    messages::invalid_trade_index(get_user_lang(pool, &event.pubkey).await, &index);
+                send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey)
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
     let pr: Option<String>;
-    // If a buyer sent me a lightning invoice we look on db an order with
-    // that order id and save the buyer pubkey and invoice fields
+    // If a buyer sent me a lightning invoice we validate it now, if not the
+    // buyer is asked for it once the seller pays the hold invoice
     if let Some(payment_request) = msg.get_payment_request() {
+        // The buyer invoice is paid with the order amount minus the buyer fee
         let order_amount = if order.amount == 0 {
             None
         } else {
-            Some(order.amount as u64)
+            let fee = get_fee(&settings.fees, order.amount);
+            let trade_fees = get_trade_fees(&settings.fees, &order, fee);
+            let payout_amount = buyer_payout_amount(&order, &trade_fees);
+            Some(payout_amount as u64)
         };
 
         // Verify if invoice is valid
         match is_valid_invoice(&payment_request, order_amount) {
+            // Market price orders don't know the amount yet
+            Ok(invoice) if order.amount == 0 && invoice.amount_milli_satoshis().is_some() => {
+                send_cant_do_msg(
+                    client,
+                    my_keys,
+                    Some(order.id),
+                    messages::market_order_invoice_amount(get_user_lang(pool, &event.pubkey).await),
+                    &buyer_pubkey,
+                )
+                .await?;
+                return Ok(());
+            }
             Ok(_) => {}
             Err(e) => match e {
                 MostroError::ParsingInvoiceError
@@ -53,7 +120,14 @@ pub async fn take_sell_action(
                 | MostroError::MinExpirationTimeError
                 | MostroError::WrongAmountError
                 | MostroError::MinAmountError => {
-                    send_dm(client, my_keys, &buyer_pubkey, e.to_string()).await?;
+                    send_cant_do_msg(
+                        client,
+                        my_keys,
+                        Some(order.id),
+                        e.to_string(),
+                        &buyer_pubkey,
+                    )
+                    .await?;
                     error!("{e}");
                     return Ok(());
                 }
@@ -64,54 +138,187 @@ pub async fn take_sell_action(
     } else {
         pr = None;
     }
-
-    let order_status = match Status::from_str(&order.status) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("TakeSell: Order Id {order_id} wrong status: {e:?}");
+    let seller_pubkey = match order.seller_pubkey.as_ref() {
+        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
+        None => {
+            error!("TakeSell: Seller pubkey not found for order {}!", order.id);
             return Ok(());
         }
     };
-    // Buyer can take pending orders only
-    match order_status {
-        Status::Pending | Status::WaitingBuyerInvoice => {}
-        _ => {
-            send_dm(
+    // Range orders are taken for a fiat amount inside the range
+    let range_child = order.max_amount > 0;
+    if range_child {
+        let fiat_amount = match &msg.content {
+            Some(Content::Amount(amount)) => *amount,
+            _ => 0,
+        };
+        if fiat_amount < order.min_amount || fiat_amount > order.max_amount {
+            let text_message = messages::out_of_range_amount(
+                get_user_lang(pool, &event.pubkey).await,
+                order.min_amount,
+                order.max_amount,
+                &order.fiat_code,
+            );
+            send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey).await?;
+            return Ok(());
+        }
+        (order, version) = match take_range_order(
+            pool,
+            client,
+            my_keys,
+            &mut order,
+            version,
+            fiat_amount,
+        )
+        .await?
+        {
+            Some(child) => child,
+            None => {
+                let text_message = MostroError::AlreadyTaken.to_string();
+                send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey)
+                    .await?;
+                return Ok(());
+            }
+        };
+    }
+    // Only one taker wins the order, the status is compared and swapped at once
+    if !take_order(pool, order.id, &taker_pubkey, version).await? {
+        // The child order just created goes back to the range
+        if range_child {
+            release_taken_order(pool, client, my_keys, &order).await?;
+        }
+        let text_message = MostroError::AlreadyTaken.to_string();
+        send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey).await?;
+        return Ok(());
+    }
+    // take_order saved the new status, later updates start from it
+    order.status = Status::WaitingPayment.to_string();
+    version += 1;
+    // Market price orders get the amount left open by the maker now, the
+    // taker is told how it was converted
+    if Denomination::from_amounts(order.amount, order.fiat_amount).is_some() {
+        if let Err(e) = set_market_order_amounts(
+            pool,
+            &mut order,
+            buyer_pubkey,
+            buyer_pubkey,
+            Action::TakeSell,
+            my_keys,
+            client,
+        )
+        .await
+        {
+            error!("TakeSell: Order Id {} market price error: {e}", order.id);
+            release_taken_order(pool, client, my_keys, &order).await?;
+            send_cant_do_msg(
                 client,
                 my_keys,
+                Some(order.id),
+                messages::price_not_available(get_user_lang(pool, &event.pubkey).await),
                 &buyer_pubkey,
-                format!("Order Id {order_id} was already taken!"),
             )
             .await?;
             return Ok(());
         }
     }
-    let seller_pubkey = match order.seller_pubkey.as_ref() {
-        Some(pk) => XOnlyPublicKey::from_bech32(pk)?,
-        None => {
-            error!("TakeSell: Seller pubkey not found for order {}!", order.id);
-            return Ok(());
-        }
-    };
-    let buyer_pubkey_bech32 = buyer_pubkey.to_bech32().ok();
-    // Add buyer pubkey to order
-    edit_buyer_pubkey_order(pool, order_id, buyer_pubkey_bech32).await?;
-    // Check market price value in sats - if order was with market price then calculate it and send a DM to buyer
-    if order.amount == 0 {
-        order.amount =
-            set_market_order_sats_amount(&mut order, buyer_pubkey, my_keys, pool, client).await?;
-    } else {
-        show_hold_invoice(
+    // Order size must be inside the operator limits
+    let limits = OrderLimits::from_settings(&settings.limits);
+    if !limits.is_valid_amount(order.amount) || !limits.is_valid_fiat_amount(order.fiat_amount) {
+        release_taken_order(pool, client, my_keys, &order).await?;
+        let text_message = messages::order_out_of_limits(
+            get_user_lang(pool, &event.pubkey).await,
+            &limits,
+            &order.fiat_code,
+        );
+        send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey).await?;
+        return Ok(());
+    }
+    // New users on either side have a lower limit
+    if let Some(max_amount) = exceeded_new_user_limit(
+        pool,
+        &settings.limits,
+        &[taker_pubkey.as_str(), order.creator_pubkey.as_str()],
+        order.amount,
+    )
+    .await?
+    {
+        release_taken_order(pool, client, my_keys, &order).await?;
+        let text_message = messages::new_user_limit(
+            get_user_lang(pool, &event.pubkey).await,
+            max_amount,
+            settings.limits.new_user_trades,
+        );
+        send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey).await?;
+        return Ok(());
+    }
+    // Neither party can go over the volume they can trade in 24 hours
+    let parties = [taker_pubkey.as_str(), order.creator_pubkey.as_str()];
+    if exceeds_daily_volume(
+        pool,
+        &settings.limits,
+        &parties,
+        order.amount,
+        Some(order.id),
+    )
+    .await?
+    {
+        release_taken_order(pool, client, my_keys, &order).await?;
+        let text_message = messages::daily_volume_limit(
+            get_user_lang(pool, &event.pubkey).await,
+            settings.limits.daily_volume_limit,
+        );
+        send_cant_do_msg(client, my_keys, Some(order.id), text_message, &buyer_pubkey).await?;
+        return Ok(());
+    }
+    if let Some(index) = trade_index {
+        add_trade_index(pool, order.id, &taker_pubkey, index).await?;
+    }
+    // Both parties get the reputation of their counterpart
+    send_counterpart_reputation(
+        pool,
+        client,
+        my_keys,
+        order.id,
+        Action::TakeSell,
+        &buyer_pubkey,
+        &seller_pubkey,
+    )
+    .await?;
+    send_counterpart_reputation(
+        pool,
+        client,
+        my_keys,
+        order.id,
+        Action::TakeSell,
+        &seller_pubkey,
+        &buyer_pubkey,
+    )
+    .await?;
+    // The buyer locks the bond before the seller is asked to pay
+    if settings.bond.buyer_bond {
+        request_bond(
             pool,
             client,
             my_keys,
+            &order,
+            &settings.bond,
             pr,
             &buyer_pubkey,
-            &seller_pubkey,
-            &order,
         )
         .await?;
+        return Ok(());
     }
+    show_hold_invoice(
+        pool,
+        client,
+        my_keys,
+        pr,
+        &buyer_pubkey,
+        &seller_pubkey,
+        &order,
+        version,
+    )
+    .await?;
 
     Ok(())
 }
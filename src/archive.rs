@@ -0,0 +1,142 @@
+use crate::db;
+use anyhow::Result;
+use easy_hasher::easy_hasher::raw_sha256;
+use mostro_core::Message;
+use nostr_sdk::prelude::hex::ToHex;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::fmt;
+use std::sync::OnceLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tracing::error;
+use uuid::Uuid;
+
+/// DM sent by Mostro waiting to be archived
+struct SentMessage {
+    receiver_pubkey: XOnlyPublicKey,
+    payload: String,
+    event_id: EventId,
+}
+
+static SENT_MESSAGES: OnceLock<UnboundedSender<SentMessage>> = OnceLock::new();
+
+/// Whether a message about an order was sent to Mostro or by Mostro
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::In => write!(f, "in"),
+            Self::Out => write!(f, "out"),
+        }
+    }
+}
+
+/// Hex sha256 of a message, the archive keeps the hash so solvers can check
+/// the messages the parties show them
+pub fn payload_hash(payload: &str) -> String {
+    raw_sha256(payload.as_bytes().to_vec()).to_hex()
+}
+
+/// Keep a message exchanged with `pubkey` in the timeline of an order with
+/// the id of the event that carried it
+pub async fn archive_message(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &XOnlyPublicKey,
+    action: &str,
+    direction: Direction,
+    payload: &str,
+    event_id: &EventId,
+) -> Result<bool> {
+    db::add_order_message(
+        pool,
+        order_id,
+        &pubkey.to_bech32()?,
+        action,
+        &direction.to_string(),
+        &payload_hash(payload),
+        &event_id.to_hex(),
+    )
+    .await
+}
+
+/// Archive the DMs sent by Mostro on the shared pool, one by one in the
+/// order they were sent
+pub fn spawn(pool: SqlitePool) {
+    let (tx, mut rx) = unbounded_channel::<SentMessage>();
+    if SENT_MESSAGES.set(tx).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some(sent) = rx.recv().await {
+            let receiver_pubkey = sent.receiver_pubkey;
+            if let Err(e) =
+                archive_sent_message(&pool, &receiver_pubkey, &sent.payload, &sent.event_id).await
+            {
+                error!("Message to {receiver_pubkey} could not be archived: {e}");
+            }
+        }
+    });
+}
+
+/// Queue a DM sent by Mostro to be archived, nothing is kept when the
+/// archive was not started
+pub fn queue_sent_message(receiver_pubkey: &XOnlyPublicKey, payload: &str, event_id: &EventId) {
+    if let Some(tx) = SENT_MESSAGES.get() {
+        let sent = SentMessage {
+            receiver_pubkey: *receiver_pubkey,
+            payload: payload.to_string(),
+            event_id: *event_id,
+        };
+        if tx.send(sent).is_err() {
+            error!("Message to {receiver_pubkey} could not be archived: archive stopped");
+        }
+    }
+}
+
+/// Keep a DM sent by Mostro when it is a message about an order
+async fn archive_sent_message(
+    pool: &SqlitePool,
+    receiver_pubkey: &XOnlyPublicKey,
+    payload: &str,
+    event_id: &EventId,
+) -> Result<()> {
+    let msg = match Message::from_json(payload) {
+        Ok(msg) => msg,
+        Err(_) => return Ok(()),
+    };
+    if let Some(order_id) = msg.order_id {
+        let action = format!("{:?}", msg.action);
+        archive_message(
+            pool,
+            order_id,
+            receiver_pubkey,
+            &action,
+            Direction::Out,
+            payload,
+            event_id,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{payload_hash, Direction};
+
+    #[test]
+    fn test_payload_hash() {
+        let hash = payload_hash(r#"{"version":0,"action":"FiatSent"}"#);
+        assert_eq!(64, hash.len());
+        assert_eq!(hash, payload_hash(r#"{"version":0,"action":"FiatSent"}"#));
+        assert_ne!(hash, payload_hash(r#"{"version":0,"action":"Release"}"#));
+        assert_eq!("out", Direction::Out.to_string());
+    }
+}
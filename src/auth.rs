@@ -0,0 +1,309 @@
+use crate::messages;
+use crate::state::order_status;
+use crate::util::{get_user_lang, is_admin, send_cant_do_msg};
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Status};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+
+/// Relation of a user with an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Maker,
+    Buyer,
+    Seller,
+    Admin,
+}
+
+/// Every role `pubkey` has in `order`, empty for strangers
+pub fn user_roles(order: &Order, pubkey: &str, is_admin: bool) -> Vec<Role> {
+    let mut roles = vec![];
+    if order.creator_pubkey == pubkey {
+        roles.push(Role::Maker);
+    }
+    if order.buyer_pubkey.as_deref() == Some(pubkey) {
+        roles.push(Role::Buyer);
+    }
+    if order.seller_pubkey.as_deref() == Some(pubkey) {
+        roles.push(Role::Seller);
+    }
+    if is_admin {
+        roles.push(Role::Admin);
+    }
+
+    roles
+}
+
+/// Whether a user with `roles` can send `action` about an order in `status`,
+/// the status itself is validated by `state::transition`
+pub fn is_authorized(roles: &[Role], action: &Action, status: &Status) -> bool {
+    let allowed: &[Role] = match (action, status) {
+        // Anyone but the maker can take an order
+        (Action::TakeSell | Action::TakeBuy, _) => return !roles.contains(&Role::Maker),
+        (Action::EditOrder, _) | (Action::Cancel, Status::Pending) => &[Role::Maker],
+        // The party who has to pay or send the invoice can back out
+        (Action::Cancel, Status::WaitingBuyerInvoice) => &[Role::Buyer],
+        (Action::Cancel, Status::WaitingPayment) => &[Role::Seller],
+        (Action::Cancel | Action::Dispute | Action::RateUser | Action::GetOrder, _) => {
+            &[Role::Buyer, Role::Seller]
+        }
+        (Action::AddInvoice | Action::FiatSent, _) => &[Role::Buyer],
+        (Action::Release, _) => &[Role::Seller],
+        (Action::AdminSettle | Action::AdminCancel, _) => &[Role::Admin],
+        _ => &[],
+    };
+
+    roles.iter().any(|role| allowed.contains(role))
+}
+
+/// Whether `pubkey` can send `action` about `order` as it is now
+pub fn can_send(order: &Order, action: &Action, pubkey: &XOnlyPublicKey) -> Result<bool> {
+    let roles = user_roles(order, &pubkey.to_bech32()?, is_admin(pubkey));
+
+    Ok(is_authorized(&roles, action, &order_status(order)?))
+}
+
+/// Check that the sender of an action is allowed to send it, if not the user
+/// gets a CantDo message and the handler must stop
+pub async fn authorize(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    order: &Order,
+    action: &Action,
+    pubkey: &XOnlyPublicKey,
+) -> Result<bool> {
+    if can_send(order, action, pubkey)? {
+        return Ok(true);
+    }
+    send_cant_do_msg(
+        client,
+        my_keys,
+        Some(order.id),
+        messages::cant_do(get_user_lang(pool, pubkey).await),
+        pubkey,
+    )
+    .await?;
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{can_send, is_authorized, Role};
+    use crate::db;
+    use crate::models::OrderExtras;
+    use crate::state::ORDER_ACTIONS;
+    use mostro_core::order::{NewOrder, Order};
+    use mostro_core::{Action, Kind, Status};
+    use nostr_sdk::prelude::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::OnceLock;
+
+    /// Roles allowed to send every action on an order in `status`, makers
+    /// of a taken order are also its buyer or seller
+    fn allowed(action: &Action, status: &Status) -> Vec<Role> {
+        match (action, status) {
+            (Action::TakeSell | Action::TakeBuy, _) => vec![Role::Buyer, Role::Seller, Role::Admin],
+            (Action::EditOrder, _) | (Action::Cancel, Status::Pending) => vec![Role::Maker],
+            (Action::Cancel, Status::WaitingBuyerInvoice) => vec![Role::Buyer],
+            (Action::Cancel, Status::WaitingPayment) => vec![Role::Seller],
+            (Action::Cancel | Action::Dispute | Action::RateUser, _) => {
+                vec![Role::Buyer, Role::Seller]
+            }
+            (Action::AddInvoice | Action::FiatSent, _) => vec![Role::Buyer],
+            (Action::Release, _) => vec![Role::Seller],
+            _ => vec![Role::Admin],
+        }
+    }
+
+    #[test]
+    fn test_every_action_authorization() {
+        let roles = [Role::Maker, Role::Buyer, Role::Seller, Role::Admin];
+        let statuses = [
+            Status::Pending,
+            Status::WaitingPayment,
+            Status::WaitingBuyerInvoice,
+            Status::Active,
+            Status::Dispute,
+        ];
+        for status in statuses {
+            for action in ORDER_ACTIONS {
+                for role in roles {
+                    assert_eq!(
+                        allowed(action, &status).contains(&role),
+                        is_authorized(&[role], action, &status),
+                        "{action:?} {status:?} {role:?}"
+                    );
+                }
+                // Strangers can only take orders
+                assert_eq!(
+                    matches!(action, Action::TakeSell | Action::TakeBuy),
+                    is_authorized(&[], action, &status),
+                    "{action:?} {status:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_maker_as_party() {
+        // The maker of a sell order is its seller after it is taken
+        let maker = [Role::Maker, Role::Seller];
+        assert!(is_authorized(&maker, &Action::Release, &Status::Active));
+        assert!(!is_authorized(&maker, &Action::FiatSent, &Status::Active));
+        assert!(!is_authorized(&maker, &Action::TakeSell, &Status::Pending));
+        // Only the parties can query the order
+        assert!(is_authorized(&maker, &Action::GetOrder, &Status::Success));
+        assert!(!is_authorized(
+            &[Role::Admin],
+            &Action::GetOrder,
+            &Status::Success
+        ));
+    }
+
+    /// Admin of the order fixtures, set on ADMIN_PUBKEYS once for every test
+    fn admin() -> &'static Keys {
+        static ADMIN: OnceLock<Keys> = OnceLock::new();
+        ADMIN.get_or_init(|| {
+            let keys = Keys::generate();
+            std::env::set_var("ADMIN_PUBKEYS", keys.public_key().to_bech32().unwrap());
+            keys
+        })
+    }
+
+    /// Sell order of `seller` in `status` as saved in the database, taken by
+    /// `buyer` unless it is pending
+    async fn sell_order(seller: &Keys, buyer: &Keys, status: Status) -> Order {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db::migrate(&pool).await.unwrap();
+        let new_order = NewOrder::new(
+            None,
+            Kind::Sell,
+            Status::Pending,
+            10_000,
+            "USD".to_string(),
+            10,
+            "SEPA".to_string(),
+            0,
+            None,
+            None,
+        );
+        let extras = OrderExtras {
+            region: None,
+            description: None,
+            denomination: None,
+        };
+        let seller = seller.public_key().to_bech32().unwrap();
+        let order = db::add_order(&pool, &new_order, "", &seller, &extras)
+            .await
+            .unwrap();
+        if status != Status::Pending {
+            let buyer = buyer.public_key().to_bech32().unwrap();
+            assert!(db::take_order(&pool, order.id, &buyer, 0).await.unwrap());
+            db::update_order_event_id_status(&pool, order.id, &status, "", order.amount)
+                .await
+                .unwrap();
+        }
+        let (order, _) = db::find_order_with_version(&pool, order.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        order
+    }
+
+    /// Whether each of `users` can send `action` about `order`
+    fn allowed_users(order: &Order, action: &Action, users: &[&Keys]) -> Vec<bool> {
+        users
+            .iter()
+            .map(|keys| can_send(order, action, &keys.public_key()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_release_authorization() {
+        let (seller, buyer, stranger) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let order = sell_order(&seller, &buyer, Status::FiatSent).await;
+        let users = [&seller, &buyer, &stranger, admin()];
+        // Only the seller releases the sats
+        assert_eq!(
+            vec![true, false, false, false],
+            allowed_users(&order, &Action::Release, &users)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_authorization() {
+        let (seller, buyer, stranger) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let users = [&seller, &buyer, &stranger, admin()];
+        // The maker pulls a pending order
+        let order = sell_order(&seller, &buyer, Status::Pending).await;
+        assert_eq!(
+            vec![true, false, false, false],
+            allowed_users(&order, &Action::Cancel, &users)
+        );
+        // The seller backs out of the hold invoice
+        let order = sell_order(&seller, &buyer, Status::WaitingPayment).await;
+        assert_eq!(
+            vec![true, false, false, false],
+            allowed_users(&order, &Action::Cancel, &users)
+        );
+        // The buyer backs out of the invoice
+        let order = sell_order(&seller, &buyer, Status::WaitingBuyerInvoice).await;
+        assert_eq!(
+            vec![false, true, false, false],
+            allowed_users(&order, &Action::Cancel, &users)
+        );
+        // Active orders are canceled cooperatively by both parties
+        let order = sell_order(&seller, &buyer, Status::Active).await;
+        assert_eq!(
+            vec![true, true, false, false],
+            allowed_users(&order, &Action::Cancel, &users)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_actions_authorization() {
+        let (seller, buyer, stranger) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let order = sell_order(&seller, &buyer, Status::Dispute).await;
+        let users = [&seller, &buyer, &stranger, admin()];
+        // Only admins resolve disputes
+        for action in [Action::AdminSettle, Action::AdminCancel] {
+            assert_eq!(
+                vec![false, false, false, true],
+                allowed_users(&order, &action, &users),
+                "{action:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_order_authorization() {
+        let (seller, buyer, stranger) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let order = sell_order(&seller, &buyer, Status::Success).await;
+        let users = [&seller, &buyer, &stranger, admin()];
+        // Only the parties can query the order
+        assert_eq!(
+            vec![true, true, false, false],
+            allowed_users(&order, &Action::GetOrder, &users)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispute_authorization() {
+        let (seller, buyer, stranger) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let order = sell_order(&seller, &buyer, Status::Active).await;
+        let users = [&seller, &buyer, &stranger, admin()];
+        // Either party opens a dispute
+        assert_eq!(
+            vec![true, true, false, false],
+            allowed_users(&order, &Action::Dispute, &users)
+        );
+    }
+}
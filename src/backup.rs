@@ -0,0 +1,102 @@
+use crate::db;
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+const BACKUP_PREFIX: &str = "mostro-";
+const BACKUP_EXTENSION: &str = ".db";
+
+/// Directory where the database is backed up set on BACKUP_DIR, backups are
+/// disabled without it
+pub fn get_backup_dir() -> Option<PathBuf> {
+    var("BACKUP_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// Cron expression of the backups set on BACKUP_SCHEDULE, every 6 hours by
+/// default
+pub fn get_backup_schedule() -> String {
+    var("BACKUP_SCHEDULE")
+        .ok()
+        .filter(|schedule| !schedule.trim().is_empty())
+        .unwrap_or_else(|| "0 0 */6 * * *".to_string())
+}
+
+/// Backups kept set on BACKUP_ROTATIONS, older ones are deleted
+pub fn get_backup_rotations() -> usize {
+    var("BACKUP_ROTATIONS")
+        .ok()
+        .and_then(|rotations| rotations.parse::<usize>().ok())
+        .filter(|rotations| *rotations > 0)
+        .unwrap_or(7)
+}
+
+pub fn backup_file_name(timestamp: i64) -> String {
+    format!("{BACKUP_PREFIX}{timestamp}{BACKUP_EXTENSION}")
+}
+
+fn backup_timestamp(file_name: &str) -> Option<i64> {
+    file_name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_EXTENSION)?
+        .parse::<i64>()
+        .ok()
+}
+
+/// Backups out of the last `rotations`, other files are never touched
+pub fn expired_backups(file_names: &[String], rotations: usize) -> Vec<String> {
+    let mut backups: Vec<(i64, &String)> = file_names
+        .iter()
+        .filter_map(|name| backup_timestamp(name).map(|timestamp| (timestamp, name)))
+        .collect();
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+
+    backups
+        .into_iter()
+        .skip(rotations)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Snapshot the database into `dir` while Mostro keeps running and delete
+/// the backups out of the rotation, returns the path of the new backup
+pub async fn backup_database(pool: &SqlitePool, dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(backup_file_name(Timestamp::now().as_i64()));
+    db::backup_into(pool, &path.to_string_lossy()).await?;
+    info!("Database backed up to {}", path.display());
+    let file_names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    for name in expired_backups(&file_names, get_backup_rotations()) {
+        if let Err(e) = fs::remove_file(dir.join(&name)) {
+            error!("Backup {name} could not be deleted: {e}");
+        }
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_file_name, expired_backups};
+
+    #[test]
+    fn test_expired_backups() {
+        let mut files: Vec<String> = (1..=5).map(backup_file_name).collect();
+        files.push("mostro.db".to_string());
+        files.push("notes.txt".to_string());
+        assert_eq!(
+            vec![backup_file_name(2), backup_file_name(1)],
+            expired_backups(&files, 3)
+        );
+        assert!(expired_backups(&files, 10).is_empty());
+    }
+}
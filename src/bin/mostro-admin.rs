@@ -0,0 +1,217 @@
+//! Admin CLI of Mostro, it talks to the admin API of a running Mostro so the
+//! operators of a headless server don't need a nostr client
+//!
+//! It reads ADMIN_API_ADDR and ADMIN_API_TOKEN from the environment or the
+//! `.env` file Mostro uses, MOSTRO_ADMIN_URL replaces the address to reach
+//! the API through a proxy.
+use anyhow::{anyhow, Result};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+const USAGE: &str = "Usage: mostro-admin <command>
+
+Commands:
+  orders list [--status <status>] [--limit <n>] [--offset <n>]
+  order show <order id>
+  order force-status <order id> <status> <reason>
+  disputes list [--status <status>] [--limit <n>] [--offset <n>]
+  dispute resolve <order id> settle [--buyer-percentage <1-100>]
+  dispute resolve <order id> cancel
+  user ban <pubkey>
+  user unban <pubkey>
+  stats [--since <YYYY-MM-DD>]";
+
+/// Request to the admin API a command is sent as
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: Method,
+    path: String,
+    body: Option<Value>,
+}
+
+impl Request {
+    fn new(method: Method, path: String) -> Self {
+        Self {
+            method,
+            path,
+            body: None,
+        }
+    }
+}
+
+/// Words of the command and the values of its `--options`
+fn split_options(args: &[String]) -> Result<(Vec<&str>, Vec<(&str, &str)>)> {
+    let mut words = vec![];
+    let mut options = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(option) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--{option} needs a value"))?;
+                options.push((option, value.as_str()));
+            }
+            None => words.push(arg.as_str()),
+        }
+    }
+
+    Ok((words, options))
+}
+
+/// Query string of the options a list takes
+fn query(options: &[(&str, &str)], allowed: &[&str]) -> Result<String> {
+    let mut pairs = vec![];
+    for (option, value) in options {
+        if !allowed.contains(option) {
+            return Err(anyhow!("Unknown option --{option}"));
+        }
+        pairs.push(format!("{option}={}", encode(value)));
+    }
+    match pairs.is_empty() {
+        true => Ok(String::new()),
+        false => Ok(format!("?{}", pairs.join("&"))),
+    }
+}
+
+/// Percent-encode a value for a path or a query
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn parse(args: &[String]) -> Result<Request> {
+    let (words, options) = split_options(args)?;
+    let list = ["status", "limit", "offset"];
+    let request = match words.as_slice() {
+        ["orders", "list"] => {
+            Request::new(Method::GET, format!("/orders{}", query(&options, &list)?))
+        }
+        ["order", "show", order_id] => {
+            Request::new(Method::GET, format!("/orders/{}", encode(order_id)))
+        }
+        ["order", "force-status", order_id, status, reason @ ..] if !reason.is_empty() => Request {
+            method: Method::POST,
+            path: format!("/orders/{}/status", encode(order_id)),
+            body: Some(json!({ "status": status, "reason": reason.join(" ") })),
+        },
+        ["disputes", "list"] => {
+            Request::new(Method::GET, format!("/disputes{}", query(&options, &list)?))
+        }
+        ["dispute", "resolve", order_id, resolution @ ("settle" | "cancel")] => {
+            let mut body = json!({ "resolution": resolution });
+            for (option, value) in &options {
+                match (*resolution, *option) {
+                    ("settle", "buyer-percentage") => {
+                        body["buyer_percentage"] = json!(value
+                            .parse::<i64>()
+                            .map_err(|_| anyhow!("--buyer-percentage is a number"))?);
+                    }
+                    _ => return Err(anyhow!("Unknown option --{option}")),
+                }
+            }
+            Request {
+                method: Method::POST,
+                path: format!("/disputes/{}/resolve", encode(order_id)),
+                body: Some(body),
+            }
+        }
+        ["user", "ban", pubkey] => {
+            Request::new(Method::POST, format!("/users/{}/ban", encode(pubkey)))
+        }
+        ["user", "unban", pubkey] => {
+            Request::new(Method::DELETE, format!("/users/{}/ban", encode(pubkey)))
+        }
+        ["stats"] => Request::new(
+            Method::GET,
+            format!("/stats{}", query(&options, &["since"])?),
+        ),
+        _ => return Err(anyhow!(USAGE)),
+    };
+
+    Ok(request)
+}
+
+/// URL and token of the admin API
+fn api() -> Result<(String, String)> {
+    let url = match dotenvy::var("MOSTRO_ADMIN_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            let addr = dotenvy::var("ADMIN_API_ADDR")
+                .map_err(|_| anyhow!("Set ADMIN_API_ADDR or MOSTRO_ADMIN_URL"))?;
+            format!("http://{addr}")
+        }
+    };
+    let token = dotenvy::var("ADMIN_API_TOKEN").map_err(|_| anyhow!("Set ADMIN_API_TOKEN"))?;
+
+    Ok((url.trim_end_matches('/').to_string(), token))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() || args.iter().any(|arg| arg == "--help") {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let request = parse(&args)?;
+    let (url, token) = api()?;
+    let mut builder = reqwest::Client::new()
+        .request(request.method, format!("{url}{}", request.path))
+        .bearer_auth(token);
+    if let Some(body) = request.body {
+        builder = builder.json(&body);
+    }
+    let response = builder.send().await?;
+    let status = response.status();
+    let body = response.json::<Value>().await?;
+    if status != StatusCode::OK {
+        let error = body["error"].as_str().unwrap_or_default();
+        return Err(anyhow!("{status}: {error}"));
+    }
+    println!("{}", serde_json::to_string_pretty(&body)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Request};
+    use reqwest::Method;
+    use serde_json::json;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Request::new(Method::GET, "/orders?status=Dispute&limit=10".to_string()),
+            parse(&args("orders list --status Dispute --limit 10")).unwrap()
+        );
+        assert_eq!(
+            Request {
+                method: Method::POST,
+                path: "/disputes/abc/resolve".to_string(),
+                body: Some(json!({ "resolution": "settle", "buyer_percentage": 60 })),
+            },
+            parse(&args("dispute resolve abc settle --buyer-percentage 60")).unwrap()
+        );
+        assert_eq!(
+            Some(json!({ "status": "Canceled", "reason": "Seller is gone" })),
+            parse(&args("order force-status abc Canceled Seller is gone"))
+                .unwrap()
+                .body
+        );
+        assert!(parse(&args("dispute resolve abc cancel --buyer-percentage 60")).is_err());
+        assert!(parse(&args("orders list --since 2023-05-01")).is_err());
+    }
+}
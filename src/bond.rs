@@ -0,0 +1,277 @@
+use crate::config::{BondSettings, FeeSettings};
+use crate::crypto::decrypt_secret;
+use crate::models::Bond;
+use crate::payout::request_payout;
+use crate::util::{get_user_lang, send_dm, show_hold_invoice};
+use crate::{db, fee, lightning, messages, shutdown};
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::hex::ToHex;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tokio::sync::mpsc::channel;
+use tonic_openssl_lnd::lnrpc::invoice::InvoiceState;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Bond of an order of `amount` sats, a percentage of the amount but never
+/// less than `min_amount`
+pub fn calculate_bond(amount: i64, percentage: f64, min_amount: i64) -> i64 {
+    let bond = (amount as f64 * percentage / 100_f64).round() as i64;
+
+    bond.max(min_amount)
+}
+
+/// Bond for an order of `amount` sats set on BOND_PERCENTAGE and BOND_MIN_AMOUNT
+pub fn get_bond_amount(bond: &BondSettings, amount: i64) -> i64 {
+    calculate_bond(amount, bond.bond_percentage, bond.bond_min_amount)
+}
+
+/// Ask the buyer for the bond hold invoice, the seller is asked to pay the
+/// order hold invoice once the bond is locked
+pub async fn request_bond(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    order: &Order,
+    bond_settings: &BondSettings,
+    payment_request: Option<String>,
+    buyer_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    // Market price orders have their amount now, we keep it and the buyer
+    // invoice until the seller hold invoice is created
+    db::edit_order_amount(pool, order.id, order.amount).await?;
+    if let Some(invoice) = payment_request {
+        db::edit_buyer_invoice_order(pool, order.id, &invoice).await?;
+    }
+    let amount = get_bond_amount(bond_settings, order.amount);
+    let lang = get_user_lang(pool, buyer_pubkey).await;
+    let description = messages::bond_invoice_description(
+        lang,
+        my_keys.public_key(),
+        &order.id.to_string(),
+        amount,
+    )?;
+    let mut ln_client = lightning::LndConnector::new().await;
+    let (invoice_response, preimage, hash) =
+        ln_client.create_hold_invoice(&description, amount).await?;
+    let bond = db::add_bond(
+        pool,
+        order.id,
+        &buyer_pubkey.to_bech32()?,
+        amount,
+        &hash.to_hex(),
+        &preimage.to_hex(),
+    )
+    .await?;
+    info!(
+        "Order Id {}: buyer bond of {} sats requested",
+        order.id, bond.amount
+    );
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::PayInvoice,
+        Some(Content::PaymentRequest(
+            Some(order.as_new_order()),
+            invoice_response.payment_request,
+        )),
+    );
+    let message = message.as_json()?;
+    send_dm(client, my_keys, buyer_pubkey, message).await?;
+
+    let mut ln_client_invoices = lightning::LndConnector::new().await;
+    let (tx, mut rx) = channel(100);
+    let invoice_task = {
+        async move {
+            ln_client_invoices.subscribe_invoice(hash, tx).await;
+        }
+    };
+    let subs = {
+        async move {
+            while let Some(msg) = rx.recv().await {
+                let hash = msg.hash.to_hex();
+                if msg.state == InvoiceState::Accepted {
+                    if let Err(e) = bond_locked(&hash).await {
+                        error!("Bond with hash {hash} could not be locked: {e}");
+                    }
+                } else {
+                    info!("Bond with hash: {hash} state {:?}", msg.state);
+                }
+            }
+        }
+    };
+    shutdown::spawn_stream(invoice_task, subs);
+
+    Ok(())
+}
+
+/// The buyer paid the bond, now the seller is asked to pay the hold invoice
+async fn bond_locked(hash: &str) -> Result<()> {
+    let pool = db::connect().await?;
+    let bond = db::find_bond_by_hash(&pool, hash).await?;
+    db::update_bond_status(&pool, bond.id, "Locked").await?;
+    info!("Order Id {}: buyer bond locked", bond.order_id);
+    let (order, version) = match db::find_order_with_version(&pool, bond.order_id).await? {
+        Some(order) => order,
+        None => {
+            error!("Bond: Order Id {} not found!", bond.order_id);
+            return Ok(());
+        }
+    };
+    // The order could have been canceled while the bond was being paid
+    if order.status != Status::WaitingPayment.to_string() || order.hash.is_some() {
+        return Ok(());
+    }
+    let (buyer_pubkey, seller_pubkey) = match (&order.buyer_pubkey, &order.seller_pubkey) {
+        (Some(buyer), Some(seller)) => (
+            XOnlyPublicKey::from_bech32(buyer)?,
+            XOnlyPublicKey::from_bech32(seller)?,
+        ),
+        _ => {
+            error!("Bond: Order Id {} without parties!", order.id);
+            return Ok(());
+        }
+    };
+    let client = crate::util::connect_nostr().await?;
+    let my_keys = crate::util::get_keys()?;
+
+    show_hold_invoice(
+        &pool,
+        &client,
+        &my_keys,
+        None,
+        &buyer_pubkey,
+        &seller_pubkey,
+        &order,
+        version,
+    )
+    .await
+}
+
+/// Return the bond of an order canceling its hold invoice
+pub async fn release_bond(pool: &SqlitePool, order_id: Uuid) -> Result<()> {
+    let bond = match db::find_active_bond(pool, order_id).await? {
+        Some(bond) => bond,
+        None => return Ok(()),
+    };
+    let mut ln_client = lightning::LndConnector::new().await;
+    ln_client.cancel_hold_invoice(&bond.hash).await?;
+    db::update_bond_status(pool, bond.id, "Released").await?;
+    info!("Order Id {order_id}: bond of {} sats released", bond.amount);
+
+    Ok(())
+}
+
+/// Charge the dispute fee to the bond of a buyer who lost a dispute, the rest
+/// of the bond is owed back to the buyer. Returns the sats charged
+pub async fn charge_dispute_fee(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    order_id: Uuid,
+    dispute_fee: i64,
+) -> Result<i64> {
+    let mut bond = match db::find_active_bond(pool, order_id).await? {
+        Some(bond) if bond.status == "Locked" && dispute_fee > 0 => bond,
+        _ => return Ok(0),
+    };
+    let charged = fee::cap_dispute_fee(bond.amount, dispute_fee);
+    let mut ln_client = lightning::LndConnector::new().await;
+    ln_client
+        .settle_hold_invoice(&decrypt_secret(&bond.preimage)?)
+        .await?;
+    db::update_bond_status(pool, bond.id, "Forfeited").await?;
+    bond.payout_amount = bond.amount - charged;
+    db::update_bond_payout(pool, bond.id, &bond.pubkey, bond.payout_amount).await?;
+    db::add_fee(pool, order_id, charged, "dispute").await?;
+    let details = format!(
+        "Dispute fee of {charged} sats taken from a bond of {} sats",
+        bond.amount
+    );
+    db::add_audit_log(pool, &bond.pubkey, "DisputeFee", Some(order_id), &details).await?;
+    info!("Order Id {order_id}: {details}");
+    if bond.payout_amount > 0 {
+        request_payout(
+            pool,
+            client,
+            my_keys,
+            order_id,
+            &bond.pubkey,
+            bond.payout_amount,
+            messages::dispute_fee_bond_refund(
+                db::get_user_language(pool, &bond.pubkey).await?,
+                charged,
+                bond.payout_amount,
+            ),
+        )
+        .await?;
+    }
+
+    Ok(charged)
+}
+
+/// Keep the bond of an order settling its hold invoice, the bond minus the
+/// Mostro fee is owed to the wronged party who is asked for an invoice
+pub async fn forfeit_bond(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    fees: &FeeSettings,
+    order_id: Uuid,
+    payee_pubkey: &str,
+) -> Result<Option<Bond>> {
+    let mut bond = match db::find_active_bond(pool, order_id).await? {
+        Some(bond) if bond.status == "Locked" => bond,
+        _ => return Ok(None),
+    };
+    let mut ln_client = lightning::LndConnector::new().await;
+    ln_client
+        .settle_hold_invoice(&decrypt_secret(&bond.preimage)?)
+        .await?;
+    db::update_bond_status(pool, bond.id, "Forfeited").await?;
+    let bond_fee = fee::get_fee(fees, bond.amount);
+    bond.payout_amount = bond.amount - bond_fee;
+    bond.payee_pubkey = Some(payee_pubkey.to_string());
+    db::update_bond_payout(pool, bond.id, payee_pubkey, bond.payout_amount).await?;
+    if bond_fee > 0 {
+        db::add_fee(pool, order_id, bond_fee, "bond").await?;
+    }
+    let details = format!(
+        "Bond of {} sats forfeited, {} sats owed to {payee_pubkey}",
+        bond.amount, bond.payout_amount
+    );
+    db::add_audit_log(pool, &bond.pubkey, "ForfeitBond", Some(order_id), &details).await?;
+    info!("Order Id {order_id}: {details}");
+
+    request_payout(
+        pool,
+        client,
+        my_keys,
+        order_id,
+        payee_pubkey,
+        bond.payout_amount,
+        messages::bond_compensation(
+            db::get_user_language(pool, payee_pubkey).await?,
+            bond.payout_amount,
+        ),
+    )
+    .await?;
+
+    Ok(Some(bond))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_bond() {
+        assert_eq!(1000, calculate_bond(100_000, 1.0, 0));
+        assert_eq!(2500, calculate_bond(50_000, 5.0, 0));
+        // Small orders pay the minimum bond
+        assert_eq!(1000, calculate_bond(10_000, 1.0, 1000));
+        assert_eq!(0, calculate_bond(0, 1.0, 0));
+    }
+}
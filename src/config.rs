@@ -0,0 +1,791 @@
+use crate::alerts::{parse_alert_events, Alert, ALERTS};
+use crate::escalation::{Escalation, ESCALATED_STATUSES};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use tracing::warn;
+
+/// File read when MOSTRO_CONFIG is not set, it is optional
+const DEFAULT_CONFIG_FILE: &str = "settings.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct NostrSettings {
+    pub nsec_privkey: String,
+    pub relays: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LightningSettings {
+    pub lnd_cert_file: String,
+    pub lnd_macaroon_file: String,
+    pub lnd_grpc_host: String,
+    pub lnd_grpc_port: u32,
+    pub invoice_expiration_window: i64,
+    pub hold_invoice_cltv_delta: u64,
+    pub min_payment_amt: u64,
+}
+
+impl Default for LightningSettings {
+    fn default() -> Self {
+        Self {
+            lnd_cert_file: String::new(),
+            lnd_macaroon_file: String::new(),
+            lnd_grpc_host: "localhost".to_string(),
+            lnd_grpc_port: 10009,
+            invoice_expiration_window: 3600,
+            hold_invoice_cltv_delta: 144,
+            min_payment_amt: 100,
+        }
+    }
+}
+
+/// Mostro fee and the routing budget of the payouts
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSettings {
+    /// Rate of the order amount, 0.003 is 0.3%
+    pub fee: f64,
+    /// Part of the fee paid by the maker
+    pub maker_fee_share: f64,
+    /// Sats charged to the party losing a dispute
+    pub dispute_fee: i64,
+    /// Most spent routing a payout, a rate of its amount
+    pub routing_fee_limit: f64,
+    /// Rate of a payout the buyer gives up when routing it costs more than
+    /// the limit
+    pub routing_fee_compensation: f64,
+}
+
+impl Default for FeeSettings {
+    fn default() -> Self {
+        Self {
+            fee: 0.0,
+            maker_fee_share: 0.5,
+            dispute_fee: 0,
+            routing_fee_limit: 0.005,
+            routing_fee_compensation: 0.01,
+        }
+    }
+}
+
+/// Order sizes and volumes a user can trade, zero means no limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitSettings {
+    pub min_order_amount: i64,
+    pub max_order_amount: i64,
+    pub min_order_fiat_amount: i64,
+    pub max_order_fiat_amount: i64,
+    /// Orders a user can have open at the same time
+    pub max_active_orders: i64,
+    /// Sats of the first trade of a new user, doubling with every trade
+    pub new_user_max_order_amount: i64,
+    /// Completed trades a user needs to leave the new user limit
+    pub new_user_trades: i64,
+    /// Sats a user can trade in 24 hours
+    pub daily_volume_limit: i64,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            min_order_amount: 0,
+            max_order_amount: 0,
+            min_order_fiat_amount: 0,
+            max_order_fiat_amount: 0,
+            max_active_orders: 0,
+            new_user_max_order_amount: 0,
+            new_user_trades: 5,
+            daily_volume_limit: 0,
+        }
+    }
+}
+
+/// How long an order waits in each status, in minutes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpirationSettings {
+    /// A pending order stays published, `exp_hours` when not set and never
+    /// expires with 0
+    pub pending_order_timeout: Option<i64>,
+    pub exp_hours: i64,
+    pub hold_invoice_payment_timeout: i64,
+    pub add_invoice_timeout: i64,
+    /// Without fiat sent a dispute is opened, never with 0
+    pub active_timeout: i64,
+    pub fiat_sent_reminder_interval: i64,
+    pub fiat_sent_window: i64,
+    /// A cooperative cancel request not accepted is discarded
+    pub cooperative_cancel_timeout: u64,
+    /// The seller has to send Release twice
+    pub release_confirmation: bool,
+    /// The second Release comes within this time
+    pub release_confirmation_timeout: i64,
+    /// Escalation schedules by setting name, the ones not set follow the
+    /// timeouts above and an empty one disables the escalation of its status
+    pub escalations: BTreeMap<String, String>,
+}
+
+impl Default for ExpirationSettings {
+    fn default() -> Self {
+        Self {
+            pending_order_timeout: None,
+            exp_hours: 24,
+            hold_invoice_payment_timeout: 15,
+            add_invoice_timeout: 15,
+            active_timeout: 0,
+            fiat_sent_reminder_interval: 60,
+            fiat_sent_window: 360,
+            cooperative_cancel_timeout: 60,
+            release_confirmation: false,
+            release_confirmation_timeout: 5,
+            escalations: BTreeMap::new(),
+        }
+    }
+}
+
+/// Bond locked by the buyers taking sell orders
+#[derive(Debug, Clone, PartialEq)]
+pub struct BondSettings {
+    pub buyer_bond: bool,
+    /// Percentage of the order amount
+    pub bond_percentage: f64,
+    /// Sats of the smallest bond
+    pub bond_min_amount: i64,
+}
+
+impl Default for BondSettings {
+    fn default() -> Self {
+        Self {
+            buyer_bond: false,
+            bond_percentage: 1.0,
+            bond_min_amount: 1000,
+        }
+    }
+}
+
+/// Alerts for the operator and where they are sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlertSettings {
+    pub alert_events: Vec<Alert>,
+    /// Blocks left before the HTLC of a hold invoice expires
+    pub alert_cltv_blocks: i64,
+    /// Sats the node needs to send and to receive
+    pub alert_min_liquidity_sats: u64,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub matrix_homeserver: Option<String>,
+    pub matrix_access_token: Option<String>,
+    pub matrix_room_id: Option<String>,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            alert_events: ALERTS.to_vec(),
+            alert_cltv_blocks: 36,
+            alert_min_liquidity_sats: 100_000,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            matrix_homeserver: None,
+            matrix_access_token: None,
+            matrix_room_id: None,
+        }
+    }
+}
+
+/// How the buyer is paid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutSettings {
+    /// Payouts of at least these sats can be split across several invoices,
+    /// never with 0
+    pub split_payout_min_amount: i64,
+    pub max_payout_parts: usize,
+    /// Minutes between tries of a failed payment
+    pub payment_retry_interval: i64,
+    /// Tries of a payment before waiting for the buyer or the admins
+    pub payment_attempts: i64,
+}
+
+impl Default for PayoutSettings {
+    fn default() -> Self {
+        Self {
+            split_payout_min_amount: 0,
+            max_payout_parts: 5,
+            payment_retry_interval: 5,
+            payment_attempts: 3,
+        }
+    }
+}
+
+/// Settings of the `[mostro]` table and their environment variables, typed
+/// and checked when they are read
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MostroSettings {
+    pub fees: FeeSettings,
+    pub limits: LimitSettings,
+    pub expiration: ExpirationSettings,
+    pub bond: BondSettings,
+    pub alerts: AlertSettings,
+    pub payout: PayoutSettings,
+    /// The trades in progress go on but no order can be published or taken
+    pub maintenance_mode: bool,
+    /// Settings of the table read through `crate::settings::var`, by name
+    pub other: BTreeMap<String, String>,
+}
+
+/// Names of the typed settings of the `[mostro]` table, the escalation
+/// schedules are the ones of `ESCALATED_STATUSES`
+const MOSTRO_SETTINGS: &[&str] = &[
+    "FEE",
+    "MAKER_FEE_SHARE",
+    "DISPUTE_FEE",
+    "ROUTING_FEE_LIMIT",
+    "ROUTING_FEE_COMPENSATION",
+    "MIN_ORDER_AMOUNT",
+    "MAX_ORDER_AMOUNT",
+    "MIN_ORDER_FIAT_AMOUNT",
+    "MAX_ORDER_FIAT_AMOUNT",
+    "MAX_ACTIVE_ORDERS",
+    "NEW_USER_MAX_ORDER_AMOUNT",
+    "NEW_USER_TRADES",
+    "DAILY_VOLUME_LIMIT",
+    "PENDING_ORDER_TIMEOUT",
+    "EXP_HOURS",
+    "HOLD_INVOICE_PAYMENT_TIMEOUT",
+    "ADD_INVOICE_TIMEOUT",
+    "ACTIVE_TIMEOUT",
+    "FIAT_SENT_REMINDER_INTERVAL",
+    "FIAT_SENT_WINDOW",
+    "COOPERATIVE_CANCEL_TIMEOUT",
+    "RELEASE_CONFIRMATION",
+    "RELEASE_CONFIRMATION_TIMEOUT",
+    "BUYER_BOND",
+    "BOND_PERCENTAGE",
+    "BOND_MIN_AMOUNT",
+    "ALERT_EVENTS",
+    "ALERT_CLTV_BLOCKS",
+    "ALERT_MIN_LIQUIDITY_SATS",
+    "TELEGRAM_BOT_TOKEN",
+    "TELEGRAM_CHAT_ID",
+    "MATRIX_HOMESERVER",
+    "MATRIX_ACCESS_TOKEN",
+    "MATRIX_ROOM_ID",
+    "SPLIT_PAYOUT_MIN_AMOUNT",
+    "MAX_PAYOUT_PARTS",
+    "PAYMENT_RETRY_INTERVAL",
+    "PAYMENT_ATTEMPTS",
+    "MAINTENANCE_MODE",
+];
+
+/// Save `value` in `field` when it parses and `is_valid` takes it
+fn set_parsed<T: FromStr>(field: &mut T, value: &str, is_valid: impl Fn(&T) -> bool) -> bool {
+    match value.trim().parse::<T>() {
+        Ok(parsed) if is_valid(&parsed) => {
+            *field = parsed;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_rate(rate: &f64) -> bool {
+    (0.0..=1.0).contains(rate)
+}
+
+fn not_negative<T: Default + PartialOrd>(value: &T) -> bool {
+    *value >= T::default()
+}
+
+fn any<T>(_: &T) -> bool {
+    true
+}
+
+impl MostroSettings {
+    /// Set the setting `name` from its value as the environment has it,
+    /// false when the value is not valid for the setting
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        let (fees, limits, expiration) = (&mut self.fees, &mut self.limits, &mut self.expiration);
+        let (bond, alerts, payout) = (&mut self.bond, &mut self.alerts, &mut self.payout);
+        match name {
+            "FEE" => set_parsed(&mut fees.fee, value, is_rate),
+            "MAKER_FEE_SHARE" => set_parsed(&mut fees.maker_fee_share, value, is_rate),
+            "DISPUTE_FEE" => set_parsed(&mut fees.dispute_fee, value, not_negative),
+            "ROUTING_FEE_LIMIT" => set_parsed(&mut fees.routing_fee_limit, value, is_rate),
+            "ROUTING_FEE_COMPENSATION" => {
+                set_parsed(&mut fees.routing_fee_compensation, value, is_rate)
+            }
+            "MIN_ORDER_AMOUNT" => set_parsed(&mut limits.min_order_amount, value, not_negative),
+            "MAX_ORDER_AMOUNT" => set_parsed(&mut limits.max_order_amount, value, not_negative),
+            "MIN_ORDER_FIAT_AMOUNT" => {
+                set_parsed(&mut limits.min_order_fiat_amount, value, not_negative)
+            }
+            "MAX_ORDER_FIAT_AMOUNT" => {
+                set_parsed(&mut limits.max_order_fiat_amount, value, not_negative)
+            }
+            "MAX_ACTIVE_ORDERS" => set_parsed(&mut limits.max_active_orders, value, not_negative),
+            "NEW_USER_MAX_ORDER_AMOUNT" => {
+                set_parsed(&mut limits.new_user_max_order_amount, value, not_negative)
+            }
+            "NEW_USER_TRADES" => set_parsed(&mut limits.new_user_trades, value, not_negative),
+            "DAILY_VOLUME_LIMIT" => set_parsed(&mut limits.daily_volume_limit, value, not_negative),
+            "PENDING_ORDER_TIMEOUT" => {
+                let mut timeout = 0;
+                let is_set = set_parsed(&mut timeout, value, not_negative);
+                if is_set {
+                    expiration.pending_order_timeout = Some(timeout);
+                }
+                is_set
+            }
+            "EXP_HOURS" => set_parsed(&mut expiration.exp_hours, value, not_negative),
+            "HOLD_INVOICE_PAYMENT_TIMEOUT" => set_parsed(
+                &mut expiration.hold_invoice_payment_timeout,
+                value,
+                not_negative,
+            ),
+            "ADD_INVOICE_TIMEOUT" => {
+                set_parsed(&mut expiration.add_invoice_timeout, value, not_negative)
+            }
+            "ACTIVE_TIMEOUT" => set_parsed(&mut expiration.active_timeout, value, not_negative),
+            "FIAT_SENT_REMINDER_INTERVAL" => set_parsed(
+                &mut expiration.fiat_sent_reminder_interval,
+                value,
+                not_negative,
+            ),
+            "FIAT_SENT_WINDOW" => set_parsed(&mut expiration.fiat_sent_window, value, not_negative),
+            "COOPERATIVE_CANCEL_TIMEOUT" => {
+                set_parsed(&mut expiration.cooperative_cancel_timeout, value, any)
+            }
+            "RELEASE_CONFIRMATION" => set_parsed(&mut expiration.release_confirmation, value, any),
+            "RELEASE_CONFIRMATION_TIMEOUT" => set_parsed(
+                &mut expiration.release_confirmation_timeout,
+                value,
+                not_negative,
+            ),
+            "BUYER_BOND" => set_parsed(&mut bond.buyer_bond, value, any),
+            "BOND_PERCENTAGE" => set_parsed(&mut bond.bond_percentage, value, not_negative),
+            "BOND_MIN_AMOUNT" => set_parsed(&mut bond.bond_min_amount, value, not_negative),
+            "ALERT_EVENTS" => match parse_alert_events(value) {
+                Some(events) => {
+                    alerts.alert_events = events;
+                    true
+                }
+                None => false,
+            },
+            "ALERT_CLTV_BLOCKS" => set_parsed(&mut alerts.alert_cltv_blocks, value, not_negative),
+            "ALERT_MIN_LIQUIDITY_SATS" => {
+                set_parsed(&mut alerts.alert_min_liquidity_sats, value, any)
+            }
+            "TELEGRAM_BOT_TOKEN" => set_text(&mut alerts.telegram_bot_token, value),
+            "TELEGRAM_CHAT_ID" => set_text(&mut alerts.telegram_chat_id, value),
+            "MATRIX_HOMESERVER" => set_text(&mut alerts.matrix_homeserver, value),
+            "MATRIX_ACCESS_TOKEN" => set_text(&mut alerts.matrix_access_token, value),
+            "MATRIX_ROOM_ID" => set_text(&mut alerts.matrix_room_id, value),
+            "SPLIT_PAYOUT_MIN_AMOUNT" => {
+                set_parsed(&mut payout.split_payout_min_amount, value, not_negative)
+            }
+            "MAX_PAYOUT_PARTS" => {
+                set_parsed(&mut payout.max_payout_parts, value, |parts| *parts > 1)
+            }
+            "PAYMENT_RETRY_INTERVAL" => {
+                set_parsed(&mut payout.payment_retry_interval, value, |minutes| {
+                    *minutes > 0
+                })
+            }
+            "PAYMENT_ATTEMPTS" => set_parsed(&mut payout.payment_attempts, value, not_negative),
+            "MAINTENANCE_MODE" => set_parsed(&mut self.maintenance_mode, value, any),
+            name => match ESCALATED_STATUSES.iter().find(|(_, var)| *var == name) {
+                Some((status, _)) => {
+                    let is_valid = value.trim().is_empty()
+                        || Escalation::parse(value).is_some_and(|escalation| {
+                            crate::escalation::is_supported(status, &escalation.action)
+                        });
+                    if is_valid {
+                        expiration
+                            .escalations
+                            .insert(name.to_string(), value.to_string());
+                    }
+                    is_valid
+                }
+                None => {
+                    self.other.insert(name.to_string(), value.to_string());
+                    true
+                }
+            },
+        }
+    }
+
+    /// Value in use of a setting the admins can change
+    pub fn runtime_value(&self, name: &str) -> Option<String> {
+        let (fees, limits) = (&self.fees, &self.limits);
+        let value = match name {
+            "FEE" => fees.fee.to_string(),
+            "MAKER_FEE_SHARE" => fees.maker_fee_share.to_string(),
+            "DISPUTE_FEE" => fees.dispute_fee.to_string(),
+            "MIN_ORDER_AMOUNT" => limits.min_order_amount.to_string(),
+            "MAX_ORDER_AMOUNT" => limits.max_order_amount.to_string(),
+            "MIN_ORDER_FIAT_AMOUNT" => limits.min_order_fiat_amount.to_string(),
+            "MAX_ORDER_FIAT_AMOUNT" => limits.max_order_fiat_amount.to_string(),
+            "MAX_ACTIVE_ORDERS" => limits.max_active_orders.to_string(),
+            "NEW_USER_MAX_ORDER_AMOUNT" => limits.new_user_max_order_amount.to_string(),
+            "NEW_USER_TRADES" => limits.new_user_trades.to_string(),
+            "DAILY_VOLUME_LIMIT" => limits.daily_volume_limit.to_string(),
+            "MAINTENANCE_MODE" => self.maintenance_mode.to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+fn set_text(field: &mut Option<String>, value: &str) -> bool {
+    *field = Some(value.to_string());
+    true
+}
+
+/// Settings Mostro needs to start, read from a TOML file where each
+/// environment variable of the same name takes precedence. The `[mostro]`
+/// table holds the other settings by their environment variable name
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub database: DatabaseSettings,
+    pub nostr: NostrSettings,
+    pub lightning: LightningSettings,
+    #[serde(rename = "mostro")]
+    mostro_table: BTreeMap<String, toml::Value>,
+    #[serde(skip)]
+    pub mostro: MostroSettings,
+    /// Settings of the `[mostro]` table that are not valid
+    #[serde(skip)]
+    mostro_errors: Vec<String>,
+}
+
+/// Replace `value` with the environment variable `name` when it is set
+fn env_override<T: FromStr>(name: &str, value: &mut T, errors: &mut Vec<String>) {
+    if let Ok(env_value) = dotenvy::var(name) {
+        match env_value.parse() {
+            Ok(parsed) => *value = parsed,
+            Err(_) => errors.push(format!("{name} has an invalid value: {env_value}")),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let mut settings: Settings = toml::from_str(content)?;
+        for (name, value) in &settings.mostro_table {
+            let (name, value) = (name.to_uppercase(), env_value(value));
+            if !settings.mostro.set(&name, &value) {
+                settings
+                    .mostro_errors
+                    .push(format!("{name} has an invalid value: {value}"));
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Apply the environment variables, those that can't be parsed are
+    /// added to `errors`
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_override("DATABASE_URL", &mut self.database.url, errors);
+        env_override("NSEC_PRIVKEY", &mut self.nostr.nsec_privkey, errors);
+        if let Ok(relays) = dotenvy::var("RELAYS") {
+            self.nostr.relays = relays
+                .split(',')
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .collect();
+        }
+        let lightning = &mut self.lightning;
+        env_override("LND_CERT_FILE", &mut lightning.lnd_cert_file, errors);
+        env_override(
+            "LND_MACAROON_FILE",
+            &mut lightning.lnd_macaroon_file,
+            errors,
+        );
+        env_override("LND_GRPC_HOST", &mut lightning.lnd_grpc_host, errors);
+        env_override("LND_GRPC_PORT", &mut lightning.lnd_grpc_port, errors);
+        env_override(
+            "INVOICE_EXPIRATION_WINDOW",
+            &mut lightning.invoice_expiration_window,
+            errors,
+        );
+        env_override(
+            "HOLD_INVOICE_CLTV_DELTA",
+            &mut lightning.hold_invoice_cltv_delta,
+            errors,
+        );
+        env_override("MIN_PAYMENT_AMT", &mut lightning.min_payment_amt, errors);
+        let escalations = ESCALATED_STATUSES.iter().map(|(_, name)| *name);
+        for name in MOSTRO_SETTINGS.iter().copied().chain(escalations) {
+            if let Ok(value) = dotenvy::var(name) {
+                // The environment value replaces the one of the file
+                let prefix = format!("{name} has");
+                self.mostro_errors
+                    .retain(|error| !error.starts_with(&prefix));
+                if !self.mostro.set(name, &value) {
+                    errors.push(format!("{name} has an invalid value: {value}"));
+                }
+            }
+        }
+    }
+
+    /// Every problem found in the settings, empty when Mostro can start
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.database.url.is_empty() {
+            errors.push("DATABASE_URL is not set".to_string());
+        }
+        if self.nostr.nsec_privkey.is_empty() {
+            errors.push("NSEC_PRIVKEY is not set".to_string());
+        } else if nostr_sdk::prelude::Keys::from_sk_str(&self.nostr.nsec_privkey).is_err() {
+            errors.push("NSEC_PRIVKEY is not a valid private key".to_string());
+        }
+        if self.nostr.relays.is_empty() {
+            errors.push("RELAYS is not set".to_string());
+        }
+        for url in &self.nostr.relays {
+            if !crate::relays::is_valid_url(url) {
+                errors.push(format!("RELAYS has an invalid relay url: {url}"));
+            }
+        }
+        let lightning = &self.lightning;
+        for (name, path) in [
+            ("LND_CERT_FILE", &lightning.lnd_cert_file),
+            ("LND_MACAROON_FILE", &lightning.lnd_macaroon_file),
+        ] {
+            if path.is_empty() {
+                errors.push(format!("{name} is not set"));
+            } else if !Path::new(path).is_file() {
+                errors.push(format!("{name} doesn't exist: {path}"));
+            }
+        }
+        if lightning.lnd_grpc_host.is_empty() {
+            errors.push("LND_GRPC_HOST is not set".to_string());
+        }
+        if lightning.invoice_expiration_window <= 0 {
+            errors.push("INVOICE_EXPIRATION_WINDOW must be greater than 0".to_string());
+        }
+        // A bad fee or limit would otherwise only show when an order reads it
+        errors.extend(self.mostro_errors.iter().cloned());
+
+        errors
+    }
+}
+
+/// Value of the `[mostro]` table as the environment would have it, lists
+/// are joined with commas
+fn env_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                toml::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+/// Value of a setting of the `[mostro]` table of the last file read
+pub fn file_var(name: &str) -> Option<String> {
+    MOSTRO_BASE
+        .read()
+        .unwrap()
+        .as_ref()?
+        .other
+        .get(name)
+        .cloned()
+}
+
+/// Sections that are only read on start, a change in them needs a restart
+pub fn restart_needed(current: &Settings, new: &Settings) -> Vec<&'static str> {
+    let mut sections = Vec::new();
+    if current.database != new.database {
+        sections.push("database");
+    }
+    if current.nostr.nsec_privkey != new.nostr.nsec_privkey {
+        sections.push("nostr.nsec_privkey");
+    }
+    if current.lightning != new.lightning {
+        sections.push("lightning");
+    }
+
+    sections
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// `[mostro]` settings of the last file read with the environment applied
+static MOSTRO_BASE: RwLock<Option<MostroSettings>> = RwLock::new(None);
+/// The base `[mostro]` settings with the values set by the admins
+static MOSTRO: RwLock<Option<Arc<MostroSettings>>> = RwLock::new(None);
+
+/// Read the settings file of MOSTRO_CONFIG, or settings.toml when it exists,
+/// apply the environment and check the result. All the errors are reported
+/// together so the operator fixes them at once
+fn read() -> Result<Settings> {
+    let path = dotenvy::var("MOSTRO_CONFIG").ok();
+    let file = path.as_deref().unwrap_or(DEFAULT_CONFIG_FILE);
+    let mut settings = match std::fs::read_to_string(file) {
+        Ok(content) => Settings::from_toml(&content)
+            .map_err(|e| anyhow!("Settings file {file} can't be read: {e}"))?,
+        Err(_) if path.is_none() => Settings::default(),
+        Err(e) => return Err(anyhow!("Settings file {file} can't be read: {e}")),
+    };
+    let mut errors = Vec::new();
+    settings.apply_env(&mut errors);
+    errors.extend(settings.validate());
+    if !errors.is_empty() {
+        return Err(anyhow!("Invalid settings:\n  {}", errors.join("\n  ")));
+    }
+
+    Ok(settings)
+}
+
+/// Load the settings Mostro starts with
+pub fn init() -> Result<&'static Settings> {
+    let settings = read()?;
+    *MOSTRO_BASE.write().unwrap() = Some(settings.mostro.clone());
+    apply_overrides(&BTreeMap::new());
+
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+/// Read the `.env` file and the settings file again, the `[mostro]`
+/// settings take the new values once the admin ones are applied with
+/// `apply_overrides`. Nothing changes when the new settings are invalid
+pub fn reload() -> Result<Settings> {
+    dotenvy::dotenv_override().ok();
+    let settings = read()?;
+    *MOSTRO_BASE.write().unwrap() = Some(settings.mostro.clone());
+
+    Ok(settings)
+}
+
+/// Apply the values set by the admins over the `[mostro]` settings of the
+/// file and the environment, those no longer valid are left out
+pub fn apply_overrides(overrides: &BTreeMap<String, String>) {
+    let base = MOSTRO_BASE.read().unwrap().clone();
+    let mut mostro = base.unwrap_or_else(|| settings().mostro.clone());
+    for (name, value) in overrides {
+        if !mostro.set(name, value) {
+            warn!("Setting {name} saved by an admin has an invalid value: {value}");
+        }
+    }
+    *MOSTRO.write().unwrap() = Some(Arc::new(mostro));
+}
+
+/// `[mostro]` settings Mostro runs with, a snapshot that doesn't change
+/// while an action uses it
+pub fn mostro() -> Arc<MostroSettings> {
+    if let Some(mostro) = MOSTRO.read().unwrap().as_ref() {
+        return mostro.clone();
+    }
+    Arc::new(settings().mostro.clone())
+}
+
+/// Settings of the running Mostro. Tools and tests that don't call `init`
+/// get the environment ones without validation
+pub fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(|| {
+        let mut settings = Settings::default();
+        settings.apply_env(&mut Vec::new());
+        settings
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{restart_needed, MostroSettings, Settings};
+    use crate::alerts::Alert;
+
+    #[test]
+    fn test_from_toml() {
+        let settings = Settings::from_toml(
+            r#"
+            [database]
+            url = "sqlite://mostro.db"
+
+            [nostr]
+            relays = ["wss://relay.damus.io", "relay.nostr.band"]
+
+            [lightning]
+            lnd_grpc_port = 10001
+
+            [mostro]
+            fee = 0.003
+            max_active_orders = "ten"
+            "#,
+        )
+        .unwrap();
+        assert_eq!("sqlite://mostro.db", settings.database.url);
+        assert_eq!(10001, settings.lightning.lnd_grpc_port);
+        // Settings not in the file keep their default
+        assert_eq!(144, settings.lightning.hold_invoice_cltv_delta);
+        assert_eq!(0.003, settings.mostro.fees.fee);
+        let errors = settings.validate();
+        assert!(errors.contains(&"NSEC_PRIVKEY is not set".to_string()));
+        assert!(errors.contains(&"RELAYS has an invalid relay url: relay.nostr.band".to_string()));
+        assert!(!errors.iter().any(|error| error.starts_with("DATABASE_URL")));
+        assert!(errors.contains(&"MAX_ACTIVE_ORDERS has an invalid value: ten".to_string()));
+        assert!(!errors.iter().any(|error| error.starts_with("FEE")));
+    }
+
+    #[test]
+    fn test_restart_needed() {
+        let current = Settings::default();
+        let mut new = current.clone();
+        new.nostr.relays = vec!["wss://relay.damus.io".to_string()];
+        new.mostro.fees.fee = 0.002;
+        // Relays and the other settings are applied without a restart
+        assert!(restart_needed(&current, &new).is_empty());
+        new.lightning.lnd_grpc_port = 10001;
+        assert_eq!(vec!["lightning"], restart_needed(&current, &new));
+    }
+
+    #[test]
+    fn test_mostro_settings() {
+        let mut mostro = MostroSettings::default();
+        assert!(mostro.set("MAKER_FEE_SHARE", "0.7"));
+        assert_eq!(0.7, mostro.fees.maker_fee_share);
+        // Invalid values leave the setting as it was
+        assert!(!mostro.set("MAKER_FEE_SHARE", "2"));
+        assert!(!mostro.set("BUYER_BOND", "yes"));
+        assert!(!mostro.set("MAX_PAYOUT_PARTS", "1"));
+        assert_eq!(0.7, mostro.fees.maker_fee_share);
+        assert!(mostro.set("PENDING_ORDER_TIMEOUT", "30"));
+        assert_eq!(Some(30), mostro.expiration.pending_order_timeout);
+        assert!(mostro.set("ALERT_EVENTS", "dispute_opened"));
+        assert_eq!(vec![Alert::DisputeOpened], mostro.alerts.alert_events);
+        assert!(!mostro.set("ALERT_EVENTS", "dispute_opened,unknown"));
+        // Escalation schedules need an action their status supports
+        assert!(mostro.set("ESCALATION_FIAT_SENT", "30,120,dispute"));
+        assert!(!mostro.set("ESCALATION_FIAT_SENT", "30,120,cancel"));
+        assert!(mostro.set("ESCALATION_ACTIVE", ""));
+        // The rest are kept by name
+        assert!(mostro.set("BACKUP_DIR", "/backups"));
+        assert_eq!(
+            Some("/backups"),
+            mostro.other.get("BACKUP_DIR").map(|dir| dir.as_str())
+        );
+        assert_eq!(
+            Some("0.7".to_string()),
+            mostro.runtime_value("MAKER_FEE_SHARE")
+        );
+        assert_eq!(None, mostro.runtime_value("BACKUP_DIR"));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::db;
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::info;
+
+/// Prefix of the secrets encrypted at rest, values without it were saved in
+/// plain text and are read as they are
+const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Key used to encrypt secrets at rest set on DB_ENCRYPTION_KEY, a nostr
+/// secret key in bech32 or hex. Without it secrets are saved in plain text
+pub fn get_encryption_keys() -> Result<Option<Keys>> {
+    match var("DB_ENCRYPTION_KEY") {
+        Ok(key) if !key.trim().is_empty() => Ok(Some(Keys::from_sk_str(key.trim())?)),
+        _ => Ok(None),
+    }
+}
+
+/// Encrypt `value` with nip04 to the pubkey of the same keys
+pub fn seal(keys: &Keys, value: &str) -> Result<String> {
+    let encrypted = encrypt(&keys.secret_key()?, &keys.public_key(), value)?;
+
+    Ok(format!("{ENCRYPTED_PREFIX}{encrypted}"))
+}
+
+/// Decrypt a value sealed with `seal`, plain values are returned unchanged
+pub fn unseal(keys: &Keys, value: &str) -> Result<String> {
+    match value.strip_prefix(ENCRYPTED_PREFIX) {
+        Some(encrypted) => Ok(decrypt(&keys.secret_key()?, &keys.public_key(), encrypted)?),
+        None => Ok(value.to_string()),
+    }
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Secret as it must be saved, encrypted when DB_ENCRYPTION_KEY is set
+pub fn encrypt_secret(value: &str) -> Result<String> {
+    match get_encryption_keys()? {
+        Some(keys) => seal(&keys, value),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Secret as it was before being saved
+pub fn decrypt_secret(value: &str) -> Result<String> {
+    if !is_sealed(value) {
+        return Ok(value.to_string());
+    }
+    match get_encryption_keys()? {
+        Some(keys) => unseal(&keys, value),
+        None => Err(anyhow::anyhow!(
+            "DB_ENCRYPTION_KEY is needed to read encrypted secrets"
+        )),
+    }
+}
+
+/// Encrypt the secrets saved in plain text before DB_ENCRYPTION_KEY was
+/// set, returns the number of rows encrypted
+pub async fn encrypt_existing_secrets(pool: &SqlitePool) -> Result<u64> {
+    let keys = match get_encryption_keys()? {
+        Some(keys) => keys,
+        None => return Err(anyhow::anyhow!("DB_ENCRYPTION_KEY is not set")),
+    };
+    let seal_plain = |value: Option<String>| -> Result<Option<String>> {
+        match value {
+            Some(value) if !is_sealed(&value) => Ok(Some(seal(&keys, &value)?)),
+            value => Ok(value),
+        }
+    };
+    let mut encrypted = 0;
+    for (order_id, preimage, buyer_invoice) in db::find_plain_order_secrets(pool).await? {
+        let preimage = seal_plain(preimage)?;
+        let buyer_invoice = seal_plain(buyer_invoice)?;
+        db::update_order_secrets(pool, order_id, preimage, buyer_invoice).await?;
+        encrypted += 1;
+    }
+    for (bond_id, preimage) in db::find_plain_bond_preimages(pool).await? {
+        db::update_bond_preimage(pool, bond_id, &seal(&keys, &preimage)?).await?;
+        encrypted += 1;
+    }
+    for (part_id, payment_request) in db::find_plain_payout_parts(pool).await? {
+        db::update_payout_part_invoice(pool, part_id, &seal(&keys, &payment_request)?).await?;
+        encrypted += 1;
+    }
+    info!("{encrypted} rows with secrets encrypted");
+
+    Ok(encrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sealed, seal, unseal};
+    use nostr_sdk::prelude::*;
+
+    #[test]
+    fn test_seal_unseal() {
+        let keys = Keys::generate();
+        let sealed = seal(&keys, "preimage").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("preimage"));
+        assert_eq!("preimage", unseal(&keys, &sealed).unwrap());
+        // Rows saved before the encryption are read as they are
+        assert_eq!("lnbc1...", unseal(&keys, "lnbc1...").unwrap());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::settings::var;
+
+/// Active ISO 4217 currency codes
+pub const ISO_4217: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+    "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD",
+    "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN",
+    "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+fn parse_currency_list(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(|code| code.trim().to_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+/// ISO 4217 currencies allowed by the comma-separated `allow_list`, all of
+/// them if there is no allow list, minus the ones in `block_list`
+pub fn supported_currencies(
+    allow_list: Option<&str>,
+    block_list: Option<&str>,
+) -> Vec<&'static str> {
+    let allowed = parse_currency_list(allow_list);
+    let blocked = parse_currency_list(block_list);
+
+    ISO_4217
+        .iter()
+        .filter(|code| allowed.is_empty() || allowed.iter().any(|a| a == *code))
+        .filter(|code| !blocked.iter().any(|b| b == *code))
+        .copied()
+        .collect()
+}
+
+/// Currencies this Mostro operates with, set by the operator on FIAT_CURRENCIES
+/// and BLOCKED_CURRENCIES
+pub fn get_supported_currencies() -> Vec<&'static str> {
+    let allow_list = var("FIAT_CURRENCIES").ok();
+    let block_list = var("BLOCKED_CURRENCIES").ok();
+
+    supported_currencies(allow_list.as_deref(), block_list.as_deref())
+}
+
+/// Currencies the operator doesn't accept set on BLOCKED_CURRENCIES
+pub fn get_blocked_currencies() -> Vec<String> {
+    parse_currency_list(var("BLOCKED_CURRENCIES").ok().as_deref())
+}
+
+pub fn is_supported_currency(fiat_code: &str) -> bool {
+    get_supported_currencies().contains(&fiat_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::supported_currencies;
+
+    #[test]
+    fn test_all_iso_currencies_without_allow_list() {
+        let currencies = supported_currencies(None, None);
+        assert!(currencies.contains(&"USD"));
+        assert!(currencies.contains(&"VES"));
+        assert!(!currencies.contains(&"XXX"));
+    }
+
+    #[test]
+    fn test_allow_list_only_keeps_iso_currencies() {
+        let currencies = supported_currencies(Some("usd, EUR,ABC"), None);
+        assert_eq!(vec!["EUR", "USD"], currencies);
+        // Blocked currencies are left out of the allow list too
+        let currencies = supported_currencies(Some("usd, EUR"), Some("eur"));
+        assert_eq!(vec!["USD"], currencies);
+        assert!(!supported_currencies(None, Some("VES")).contains(&"VES"));
+    }
+}
@@ -1,29 +1,185 @@
-use dotenvy::var;
+use crate::settings::var;
 use nostr_sdk::prelude::*;
-use sqlx::migrate::MigrateDatabase;
+use sqlx::migrate::{MigrateDatabase, Migrator};
 use sqlx::pool::Pool;
-use sqlx::Sqlite;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 use sqlx::SqlitePool;
+use sqlx::{FromRow, Row, Sqlite};
+use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::crypto::encrypt_secret;
+use crate::denomination::Denomination;
+use crate::fee::TradeFees;
+use crate::i18n::Lang;
+use crate::lifecycle::{is_transition, Trigger};
+use crate::models::{
+    Activity, AuditLog, Bond, Dispute, DisputeMessage, EscalationTimer, Fee, MonthlyFees,
+    OrderDetail, OrderEvent, OrderExtras, OrderMessage, OutboxMessage, Payment, Payout, PayoutPart,
+    PurgeReport, Relay, Setting, Trade, User, UserReputation,
+};
 use mostro_core::order::{NewOrder, Order};
 use mostro_core::{Kind, Status};
 
 pub async fn connect() -> Result<Pool<Sqlite>, sqlx::Error> {
-    let db_url = var("DATABASE_URL").expect("DATABASE_URL is not set");
-    if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
+    let db_url = &crate::config::settings().database.url;
+    if !Sqlite::database_exists(db_url).await.unwrap_or(false) {
         panic!("Not database found, please create a new one first!");
     }
-    let pool = SqlitePool::connect(&db_url).await?;
+    let pool = SqlitePoolOptions::new()
+        .max_connections(get_pool_size())
+        .connect_with(connect_options()?)
+        .await?;
 
     Ok(pool)
 }
 
+/// Options of every connection to the database at DATABASE_URL, with the
+/// SQLCipher key and the journal, synchronous and busy settings
+pub fn connect_options() -> Result<SqliteConnectOptions, sqlx::Error> {
+    let db_url = &crate::config::settings().database.url;
+    let mut options = SqliteConnectOptions::from_str(db_url)?;
+    // The key pragma must be the first statement run on every connection
+    if let Some(passphrase) = get_passphrase()? {
+        if !cfg!(feature = "sqlcipher") {
+            return Err(sqlx::Error::Configuration(
+                "SQLITE_PASSPHRASE needs Mostro built with the sqlcipher feature".into(),
+            ));
+        }
+        options = options.pragma("key", format!("'{}'", passphrase.replace('\'', "''")));
+    }
+    let options = options
+        .journal_mode(get_journal_mode())
+        .synchronous(get_synchronous())
+        .busy_timeout(Duration::from_millis(get_busy_timeout()));
+
+    Ok(options)
+}
+
+/// SQLCipher passphrase of the database, set on SQLITE_PASSPHRASE or read
+/// from the file at SQLITE_PASSPHRASE_FILE so it can come from a keyring or
+/// a secrets manager instead of the environment
+fn get_passphrase() -> Result<Option<String>, sqlx::Error> {
+    if let Ok(passphrase) = var("SQLITE_PASSPHRASE") {
+        return Ok(Some(passphrase));
+    }
+    match var("SQLITE_PASSPHRASE_FILE") {
+        Ok(path) => {
+            let passphrase = std::fs::read_to_string(&path).map_err(|e| {
+                sqlx::Error::Configuration(format!("SQLITE_PASSPHRASE_FILE {path}: {e}").into())
+            })?;
+            Ok(Some(passphrase.trim_end_matches(['\r', '\n']).to_string()))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Journal mode set on SQLITE_JOURNAL_MODE, WAL by default so readers don't
+/// block the writer
+fn get_journal_mode() -> SqliteJournalMode {
+    var("SQLITE_JOURNAL_MODE")
+        .ok()
+        .and_then(|mode| SqliteJournalMode::from_str(&mode).ok())
+        .unwrap_or(SqliteJournalMode::Wal)
+}
+
+/// Synchronous level set on SQLITE_SYNCHRONOUS, NORMAL is safe with WAL
+fn get_synchronous() -> SqliteSynchronous {
+    var("SQLITE_SYNCHRONOUS")
+        .ok()
+        .and_then(|level| SqliteSynchronous::from_str(&level).ok())
+        .unwrap_or(SqliteSynchronous::Normal)
+}
+
+/// Milliseconds a connection waits for a lock set on SQLITE_BUSY_TIMEOUT
+fn get_busy_timeout() -> u64 {
+    var("SQLITE_BUSY_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .unwrap_or(5000)
+}
+
+/// Connections of each pool set on DB_POOL_SIZE
+fn get_pool_size() -> u32 {
+    var("DB_POOL_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<u32>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(10)
+}
+
+/// Copy the database into a new file at `path`, the copy is consistent
+/// while other connections keep writing
+pub async fn backup_into(pool: &SqlitePool, path: &str) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("VACUUM INTO ?1")
+        .bind(path)
+        .execute(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether the database answers a query
+pub async fn ping(pool: &SqlitePool) -> anyhow::Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+
+    Ok(())
+}
+
+/// Problems found by `PRAGMA integrity_check`, a single `ok` when none
+pub async fn integrity_check(pool: &SqlitePool) -> anyhow::Result<Vec<String>> {
+    let rows = sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+/// Refresh the statistics the query planner uses
+pub async fn analyze(pool: &SqlitePool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("ANALYZE").execute(&mut conn).await?;
+
+    Ok(())
+}
+
+/// Rebuild the database file giving back the space of deleted rows
+pub async fn vacuum(pool: &SqlitePool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("VACUUM").execute(&mut conn).await?;
+
+    Ok(())
+}
+
+/// Create the database of DATABASE_URL when it doesn't exist yet, the
+/// migrations give it its schema
+pub async fn create_database() -> Result<(), sqlx::Error> {
+    let db_url = &crate::config::settings().database.url;
+    if !Sqlite::database_exists(db_url).await? {
+        Sqlite::create_database(db_url).await?;
+    }
+
+    Ok(())
+}
+
+/// Migrations embedded in the binary, the `.down.sql` ones roll back the
+/// migration with the same version
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Apply the migrations embedded in the binary that are not applied yet,
+/// sqlx keeps the applied versions in the `_sqlx_migrations` table
+pub async fn migrate(pool: &SqlitePool) -> Result<(), sqlx::migrate::MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
 pub async fn add_order(
     pool: &SqlitePool,
     order: &NewOrder,
     event_id: &str,
     initiator_pubkey: &str,
+    extras: &OrderExtras,
 ) -> anyhow::Result<Order> {
     let mut conn = pool.acquire().await?;
     let uuid = Uuid::new_v4();
@@ -37,7 +193,10 @@ pub async fn add_order(
     }
     let kind = order.kind.to_string();
     let status = order.status.to_string();
-    let buyer_invoice = order.buyer_invoice.as_ref();
+    let buyer_invoice = match order.buyer_invoice.as_deref() {
+        Some(invoice) => Some(encrypt_secret(invoice)?),
+        None => None,
+    };
     let price_from_api = order.amount == 0;
 
     let order = sqlx::query_as::<_, Order>(
@@ -56,9 +215,15 @@ pub async fn add_order(
         price_from_api,
         fiat_code,
         fiat_amount,
+        min_amount,
+        max_amount,
         buyer_invoice,
+        region,
+        description,
+        denomination,
         created_at
-      ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+      ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+        ?19, ?20)
         RETURNING *
       "#,
     )
@@ -75,7 +240,12 @@ pub async fn add_order(
     .bind(price_from_api)
     .bind(&order.fiat_code)
     .bind(order.fiat_amount)
+    .bind(order.min_amount.unwrap_or(0))
+    .bind(order.max_amount.unwrap_or(0))
     .bind(buyer_invoice)
+    .bind(&extras.region)
+    .bind(&extras.description)
+    .bind(&extras.denomination)
     .bind(created_at.as_i64())
     .fetch_one(&mut conn)
     .await?;
@@ -97,6 +267,8 @@ pub async fn edit_order(
     let status = status.to_string();
     let buyer_pubkey = buyer_pubkey.to_bech32()?;
     let seller_pubkey = seller_pubkey.to_bech32()?;
+    let preimage = encrypt_secret(preimage)?;
+    let taken_at = Timestamp::now().as_i64();
     let rows_affected = sqlx::query!(
         r#"
     UPDATE orders
@@ -105,14 +277,16 @@ pub async fn edit_order(
     seller_pubkey = ?2,
     status = ?3,
     preimage = ?4,
-    hash = ?5
-    WHERE id = ?6
+    hash = ?5,
+    taken_at = ?6
+    WHERE id = ?7
     "#,
         buyer_pubkey,
         seller_pubkey,
         status,
         preimage,
         hash,
+        taken_at,
         order_id
     )
     .execute(&mut conn)
@@ -128,6 +302,7 @@ pub async fn edit_buyer_invoice_order(
     buyer_invoice: &str,
 ) -> anyhow::Result<bool> {
     let mut conn = pool.acquire().await?;
+    let buyer_invoice = encrypt_secret(buyer_invoice)?;
     let rows_affected = sqlx::query!(
         r#"
             UPDATE orders
@@ -221,6 +396,76 @@ pub async fn update_order_event_id_status(
     Ok(rows_affected > 0)
 }
 
+/// Status of an order and its event id written only if the order is still at
+/// `from_status` and `version`, as the caller read it. The transition and the
+/// order event to publish are saved in the same transaction, a crash before
+/// the event reaches the relays leaves it in the outbox. Returns the outbox
+/// id of the event or None for a stale write
+#[allow(clippy::too_many_arguments)]
+pub async fn swap_order_event_id_status(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    from_status: &str,
+    version: i64,
+    status: &Status,
+    event: &Event,
+    amount: i64,
+    trigger: Trigger,
+) -> anyhow::Result<Option<i64>> {
+    let mut tx = pool.begin().await?;
+    let status = status.to_string();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            status = ?1,
+            amount = ?2,
+            event_id = ?3,
+            version = ?6 + 1
+            WHERE id = ?4 AND status = ?5 AND version = ?6
+        "#,
+    )
+    .bind(&status)
+    .bind(amount)
+    .bind(event.id.to_hex())
+    .bind(order_id)
+    .bind(from_status)
+    .bind(version)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    if rows_affected == 0 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+    if is_transition(Some(from_status), &status) {
+        add_take_event(&mut tx, order_id, from_status, &status, trigger).await?;
+    }
+    let created_at = Timestamp::now().as_i64();
+    let outbox_id = sqlx::query(
+        r#"
+            INSERT INTO outbox (
+            event_id,
+            receiver_pubkey,
+            event,
+            status,
+            kind,
+            created_at,
+            updated_at
+            ) VALUES (?1, '', ?2, 'Pending', 'order', ?3, ?3)
+        "#,
+    )
+    .bind(event.id.to_hex())
+    .bind(event.as_json())
+    .bind(created_at)
+    .execute(&mut tx)
+    .await?
+    .last_insert_rowid();
+    tx.commit().await?;
+
+    Ok(Some(outbox_id))
+}
+
 pub async fn find_order_by_event_id(pool: &SqlitePool, event_id: &str) -> anyhow::Result<Order> {
     let order = sqlx::query_as::<_, Order>(
         r#"
@@ -236,8 +481,33 @@ pub async fn find_order_by_event_id(pool: &SqlitePool, event_id: &str) -> anyhow
     Ok(order)
 }
 
-pub async fn find_order_by_hash(pool: &SqlitePool, hash: &str) -> anyhow::Result<Order> {
-    let order = sqlx::query_as::<_, Order>(
+/// An order with its version, read at once so a status update written with
+/// that version fails if the order changed after this read
+pub async fn find_order_with_version(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<(Order, i64)>> {
+    let row = sqlx::query(
+        r#"
+          SELECT *
+          FROM orders
+          WHERE id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+    let order = match row {
+        Some(row) => Some((Order::from_row(&row)?, row.try_get("version")?)),
+        None => None,
+    };
+
+    Ok(order)
+}
+
+/// The order of a hold invoice with its version
+pub async fn find_order_by_hash(pool: &SqlitePool, hash: &str) -> anyhow::Result<(Order, i64)> {
+    let row = sqlx::query(
         r#"
           SELECT *
           FROM orders
@@ -248,16 +518,51 @@ pub async fn find_order_by_hash(pool: &SqlitePool, hash: &str) -> anyhow::Result
     .fetch_one(pool)
     .await?;
 
-    Ok(order)
+    Ok((Order::from_row(&row)?, row.try_get("version")?))
+}
+
+/// Orders with a hold invoice which is not settled or canceled yet
+pub async fn find_orders_in_escrow(pool: &SqlitePool) -> anyhow::Result<Vec<Order>> {
+    let orders = sqlx::query_as::<_, Order>(
+        r#"
+          SELECT *
+          FROM orders
+          WHERE hash IS NOT NULL AND status IN
+          ('WaitingPayment', 'WaitingBuyerInvoice', 'Active', 'FiatSent', 'Dispute')
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+/// Orders taken and not finished yet, a restart can leave them waiting for
+/// LND events that already happened
+pub async fn find_orders_in_progress(pool: &SqlitePool) -> anyhow::Result<Vec<Order>> {
+    let orders = sqlx::query_as::<_, Order>(
+        r#"
+          SELECT *
+          FROM orders
+          WHERE status IN (
+            'WaitingPayment',
+            'WaitingBuyerInvoice',
+            'Active',
+            'FiatSent',
+            'Dispute',
+            'SettledHoldInvoice'
+          )
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
 }
 
-pub async fn find_order_by_date(pool: &SqlitePool) -> anyhow::Result<Vec<Order>> {
-    let exp_hours = var("EXP_HOURS")
-        .expect("EXP_HOURS is not set")
-        .as_str()
-        .parse::<u64>()
-        .unwrap();
-    let expire_time = Timestamp::now() - (3600 * exp_hours);
+/// Pending orders created more than `timeout` seconds ago
+pub async fn find_order_by_date(pool: &SqlitePool, timeout: i64) -> anyhow::Result<Vec<Order>> {
+    let expire_time = Timestamp::now().as_i64() - timeout;
     let order = sqlx::query_as::<_, Order>(
         r#"
           SELECT *
@@ -265,19 +570,23 @@ pub async fn find_order_by_date(pool: &SqlitePool) -> anyhow::Result<Vec<Order>>
           WHERE created_at < ?1 AND status == 'Pending'
         "#,
     )
-    .bind(expire_time.to_string())
+    .bind(expire_time)
     .fetch_all(pool)
     .await?;
 
     Ok(order)
 }
 
+/// Give an order back to the book, the transition is recorded here as the
+/// order is published again with the status already saved
 pub async fn update_order_to_initial_state(
     pool: &SqlitePool,
     order_id: Uuid,
     amount: i64,
     fee: i64,
+    trigger: Trigger,
 ) -> anyhow::Result<bool> {
+    let from_status = find_order_status(pool, order_id).await?;
     let mut conn = pool.acquire().await?;
     let status = "Pending".to_string();
     let hash: Option<String> = None;
@@ -307,24 +616,34 @@ pub async fn update_order_to_initial_state(
     .execute(&mut conn)
     .await?
     .rows_affected();
+    if rows_affected > 0 && is_transition(from_status.as_deref(), &status) {
+        add_order_event(pool, order_id, from_status.as_deref(), &status, trigger).await?;
+    }
 
     Ok(rows_affected > 0)
 }
 
 pub async fn init_cancel_order(pool: &SqlitePool, order: &Order) -> anyhow::Result<bool> {
     let mut conn = pool.acquire().await?;
+    // We keep track of when the cooperative cancel was requested to expire it
+    let cancel_initiated_at = match order.cancel_initiator_pubkey {
+        Some(_) => Timestamp::now().as_i64(),
+        None => 0,
+    };
     let rows_affected = sqlx::query!(
         r#"
             UPDATE orders
             SET
             cancel_initiator_pubkey = ?1,
             buyer_cooperativecancel = ?2,
-            seller_cooperativecancel = ?3
-            WHERE id = ?4
+            seller_cooperativecancel = ?3,
+            cancel_initiated_at = ?4
+            WHERE id = ?5
         "#,
         order.cancel_initiator_pubkey,
         order.buyer_cooperativecancel,
         order.seller_cooperativecancel,
+        cancel_initiated_at,
         order.id,
     )
     .execute(&mut conn)
@@ -333,3 +652,2888 @@ pub async fn init_cancel_order(pool: &SqlitePool, order: &Order) -> anyhow::Resu
 
     Ok(rows_affected > 0)
 }
+
+pub async fn add_audit_log(
+    pool: &SqlitePool,
+    pubkey: &str,
+    action: &str,
+    order_id: Option<Uuid>,
+    details: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO audit_log (
+            pubkey,
+            action,
+            order_id,
+            details,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(pubkey)
+    .bind(action)
+    .bind(order_id)
+    .bind(details)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Audit log entries written since `since`, oldest first
+pub async fn find_audit_log(pool: &SqlitePool, since: i64) -> anyhow::Result<Vec<AuditLog>> {
+    let entries = sqlx::query_as::<_, AuditLog>(
+        r#"
+          SELECT *
+          FROM audit_log
+          WHERE created_at >= ?1
+          ORDER BY id ASC
+        "#,
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
+
+pub async fn add_dispute(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    initiator_pubkey: &str,
+) -> anyhow::Result<Dispute> {
+    let mut conn = pool.acquire().await?;
+    let id = Uuid::new_v4();
+    let created_at = Timestamp::now();
+    let dispute = sqlx::query_as::<_, Dispute>(
+        r#"
+        INSERT INTO disputes (
+        id,
+        order_id,
+        status,
+        initiator_pubkey,
+        created_at
+      ) VALUES (?1, ?2, ?3, ?4, ?5)
+        RETURNING *
+      "#,
+    )
+    .bind(id)
+    .bind(order_id)
+    .bind("Initiated")
+    .bind(initiator_pubkey)
+    .bind(created_at.as_i64())
+    .fetch_one(&mut conn)
+    .await?;
+
+    Ok(dispute)
+}
+
+pub async fn find_dispute_by_id(pool: &SqlitePool, id: Uuid) -> anyhow::Result<Dispute> {
+    let dispute = sqlx::query_as::<_, Dispute>(
+        r#"
+          SELECT *
+          FROM disputes
+          WHERE id = ?1
+        "#,
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(dispute)
+}
+
+/// Dispute of an order not resolved yet
+pub async fn find_open_dispute(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<Dispute>> {
+    let dispute = sqlx::query_as::<_, Dispute>(
+        r#"
+          SELECT *
+          FROM disputes
+          WHERE order_id = ?1 AND status IN ('Initiated', 'InProgress')
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(dispute)
+}
+
+/// Assign a dispute to a solver, false when another solver took it first
+pub async fn take_dispute(
+    pool: &SqlitePool,
+    dispute_id: Uuid,
+    solver_pubkey: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let taken_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE disputes
+            SET
+            status = 'InProgress',
+            solver_pubkey = ?1,
+            taken_at = ?2
+            WHERE id = ?3 AND status == 'Initiated'
+        "#,
+    )
+    .bind(solver_pubkey)
+    .bind(taken_at)
+    .bind(dispute_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Close the open dispute of an order, the solver who resolved it is kept
+/// when nobody took it before
+pub async fn resolve_dispute(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    status: &str,
+    solver_pubkey: &str,
+    resolution: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let resolved_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE disputes
+            SET
+            status = ?1,
+            solver_pubkey = COALESCE(solver_pubkey, ?2),
+            resolution = ?3,
+            resolved_at = ?4
+            WHERE order_id = ?5 AND status IN ('Initiated', 'InProgress')
+        "#,
+    )
+    .bind(status)
+    .bind(solver_pubkey)
+    .bind(resolution)
+    .bind(resolved_at)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_order_dispute(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    buyer_dispute: bool,
+    seller_dispute: bool,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            buyer_dispute = ?1,
+            seller_dispute = ?2
+            WHERE id = ?3
+        "#,
+    )
+    .bind(buyer_dispute)
+    .bind(seller_dispute)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn add_dispute_message(
+    pool: &SqlitePool,
+    dispute_id: Uuid,
+    sender_pubkey: &str,
+    content: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO dispute_messages (
+            dispute_id,
+            sender_pubkey,
+            content,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4)
+        "#,
+    )
+    .bind(dispute_id)
+    .bind(sender_pubkey)
+    .bind(content)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn find_dispute_messages(
+    pool: &SqlitePool,
+    dispute_id: Uuid,
+) -> anyhow::Result<Vec<DisputeMessage>> {
+    let messages = sqlx::query_as::<_, DisputeMessage>(
+        r#"
+          SELECT *
+          FROM dispute_messages
+          WHERE dispute_id = ?1
+          ORDER BY created_at ASC, id ASC
+        "#,
+    )
+    .bind(dispute_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+pub async fn add_order_message(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    action: &str,
+    direction: &str,
+    payload_hash: &str,
+    event_id: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO order_messages (
+            order_id,
+            pubkey,
+            action,
+            direction,
+            payload_hash,
+            created_at,
+            event_id
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .bind(action)
+    .bind(direction)
+    .bind(payload_hash)
+    .bind(created_at.as_i64())
+    .bind(event_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Every message exchanged about an order, oldest first
+pub async fn find_order_messages(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Vec<OrderMessage>> {
+    let messages = sqlx::query_as::<_, OrderMessage>(
+        r#"
+          SELECT *
+          FROM order_messages
+          WHERE order_id = ?1
+          ORDER BY created_at ASC, id ASC
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+/// Messages a user exchanged with Mostro about an order, oldest first
+pub async fn find_user_order_messages(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+) -> anyhow::Result<Vec<OrderMessage>> {
+    let messages = sqlx::query_as::<_, OrderMessage>(
+        r#"
+          SELECT *
+          FROM order_messages
+          WHERE order_id = ?1 AND pubkey = ?2
+          ORDER BY created_at ASC, id ASC
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+/// Status of an order as saved now, before a transition is written
+pub async fn find_order_status(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<String>> {
+    let status = sqlx::query_scalar::<_, String>(
+        r#"
+          SELECT status
+          FROM orders
+          WHERE id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(status)
+}
+
+pub async fn add_order_event(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    from_status: Option<&str>,
+    to_status: &str,
+    trigger: Trigger,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO order_events (
+            order_id,
+            from_status,
+            to_status,
+            trigger,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(order_id)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(trigger.to_string())
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Every status transition of an order, oldest first
+pub async fn find_order_events(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Vec<OrderEvent>> {
+    let events = sqlx::query_as::<_, OrderEvent>(
+        r#"
+          SELECT *
+          FROM order_events
+          WHERE order_id = ?1
+          ORDER BY created_at ASC, id ASC
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+pub async fn find_expired_cancel_requests(
+    pool: &SqlitePool,
+    timeout_minutes: u64,
+) -> anyhow::Result<Vec<Order>> {
+    let expire_time = Timestamp::now() - (60 * timeout_minutes);
+    let orders = sqlx::query_as::<_, Order>(
+        r#"
+          SELECT *
+          FROM orders
+          WHERE cancel_initiator_pubkey IS NOT NULL
+          AND cancel_initiated_at < ?1
+          AND (status == 'Active' OR status == 'FiatSent' OR status == 'Dispute')
+        "#,
+    )
+    .bind(expire_time.as_i64())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+pub async fn update_order_invoice_held_at(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    invoice_held_at: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            invoice_held_at = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(invoice_held_at)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Keep the hash of the receipt sent to the parties of a successful order
+pub async fn update_order_receipt_hash(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    receipt_hash: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            receipt_hash = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(receipt_hash)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_order_release_requested(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    release_requested_at: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            release_requested_at = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(release_requested_at)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn find_release_requested_at(pool: &SqlitePool, order_id: Uuid) -> anyhow::Result<i64> {
+    let release_requested_at = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT release_requested_at
+          FROM orders
+          WHERE id == ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(release_requested_at)
+}
+
+/// Failed buyer payment of an order: when it last failed, 0 while it is
+/// being paid, how many times it failed and the sats the buyer gave up to
+/// raise the routing budget
+pub async fn find_payment_failure(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<(i64, i64, i64)> {
+    let failure = sqlx::query_as::<_, (i64, i64, i64)>(
+        r#"
+          SELECT payment_failed_at, payment_attempts, routing_compensation
+          FROM orders
+          WHERE id == ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(failure)
+}
+
+/// Record a failed buyer payment returning how many times it failed
+pub async fn update_payment_failed(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    failed_at: i64,
+) -> anyhow::Result<i64> {
+    let mut conn = pool.acquire().await?;
+    let attempts = sqlx::query_scalar::<_, i64>(
+        r#"
+            UPDATE orders
+            SET
+            payment_failed_at = ?1,
+            payment_attempts = payment_attempts + 1
+            WHERE id = ?2
+            RETURNING payment_attempts
+        "#,
+    )
+    .bind(failed_at)
+    .bind(order_id)
+    .fetch_one(&mut conn)
+    .await?;
+
+    Ok(attempts)
+}
+
+/// Mark a failed buyer payment as being paid again, false if it is already
+/// being paid so it is never paid twice at the same time
+pub async fn take_failed_payment(pool: &SqlitePool, order_id: Uuid) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            payment_failed_at = 0
+            WHERE id = ?1 AND payment_failed_at > 0
+        "#,
+    )
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_routing_compensation(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    compensation: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            routing_compensation = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(compensation)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Released orders whose buyer payment failed before `failed_before` and
+/// failed less than `max_attempts` times
+pub async fn find_failed_payments(
+    pool: &SqlitePool,
+    failed_before: i64,
+    max_attempts: i64,
+) -> anyhow::Result<Vec<Order>> {
+    let orders = sqlx::query_as::<_, Order>(
+        r#"
+          SELECT *
+          FROM orders
+          WHERE status == 'SettledHoldInvoice' AND payment_failed_at > 0
+          AND payment_failed_at < ?1 AND payment_attempts < ?2
+        "#,
+    )
+    .bind(failed_before)
+    .bind(max_attempts)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+pub async fn update_order_fiat_sent(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    fiat_sent_at: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            fiat_sent_at = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(fiat_sent_at)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Orders in `status` with their escalation timer, `started_column` is the
+/// column with the time the order entered the status if there is one
+pub async fn find_escalation_timers(
+    pool: &SqlitePool,
+    status: &Status,
+    started_column: Option<&str>,
+) -> anyhow::Result<Vec<EscalationTimer>> {
+    let state_started_at = match started_column {
+        Some(column) => format!("COALESCE(o.{column}, 0)"),
+        None => "0".to_string(),
+    };
+    let timers = sqlx::query_as::<_, EscalationTimer>(&format!(
+        r#"
+          SELECT
+            o.id,
+            {state_started_at} AS state_started_at,
+            e.started_at,
+            COALESCE(e.reminders, 0) AS reminders,
+            COALESCE(e.escalated, 0) AS escalated
+          FROM orders o
+          LEFT JOIN escalations e ON e.order_id = o.id AND e.status = o.status
+          WHERE o.status == ?1
+        "#
+    ))
+    .bind(status.to_string())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(timers)
+}
+
+pub async fn update_escalation(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    status: &Status,
+    started_at: i64,
+    reminders: i64,
+    escalated: bool,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO escalations (order_id, status, started_at, reminders, escalated)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (order_id, status) DO UPDATE SET
+            started_at = ?3,
+            reminders = ?4,
+            escalated = ?5
+        "#,
+    )
+    .bind(order_id)
+    .bind(status.to_string())
+    .bind(started_at)
+    .bind(reminders)
+    .bind(escalated)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Take `fiat_amount` from the range of a pending parent order creating a
+/// child order for it, the capacity is checked and reduced in the same
+/// transaction so simultaneous takers never get more than what is left.
+/// Returns the child order and the range maximum left on the parent, None if
+/// the amount is not available anymore
+pub async fn add_range_child_order(
+    pool: &SqlitePool,
+    parent_id: Uuid,
+    fiat_amount: i64,
+) -> anyhow::Result<Option<(Order, i64)>> {
+    let mut tx = pool.begin().await?;
+    let max_amount = sqlx::query_scalar::<_, i64>(
+        r#"
+            UPDATE orders
+            SET
+            max_amount = max_amount - ?1
+            WHERE id = ?2 AND status == 'Pending' AND ?1 >= min_amount AND ?1 <= max_amount
+            RETURNING max_amount
+        "#,
+    )
+    .bind(fiat_amount)
+    .bind(parent_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    let max_amount = match max_amount {
+        Some(max_amount) => max_amount,
+        None => return Ok(None),
+    };
+    let uuid = Uuid::new_v4();
+    let created_at = Timestamp::now();
+    // The child order has the same terms than the parent but a fixed fiat amount
+    let order = sqlx::query_as::<_, Order>(
+        r#"
+        INSERT INTO orders (
+        id,
+        kind,
+        event_id,
+        creator_pubkey,
+        buyer_pubkey,
+        seller_pubkey,
+        status,
+        premium,
+        payment_method,
+        amount,
+        price_from_api,
+        fiat_code,
+        fiat_amount,
+        range_parent_id,
+        region,
+        description,
+        denomination,
+        created_at
+      )
+        SELECT ?1, kind, '', creator_pubkey, buyer_pubkey, seller_pubkey, 'Pending',
+        premium, payment_method, 0, 1, fiat_code, ?2, id, region, description, 'fiat', ?3
+        FROM orders
+        WHERE id = ?4
+        RETURNING *
+      "#,
+    )
+    .bind(uuid)
+    .bind(fiat_amount)
+    .bind(created_at.as_i64())
+    .bind(parent_id)
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some((order, max_amount)))
+}
+
+/// Undo `add_range_child_order` when the take of a child order can't go on,
+/// the child is removed and its fiat amount is given back to the range of
+/// the parent in the same transaction. Returns the parent order with its
+/// version and if it had been closed because its range was exhausted, None
+/// if `child_id` is not a child order waiting to be taken
+pub async fn release_range_child_order(
+    pool: &SqlitePool,
+    child_id: Uuid,
+) -> anyhow::Result<Option<(Order, i64, bool)>> {
+    let mut tx = pool.begin().await?;
+    let child = sqlx::query_as::<_, (i64, Uuid)>(
+        r#"
+            SELECT fiat_amount, range_parent_id
+            FROM orders
+            WHERE id = ?1 AND range_parent_id IS NOT NULL
+            AND status IN ('Pending', 'WaitingPayment')
+        "#,
+    )
+    .bind(child_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    let (fiat_amount, parent_id) = match child {
+        Some(child) => child,
+        None => return Ok(None),
+    };
+    for query in [
+        "DELETE FROM order_events WHERE order_id = ?1",
+        "DELETE FROM orders_search WHERE order_id = ?1",
+        "DELETE FROM orders WHERE id = ?1",
+    ] {
+        sqlx::query(query).bind(child_id).execute(&mut tx).await?;
+    }
+    let exhausted = sqlx::query_scalar::<_, bool>(
+        r#"
+            SELECT status == 'Canceled' AND max_amount < min_amount
+            FROM orders
+            WHERE id = ?1
+        "#,
+    )
+    .bind(parent_id)
+    .fetch_one(&mut tx)
+    .await?;
+    let parent = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            max_amount = max_amount + ?1
+            WHERE id = ?2
+            RETURNING *
+        "#,
+    )
+    .bind(fiat_amount)
+    .bind(parent_id)
+    .fetch_one(&mut tx)
+    .await?;
+    let version = parent.try_get("version")?;
+    let parent = Order::from_row(&parent)?;
+    tx.commit().await?;
+
+    Ok(Some((parent, version, exhausted)))
+}
+
+pub async fn update_order_fee(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    fee: i64,
+    trade_fees: &TradeFees,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            fee = ?1,
+            seller_fee = ?2,
+            buyer_fee = ?3
+            WHERE id = ?4
+        "#,
+    )
+    .bind(fee)
+    .bind(trade_fees.seller_fee)
+    .bind(trade_fees.buyer_fee)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Fee paid by each party saved when the hold invoice of the order was
+/// created, None for orders taken before they were saved
+pub async fn find_trade_fees(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<TradeFees>> {
+    let fees = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+        r#"
+          SELECT seller_fee, buyer_fee
+          FROM orders
+          WHERE id == ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match fees {
+        Some((Some(seller_fee), Some(buyer_fee))) => Some(TradeFees {
+            seller_fee,
+            buyer_fee,
+        }),
+        _ => None,
+    })
+}
+
+/// Record a fee earned in the ledger with its value in the fiat currency of
+/// the order at the trade price
+pub async fn add_fee(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    amount: i64,
+    paid_by: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO fees (
+            order_id,
+            amount,
+            paid_by,
+            created_at,
+            fiat_code,
+            fiat_amount
+            )
+            SELECT ?1, ?2, ?3, ?4, o.fiat_code,
+            CASE WHEN o.amount > 0 THEN ?2 * o.fiat_amount * 1.0 / o.amount END
+            FROM (SELECT 1)
+            LEFT JOIN all_orders o ON o.id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .bind(amount)
+    .bind(paid_by)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Orders created by a user which are not finished yet
+pub async fn count_active_orders(pool: &SqlitePool, creator_pubkey: &str) -> anyhow::Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COUNT(*)
+          FROM orders
+          WHERE creator_pubkey == ?1 AND status NOT IN (
+            'Canceled',
+            'CanceledByAdmin',
+            'CompletedByAdmin',
+            'CooperativelyCanceled',
+            'Expired',
+            'SettledHoldInvoice',
+            'Success'
+          )
+        "#,
+    )
+    .bind(creator_pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Sats of the orders a user traded or is trading taken since `since`,
+/// canceled orders and `except_order_id` don't count
+pub async fn find_user_volume_since(
+    pool: &SqlitePool,
+    pubkey: &str,
+    since: i64,
+    except_order_id: Option<Uuid>,
+) -> anyhow::Result<i64> {
+    let volume = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COALESCE(SUM(amount), 0)
+          FROM all_orders
+          WHERE (buyer_pubkey == ?1 OR seller_pubkey == ?1) AND taken_at >= ?2
+          AND (?3 IS NULL OR id != ?3)
+          AND status NOT IN (
+            'Pending',
+            'Canceled',
+            'CanceledByAdmin',
+            'CooperativelyCanceled',
+            'Expired'
+          )
+        "#,
+    )
+    .bind(pubkey)
+    .bind(since)
+    .bind(except_order_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(volume)
+}
+
+pub async fn add_rating(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    rater_pubkey: &str,
+    rated_pubkey: &str,
+    rating: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO ratings (
+            order_id,
+            rater_pubkey,
+            rated_pubkey,
+            rating,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(order_id)
+    .bind(rater_pubkey)
+    .bind(rated_pubkey)
+    .bind(rating)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn has_rated(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    rater_pubkey: &str,
+) -> anyhow::Result<bool> {
+    let count = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COUNT(*)
+          FROM ratings
+          WHERE order_id == ?1 AND rater_pubkey == ?2
+        "#,
+    )
+    .bind(order_id)
+    .bind(rater_pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// Ratings received by a user, days since the first order the user was part of
+/// and trade statistics, all of them are added up as ratings and status
+/// changes are saved
+pub async fn get_user_reputation(
+    pool: &SqlitePool,
+    pubkey: &str,
+) -> anyhow::Result<UserReputation> {
+    let reputation = sqlx::query_as::<_, UserReputation>(
+        r#"
+          SELECT
+            total_ratings, average_rating, days_active, trades_completed, volume,
+            completion_rate, average_release_time
+          FROM user_reputations
+          WHERE pubkey == ?1
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(reputation.unwrap_or_default())
+}
+
+pub async fn add_user_if_not_exists(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT OR IGNORE INTO users (pubkey, created_at) VALUES (?1, ?2)
+        "#,
+    )
+    .bind(pubkey)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn find_user(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<Option<User>> {
+    let user = sqlx::query_as::<_, User>(
+        r#"
+          SELECT *
+          FROM users
+          WHERE pubkey == ?1
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn is_user_banned(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<bool> {
+    let user = find_user(pool, pubkey).await?;
+
+    Ok(user.map(|u| u.is_banned).unwrap_or(false))
+}
+
+/// Add a completed trade of `amount` sats to the user statistics
+pub async fn update_user_trade(
+    pool: &SqlitePool,
+    pubkey: &str,
+    amount: i64,
+) -> anyhow::Result<bool> {
+    add_user_if_not_exists(pool, pubkey).await?;
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE users
+            SET
+            trades_completed = trades_completed + 1,
+            volume = volume + ?1
+            WHERE pubkey = ?2
+        "#,
+    )
+    .bind(amount)
+    .bind(pubkey)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Add disputes opened and lost to the user statistics
+pub async fn update_user_disputes(
+    pool: &SqlitePool,
+    pubkey: &str,
+    opened: i64,
+    lost: i64,
+) -> anyhow::Result<bool> {
+    add_user_if_not_exists(pool, pubkey).await?;
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE users
+            SET
+            disputes_opened = disputes_opened + ?1,
+            disputes_lost = disputes_lost + ?2
+            WHERE pubkey = ?3
+        "#,
+    )
+    .bind(opened)
+    .bind(lost)
+    .bind(pubkey)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_user_banned(
+    pool: &SqlitePool,
+    pubkey: &str,
+    is_banned: bool,
+) -> anyhow::Result<bool> {
+    add_user_if_not_exists(pool, pubkey).await?;
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE users
+            SET
+            is_banned = ?1
+            WHERE pubkey = ?2
+        "#,
+    )
+    .bind(is_banned)
+    .bind(pubkey)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_user_language(
+    pool: &SqlitePool,
+    pubkey: &str,
+    lang: Lang,
+) -> anyhow::Result<bool> {
+    add_user_if_not_exists(pool, pubkey).await?;
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE users
+            SET
+            language = ?1
+            WHERE pubkey = ?2
+        "#,
+    )
+    .bind(lang.code())
+    .bind(pubkey)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Language declared by the user, English if it never declared one
+pub async fn get_user_language(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<Lang> {
+    let code = sqlx::query_scalar::<_, String>(
+        r#"
+          SELECT language
+          FROM users
+          WHERE pubkey = ?1
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(code
+        .as_deref()
+        .and_then(Lang::from_code)
+        .unwrap_or_default())
+}
+
+/// Record the id of a handled event, false if it was already handled
+pub async fn add_processed_event(
+    pool: &SqlitePool,
+    event_id: &str,
+    pubkey: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT OR IGNORE INTO processed_events (
+            event_id,
+            pubkey,
+            created_at
+            ) VALUES (?1, ?2, ?3)
+        "#,
+    )
+    .bind(event_id)
+    .bind(pubkey)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Delete the ids of events handled before `before`, returns how many
+pub async fn delete_processed_events(pool: &SqlitePool, before: i64) -> anyhow::Result<u64> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            DELETE FROM processed_events
+            WHERE created_at < ?1
+        "#,
+    )
+    .bind(before)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected)
+}
+
+/// Assign the taker to a Pending order and move it to WaitingPayment in a
+/// single compare-and-swap on the `version` the taker read, only one of two
+/// concurrent takers gets true
+pub async fn take_order(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    taker_pubkey: &str,
+    version: i64,
+) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let taken_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            status = 'WaitingPayment',
+            buyer_pubkey = CASE WHEN kind == 'Sell' THEN ?1 ELSE buyer_pubkey END,
+            seller_pubkey = CASE WHEN kind == 'Buy' THEN ?1 ELSE seller_pubkey END,
+            taken_at = ?2
+            WHERE id = ?3 AND status == 'Pending' AND version = ?4
+        "#,
+    )
+    .bind(taker_pubkey)
+    .bind(taken_at.as_i64())
+    .bind(order_id)
+    .bind(version)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    if rows_affected > 0 {
+        add_take_event(
+            &mut tx,
+            order_id,
+            "Pending",
+            "WaitingPayment",
+            Trigger::User,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(rows_affected > 0)
+}
+
+/// Give back to the book an order taken with `take_order` when the take
+/// can't go on
+pub async fn untake_order(pool: &SqlitePool, order_id: Uuid) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            status = 'Pending',
+            buyer_pubkey = CASE WHEN kind == 'Sell' THEN NULL ELSE buyer_pubkey END,
+            seller_pubkey = CASE WHEN kind == 'Buy' THEN NULL ELSE seller_pubkey END,
+            fiat_amount = CASE WHEN denomination == 'sats' THEN 0 ELSE fiat_amount END,
+            taken_at = 0
+            WHERE id = ?1 AND status == 'WaitingPayment'
+        "#,
+    )
+    .bind(order_id)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    if rows_affected > 0 {
+        add_take_event(
+            &mut tx,
+            order_id,
+            "WaitingPayment",
+            "Pending",
+            Trigger::System,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(rows_affected > 0)
+}
+
+/// Lifecycle entry of a status change, written in the same transaction as
+/// the status so both are saved or none
+async fn add_take_event(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    order_id: Uuid,
+    from_status: &str,
+    to_status: &str,
+    trigger: Trigger,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            INSERT INTO order_events (
+            order_id,
+            from_status,
+            to_status,
+            trigger,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(order_id)
+    .bind(from_status)
+    .bind(to_status)
+    .bind(trigger.to_string())
+    .bind(Timestamp::now().as_i64())
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn edit_order_amount(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    amount: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            amount = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(amount)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Fiat amount of a sats denominated order, computed when it is taken and
+/// cleared when it is published again
+pub async fn edit_order_fiat_amount(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    fiat_amount: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            fiat_amount = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(fiat_amount)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn add_bond(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    amount: i64,
+    hash: &str,
+    preimage: &str,
+) -> anyhow::Result<Bond> {
+    let mut conn = pool.acquire().await?;
+    let uuid = Uuid::new_v4();
+    let created_at = Timestamp::now();
+    let bond = sqlx::query_as::<_, Bond>(
+        r#"
+        INSERT INTO bonds (
+        id,
+        order_id,
+        pubkey,
+        amount,
+        hash,
+        preimage,
+        status,
+        created_at
+      ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'Requested', ?7)
+        RETURNING *
+      "#,
+    )
+    .bind(uuid)
+    .bind(order_id)
+    .bind(pubkey)
+    .bind(amount)
+    .bind(hash)
+    .bind(encrypt_secret(preimage)?)
+    .bind(created_at.as_i64())
+    .fetch_one(&mut conn)
+    .await?;
+
+    Ok(bond)
+}
+
+/// Move the finished orders created before `created_before` to the archive,
+/// returns the number of orders archived
+pub async fn archive_orders(
+    pool: &SqlitePool,
+    created_before: i64,
+    statuses: &[&str],
+) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    let statuses = statuses
+        .iter()
+        .map(|status| format!("'{status}'"))
+        .collect::<Vec<String>>()
+        .join(",");
+    let archived = sqlx::query(&format!(
+        r#"
+            INSERT INTO orders_archive
+            SELECT * FROM orders
+            WHERE created_at < ?1 AND status IN ({statuses})
+        "#
+    ))
+    .bind(created_before)
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+    sqlx::query(
+        r#"
+            DELETE FROM orders
+            WHERE id IN (SELECT id FROM orders_archive)
+        "#,
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(archived)
+}
+
+/// Order moved to the archive once finished
+pub async fn find_archived_order(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<Order>> {
+    let order = sqlx::query_as::<_, Order>(
+        r#"
+          SELECT *
+          FROM orders_archive
+          WHERE id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(order)
+}
+
+/// Orders with a preimage or buyer invoice saved in plain text
+pub async fn find_plain_order_secrets(
+    pool: &SqlitePool,
+) -> anyhow::Result<Vec<(Uuid, Option<String>, Option<String>)>> {
+    let secrets = sqlx::query_as::<_, (Uuid, Option<String>, Option<String>)>(
+        r#"
+          SELECT id, preimage, buyer_invoice
+          FROM orders
+          WHERE (preimage IS NOT NULL AND preimage NOT LIKE 'enc:%')
+          OR (buyer_invoice IS NOT NULL AND buyer_invoice NOT LIKE 'enc:%')
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(secrets)
+}
+
+pub async fn update_order_secrets(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    preimage: Option<String>,
+    buyer_invoice: Option<String>,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            preimage = ?1,
+            buyer_invoice = ?2
+            WHERE id = ?3
+        "#,
+    )
+    .bind(preimage)
+    .bind(buyer_invoice)
+    .bind(order_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Bonds with the preimage saved in plain text
+pub async fn find_plain_bond_preimages(pool: &SqlitePool) -> anyhow::Result<Vec<(Uuid, String)>> {
+    let preimages = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+          SELECT id, preimage
+          FROM bonds
+          WHERE preimage NOT LIKE 'enc:%'
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(preimages)
+}
+
+pub async fn update_bond_preimage(
+    pool: &SqlitePool,
+    bond_id: Uuid,
+    preimage: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE bonds
+            SET
+            preimage = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(preimage)
+    .bind(bond_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Payout parts with the invoice saved in plain text
+pub async fn find_plain_payout_parts(pool: &SqlitePool) -> anyhow::Result<Vec<(Uuid, String)>> {
+    let invoices = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+          SELECT id, payment_request
+          FROM payout_parts
+          WHERE payment_request NOT LIKE 'enc:%'
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(invoices)
+}
+
+pub async fn update_payout_part_invoice(
+    pool: &SqlitePool,
+    part_id: Uuid,
+    payment_request: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE payout_parts
+            SET
+            payment_request = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(payment_request)
+    .bind(part_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn find_bond_by_hash(pool: &SqlitePool, hash: &str) -> anyhow::Result<Bond> {
+    let bond = sqlx::query_as::<_, Bond>(
+        r#"
+          SELECT *
+          FROM bonds
+          WHERE hash = ?1
+        "#,
+    )
+    .bind(hash)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(bond)
+}
+
+/// Bond of an order still requested or locked
+pub async fn find_active_bond(pool: &SqlitePool, order_id: Uuid) -> anyhow::Result<Option<Bond>> {
+    let bond = sqlx::query_as::<_, Bond>(
+        r#"
+          SELECT *
+          FROM bonds
+          WHERE order_id = ?1 AND status IN ('Requested', 'Locked')
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(bond)
+}
+
+pub async fn update_bond_status(
+    pool: &SqlitePool,
+    bond_id: Uuid,
+    status: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE bonds
+            SET
+            status = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(status)
+    .bind(bond_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_bond_payout(
+    pool: &SqlitePool,
+    bond_id: Uuid,
+    payee_pubkey: &str,
+    payout_amount: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE bonds
+            SET
+            payee_pubkey = ?1,
+            payout_amount = ?2
+            WHERE id = ?3
+        "#,
+    )
+    .bind(payee_pubkey)
+    .bind(payout_amount)
+    .bind(bond_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn add_payout(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    amount: i64,
+) -> anyhow::Result<Payout> {
+    let mut conn = pool.acquire().await?;
+    let uuid = Uuid::new_v4();
+    let created_at = Timestamp::now();
+    let payout = sqlx::query_as::<_, Payout>(
+        r#"
+        INSERT INTO payouts (
+        id,
+        order_id,
+        pubkey,
+        amount,
+        status,
+        created_at
+      ) VALUES (?1, ?2, ?3, ?4, 'Pending', ?5)
+        RETURNING *
+      "#,
+    )
+    .bind(uuid)
+    .bind(order_id)
+    .bind(pubkey)
+    .bind(amount)
+    .bind(created_at.as_i64())
+    .fetch_one(&mut conn)
+    .await?;
+
+    Ok(payout)
+}
+
+/// Payout of an order waiting for an invoice of the user
+pub async fn find_pending_payout(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+) -> anyhow::Result<Option<Payout>> {
+    let payout = sqlx::query_as::<_, Payout>(
+        r#"
+          SELECT *
+          FROM payouts
+          WHERE order_id = ?1 AND pubkey = ?2 AND status == 'Pending'
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(payout)
+}
+
+pub async fn find_payouts(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+) -> anyhow::Result<Vec<Payout>> {
+    let payouts = sqlx::query_as::<_, Payout>(
+        r#"
+          SELECT *
+          FROM payouts
+          WHERE order_id = ?1 AND pubkey = ?2
+          ORDER BY created_at
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(payouts)
+}
+
+/// Start a payment attempt, nothing is added while an attempt with the
+/// same hash is in flight
+pub async fn add_payment(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    payment_hash: &str,
+    amount: i64,
+    status: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO payments (
+            order_id,
+            pubkey,
+            payment_hash,
+            amount,
+            status,
+            created_at,
+            updated_at
+            )
+            SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?6
+            WHERE NOT EXISTS (
+              SELECT 1 FROM payments WHERE payment_hash = ?3 AND status = 'InFlight'
+            )
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .bind(payment_hash)
+    .bind(amount)
+    .bind(status)
+    .bind(created_at)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Close the attempt in flight of a payment, false when there is none
+pub async fn update_payment(
+    pool: &SqlitePool,
+    payment_hash: &str,
+    status: &str,
+    fee_paid: i64,
+    failure_reason: Option<&str>,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let updated_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE payments
+            SET
+            status = ?1,
+            fee_paid = ?2,
+            failure_reason = ?3,
+            updated_at = ?4
+            WHERE payment_hash = ?5 AND status = 'InFlight'
+        "#,
+    )
+    .bind(status)
+    .bind(fee_paid)
+    .bind(failure_reason)
+    .bind(updated_at)
+    .bind(payment_hash)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Payment attempts of an order to `pubkey`, oldest first
+pub async fn find_payments(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+) -> anyhow::Result<Vec<Payment>> {
+    let payments = sqlx::query_as::<_, Payment>(
+        r#"
+          SELECT *
+          FROM payments
+          WHERE order_id = ?1 AND pubkey = ?2
+          ORDER BY created_at ASC, id ASC
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(payments)
+}
+
+/// Replace the invoices a buyer sent to split the payout of an order, the
+/// parts are paid in the order they were sent
+pub async fn add_payout_parts(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    invoices: &[(String, i64)],
+) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM payout_parts WHERE order_id = ?1")
+        .bind(order_id)
+        .execute(&mut tx)
+        .await?;
+    let created_at = Timestamp::now().as_i64();
+    for (part, (payment_request, amount)) in invoices.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO payout_parts (
+            id,
+            order_id,
+            part,
+            payment_request,
+            amount,
+            status,
+            created_at
+          ) VALUES (?1, ?2, ?3, ?4, ?5, 'Pending', ?6)
+          "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(order_id)
+        .bind(part as i64 + 1)
+        .bind(encrypt_secret(payment_request)?)
+        .bind(amount)
+        .bind(created_at)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(!invoices.is_empty())
+}
+
+pub async fn find_payout_parts(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Vec<PayoutPart>> {
+    let parts = sqlx::query_as::<_, PayoutPart>(
+        r#"
+          SELECT *
+          FROM payout_parts
+          WHERE order_id = ?1
+          ORDER BY part
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(parts)
+}
+
+pub async fn update_payout_part_status(
+    pool: &SqlitePool,
+    part_id: Uuid,
+    status: &str,
+    payment_hash: Option<&str>,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE payout_parts
+            SET
+            status = ?1,
+            payment_hash = ?2
+            WHERE id = ?3
+        "#,
+    )
+    .bind(status)
+    .bind(payment_hash)
+    .bind(part_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_payout_status(
+    pool: &SqlitePool,
+    payout_id: Uuid,
+    status: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE payouts
+            SET
+            status = ?1
+            WHERE id = ?2
+        "#,
+    )
+    .bind(status)
+    .bind(payout_id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn add_recurring_order(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    remaining: i64,
+    max_amount: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO recurring_orders (
+            order_id,
+            remaining,
+            max_amount,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4)
+        "#,
+    )
+    .bind(order_id)
+    .bind(remaining)
+    .bind(max_amount)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Remove the recurring flag of an order returning how many times it can
+/// still be published and its range maximum, this way it is republished once
+pub async fn take_recurring_order(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<(i64, i64)>> {
+    let mut conn = pool.acquire().await?;
+    let recurring = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+          DELETE FROM recurring_orders
+          WHERE order_id = ?1
+          RETURNING remaining, max_amount
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(&mut conn)
+    .await?;
+
+    Ok(recurring)
+}
+
+/// Replace the terms of an order only while it is still Pending
+pub async fn edit_order_terms(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    order: &NewOrder,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let price_from_api = order.amount == 0;
+    let denomination =
+        Denomination::from_amounts(order.amount, order.fiat_amount).map(|d| d.to_string());
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE orders
+            SET
+            amount = ?1,
+            price_from_api = ?2,
+            fiat_amount = ?3,
+            min_amount = ?4,
+            max_amount = ?5,
+            premium = ?6,
+            payment_method = ?7,
+            denomination = ?9
+            WHERE id = ?8 AND status == 'Pending'
+        "#,
+    )
+    .bind(order.amount)
+    .bind(price_from_api)
+    .bind(order.fiat_amount)
+    .bind(order.min_amount.unwrap_or(0))
+    .bind(order.max_amount.unwrap_or(0))
+    .bind(order.premium)
+    .bind(&order.payment_method)
+    .bind(order_id)
+    .bind(denomination)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn find_order_extras(pool: &SqlitePool, order_id: Uuid) -> anyhow::Result<OrderExtras> {
+    let extras = sqlx::query_as::<_, OrderExtras>(
+        r#"
+          SELECT region, description, denomination
+          FROM orders
+          WHERE id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(extras.unwrap_or_default())
+}
+
+pub async fn find_order_detail(
+    pool: &SqlitePool,
+    order_id: Uuid,
+) -> anyhow::Result<Option<OrderDetail>> {
+    let detail = sqlx::query_as::<_, OrderDetail>(
+        r#"
+          SELECT
+            id, kind, status, amount, fee, fiat_code, fiat_amount,
+            payment_method, premium, creator_pubkey, buyer_pubkey,
+            seller_pubkey, region, description,
+            buyer_invoice IS NOT NULL AS buyer_invoice_sent,
+            created_at, taken_at, invoice_held_at, fiat_sent_at
+          FROM all_orders
+          WHERE id = ?1
+        "#,
+    )
+    .bind(order_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(detail)
+}
+
+/// Orders made or taken by a user, newest first
+pub async fn find_user_orders(
+    pool: &SqlitePool,
+    pubkey: &str,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<OrderDetail>> {
+    let orders = sqlx::query_as::<_, OrderDetail>(
+        r#"
+          SELECT
+            id, kind, status, amount, fee, fiat_code, fiat_amount,
+            payment_method, premium, creator_pubkey, buyer_pubkey,
+            seller_pubkey, region, description,
+            buyer_invoice IS NOT NULL AS buyer_invoice_sent,
+            created_at, taken_at, invoice_held_at, fiat_sent_at
+          FROM all_orders
+          WHERE creator_pubkey = ?1 OR buyer_pubkey = ?1 OR seller_pubkey = ?1
+          ORDER BY created_at DESC
+          LIMIT ?2 OFFSET ?3
+        "#,
+    )
+    .bind(pubkey)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+/// Orders matching a full text query on their description, payment method
+/// and pubkeys, archived ones included, newest first
+pub async fn search_orders(
+    pool: &SqlitePool,
+    query: &str,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<OrderDetail>> {
+    let orders = sqlx::query_as::<_, OrderDetail>(
+        r#"
+          SELECT
+            id, kind, status, amount, fee, fiat_code, fiat_amount,
+            payment_method, premium, creator_pubkey, buyer_pubkey,
+            seller_pubkey, region, description,
+            buyer_invoice IS NOT NULL AS buyer_invoice_sent,
+            created_at, taken_at, invoice_held_at, fiat_sent_at
+          FROM all_orders
+          WHERE id IN (SELECT order_id FROM orders_search WHERE orders_search MATCH ?1)
+          ORDER BY created_at DESC
+          LIMIT ?2 OFFSET ?3
+        "#,
+    )
+    .bind(query)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+/// Orders in a status, or all of them, archived ones included, newest first
+pub async fn find_order_details(
+    pool: &SqlitePool,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<OrderDetail>> {
+    let orders = sqlx::query_as::<_, OrderDetail>(
+        r#"
+          SELECT
+            id, kind, status, amount, fee, fiat_code, fiat_amount,
+            payment_method, premium, creator_pubkey, buyer_pubkey,
+            seller_pubkey, region, description,
+            buyer_invoice IS NOT NULL AS buyer_invoice_sent,
+            created_at, taken_at, invoice_held_at, fiat_sent_at
+          FROM all_orders
+          WHERE ?1 IS NULL OR status = ?1
+          ORDER BY created_at DESC
+          LIMIT ?2 OFFSET ?3
+        "#,
+    )
+    .bind(status)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orders)
+}
+
+/// Disputes in a status, or all of them, newest first
+pub async fn find_disputes(
+    pool: &SqlitePool,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<Dispute>> {
+    let disputes = sqlx::query_as::<_, Dispute>(
+        r#"
+          SELECT *
+          FROM disputes
+          WHERE ?1 IS NULL OR status = ?1
+          ORDER BY created_at DESC
+          LIMIT ?2 OFFSET ?3
+        "#,
+    )
+    .bind(status)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(disputes)
+}
+
+pub async fn count_search_orders(pool: &SqlitePool, query: &str) -> anyhow::Result<i64> {
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COUNT(DISTINCT order_id)
+          FROM orders_search
+          WHERE orders_search MATCH ?1
+        "#,
+    )
+    .bind(query)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(total)
+}
+
+/// Orders in the database, archived ones included
+pub async fn count_orders(pool: &SqlitePool) -> anyhow::Result<i64> {
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COUNT(*)
+          FROM all_orders
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(total)
+}
+
+/// Orders created, fees earned and payouts failed since a unix time, and the
+/// disputes waiting for a solver or being solved now
+pub async fn count_activity(pool: &SqlitePool, since: i64) -> anyhow::Result<Activity> {
+    let activity = sqlx::query_as::<_, Activity>(
+        r#"
+          SELECT
+            (SELECT COUNT(*) FROM all_orders WHERE created_at >= ?1) AS new_orders,
+            (SELECT COALESCE(SUM(amount), 0) FROM fees WHERE created_at >= ?1) AS fees,
+            (SELECT COUNT(*) FROM disputes
+             WHERE status IN ('Initiated', 'InProgress')) AS pending_disputes,
+            (SELECT COUNT(*) FROM payments
+             WHERE status = 'Failed' AND updated_at >= ?1) AS failed_payouts
+        "#,
+    )
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(activity)
+}
+
+/// Orders not archived yet by status
+pub async fn count_orders_by_status(pool: &SqlitePool) -> anyhow::Result<Vec<(String, i64)>> {
+    let counts = sqlx::query_as::<_, (String, i64)>(
+        r#"
+          SELECT status, COUNT(*)
+          FROM orders
+          GROUP BY status
+          ORDER BY status
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(counts)
+}
+
+pub async fn count_user_orders(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<i64> {
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT COUNT(*)
+          FROM all_orders
+          WHERE creator_pubkey = ?1 OR buyer_pubkey = ?1 OR seller_pubkey = ?1
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(total)
+}
+
+/// Highest trade index used by `pubkey`, None if it never sent one
+pub async fn find_last_trade_index(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<Option<i64>> {
+    let index = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+          SELECT MAX(trade_index)
+          FROM trade_indexes
+          WHERE pubkey = ?1
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(index)
+}
+
+/// Store the trade index used by `pubkey` in an order, false if the index was
+/// already used
+pub async fn add_trade_index(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    trade_index: i64,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT OR IGNORE INTO trade_indexes (
+            order_id,
+            pubkey,
+            trade_index,
+            created_at
+            ) VALUES (?1, ?2, ?3, ?4)
+        "#,
+    )
+    .bind(order_id)
+    .bind(pubkey)
+    .bind(trade_index)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Keep a DM no relay took to publish it later
+pub async fn add_outbox_message(
+    pool: &SqlitePool,
+    event_id: &str,
+    receiver_pubkey: &str,
+    event: &str,
+    error: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO outbox (
+            event_id,
+            receiver_pubkey,
+            event,
+            status,
+            last_error,
+            created_at,
+            updated_at
+            ) VALUES (?1, ?2, ?3, 'Pending', ?4, ?5, ?5)
+        "#,
+    )
+    .bind(event_id)
+    .bind(receiver_pubkey)
+    .bind(event)
+    .bind(error)
+    .bind(created_at)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// DMs waiting in the outbox, oldest first so they arrive in order
+pub async fn find_pending_outbox_messages(pool: &SqlitePool) -> anyhow::Result<Vec<OutboxMessage>> {
+    let messages = sqlx::query_as::<_, OutboxMessage>(
+        r#"
+          SELECT *
+          FROM outbox
+          WHERE status = 'Pending'
+          ORDER BY id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(messages)
+}
+
+/// Record a try to deliver a DM of the outbox
+pub async fn update_outbox_message(
+    pool: &SqlitePool,
+    id: i64,
+    status: &str,
+    error: Option<&str>,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let updated_at = Timestamp::now().as_i64();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE outbox
+            SET status = ?1,
+            attempts = attempts + 1,
+            last_error = COALESCE(?2, last_error),
+            updated_at = ?3
+            WHERE id = ?4
+        "#,
+    )
+    .bind(status)
+    .bind(error)
+    .bind(updated_at)
+    .bind(id)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Fees earned between `from` and `to`, oldest first
+pub async fn find_fees(pool: &SqlitePool, from: i64, to: i64) -> anyhow::Result<Vec<Fee>> {
+    let fees = sqlx::query_as::<_, Fee>(
+        r#"
+          SELECT *
+          FROM fees
+          WHERE created_at >= ?1 AND created_at < ?2
+          ORDER BY created_at
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(fees)
+}
+
+/// Fees earned between `from` and `to` added up by UTC month and currency
+pub async fn find_monthly_fees(
+    pool: &SqlitePool,
+    from: i64,
+    to: i64,
+) -> anyhow::Result<Vec<MonthlyFees>> {
+    let totals = sqlx::query_as::<_, MonthlyFees>(
+        r#"
+          SELECT strftime('%Y-%m', created_at, 'unixepoch') AS month,
+          COALESCE(fiat_code, '') AS fiat_code,
+          COUNT(DISTINCT order_id) AS orders,
+          SUM(amount) AS amount,
+          COALESCE(SUM(fiat_amount), 0.0) AS fiat_amount
+          FROM fees
+          WHERE created_at >= ?1 AND created_at < ?2
+          GROUP BY month, fiat_code
+          ORDER BY month, fiat_code
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(totals)
+}
+
+/// Orders completed between `from` and `to`, oldest first. Orders completed
+/// before their transitions were recorded take the time the fiat was sent
+pub async fn find_trades(pool: &SqlitePool, from: i64, to: i64) -> anyhow::Result<Vec<Trade>> {
+    let trades = sqlx::query_as::<_, Trade>(
+        r#"
+          SELECT * FROM (
+            SELECT
+              o.id, o.kind, o.amount, o.fee, o.fiat_code, o.fiat_amount,
+              o.payment_method, o.premium, o.created_at,
+              COALESCE(
+                (SELECT MAX(e.created_at) FROM order_events e
+                 WHERE e.order_id = o.id AND e.to_status = 'Success'),
+                NULLIF(o.fiat_sent_at, 0),
+                o.taken_at
+              ) AS completed_at
+            FROM all_orders o
+            WHERE o.status = 'Success'
+          )
+          WHERE completed_at >= ?1 AND completed_at < ?2
+          ORDER BY completed_at
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(trades)
+}
+
+/// Keep an incoming event as it was received
+pub async fn add_raw_event(
+    pool: &SqlitePool,
+    event_id: &str,
+    pubkey: &str,
+    event: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let received_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT OR IGNORE INTO raw_events (
+            event_id,
+            pubkey,
+            event,
+            received_at
+            ) VALUES (?1, ?2, ?3, ?4)
+        "#,
+    )
+    .bind(event_id)
+    .bind(pubkey)
+    .bind(event)
+    .bind(received_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Delete the events received before `before`, returns how many
+pub async fn delete_raw_events(pool: &SqlitePool, before: i64) -> anyhow::Result<u64> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            DELETE FROM raw_events
+            WHERE received_at < ?1
+        "#,
+    )
+    .bind(before)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected)
+}
+
+pub async fn find_settings(pool: &SqlitePool) -> anyhow::Result<Vec<Setting>> {
+    let settings = sqlx::query_as::<_, Setting>(
+        r#"
+          SELECT *
+          FROM settings
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(settings)
+}
+
+pub async fn set_setting(
+    pool: &SqlitePool,
+    name: &str,
+    value: &str,
+    updated_by: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let updated_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT INTO settings (name, value, updated_by, updated_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(name) DO UPDATE SET
+            value = excluded.value,
+            updated_by = excluded.updated_by,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(name)
+    .bind(value)
+    .bind(updated_by)
+    .bind(updated_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn delete_setting(pool: &SqlitePool, name: &str) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            DELETE FROM settings
+            WHERE name = ?1
+        "#,
+    )
+    .bind(name)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Orders of a user not finished yet and payouts still owed to the user
+pub async fn count_user_open_items(pool: &SqlitePool, pubkey: &str) -> anyhow::Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        r#"
+          SELECT
+            (SELECT COUNT(*)
+            FROM orders
+            WHERE ?1 IN (creator_pubkey, buyer_pubkey, seller_pubkey) AND status NOT IN (
+              'Canceled',
+              'CanceledByAdmin',
+              'CompletedByAdmin',
+              'CooperativelyCanceled',
+              'Expired',
+              'SettledHoldInvoice',
+              'Success'
+            ))
+            +
+            (SELECT COUNT(*)
+            FROM payouts
+            WHERE pubkey = ?1 AND status == 'Pending')
+        "#,
+    )
+    .bind(pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Delete the messages, events, ratings and user record of `pubkey` and
+/// replace it with `anonymous_pubkey` in the orders and payment records
+/// Mostro has to keep. `hex_pubkey` is the same key as the outbox keeps it.
+/// Everything runs in one transaction which a dry run rolls back, so the
+/// report has the rows a real purge would change
+pub async fn purge_user_data(
+    pool: &SqlitePool,
+    pubkey: &str,
+    hex_pubkey: &str,
+    anonymous_pubkey: &str,
+    dry_run: bool,
+) -> anyhow::Result<PurgeReport> {
+    let mut tx = pool.begin().await?;
+    let mut report = PurgeReport {
+        dry_run,
+        ..Default::default()
+    };
+    // Archived orders are not covered by the search index triggers
+    sqlx::query(
+        r#"
+            UPDATE orders_search
+            SET pubkeys = REPLACE(pubkeys, ?1, ?2)
+            WHERE order_id IN (
+              SELECT id FROM orders_archive
+              WHERE ?1 IN (creator_pubkey, buyer_pubkey, seller_pubkey)
+            )
+        "#,
+    )
+    .bind(pubkey)
+    .bind(anonymous_pubkey)
+    .execute(&mut tx)
+    .await?;
+    for table in ["orders", "orders_archive"] {
+        report.orders += sqlx::query(&format!(
+            r#"
+                UPDATE {table}
+                SET
+                buyer_invoice = CASE WHEN buyer_pubkey = ?1 THEN NULL ELSE buyer_invoice END,
+                creator_pubkey = CASE WHEN creator_pubkey = ?1 THEN ?2 ELSE creator_pubkey END,
+                buyer_pubkey = CASE WHEN buyer_pubkey = ?1 THEN ?2 ELSE buyer_pubkey END,
+                seller_pubkey = CASE WHEN seller_pubkey = ?1 THEN ?2 ELSE seller_pubkey END,
+                cancel_initiator_pubkey = CASE
+                  WHEN cancel_initiator_pubkey = ?1 THEN ?2 ELSE cancel_initiator_pubkey
+                END
+                WHERE ?1 IN (creator_pubkey, buyer_pubkey, seller_pubkey, cancel_initiator_pubkey)
+            "#
+        ))
+        .bind(pubkey)
+        .bind(anonymous_pubkey)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+    }
+    for query in [
+        "DELETE FROM order_messages WHERE pubkey = ?1",
+        "DELETE FROM dispute_messages WHERE sender_pubkey = ?1",
+    ] {
+        report.messages += sqlx::query(query)
+            .bind(pubkey)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    // The ratings given count in the reputation of the counterparts, they
+    // stay without the rater
+    report.ratings = sqlx::query("DELETE FROM ratings WHERE rated_pubkey = ?1")
+        .bind(pubkey)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+    report.ratings += sqlx::query("UPDATE ratings SET rater_pubkey = ?2 WHERE rater_pubkey = ?1")
+        .bind(pubkey)
+        .bind(anonymous_pubkey)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+    for (query, key) in [
+        ("DELETE FROM raw_events WHERE pubkey = ?1", pubkey),
+        ("DELETE FROM processed_events WHERE pubkey = ?1", pubkey),
+        ("DELETE FROM outbox WHERE receiver_pubkey = ?1", hex_pubkey),
+    ] {
+        report.events += sqlx::query(query)
+            .bind(key)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    for query in [
+        "UPDATE bonds SET pubkey = ?2 WHERE pubkey = ?1",
+        "UPDATE bonds SET payee_pubkey = ?2 WHERE payee_pubkey = ?1",
+        "UPDATE payouts SET pubkey = ?2 WHERE pubkey = ?1",
+        "UPDATE payments SET pubkey = ?2 WHERE pubkey = ?1",
+        "UPDATE disputes SET initiator_pubkey = ?2 WHERE initiator_pubkey = ?1",
+    ] {
+        report.payments += sqlx::query(query)
+            .bind(pubkey)
+            .bind(anonymous_pubkey)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+    }
+    report.trade_indexes = sqlx::query("DELETE FROM trade_indexes WHERE pubkey = ?1")
+        .bind(pubkey)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+    report.user = sqlx::query("DELETE FROM users WHERE pubkey = ?1")
+        .bind(pubkey)
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+        > 0;
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    Ok(report)
+}
+
+pub async fn find_relays(pool: &SqlitePool) -> anyhow::Result<Vec<Relay>> {
+    let relays = sqlx::query_as::<_, Relay>(
+        r#"
+          SELECT *
+          FROM relays
+          ORDER BY created_at, url
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(relays)
+}
+
+pub async fn add_relay(pool: &SqlitePool, url: &str, updated_by: &str) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let created_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            INSERT OR IGNORE INTO relays (url, updated_by, created_at)
+            VALUES (?1, ?2, ?3)
+        "#,
+    )
+    .bind(url)
+    .bind(updated_by)
+    .bind(created_at.as_i64())
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn update_relay_enabled(
+    pool: &SqlitePool,
+    url: &str,
+    enabled: bool,
+    updated_by: &str,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE relays
+            SET
+            enabled = ?1,
+            updated_by = ?2
+            WHERE url = ?3
+        "#,
+    )
+    .bind(enabled)
+    .bind(updated_by)
+    .bind(url)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+/// Status and latency in milliseconds seen by the last check of a relay
+pub async fn update_relay_status(
+    pool: &SqlitePool,
+    url: &str,
+    status: &str,
+    latency: Option<i64>,
+) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let checked_at = Timestamp::now();
+    let rows_affected = sqlx::query(
+        r#"
+            UPDATE relays
+            SET
+            status = ?1,
+            latency = ?2,
+            checked_at = ?3
+            WHERE url = ?4
+        "#,
+    )
+    .bind(status)
+    .bind(latency)
+    .bind(checked_at.as_i64())
+    .bind(url)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
+
+pub async fn delete_relay(pool: &SqlitePool, url: &str) -> anyhow::Result<bool> {
+    let mut conn = pool.acquire().await?;
+    let rows_affected = sqlx::query(
+        r#"
+            DELETE FROM relays
+            WHERE url = ?1
+        "#,
+    )
+    .bind(url)
+    .execute(&mut conn)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
+}
@@ -0,0 +1,56 @@
+use crate::db;
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::info;
+
+/// Days the id of a handled event is kept, PROCESSED_EVENTS_MAX_AGE
+pub fn get_max_age_days() -> i64 {
+    var("PROCESSED_EVENTS_MAX_AGE")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(30)
+}
+
+/// Events older than the ids kept can't be told apart from a replay of an
+/// event already handled, they are dropped
+pub fn is_too_old(created_at: i64, now: i64, max_age_days: i64) -> bool {
+    created_at < now - max_age_days * 86400
+}
+
+/// Record a handled event, false when it was already handled or it is too
+/// old to know, relays can deliver the same event more than once
+pub async fn is_new_event(pool: &SqlitePool, event: &Event) -> Result<bool> {
+    let now = Timestamp::now().as_i64();
+    if is_too_old(event.created_at.as_i64(), now, get_max_age_days()) {
+        return Ok(false);
+    }
+
+    db::add_processed_event(pool, &event.id.to_hex(), &event.pubkey.to_bech32()?).await
+}
+
+/// Forget the ids of events handled more than PROCESSED_EVENTS_MAX_AGE days ago
+pub async fn prune_processed_events(pool: &SqlitePool) -> Result<()> {
+    let before = Timestamp::now().as_i64() - get_max_age_days() * 86400;
+    let pruned = db::delete_processed_events(pool, before).await?;
+    if pruned > 0 {
+        info!("{pruned} processed event ids pruned");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_too_old;
+
+    #[test]
+    fn test_is_too_old() {
+        let now = 100 * 86400;
+        assert!(!is_too_old(now, now, 30));
+        assert!(!is_too_old(now - 30 * 86400, now, 30));
+        assert!(is_too_old(now - 30 * 86400 - 1, now, 30));
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Side of an order fixed by the maker, the other one is computed at market
+/// price when the order is taken. Orders with both amounts have a fixed price
+/// and no denomination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denomination {
+    /// Fixed sats amount, the fiat amount is computed when taken
+    Sats,
+    /// Fixed fiat amount, the sats amount is computed when taken
+    Fiat,
+}
+
+impl Denomination {
+    /// Denomination of a pending order from its amounts, the side left at
+    /// zero is the one computed
+    pub fn from_amounts(amount: i64, fiat_amount: i64) -> Option<Self> {
+        match (amount, fiat_amount) {
+            (0, _) => Some(Self::Fiat),
+            (_, 0) => Some(Self::Sats),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Denomination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Sats => write!(f, "sats"),
+            Self::Fiat => write!(f, "fiat"),
+        }
+    }
+}
+
+impl FromStr for Denomination {
+    type Err = ();
+
+    fn from_str(denomination: &str) -> Result<Self, Self::Err> {
+        match denomination {
+            "sats" => Ok(Self::Sats),
+            "fiat" => Ok(Self::Fiat),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Fiat amount of `sats` at `btc_price`, the inverse of the quote of fiat
+/// denominated orders so both give the same price with the same premium
+pub fn fiat_from_sats(sats: i64, btc_price: f64, premium: i64) -> i64 {
+    let fiat = sats as f64 / 100_000_000_f64 * btc_price;
+
+    (fiat / (1_f64 + premium as f64 / 100_f64)).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fiat_from_sats, Denomination};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_denomination() {
+        assert_eq!(Some(Denomination::Fiat), Denomination::from_amounts(0, 100));
+        assert_eq!(
+            Some(Denomination::Sats),
+            Denomination::from_amounts(100_000, 0)
+        );
+        assert_eq!(None, Denomination::from_amounts(100_000, 100));
+        assert_eq!(
+            Ok(Denomination::Sats),
+            Denomination::from_str(&Denomination::Sats.to_string())
+        );
+    }
+
+    #[test]
+    fn test_fiat_from_sats() {
+        assert_eq!(30, fiat_from_sats(100_000, 30_000.0, 0));
+        // Inverse of the premium applied by get_market_quote
+        assert_eq!(29, fiat_from_sats(100_000, 30_000.0, 5));
+        assert_eq!(32, fiat_from_sats(100_000, 30_000.0, -5));
+    }
+}
@@ -0,0 +1,58 @@
+use nostr_sdk::prelude::*;
+
+/// Maximum number of characters of an order description
+pub const MAX_DESCRIPTION_LENGTH: usize = 280;
+
+/// Makers describe their terms tagging the message with
+/// `["description", <text>]`
+pub fn get_description_tag(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "description" => {
+            values.first().cloned()
+        }
+        _ => None,
+    })
+}
+
+/// Clean a free-text order description: control characters and markup
+/// brackets are removed and whitespace collapsed, None if nothing is left or
+/// it is longer than MAX_DESCRIPTION_LENGTH
+pub fn sanitize_description(text: &str) -> Option<String> {
+    let cleaned: String = text
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| !c.is_control() && *c != '<' && *c != '>')
+        .collect();
+    let description = cleaned.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if description.is_empty() || description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return None;
+    }
+
+    Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize_description, MAX_DESCRIPTION_LENGTH};
+
+    #[test]
+    fn test_sanitize_description() {
+        assert_eq!(
+            Some("Banco Mercantil only, 9am to 5pm".to_string()),
+            sanitize_description("  Banco Mercantil only,\n\t9am to 5pm \u{0007}")
+        );
+        assert_eq!(
+            Some("scriptalert(1)/script".to_string()),
+            sanitize_description("<script>alert(1)</script>")
+        );
+    }
+
+    #[test]
+    fn test_invalid_descriptions() {
+        assert_eq!(None, sanitize_description(" \n "));
+        assert_eq!(
+            None,
+            sanitize_description(&"a".repeat(MAX_DESCRIPTION_LENGTH + 1))
+        );
+    }
+}
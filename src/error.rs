@@ -8,6 +8,11 @@ pub enum MostroError {
     MinExpirationTimeError,
     MinAmountError,
     WrongAmountError,
+    PriceUnavailable,
+    TooManyOrders,
+    InvalidTransition,
+    AlreadyTaken,
+    StaleOrder,
 }
 
 impl std::error::Error for MostroError {}
@@ -21,6 +26,13 @@ impl fmt::Display for MostroError {
             MostroError::MinExpirationTimeError => write!(f, "Minimal expiration time on invoice"),
             MostroError::MinAmountError => write!(f, "Minimal payment amount"),
             MostroError::WrongAmountError => write!(f, "The amount on this invoice is wrong"),
+            MostroError::PriceUnavailable => write!(f, "Market price is not available"),
+            MostroError::TooManyOrders => write!(f, "Too many active orders"),
+            MostroError::InvalidTransition => {
+                write!(f, "Action not allowed in the current order status")
+            }
+            MostroError::AlreadyTaken => write!(f, "This order was already taken"),
+            MostroError::StaleOrder => write!(f, "The order was changed by another action"),
         }
     }
 }
@@ -0,0 +1,214 @@
+use crate::config::ExpirationSettings;
+use mostro_core::Status;
+use std::str::FromStr;
+
+/// What Mostro does with an order stalled in a status once its window is over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscalationAction {
+    /// Cancel the order or publish it again if the taker is the one stalling
+    Cancel,
+    /// Open a dispute on behalf of the party waiting
+    Dispute,
+    /// Let the admins know the order needs attention
+    AdminAlert,
+}
+
+impl FromStr for EscalationAction {
+    type Err = ();
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action.trim().to_lowercase().as_str() {
+            "cancel" => Ok(Self::Cancel),
+            "dispute" => Ok(Self::Dispute),
+            "admin_alert" | "alert" => Ok(Self::AdminAlert),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Next thing to do with a stalled order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Wait,
+    /// Send the reminder with this number
+    Reminder(i64),
+    /// Last reminder before the automatic action
+    FinalWarning,
+    /// Run the automatic action
+    Act,
+}
+
+/// Reminder → final warning → automatic action schedule of a status, times
+/// in seconds. Without interval no reminders are sent and without window
+/// the reminders go on and nothing is done automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Escalation {
+    pub reminder_interval: i64,
+    pub window: i64,
+    pub action: EscalationAction,
+}
+
+impl Escalation {
+    /// Parse `<reminder interval minutes>,<window minutes>,<action>`
+    pub fn parse(schedule: &str) -> Option<Self> {
+        let mut parts = schedule.split(',');
+        let reminder_interval = parts.next()?.trim().parse::<i64>().ok()?;
+        let window = parts.next()?.trim().parse::<i64>().ok()?;
+        let action = EscalationAction::from_str(parts.next()?).ok()?;
+        if parts.next().is_some() || reminder_interval < 0 || window < 0 {
+            return None;
+        }
+
+        Some(Self {
+            reminder_interval: reminder_interval * 60,
+            window: window * 60,
+            action,
+        })
+    }
+
+    pub fn next_step(&self, elapsed: i64, reminders: i64) -> Step {
+        if self.window > 0 && elapsed >= self.window {
+            return Step::Act;
+        }
+        if self.reminder_interval == 0 || elapsed < (reminders + 1) * self.reminder_interval {
+            return Step::Wait;
+        }
+        if self.window > 0 && elapsed + self.reminder_interval >= self.window {
+            Step::FinalWarning
+        } else {
+            Step::Reminder(reminders + 1)
+        }
+    }
+}
+
+/// Statuses an order can stall in, with the env var of their schedule
+pub const ESCALATED_STATUSES: &[(Status, &str)] = &[
+    (Status::WaitingPayment, "ESCALATION_WAITING_PAYMENT"),
+    (
+        Status::WaitingBuyerInvoice,
+        "ESCALATION_WAITING_BUYER_INVOICE",
+    ),
+    (Status::Active, "ESCALATION_ACTIVE"),
+    (Status::FiatSent, "ESCALATION_FIAT_SENT"),
+    (Status::Dispute, "ESCALATION_DISPUTE"),
+];
+
+/// Automatic actions that make sense in every status, Mostro can only
+/// cancel before the fiat part of the trade starts and only open a dispute
+/// after it
+pub fn is_supported(status: &Status, action: &EscalationAction) -> bool {
+    match action {
+        EscalationAction::Cancel => {
+            matches!(status, Status::WaitingPayment | Status::WaitingBuyerInvoice)
+        }
+        EscalationAction::Dispute => matches!(status, Status::Active | Status::FiatSent),
+        EscalationAction::AdminAlert => true,
+    }
+}
+
+/// Seconds a pending order stays published, PENDING_ORDER_TIMEOUT in minutes
+/// or EXP_HOURS when not set. With 0 pending orders never expire
+pub fn get_pending_timeout(expiration: &ExpirationSettings) -> i64 {
+    match expiration.pending_order_timeout {
+        Some(minutes) => minutes * 60,
+        None => expiration.exp_hours * 3600,
+    }
+}
+
+/// Schedule of a status when its env var is not set, it follows the older
+/// timeout settings
+fn default_schedule(expiration: &ExpirationSettings, status: &Status) -> Option<Escalation> {
+    let (reminder_interval, window, action) = match status {
+        Status::WaitingPayment => (
+            0,
+            expiration.hold_invoice_payment_timeout,
+            EscalationAction::Cancel,
+        ),
+        Status::WaitingBuyerInvoice => {
+            (0, expiration.add_invoice_timeout, EscalationAction::Cancel)
+        }
+        Status::Active => match expiration.active_timeout {
+            0 => return None,
+            window => (0, window, EscalationAction::Dispute),
+        },
+        Status::FiatSent => (
+            expiration.fiat_sent_reminder_interval,
+            expiration.fiat_sent_window,
+            EscalationAction::Dispute,
+        ),
+        _ => return None,
+    };
+
+    Some(Escalation {
+        reminder_interval: reminder_interval * 60,
+        window: window * 60,
+        action,
+    })
+}
+
+/// Escalation of a status set on its env var, an empty var disables it.
+/// The schedules are checked when the settings are read
+pub fn get_escalation(
+    expiration: &ExpirationSettings,
+    status: &Status,
+    env_var: &str,
+) -> Option<Escalation> {
+    match expiration.escalations.get(env_var) {
+        Some(schedule) => Escalation::parse(schedule),
+        None => default_schedule(expiration, status),
+    }
+}
+
+/// Start of the current status and the reminders already sent, the saved
+/// timer is discarded when the order entered the status again
+pub fn current_timer(
+    state_started_at: i64,
+    saved_started_at: Option<i64>,
+    reminders: i64,
+    escalated: bool,
+) -> (i64, i64, bool) {
+    let started_at = match state_started_at {
+        0 => saved_started_at.unwrap_or(0),
+        started_at => started_at,
+    };
+    if saved_started_at == Some(started_at) {
+        (started_at, reminders, escalated)
+    } else {
+        (started_at, 0, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_timer, Escalation, EscalationAction, Step};
+
+    #[test]
+    fn test_escalation_ladder() {
+        let escalation = Escalation::parse("60, 360, dispute").unwrap();
+        assert_eq!(EscalationAction::Dispute, escalation.action);
+        assert_eq!(Step::Wait, escalation.next_step(59 * 60, 0));
+        assert_eq!(Step::Reminder(1), escalation.next_step(60 * 60, 0));
+        assert_eq!(Step::Wait, escalation.next_step(90 * 60, 1));
+        assert_eq!(Step::FinalWarning, escalation.next_step(300 * 60, 4));
+        assert_eq!(Step::Act, escalation.next_step(360 * 60, 5));
+        // Without reminders the action comes at the end of the window
+        let escalation = Escalation::parse("0,15,cancel").unwrap();
+        assert_eq!(Step::Wait, escalation.next_step(14 * 60, 0));
+        assert_eq!(Step::Act, escalation.next_step(15 * 60, 0));
+        // Without window the reminders never end
+        let escalation = Escalation::parse("30,0,admin_alert").unwrap();
+        assert_eq!(Step::Reminder(100), escalation.next_step(100 * 30 * 60, 99));
+        assert_eq!(None, Escalation::parse("30,0,refund"));
+        assert_eq!(None, Escalation::parse("-1,10,cancel"));
+    }
+
+    #[test]
+    fn test_current_timer() {
+        // Statuses without their own timestamp start when first seen
+        assert_eq!((0, 0, false), current_timer(0, None, 0, false));
+        assert_eq!((100, 2, false), current_timer(0, Some(100), 2, false));
+        assert_eq!((100, 1, true), current_timer(100, Some(100), 1, true));
+        // The order was taken again
+        assert_eq!((200, 0, false), current_timer(200, Some(100), 1, true));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::FeeSettings;
+use crate::db;
+use mostro_core::order::Order;
+use sqlx::SqlitePool;
+
+/// Mostro fee paid by each party of the trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradeFees {
+    /// Added to the seller hold invoice
+    pub seller_fee: i64,
+    /// Deducted from the buyer payout
+    pub buyer_fee: i64,
+}
+
+/// Part of the dispute fee charged to a party with `available` sats left in
+/// the trade, never more than what it has
+pub fn cap_dispute_fee(available: i64, dispute_fee: i64) -> i64 {
+    dispute_fee.clamp(0, available.max(0))
+}
+
+pub fn calculate_fee(amount: i64, fee_rate: f64) -> i64 {
+    (amount as f64 * fee_rate).round() as i64
+}
+
+/// Mostro fee for an order of `amount` sats
+pub fn get_fee(fees: &FeeSettings, amount: i64) -> i64 {
+    calculate_fee(amount, fees.fee)
+}
+
+/// Split the fee between maker and taker, the taker pays what is left after
+/// rounding the maker part
+pub fn split_fee(fee: i64, maker_share: f64, seller_is_maker: bool) -> TradeFees {
+    let maker_fee = (fee as f64 * maker_share).round() as i64;
+    let taker_fee = fee - maker_fee;
+
+    if seller_is_maker {
+        TradeFees {
+            seller_fee: maker_fee,
+            buyer_fee: taker_fee,
+        }
+    } else {
+        TradeFees {
+            seller_fee: taker_fee,
+            buyer_fee: maker_fee,
+        }
+    }
+}
+
+/// Fee paid by each party of an order, the maker of a sell order is the seller
+pub fn get_trade_fees(fees: &FeeSettings, order: &Order, fee: i64) -> TradeFees {
+    split_fee(fee, fees.maker_fee_share, order.kind == "Sell")
+}
+
+/// Fee paid by each party of a taken order as saved with its hold invoice,
+/// orders taken before they were saved split the fee with the current share
+pub async fn find_trade_fees(
+    pool: &SqlitePool,
+    fees: &FeeSettings,
+    order: &Order,
+) -> anyhow::Result<TradeFees> {
+    match db::find_trade_fees(pool, order.id).await? {
+        Some(trade_fees) => Ok(trade_fees),
+        None => Ok(get_trade_fees(fees, order, order.fee)),
+    }
+}
+
+/// Sats the seller has to lock on the hold invoice
+pub fn seller_escrow_amount(order: &Order, trade_fees: &TradeFees) -> i64 {
+    order.amount + trade_fees.seller_fee
+}
+
+/// Sats the buyer receives once the seller releases
+pub fn buyer_payout_amount(order: &Order, trade_fees: &TradeFees) -> i64 {
+    order.amount - trade_fees.buyer_fee
+}
+
+/// Most sats spent routing a payment of `amount` sats, ROUTING_FEE_LIMIT is
+/// a rate of the amount, 0.005 is 0.5%
+pub fn get_routing_fee_limit(fees: &FeeSettings, amount: i64) -> i64 {
+    calculate_fee(amount, fees.routing_fee_limit).max(1)
+}
+
+/// Sats a buyer gives up from a payout of `amount` sats when routing it
+/// costs more than the limit, they are added to the routing budget.
+/// ROUTING_FEE_COMPENSATION is a rate of the amount, 0.01 is 1%
+pub fn get_routing_compensation(fees: &FeeSettings, amount: i64) -> i64 {
+    calculate_fee(amount, fees.routing_fee_compensation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_fee() {
+        assert_eq!(300, calculate_fee(100_000, 0.003));
+        assert_eq!(0, calculate_fee(100_000, 0.0));
+        assert_eq!(2, calculate_fee(500, 0.003));
+    }
+
+    #[test]
+    fn test_split_fee() {
+        let fees = split_fee(300, 0.5, true);
+        assert_eq!(150, fees.seller_fee);
+        assert_eq!(150, fees.buyer_fee);
+        // The maker of a buy order is the buyer
+        let fees = split_fee(300, 1.0, false);
+        assert_eq!(0, fees.seller_fee);
+        assert_eq!(300, fees.buyer_fee);
+        // Nothing is lost rounding
+        let fees = split_fee(301, 0.5, true);
+        assert_eq!(301, fees.seller_fee + fees.buyer_fee);
+    }
+
+    #[test]
+    fn test_cap_dispute_fee() {
+        assert_eq!(5000, cap_dispute_fee(30_000, 5000));
+        assert_eq!(1000, cap_dispute_fee(1000, 5000));
+        assert_eq!(0, cap_dispute_fee(0, 5000));
+        assert_eq!(0, cap_dispute_fee(30_000, 0));
+    }
+}
@@ -1,12 +1,21 @@
-use crate::util::send_dm;
-use log::info;
+use crate::config;
+use crate::fee::{buyer_payout_amount, find_trade_fees};
+use crate::lifecycle::Trigger;
+use crate::messages;
+use crate::state::order_status;
+use crate::util::{get_user_lang, send_dm};
 use mostro_core::{order::SmallOrder, Action, Content, Message, Status};
 use nostr_sdk::prelude::*;
+use tracing::{debug, error, info};
 
 pub async fn hold_invoice_paid(hash: &str) {
     let pool = crate::db::connect().await.unwrap();
     let client = crate::util::connect_nostr().await.unwrap();
-    let order = crate::db::find_order_by_hash(&pool, hash).await.unwrap();
+    let (order, version) = crate::db::find_order_by_hash(&pool, hash).await.unwrap();
+    // A subscription started again after a restart reports invoices already paid
+    if order_status(&order) != Ok(Status::WaitingPayment) {
+        return;
+    }
     let my_keys = crate::util::get_keys().unwrap();
     let seller_pubkey = XOnlyPublicKey::from_bech32(order.seller_pubkey.as_ref().unwrap()).unwrap();
     let buyer_pubkey = XOnlyPublicKey::from_bech32(order.buyer_pubkey.as_ref().unwrap()).unwrap();
@@ -15,11 +24,19 @@ pub async fn hold_invoice_paid(hash: &str) {
         "Order Id: {} - Seller paid invoice with hash: {hash}",
         order.id
     );
+    crate::db::update_order_invoice_held_at(&pool, order.id, Timestamp::now().as_i64())
+        .await
+        .unwrap();
 
+    // The buyer is paid the order amount minus the buyer fee
+    let trade_fees = find_trade_fees(&pool, &config::mostro().fees, &order)
+        .await
+        .unwrap();
+    let payout_amount = buyer_payout_amount(&order, &trade_fees);
     // We send this data related to the order to the parties
     let order_data = SmallOrder::new(
         order.id,
-        order.amount,
+        payout_amount,
         order.fiat_code.clone(),
         order.fiat_amount,
         order.payment_method.clone(),
@@ -28,7 +45,7 @@ pub async fn hold_invoice_paid(hash: &str) {
         order.seller_pubkey.as_ref().cloned(),
     );
     let status;
-    println!("buyer_invoice {:#?}", order.buyer_invoice);
+    debug!("buyer_invoice {:#?}", order.buyer_invoice);
     if order.buyer_invoice.is_some() {
         // We send a confirmation message to seller
         let message = Message::new(
@@ -75,15 +92,36 @@ pub async fn hold_invoice_paid(hash: &str) {
     }
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    crate::util::update_order_event(&pool, &client, &my_keys, status, &order, None)
-        .await
-        .unwrap();
+    if let Err(e) = crate::util::update_order_event(
+        &pool,
+        &client,
+        &my_keys,
+        status,
+        &order,
+        version,
+        None,
+        Trigger::Lightning,
+    )
+    .await
+    {
+        // Another action changed the order meanwhile, it goes on from there
+        error!("Order Id {}: hold invoice paid: {e}", order.id);
+        return;
+    }
 }
 
 pub async fn hold_invoice_settlement(hash: &str) {
     let pool = crate::db::connect().await.unwrap();
     let client = crate::util::connect_nostr().await.unwrap();
-    let order = crate::db::find_order_by_hash(&pool, hash).await.unwrap();
+    let (order, version) = crate::db::find_order_by_hash(&pool, hash).await.unwrap();
+    // Release and the admins save the status themselves, the subscription
+    // only catches a hold invoice settled while the trade was going on
+    if !matches!(
+        order_status(&order),
+        Ok(Status::Active | Status::FiatSent | Status::Dispute)
+    ) {
+        return;
+    }
     let my_keys = crate::util::get_keys().unwrap();
     let seller_pubkey = XOnlyPublicKey::from_bech32(order.seller_pubkey.as_ref().unwrap()).unwrap();
     let buyer_pubkey = XOnlyPublicKey::from_bech32(order.buyer_pubkey.as_ref().unwrap()).unwrap();
@@ -94,16 +132,22 @@ pub async fn hold_invoice_settlement(hash: &str) {
 
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    crate::util::update_order_event(
+    if let Err(e) = crate::util::update_order_event(
         &pool,
         &client,
         &my_keys,
         Status::SettledHoldInvoice,
         &order,
+        version,
         None,
+        Trigger::Lightning,
     )
     .await
-    .unwrap();
+    {
+        // Another action changed the order meanwhile, it goes on from there
+        error!("Order Id {}: hold invoice settled: {e}", order.id);
+        return;
+    }
     // We send a *funds released* message to seller
     let message = Message::new(0, Some(order.id), Action::HoldInvoicePaymentSettled, None);
     let message = message.as_json().unwrap();
@@ -118,10 +162,28 @@ pub async fn hold_invoice_settlement(hash: &str) {
         .unwrap();
 }
 
+/// The hold invoice of an order was canceled, when Mostro didn't cancel it
+/// the escrow vanished outside Mostro, by the operator or because it expired
+/// on the node, and the order is canceled as it can't go on without escrow
 pub async fn hold_invoice_canceled(hash: &str) {
     let pool = crate::db::connect().await.unwrap();
     let client = crate::util::connect_nostr().await.unwrap();
-    let order = crate::db::find_order_by_hash(&pool, hash).await.unwrap();
+    // The order could have been already returned to the book
+    let (order, version) = match crate::db::find_order_by_hash(&pool, hash).await {
+        Ok(order) => order,
+        Err(_) => return,
+    };
+    // If the order was already canceled or finished the parties were already notified
+    if !matches!(
+        order_status(&order),
+        Ok(Status::WaitingPayment
+            | Status::WaitingBuyerInvoice
+            | Status::Active
+            | Status::FiatSent
+            | Status::Dispute)
+    ) {
+        return;
+    }
     let my_keys = crate::util::get_keys().unwrap();
     let seller_pubkey = order.seller_pubkey.as_ref().unwrap();
     let seller_pubkey = XOnlyPublicKey::from_bech32(seller_pubkey).unwrap();
@@ -134,16 +196,36 @@ pub async fn hold_invoice_canceled(hash: &str) {
     );
     // We publish a new replaceable kind nostr event with the status updated
     // and update on local database the status and new event id
-    crate::util::update_order_event(&pool, &client, &my_keys, Status::Canceled, &order, None)
-        .await
-        .unwrap();
+    if let Err(e) = crate::util::update_order_event(
+        &pool,
+        &client,
+        &my_keys,
+        Status::Canceled,
+        &order,
+        version,
+        None,
+        Trigger::Lightning,
+    )
+    .await
+    {
+        // Another action changed the order meanwhile, it goes on from there
+        error!("Order Id {}: hold invoice canceled: {e}", order.id);
+        return;
+    }
+    // A buyer bond is returned as the trade can't go on
+    if let Err(e) = crate::bond::release_bond(&pool, order.id).await {
+        error!("Order Id {}: bond could not be released: {e}", order.id);
+    }
     // We send "order canceled" messages to both parties
-    let message = Message::new(0, Some(order.id), Action::HoldInvoicePaymentCanceled, None);
-    let message = message.as_json().unwrap();
-    send_dm(&client, &my_keys, &seller_pubkey, message.clone())
-        .await
-        .unwrap();
-    send_dm(&client, &my_keys, &buyer_pubkey, message)
-        .await
-        .unwrap();
+    for pubkey in [seller_pubkey, buyer_pubkey] {
+        let text_message = messages::escrow_canceled(get_user_lang(&pool, &pubkey).await);
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::HoldInvoicePaymentCanceled,
+            Some(Content::TextMessage(text_message)),
+        );
+        let message = message.as_json().unwrap();
+        send_dm(&client, &my_keys, &pubkey, message).await.unwrap();
+    }
 }
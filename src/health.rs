@@ -0,0 +1,177 @@
+use crate::lightning::LndConnector;
+use crate::webhook::{self, WebhookEvent};
+use crate::{db, maintenance};
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Seconds a dependency has to answer before it is seen as down
+const CHECK_TIMEOUT: u64 = 5;
+
+/// Outcome of the check of a dependency, with the reason when it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub detail: Option<String>,
+}
+
+impl From<Result<String>> for Check {
+    fn from(result: Result<String>) -> Self {
+        match result {
+            Ok(detail) => Self {
+                ok: true,
+                detail: Some(detail).filter(|detail| !detail.is_empty()),
+            },
+            Err(e) => Self {
+                ok: false,
+                detail: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    pub database: Check,
+    pub lnd: Check,
+    pub relays: Check,
+}
+
+impl Health {
+    /// Mostro is alive while its database works, restarting it doesn't fix
+    /// LND or the relays being down
+    pub fn is_alive(&self) -> bool {
+        self.database.ok
+    }
+
+    /// Mostro can take trades when it reaches all of its dependencies
+    pub fn is_ready(&self) -> bool {
+        self.database.ok && self.lnd.ok && self.relays.ok
+    }
+}
+
+/// Connection kept between checks, dropped when LND fails to answer
+static LND: Mutex<Option<LndConnector>> = Mutex::const_new(None);
+/// Whether LND answered the last time it was watched
+static LND_UP: AtomicBool = AtomicBool::new(true);
+
+async fn check_database(pool: &SqlitePool) -> Result<String> {
+    db::ping(pool).await?;
+    // A failed integrity check means the data can't be trusted
+    let failed = maintenance::health()
+        .into_iter()
+        .filter_map(|(task, run)| run.error.map(|error| format!("{task} failed: {error}")))
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        return Err(anyhow!(failed.join("; ")));
+    }
+
+    Ok(String::new())
+}
+
+async fn check_lnd() -> Result<String> {
+    let mut lnd = LND.lock().await;
+    if lnd.is_none() {
+        *lnd = Some(LndConnector::try_connect(&crate::config::settings().lightning).await?);
+    }
+    let info = match lnd.as_mut() {
+        Some(connector) => connector.get_info().await,
+        None => return Err(anyhow!("Not connected")),
+    };
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            *lnd = None;
+            return Err(e);
+        }
+    };
+    if !info.synced_to_chain {
+        return Err(anyhow!("LND is not synced to the chain"));
+    }
+
+    Ok(format!("{} at block {}", info.alias, info.block_height))
+}
+
+async fn check_relays(client: &Client) -> Result<String> {
+    let relays = client.relays().await;
+    let mut connected = 0;
+    for relay in relays.values() {
+        if relay.status().await == RelayStatus::Connected {
+            connected += 1;
+        }
+    }
+    if connected == 0 {
+        return Err(anyhow!("None of the {} relays is connected", relays.len()));
+    }
+
+    Ok(format!("{connected} of {} relays connected", relays.len()))
+}
+
+/// Give up on a check that takes longer than CHECK_TIMEOUT seconds
+async fn timed(check: impl std::future::Future<Output = Result<String>>) -> Check {
+    match tokio::time::timeout(Duration::from_secs(CHECK_TIMEOUT), check).await {
+        Ok(result) => result.into(),
+        Err(_) => Check {
+            ok: false,
+            detail: Some(format!("No answer in {CHECK_TIMEOUT} seconds")),
+        },
+    }
+}
+
+/// Check LND and tell the webhooks when it stops answering, once until it
+/// is back
+pub async fn watch_lnd() {
+    let lnd = timed(check_lnd()).await;
+    let was_up = LND_UP.swap(lnd.ok, Ordering::SeqCst);
+    match (was_up, lnd.ok) {
+        (true, false) => {
+            let error = lnd.detail.unwrap_or_default();
+            warn!("LND disconnected: {error}");
+            webhook::notify(WebhookEvent::LndDisconnected, json!({ "error": error }));
+        }
+        (false, true) => info!("LND connected again"),
+        _ => {}
+    }
+}
+
+/// Check the database, LND and the relays the client is connected to
+pub async fn check(pool: &SqlitePool, client: &Client) -> Health {
+    let (database, lnd, relays) = tokio::join!(
+        timed(check_database(pool)),
+        timed(check_lnd()),
+        timed(check_relays(client))
+    );
+
+    Health {
+        database,
+        lnd,
+        relays,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, Health};
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_health() {
+        let ok = Check::from(Ok(String::new()));
+        assert_eq!(None, ok.detail);
+        let down = Check::from(Err(anyhow!("LND is not synced to the chain")));
+        let health = Health {
+            database: ok.clone(),
+            lnd: down,
+            relays: ok,
+        };
+        // LND being down makes Mostro not ready but it is still alive
+        assert!(health.is_alive());
+        assert!(!health.is_ready());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::settings::var;
+use crate::{db, shutdown};
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Kind of the heartbeat, the application data of NIP-78 so every beat
+/// replaces the last one
+pub const HEARTBEAT_KIND: u64 = 30078;
+/// `d` tag of the heartbeat
+pub const HEARTBEAT_ID: &str = "mostro-heartbeat";
+
+/// Work waiting in Mostro when the heartbeat was published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Queues {
+    /// DMs and events no relay took yet
+    pub outbox: usize,
+    pub payments_in_flight: usize,
+    /// Hold invoices whose updates Mostro is following
+    pub invoice_subscriptions: usize,
+}
+
+/// Content of the heartbeat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heartbeat {
+    pub version: String,
+    pub started_at: i64,
+    /// Seconds since Mostro started
+    pub uptime: i64,
+    pub queues: Queues,
+}
+
+/// Seconds between heartbeats set on HEARTBEAT_INTERVAL, 300 by default, 0
+/// disables them
+pub fn get_heartbeat_interval() -> Option<u64> {
+    let interval = var("HEARTBEAT_INTERVAL")
+        .ok()
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .unwrap_or(300);
+
+    Some(interval).filter(|interval| *interval > 0)
+}
+
+/// Tags of the heartbeat, it expires after two intervals so relays drop it
+/// and clients see Mostro is gone when it stops beating (NIP-40)
+fn heartbeat_tags(heartbeat: &Heartbeat, now: i64, interval: u64) -> Vec<Tag> {
+    let expiration = now + 2 * interval as i64;
+    vec![
+        Tag::Generic(
+            TagKind::Custom("d".to_string()),
+            vec![HEARTBEAT_ID.to_string()],
+        ),
+        Tag::Generic(
+            TagKind::Custom("version".to_string()),
+            vec![heartbeat.version.clone()],
+        ),
+        Tag::Generic(
+            TagKind::Custom("expiration".to_string()),
+            vec![expiration.to_string()],
+        ),
+    ]
+}
+
+async fn publish(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    started_at: i64,
+    interval: u64,
+) -> Result<()> {
+    let now = Timestamp::now().as_i64();
+    let heartbeat = Heartbeat {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started_at,
+        uptime: now - started_at,
+        queues: Queues {
+            outbox: db::find_pending_outbox_messages(pool).await?.len(),
+            payments_in_flight: shutdown::payments_in_flight(),
+            invoice_subscriptions: shutdown::streams_followed(),
+        },
+    };
+    let tags = heartbeat_tags(&heartbeat, now, interval);
+    let event = EventBuilder::new(
+        Kind::Custom(HEARTBEAT_KIND),
+        serde_json::to_string(&heartbeat)?,
+        &tags,
+    )
+    .to_event(my_keys)?;
+    // A heartbeat no relay took is not kept, the next one replaces it
+    client.send_event(event).await?;
+
+    Ok(())
+}
+
+/// Publish a heartbeat every HEARTBEAT_INTERVAL seconds until Mostro shuts
+/// down, so clients and monitors can tell it is alive without trading
+pub fn spawn(pool: SqlitePool, client: Client, my_keys: Keys) {
+    let interval = match get_heartbeat_interval() {
+        Some(interval) => interval,
+        None => return,
+    };
+    let started_at = Timestamp::now().as_i64();
+    info!("Publishing a heartbeat every {interval} seconds");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            if shutdown::is_shutting_down() {
+                break;
+            }
+            if let Err(e) = publish(&pool, &client, &my_keys, started_at, interval).await {
+                error!("Heartbeat not published: {e}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{heartbeat_tags, Heartbeat, Queues};
+    use nostr_sdk::prelude::*;
+
+    #[test]
+    fn test_heartbeat_tags() {
+        let heartbeat = Heartbeat {
+            version: "0.6.2".to_string(),
+            started_at: 1_684_900_000,
+            uptime: 29_600,
+            queues: Queues {
+                outbox: 2,
+                payments_in_flight: 1,
+                invoice_subscriptions: 4,
+            },
+        };
+        let tags = heartbeat_tags(&heartbeat, 1_684_929_600, 300);
+        assert!(tags.contains(&Tag::Generic(
+            TagKind::Custom("expiration".to_string()),
+            vec!["1684930200".to_string()],
+        )));
+        assert!(tags.contains(&Tag::Generic(
+            TagKind::Custom("version".to_string()),
+            vec!["0.6.2".to_string()],
+        )));
+        assert_eq!(
+            r#"{"version":"0.6.2","started_at":1684900000,"uptime":29600,"queues":{"outbox":2,"payments_in_flight":1,"invoice_subscriptions":4}}"#,
+            serde_json::to_string(&heartbeat).unwrap()
+        );
+    }
+}
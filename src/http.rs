@@ -0,0 +1,91 @@
+use crate::health::{check, Health};
+use crate::metrics;
+use crate::settings::var;
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use nostr_sdk::Client;
+use sqlx::SqlitePool;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tracing::{error, info, warn};
+
+/// Address the endpoints for the operators listen on, HTTP_ADDR. They are
+/// off when it isn't set
+pub fn get_http_addr() -> Option<SocketAddr> {
+    let addr = var("HTTP_ADDR").ok()?;
+    match addr.parse() {
+        Ok(addr) => Some(addr),
+        Err(_) => {
+            warn!("HTTP_ADDR {addr} is not a valid address, the HTTP endpoints are off");
+            None
+        }
+    }
+}
+
+/// Health of Mostro as JSON, 503 when `is_up` says it isn't up
+async fn health(
+    pool: &SqlitePool,
+    client: &Client,
+    is_up: fn(&Health) -> bool,
+) -> hyper::http::Result<Response<Body>> {
+    let health = check(pool, client).await;
+    let status = match is_up(&health) {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let body = serde_json::to_string(&health).unwrap_or_default();
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+}
+
+async fn handle(
+    pool: SqlitePool,
+    client: Client,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/healthz") => health(&pool, &client, Health::is_alive).await,
+        (&Method::GET, "/readyz") => health(&pool, &client, Health::is_ready).await,
+        (&Method::GET, "/metrics") => match metrics::gather(&pool).await {
+            Ok(metrics) => Response::builder()
+                .header("Content-Type", "text/plain; version=0.0.4")
+                .body(Body::from(metrics)),
+            Err(e) => {
+                error!("Metrics could not be gathered: {e}");
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+            }
+        },
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+
+    Ok(response.expect("Response is valid"))
+}
+
+/// Serve the endpoints for the operators in the background
+pub fn serve(addr: SocketAddr, pool: SqlitePool, client: Client) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let (pool, client) = (pool.clone(), client.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(pool.clone(), client.clone(), request)
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    info!("HTTP endpoints listening on {addr}");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("HTTP server stopped: {e}");
+        }
+    });
+
+    Ok(())
+}
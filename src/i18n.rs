@@ -0,0 +1,108 @@
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const EN: &str = include_str!("../locales/en.ftl");
+const ES: &str = include_str!("../locales/es.ftl");
+
+/// Languages Mostro talks to users in, English is the default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+}
+
+impl Lang {
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().get(..2) {
+            Some("en") => Some(Self::En),
+            Some("es") => Some(Self::Es),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Es => "es",
+        }
+    }
+
+    fn templates(&self) -> &'static HashMap<&'static str, &'static str> {
+        static EN_TEMPLATES: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+        static ES_TEMPLATES: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+        match self {
+            Self::En => EN_TEMPLATES.get_or_init(|| parse_templates(EN)),
+            Self::Es => ES_TEMPLATES.get_or_init(|| parse_templates(ES)),
+        }
+    }
+}
+
+/// Clients declare the language of the user tagging their messages with
+/// `["lang", <code>]`
+pub fn get_lang_tag(event: &Event) -> Option<Lang> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "lang" => {
+            values.first().and_then(|code| Lang::from_code(code))
+        }
+        _ => None,
+    })
+}
+
+/// Templates are `key = text` lines, lines starting with `#` are comments
+fn parse_templates(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(key, text)| (key.trim(), text.trim()))
+        .collect()
+}
+
+/// Text of `key` in `lang` with every `{ $name }` replaced by its argument,
+/// English is used when the template is missing in `lang`
+pub fn translate(lang: Lang, key: &str, args: &[(&str, String)]) -> String {
+    let template = lang
+        .templates()
+        .get(key)
+        .or_else(|| Lang::En.templates().get(key))
+        .copied()
+        .unwrap_or(key);
+
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{ ${name} }}"), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_templates, translate, Lang, EN, ES};
+
+    #[test]
+    fn test_translate() {
+        assert_eq!("You can't do that!", translate(Lang::En, "cant-do", &[]));
+        assert_eq!("¡No puedes hacer eso!", translate(Lang::Es, "cant-do", &[]));
+        assert_eq!(
+            "Payment of 1000 sats sent",
+            translate(Lang::En, "payout-paid", &[("amount", 1000.to_string())])
+        );
+        assert_eq!(Some(Lang::Es), Lang::from_code("es-VE"));
+        assert_eq!(None, Lang::from_code("xx"));
+    }
+
+    #[test]
+    fn test_every_template_is_translated() {
+        let en = parse_templates(EN);
+        let es = parse_templates(ES);
+        let mut missing: Vec<&&str> = en.keys().filter(|key| !es.contains_key(*key)).collect();
+        missing.sort();
+        assert!(missing.is_empty(), "missing in es: {missing:?}");
+        // Both languages use the same arguments
+        for (key, text) in en.iter() {
+            let args = |text: &str| text.matches("{ $").count();
+            assert_eq!(args(text), args(es[key]), "{key}");
+        }
+    }
+}
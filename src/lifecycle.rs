@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// What made an order change its status, kept with every transition of the
+/// order lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// A message of the buyer or the seller
+    User,
+    /// A message of an admin or a solver
+    Admin,
+    /// A window of the scheduler ran out
+    Timeout,
+    /// An invoice or a payment changed its state on the node
+    Lightning,
+    /// Mostro itself, like a take that couldn't go on
+    System,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::Admin => write!(f, "admin"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Lightning => write!(f, "lightning"),
+            Self::System => write!(f, "system"),
+        }
+    }
+}
+
+/// Only real transitions go to the lifecycle, orders are published again
+/// with the same status when edited or when the amount changes
+pub fn is_transition(from_status: Option<&str>, to_status: &str) -> bool {
+    from_status != Some(to_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_transition, Trigger};
+
+    #[test]
+    fn test_transition() {
+        assert!(is_transition(Some("Pending"), "WaitingPayment"));
+        assert!(is_transition(None, "Pending"));
+        assert!(!is_transition(Some("Pending"), "Pending"));
+        assert_eq!("timeout", Trigger::Timeout.to_string());
+    }
+}
@@ -1,7 +1,6 @@
 use crate::error::MostroError;
 use chrono::prelude::*;
 use chrono::Duration;
-use dotenvy::var;
 use lightning_invoice::{Invoice, SignedRawInvoice};
 use std::str::FromStr;
 
@@ -18,9 +17,8 @@ pub fn is_valid_invoice(
     amount: Option<u64>,
 ) -> Result<Invoice, MostroError> {
     let invoice = Invoice::from_str(payment_request)?;
-    let min_payment_amount = var("MIN_PAYMENT_AMT")
-        .expect("INVOICE_EXPIRATION_WINDOW is not set")
-        .parse::<u64>()?;
+    let settings = &crate::config::settings().lightning;
+    let min_payment_amount = settings.min_payment_amt;
 
     let amount_msat = invoice.amount_milli_satoshis().unwrap_or(0) / 1000;
 
@@ -39,10 +37,7 @@ pub fn is_valid_invoice(
 
     let (parsed_invoice, _, _) = parsed.into_parts();
 
-    let expiration_window = var("INVOICE_EXPIRATION_WINDOW")
-        .expect("INVOICE_EXPIRATION_WINDOW is not set")
-        .parse::<i64>()?;
-    let latest_date = Utc::now() + Duration::seconds(expiration_window);
+    let latest_date = Utc::now() + Duration::seconds(settings.invoice_expiration_window);
     let latest_date = latest_date.timestamp() as u64;
     let expires_at =
         invoice.expiry_time().as_secs() + parsed_invoice.data.timestamp.as_unix_timestamp();
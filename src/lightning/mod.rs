@@ -1,10 +1,9 @@
 pub mod invoice;
+use crate::config::LightningSettings;
 use crate::lightning::invoice::decode_invoice;
 
 use anyhow::Result;
-use dotenvy::var;
 use easy_hasher::easy_hasher::*;
-use log::info;
 use nostr_sdk::nostr::hashes::hex::{FromHex, ToHex};
 use nostr_sdk::nostr::secp256k1::rand::{self, RngCore};
 use tokio::sync::mpsc::Sender;
@@ -12,12 +11,18 @@ use tonic_openssl_lnd::invoicesrpc::{
     AddHoldInvoiceRequest, AddHoldInvoiceResp, CancelInvoiceMsg, CancelInvoiceResp,
     SettleInvoiceMsg, SettleInvoiceResp,
 };
-use tonic_openssl_lnd::lnrpc::{invoice::InvoiceState, Payment};
+use tonic_openssl_lnd::lnrpc::payment::PaymentStatus;
+use tonic_openssl_lnd::lnrpc::{
+    invoice::InvoiceState, ChannelBalanceRequest, ChannelBalanceResponse, GetInfoRequest,
+    GetInfoResponse, Invoice, Payment, PaymentHash,
+};
 use tonic_openssl_lnd::routerrpc::{SendPaymentRequest, TrackPaymentRequest};
 use tonic_openssl_lnd::{LndClient, LndClientError};
+use tracing::info;
 
 pub struct LndConnector {
     client: LndClient,
+    hold_invoice_cltv_delta: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -32,21 +37,73 @@ pub struct PaymentMessage {
 }
 
 impl LndConnector {
+    /// Connect with the lightning settings of the running Mostro
     pub async fn new() -> Self {
-        let port: u32 = var("LND_GRPC_PORT")
-            .expect("LND_GRPC_PORT must be set")
-            .parse()
-            .expect("port is not u32");
-        let host = var("LND_GRPC_HOST").expect("LND_GRPC_HOST must be set");
-        let tls_path = var("LND_CERT_FILE").expect("LND_CERT_FILE must be set");
-        let macaroon_path = var("LND_MACAROON_FILE").expect("LND_MACAROON_FILE must be set");
+        Self::connect(&crate::config::settings().lightning).await
+    }
 
-        // Connecting to LND requires only host, port, cert file, and macaroon file
-        let client = tonic_openssl_lnd::connect(host, port, tls_path, macaroon_path)
+    pub async fn connect(settings: &LightningSettings) -> Self {
+        Self::try_connect(settings)
             .await
-            .expect("Failed connecting to LND");
+            .expect("Failed connecting to LND")
+    }
+
+    /// Connect without panicking when LND can't be reached
+    pub async fn try_connect(settings: &LightningSettings) -> Result<Self> {
+        // Connecting to LND requires only host, port, cert file, and macaroon file
+        let client = tonic_openssl_lnd::connect(
+            settings.lnd_grpc_host.clone(),
+            settings.lnd_grpc_port,
+            settings.lnd_cert_file.clone(),
+            settings.lnd_macaroon_file.clone(),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(Self {
+            client,
+            hold_invoice_cltv_delta: settings.hold_invoice_cltv_delta,
+        })
+    }
+
+    /// State of the node, used to check LND answers and is synced
+    pub async fn get_info(&mut self) -> Result<GetInfoResponse> {
+        let info = self
+            .client
+            .lightning()
+            .get_info(GetInfoRequest {})
+            .await?
+            .into_inner();
 
-        Self { client }
+        Ok(info)
+    }
+
+    /// Sats the node can send and receive through its open channels
+    pub async fn channel_balance(&mut self) -> Result<ChannelBalanceResponse> {
+        let balance = self
+            .client
+            .lightning()
+            .channel_balance(ChannelBalanceRequest {})
+            .await?
+            .into_inner();
+
+        Ok(balance)
+    }
+
+    /// Invoice with the HTLCs paying it, used to know when they expire
+    pub async fn lookup_invoice(&mut self, hash: &str) -> Result<Invoice> {
+        let r_hash = FromHex::from_hex(hash)?;
+        let invoice = self
+            .client
+            .lightning()
+            .lookup_invoice(PaymentHash {
+                r_hash,
+                ..Default::default()
+            })
+            .await?
+            .into_inner();
+
+        Ok(invoice)
     }
 
     pub async fn create_hold_invoice(
@@ -57,10 +114,7 @@ impl LndConnector {
         let mut preimage = [0u8; 32];
         rand::thread_rng().fill_bytes(&mut preimage);
         let hash = raw_sha256(preimage.to_vec());
-        let cltv_expiry: u64 = var("HOLD_INVOICE_CLTV_DELTA")
-            .expect("HOLD_INVOICE_CLTV_DELTA must be set")
-            .parse()
-            .expect("cltv delta is not i64");
+        let cltv_expiry = self.hold_invoice_cltv_delta;
 
         let invoice = AddHoldInvoiceRequest {
             hash: hash.to_vec(),
@@ -150,10 +204,13 @@ impl LndConnector {
         Ok(cancel)
     }
 
+    /// Pay an invoice spending at most `fee_limit` sats on routing, a payment
+    /// which failed before is tried again
     pub async fn send_payment(
         &mut self,
         payment_request: &str,
         amount: i64,
+        fee_limit: i64,
         listener: Sender<PaymentMessage>,
     ) {
         let invoice = decode_invoice(payment_request).unwrap();
@@ -172,16 +229,36 @@ impl LndConnector {
             .track_payment_v2(track_payment_req)
             .await;
 
-        // We only send the payment if it wasn't attempted before
-        if track.is_ok() {
-            info!("Aborting paying invoice with hash {} to buyer", hash);
-            return;
+        // We only send the payment if it wasn't attempted before or it failed,
+        // a payment attempted before is followed until it is over
+        if let Ok(track) = track {
+            let mut track = track.into_inner();
+            match track.message().await {
+                Ok(Some(payment)) if payment.status == PaymentStatus::Failed as i32 => {}
+                Ok(Some(payment)) => {
+                    info!("Following the payment of invoice with hash {}", hash);
+                    let mut payment = Some(payment);
+                    while let Some(tracked) = payment {
+                        let msg = PaymentMessage { payment: tracked };
+                        if listener.send(msg).await.is_err() {
+                            return;
+                        }
+                        payment = track.message().await.ok().flatten();
+                    }
+                    return;
+                }
+                _ => {
+                    info!("Aborting paying invoice with hash {} to buyer", hash);
+                    return;
+                }
+            }
         }
 
         let invoice_amount_milli = invoice.amount_milli_satoshis();
         let mut request = SendPaymentRequest {
             payment_request: payment_request.to_string(),
             timeout_seconds: 60,
+            fee_limit_sat: fee_limit,
             ..Default::default()
         };
 
@@ -0,0 +1,152 @@
+use crate::config::LimitSettings;
+use crate::db;
+use anyhow::Result;
+use nostr_sdk::Timestamp;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+/// Order size bounds set by the operator, zero means no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OrderLimits {
+    pub min_amount: i64,
+    pub max_amount: i64,
+    pub min_fiat_amount: i64,
+    pub max_fiat_amount: i64,
+}
+
+fn in_bounds(value: i64, min: i64, max: i64) -> bool {
+    (min == 0 || value >= min) && (max == 0 || value <= max)
+}
+
+impl OrderLimits {
+    /// Limits set on MIN_ORDER_AMOUNT, MAX_ORDER_AMOUNT, MIN_ORDER_FIAT_AMOUNT
+    /// and MAX_ORDER_FIAT_AMOUNT
+    pub fn from_settings(limits: &LimitSettings) -> Self {
+        Self {
+            min_amount: limits.min_order_amount,
+            max_amount: limits.max_order_amount,
+            min_fiat_amount: limits.min_order_fiat_amount,
+            max_fiat_amount: limits.max_order_fiat_amount,
+        }
+    }
+
+    /// Sats amount inside the limits, market price orders have no amount yet
+    pub fn is_valid_amount(&self, amount: i64) -> bool {
+        amount == 0 || in_bounds(amount, self.min_amount, self.max_amount)
+    }
+
+    pub fn is_valid_fiat_amount(&self, fiat_amount: i64) -> bool {
+        in_bounds(fiat_amount, self.min_fiat_amount, self.max_fiat_amount)
+    }
+}
+
+/// Most sats a user with `trades` completed trades can trade when new users
+/// start at `base`, the limit doubles with every completed trade and is gone
+/// after `trades_required` trades. None if there is no limit
+pub fn new_user_max_amount(base: i64, trades: i64, trades_required: i64) -> Option<i64> {
+    if base == 0 || trades >= trades_required {
+        return None;
+    }
+
+    Some(base.saturating_mul(2_i64.saturating_pow(trades as u32)))
+}
+
+/// Limit of the first of `pubkeys` that can't trade `amount` sats yet, new
+/// users start at NEW_USER_MAX_ORDER_AMOUNT sats
+pub async fn exceeded_new_user_limit(
+    pool: &SqlitePool,
+    limits: &LimitSettings,
+    pubkeys: &[&str],
+    amount: i64,
+) -> Result<Option<i64>> {
+    let base = limits.new_user_max_order_amount;
+    if base == 0 || amount == 0 {
+        return Ok(None);
+    }
+    for pubkey in pubkeys {
+        let trades = db::find_user(pool, pubkey)
+            .await?
+            .map(|user| user.trades_completed)
+            .unwrap_or(0);
+        match new_user_max_amount(base, trades, limits.new_user_trades) {
+            Some(max_amount) if amount > max_amount => return Ok(Some(max_amount)),
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Whether a user who traded `volume` sats in the last 24 hours can trade
+/// `amount` sats more
+pub fn is_within_volume_limit(volume: i64, amount: i64, limit: i64) -> bool {
+    limit == 0 || volume.saturating_add(amount) <= limit
+}
+
+/// Whether any of `pubkeys` would go over the daily volume limit trading
+/// `amount` sats, the order being taken is not counted twice
+pub async fn exceeds_daily_volume(
+    pool: &SqlitePool,
+    limits: &LimitSettings,
+    pubkeys: &[&str],
+    amount: i64,
+    order_id: Option<Uuid>,
+) -> Result<bool> {
+    let limit = limits.daily_volume_limit;
+    if limit == 0 {
+        return Ok(false);
+    }
+    let since = Timestamp::now().as_i64() - 86400;
+    for pubkey in pubkeys {
+        let volume = db::find_user_volume_since(pool, pubkey, since, order_id).await?;
+        if !is_within_volume_limit(volume, amount, limit) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_within_volume_limit, new_user_max_amount, OrderLimits};
+
+    #[test]
+    fn test_order_limits() {
+        let limits = OrderLimits {
+            min_amount: 1_000,
+            max_amount: 1_000_000,
+            min_fiat_amount: 0,
+            max_fiat_amount: 500,
+        };
+        assert!(limits.is_valid_amount(0));
+        assert!(limits.is_valid_amount(1_000));
+        assert!(!limits.is_valid_amount(999));
+        assert!(!limits.is_valid_amount(1_000_001));
+        assert!(limits.is_valid_fiat_amount(1));
+        assert!(!limits.is_valid_fiat_amount(501));
+    }
+
+    #[test]
+    fn test_no_order_limits() {
+        let limits = OrderLimits::default();
+        assert!(limits.is_valid_amount(i64::MAX));
+        assert!(limits.is_valid_fiat_amount(i64::MAX));
+    }
+
+    #[test]
+    fn test_new_user_max_amount() {
+        assert_eq!(Some(50_000), new_user_max_amount(50_000, 0, 5));
+        assert_eq!(Some(200_000), new_user_max_amount(50_000, 2, 5));
+        // Enough completed trades or no limit for new users
+        assert_eq!(None, new_user_max_amount(50_000, 5, 5));
+        assert_eq!(None, new_user_max_amount(0, 0, 5));
+    }
+
+    #[test]
+    fn test_daily_volume_limit() {
+        assert!(is_within_volume_limit(900_000, 100_000, 1_000_000));
+        assert!(!is_within_volume_limit(900_000, 100_001, 1_000_000));
+        assert!(is_within_volume_limit(i64::MAX, 1, 0));
+    }
+}
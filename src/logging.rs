@@ -0,0 +1,21 @@
+use crate::settings::var;
+use tracing_subscriber::EnvFilter;
+
+/// Send the logs to stderr, as text or as JSON lines with the span context
+/// when LOG_FORMAT is `json`. RUST_LOG filters them by module, e.g.
+/// `info,mostro::scheduler=warn,sqlx=error`, the records of the crates using
+/// `log` are included
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+        _ => builder.init(),
+    }
+}
@@ -1,12 +1,58 @@
+pub mod accounting;
+pub mod admin_api;
+pub mod alerts;
 pub mod app;
+pub mod archive;
+pub mod auth;
+pub mod backup;
+pub mod bond;
+pub mod config;
+pub mod crypto;
+pub mod currency;
 pub mod db;
+pub mod dedup;
+pub mod denomination;
+pub mod description;
 pub mod error;
+pub mod escalation;
+pub mod fee;
 pub mod flow;
+pub mod health;
+pub mod heartbeat;
+pub mod http;
+pub mod i18n;
+pub mod lifecycle;
 pub mod lightning;
+pub mod limits;
+pub mod logging;
+pub mod maintenance;
 pub mod messages;
+pub mod metrics;
+pub mod migration;
 pub mod models;
+pub mod order_archive;
+pub mod outbox;
+pub mod payment_method;
+pub mod payout;
+pub mod price;
+pub mod raw_events;
+pub mod receipt;
+pub mod recovery;
+pub mod recurring;
+pub mod region;
+pub mod relays;
+pub mod reporting;
+pub mod rounding;
 pub mod scheduler;
+pub mod seed;
+pub mod settings;
+pub mod shutdown;
+pub mod state;
+pub mod stats;
+pub mod summary;
+pub mod trade_index;
 pub mod util;
+pub mod webhook;
 
 use crate::app::run;
 
@@ -19,24 +65,120 @@ use scheduler::start_scheduler;
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    pretty_env_logger::init();
+    // Settings come from the settings file and the environment, Mostro
+    // doesn't start until all of them are valid
+    let settings = config::init()?;
+    logging::init();
+    // Panics and failed actions go to the error tracker when one is set
+    reporting::init();
+    let args: Vec<String> = std::env::args().collect();
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let rollback = args.iter().position(|arg| arg == "--rollback");
+    // Migrations are applied on start unless the operator runs them by hand,
+    // previews them or rolls them back
+    let migrate = !args.iter().any(|arg| arg == "--no-migrate") && !dry_run && rollback.is_none();
+    if migrate {
+        db::create_database().await?;
+    }
     // Connect to database
     let pool = db::connect().await?;
+    if migrate {
+        db::migrate(&pool).await?;
+    }
+    // Operators check the pending migrations of an upgrade and roll back
+    // the last ones to a version after a bad upgrade
+    if let Some(index) = rollback {
+        let target = args
+            .get(index + 1)
+            .and_then(|version| version.parse::<i64>().ok())
+            .ok_or_else(|| anyhow::anyhow!("--rollback needs the version to go back to"))?;
+        println!("{}", migration::rollback(&pool, target, dry_run).await?);
+        return Ok(());
+    }
+    if dry_run {
+        println!("{}", migration::dry_run(&pool).await?);
+        return Ok(());
+    }
+    // Settings changed by the admins override the environment
+    settings::load(&pool).await?;
+    // Relays are read from the registry, RELAYS only fills it the first time
+    relays::load(&pool).await?;
+    // Secrets saved before DB_ENCRYPTION_KEY was set are encrypted once
+    if std::env::args().any(|arg| arg == "--encrypt-secrets") {
+        crypto::encrypt_existing_secrets(&pool).await?;
+        return Ok(());
+    }
+    // Operators export the audit log as JSON lines, since a unix time if given
+    if let Some(index) = args.iter().position(|arg| arg == "--export-audit-log") {
+        let since = args
+            .get(index + 1)
+            .and_then(|since| since.parse::<i64>().ok())
+            .unwrap_or(0);
+        for entry in db::find_audit_log(&pool, since).await? {
+            println!("{}", serde_json::to_string(&entry)?);
+        }
+        return Ok(());
+    }
+    // Operators export the completed trades or the fees earned for their
+    // accounting as CSV or JSON lines, optionally between two dates
+    for (flag, history) in [("--export-trades", "trades"), ("--export-fees", "fees")] {
+        if let Some(index) = args.iter().position(|arg| arg == flag) {
+            println!(
+                "{}",
+                accounting::export(&pool, history, &args[index + 1..]).await?
+            );
+            return Ok(());
+        }
+    }
+    // Contributors fill an empty database with orders in every status and
+    // get the keys of the users to act as them
+    if args.iter().any(|arg| arg == "--seed-dev-data") {
+        for keys in seed::seed_dev_data(&pool).await? {
+            println!(
+                "{} {}",
+                keys.public_key().to_bech32()?,
+                keys.secret_key()?.to_bech32()?
+            );
+        }
+        return Ok(());
+    }
+    // DMs about an order are archived for the solvers on the shared pool
+    archive::spawn(pool.clone());
     // Connect to relays
-    let client = util::connect_nostr().await?;
-    let my_keys = util::get_keys()?;
+    let client = util::connect_nostr_with(&settings.nostr).await?;
+    let my_keys = util::get_keys_with(&settings.nostr)?;
 
     let subscription = Filter::new()
         .pubkey(my_keys.public_key())
         .since(Timestamp::now());
 
     client.subscribe(vec![subscription]).await;
-    let mut ln_client = LndConnector::new().await;
+    let mut ln_client = LndConnector::connect(&settings.lightning).await;
 
     // Start scheduler for tasks
-    start_scheduler().await.unwrap().start().await?;
+    let mut sched = start_scheduler(settings).await.unwrap();
+    sched.start().await?;
+    // Orders left in progress by a restart are reconciled with LND
+    recovery::recover_orders(&pool).await?;
+    // Operators scrape the metrics and probe the health of Mostro
+    if let Some(addr) = http::get_http_addr() {
+        http::serve(addr, pool.clone(), client.clone())?;
+    }
+    // Operators and dashboards manage Mostro through the local admin API
+    if let Some((addr, token)) = admin_api::get_admin_api_config() {
+        admin_api::serve(addr, token, pool.clone(), client.clone(), my_keys.clone())?;
+    }
+    // Clients and monitors see Mostro is alive from its heartbeat
+    heartbeat::spawn(pool.clone(), client.clone(), my_keys.clone());
+    // Operators apply the settings they edit with `kill -HUP`
+    settings::reload_on_hangup(pool.clone(), client.clone(), my_keys.clone())?;
+
+    // SIGTERM and SIGINT stop Mostro once the work in progress is finished
+    shutdown::listen()?;
 
-    run(my_keys, client, &mut ln_client, pool).await
+    run(my_keys, client.clone(), &mut ln_client, pool.clone()).await?;
+    sched.shutdown().await?;
+    shutdown::finish(&pool, &client).await
 }
 
 #[cfg(test)]
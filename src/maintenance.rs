@@ -0,0 +1,154 @@
+use crate::settings::var;
+use crate::util::{get_admin_pubkeys, get_user_lang, send_dm};
+use crate::webhook::{self, WebhookEvent};
+use crate::{db, messages};
+use anyhow::{anyhow, Result};
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use tracing::{error, info};
+
+/// Database maintenance run on its own schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Task {
+    IntegrityCheck,
+    Analyze,
+    Vacuum,
+}
+
+pub const TASKS: [Task; 3] = [Task::IntegrityCheck, Task::Analyze, Task::Vacuum];
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IntegrityCheck => write!(f, "integrity_check"),
+            Self::Analyze => write!(f, "analyze"),
+            Self::Vacuum => write!(f, "vacuum"),
+        }
+    }
+}
+
+impl Task {
+    /// Cron expression of the task set on its env var, an empty one disables it
+    pub fn schedule(&self) -> Option<String> {
+        let (env_var, default) = match self {
+            Self::IntegrityCheck => ("DB_INTEGRITY_CHECK_SCHEDULE", "0 0 3 * * *"),
+            Self::Analyze => ("DB_ANALYZE_SCHEDULE", "0 30 3 * * *"),
+            Self::Vacuum => ("DB_VACUUM_SCHEDULE", "0 0 4 * * Sun"),
+        };
+        let schedule = var(env_var).unwrap_or_else(|_| default.to_string());
+        match schedule.trim() {
+            "" => None,
+            schedule => Some(schedule.to_string()),
+        }
+    }
+}
+
+/// Last run of a task, with the error if it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskRun {
+    pub ran_at: i64,
+    pub error: Option<String>,
+}
+
+static LAST_RUNS: Mutex<BTreeMap<Task, TaskRun>> = Mutex::new(BTreeMap::new());
+
+/// Last run of every task run since Mostro started, the database is healthy
+/// while none of them failed
+pub fn health() -> BTreeMap<Task, TaskRun> {
+    LAST_RUNS.lock().unwrap().clone()
+}
+
+/// Rows of `PRAGMA integrity_check` are a single `ok` for a sound database,
+/// otherwise the problems found
+pub fn integrity_result(rows: &[String]) -> Result<(), String> {
+    match rows {
+        [row] if row == "ok" => Ok(()),
+        [] => Err("integrity check returned nothing".to_string()),
+        rows => Err(rows.join("; ")),
+    }
+}
+
+async fn run(pool: &SqlitePool, task: Task) -> Result<()> {
+    match task {
+        Task::IntegrityCheck => {
+            integrity_result(&db::integrity_check(pool).await?).map_err(|e| anyhow!(e))
+        }
+        Task::Analyze => db::analyze(pool).await,
+        Task::Vacuum => db::vacuum(pool).await,
+    }
+}
+
+/// Run a maintenance task keeping its outcome for the health status, the
+/// admins are told when it fails
+pub async fn run_task(pool: &SqlitePool, task: Task) -> Result<()> {
+    let result = run(pool, task).await;
+    let error = result.as_ref().err().map(|e| e.to_string());
+    LAST_RUNS.lock().unwrap().insert(
+        task,
+        TaskRun {
+            ran_at: Timestamp::now().as_i64(),
+            error: error.clone(),
+        },
+    );
+    match error {
+        None => info!("Database {task} done"),
+        Some(error) => {
+            error!("Database {task} failed: {error}");
+            webhook::notify(
+                WebhookEvent::DatabaseError,
+                json!({ "context": task.to_string(), "error": error }),
+            );
+            alert_admins(pool, task, &error).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn alert_admins(pool: &SqlitePool, task: Task, error: &str) -> Result<()> {
+    let client = crate::util::connect_nostr().await?;
+    let keys = crate::util::get_keys()?;
+    for admin_pubkey in get_admin_pubkeys() {
+        let text_message = messages::maintenance_failed_alert(
+            get_user_lang(pool, &admin_pubkey).await,
+            &task.to_string(),
+            error,
+        );
+        let message = Message::new(
+            0,
+            None,
+            Action::CantDo,
+            Some(Content::TextMessage(text_message)),
+        );
+        send_dm(&client, &keys, &admin_pubkey, message.as_json()?).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::integrity_result;
+
+    #[test]
+    fn test_integrity_result() {
+        assert_eq!(Ok(()), integrity_result(&["ok".to_string()]));
+        assert!(integrity_result(&[]).is_err());
+        let rows = [
+            "row 1 missing from index orders_id".to_string(),
+            "wrong # of entries in index orders_id".to_string(),
+        ];
+        assert_eq!(
+            Err(
+                "row 1 missing from index orders_id; wrong # of entries in index orders_id"
+                    .to_string()
+            ),
+            integrity_result(&rows)
+        );
+    }
+}
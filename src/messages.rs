@@ -1,17 +1,568 @@
+use crate::denomination::Denomination;
+use crate::i18n::{translate, Lang};
+use crate::limits::OrderLimits;
+use crate::models::UserReputation;
+use crate::rounding::Conversion;
+use crate::summary::Summary;
 use anyhow::Result;
+use mostro_core::order::Order;
 use nostr_sdk::prelude::*;
 
-pub fn cant_do() -> String {
-    "You can't do that!".to_string()
+pub fn cant_do(lang: Lang) -> String {
+    translate(lang, "cant-do", &[])
 }
 
-pub fn hold_invoice_description(
+pub fn maintenance_mode(lang: Lang) -> String {
+    translate(lang, "maintenance-mode", &[])
+}
+
+pub fn purge_user_busy(lang: Lang, open_items: i64) -> String {
+    translate(
+        lang,
+        "purge-user-busy",
+        &[("open_items", open_items.to_string())],
+    )
+}
+
+pub fn wrong_status(lang: Lang, status: &str, actions: &[String]) -> String {
+    let actions = if actions.is_empty() {
+        translate(lang, "no-actions", &[])
+    } else {
+        actions.join(", ")
+    };
+    translate(
+        lang,
+        "wrong-status",
+        &[("status", status.to_string()), ("actions", actions)],
+    )
+}
+
+pub fn escrow_canceled(lang: Lang) -> String {
+    translate(lang, "escrow-canceled", &[])
+}
+
+pub fn order_expired(lang: Lang) -> String {
+    translate(lang, "order-expired", &[])
+}
+
+pub fn cooperative_cancel_expired(lang: Lang) -> String {
+    translate(lang, "cooperative-cancel-expired", &[])
+}
+
+pub fn hold_invoice_not_paid_canceled(lang: Lang) -> String {
+    translate(lang, "hold-invoice-not-paid-canceled", &[])
+}
+
+pub fn hold_invoice_not_paid_republished(lang: Lang) -> String {
+    translate(lang, "hold-invoice-not-paid-republished", &[])
+}
+
+pub fn release_reminder(
+    lang: Lang,
+    reminder: i64,
+    elapsed: i64,
+    minutes_left: Option<i64>,
+) -> String {
+    let mut args = vec![
+        ("reminder", reminder.to_string()),
+        ("elapsed", elapsed.to_string()),
+    ];
+    match minutes_left {
+        Some(minutes_left) => {
+            args.push(("minutes", minutes_left.to_string()));
+            translate(lang, "release-reminder", &args)
+        }
+        None => translate(lang, "release-reminder-no-dispute", &args),
+    }
+}
+
+pub fn release_final_warning(lang: Lang, minutes_left: i64) -> String {
+    translate(
+        lang,
+        "release-final-warning",
+        &[("minutes", minutes_left.to_string())],
+    )
+}
+
+pub fn stalled_reminder(lang: Lang, reminder: i64, status: &str, elapsed: i64) -> String {
+    translate(
+        lang,
+        "stalled-reminder",
+        &[
+            ("reminder", reminder.to_string()),
+            ("status", status.to_string()),
+            ("elapsed", elapsed.to_string()),
+        ],
+    )
+}
+
+pub fn stalled_final_warning(lang: Lang, status: &str, minutes_left: i64) -> String {
+    translate(
+        lang,
+        "stalled-final-warning",
+        &[
+            ("status", status.to_string()),
+            ("minutes", minutes_left.to_string()),
+        ],
+    )
+}
+
+pub fn stalled_order_alert(lang: Lang, order_id: &str, status: &str, elapsed: i64) -> String {
+    translate(
+        lang,
+        "stalled-order-alert",
+        &[
+            ("order_id", order_id.to_string()),
+            ("status", status.to_string()),
+            ("elapsed", elapsed.to_string()),
+        ],
+    )
+}
+
+pub fn maintenance_failed_alert(lang: Lang, task: &str, error: &str) -> String {
+    translate(
+        lang,
+        "maintenance-failed-alert",
+        &[("task", task.to_string()), ("error", error.to_string())],
+    )
+}
+
+pub fn daily_summary(lang: Lang, summary: &Summary) -> String {
+    let liquidity = match &summary.liquidity {
+        Ok(liquidity) => translate(
+            lang,
+            "node-liquidity",
+            &[
+                ("active", liquidity.active_channels.to_string()),
+                ("inactive", liquidity.inactive_channels.to_string()),
+                ("outbound", liquidity.outbound.to_string()),
+                ("inbound", liquidity.inbound.to_string()),
+            ],
+        ),
+        Err(error) => translate(lang, "node-unreachable", &[("error", error.to_string())]),
+    };
+    translate(
+        lang,
+        "daily-summary",
+        &[
+            ("new_orders", summary.activity.new_orders.to_string()),
+            ("trades", summary.trades.to_string()),
+            ("volume", summary.volume.to_string()),
+            ("fees", summary.activity.fees.to_string()),
+            ("disputes", summary.activity.pending_disputes.to_string()),
+            (
+                "failed_payouts",
+                summary.activity.failed_payouts.to_string(),
+            ),
+            ("liquidity", liquidity),
+        ],
+    )
+}
+
+pub fn inconsistent_order_alert(lang: Lang, order_id: &str, status: &str, reason: &str) -> String {
+    translate(
+        lang,
+        "inconsistent-order-alert",
+        &[
+            ("order_id", order_id.to_string()),
+            ("status", status.to_string()),
+            ("reason", reason.to_string()),
+        ],
+    )
+}
+
+pub fn routing_fee_shortfall(lang: Lang, reduced_amount: i64, retry_minutes: i64) -> String {
+    translate(
+        lang,
+        "routing-fee-shortfall",
+        &[
+            ("amount", reduced_amount.to_string()),
+            ("minutes", retry_minutes.to_string()),
+        ],
+    )
+}
+
+pub fn status_forced(lang: Lang, status: &str, reason: &str) -> String {
+    translate(
+        lang,
+        "status-forced",
+        &[
+            ("status", status.to_string()),
+            ("reason", reason.to_string()),
+        ],
+    )
+}
+
+pub fn price_not_available(lang: Lang) -> String {
+    translate(lang, "price-not-available", &[])
+}
+
+pub fn market_order_invoice_amount(lang: Lang) -> String {
+    translate(lang, "market-order-invoice-amount", &[])
+}
+
+/// How the side of the order left open by the maker was quoted
+pub fn market_quote(
+    lang: Lang,
+    denomination: Denomination,
+    amount: i64,
+    fiat_code: &str,
+    fiat_amount: i64,
+) -> String {
+    let key = match denomination {
+        Denomination::Sats => "market-quote-sats",
+        Denomination::Fiat => "market-quote-fiat",
+    };
+    translate(
+        lang,
+        key,
+        &[
+            ("amount", amount.to_string()),
+            ("fiat_code", fiat_code.to_string()),
+            ("fiat_amount", fiat_amount.to_string()),
+        ],
+    )
+}
+
+pub fn sats_conversion(
+    lang: Lang,
+    fiat_amount: i64,
+    fiat_code: &str,
+    premium: i64,
+    conversion: &Conversion,
+) -> String {
+    translate(
+        lang,
+        "sats-conversion",
+        &[
+            ("fiat_amount", fiat_amount.to_string()),
+            ("fiat_code", fiat_code.to_string()),
+            ("price", format!("{:.2}", conversion.btc_price)),
+            ("premium", premium.to_string()),
+            ("exact", format!("{:.3}", conversion.exact_sats)),
+            ("rounding", conversion.policy.rounding.to_string()),
+            ("granularity", conversion.policy.granularity.to_string()),
+            ("amount", conversion.sats.to_string()),
+        ],
+    )
+}
+
+pub fn invalid_amounts(lang: Lang) -> String {
+    translate(lang, "invalid-amounts", &[])
+}
+
+pub fn buyer_fee_breakdown(lang: Lang, amount: i64, fee: i64, payout_amount: i64) -> String {
+    translate(
+        lang,
+        "buyer-fee-breakdown",
+        &[
+            ("amount", amount.to_string()),
+            ("fee", fee.to_string()),
+            ("total", payout_amount.to_string()),
+        ],
+    )
+}
+
+pub fn order_out_of_limits(lang: Lang, limits: &OrderLimits, fiat_code: &str) -> String {
+    let limit = |value: i64| {
+        if value == 0 {
+            translate(lang, "no-limit", &[])
+        } else {
+            value.to_string()
+        }
+    };
+    translate(
+        lang,
+        "order-out-of-limits",
+        &[
+            ("min_amount", limit(limits.min_amount)),
+            ("max_amount", limit(limits.max_amount)),
+            ("min_fiat_amount", limit(limits.min_fiat_amount)),
+            ("max_fiat_amount", limit(limits.max_fiat_amount)),
+            ("fiat_code", fiat_code.to_string()),
+        ],
+    )
+}
+
+pub fn bond_invoice_description(
+    lang: Lang,
     mostro_pubkey: XOnlyPublicKey,
     order_id: &str,
+    amount: i64,
+) -> Result<String> {
+    Ok(translate(
+        lang,
+        "bond-invoice-description",
+        &[
+            ("mostro", mostro_pubkey.to_bech32()?),
+            ("order_id", order_id.to_string()),
+            ("amount", amount.to_string()),
+        ],
+    ))
+}
+
+pub fn bond_compensation(lang: Lang, amount: i64) -> String {
+    translate(lang, "bond-compensation", &[("amount", amount.to_string())])
+}
+
+pub fn dispute_fee_bond_refund(lang: Lang, dispute_fee: i64, amount: i64) -> String {
+    translate(
+        lang,
+        "dispute-fee-bond-refund",
+        &[
+            ("fee", dispute_fee.to_string()),
+            ("amount", amount.to_string()),
+        ],
+    )
+}
+
+pub fn invalid_split(lang: Lang) -> String {
+    translate(lang, "invalid-split", &[])
+}
+
+pub fn dispute_taken(lang: Lang) -> String {
+    translate(lang, "dispute-taken", &[])
+}
+
+pub fn dispute_assigned_to_other(lang: Lang) -> String {
+    translate(lang, "dispute-assigned-to-other", &[])
+}
+
+pub fn dispute_split(lang: Lang, amount: i64) -> String {
+    translate(lang, "dispute-split", &[("amount", amount.to_string())])
+}
+
+pub fn dispute_won_fee(lang: Lang, dispute_fee: i64, amount: i64) -> String {
+    translate(
+        lang,
+        "dispute-won-fee",
+        &[
+            ("fee", dispute_fee.to_string()),
+            ("amount", amount.to_string()),
+        ],
+    )
+}
+
+pub fn payout_paid(lang: Lang, amount: i64) -> String {
+    translate(lang, "payout-paid", &[("amount", amount.to_string())])
+}
+
+pub fn invalid_split_payout(lang: Lang, min_amount: i64, max_parts: usize) -> String {
+    if min_amount == 0 {
+        return translate(lang, "split-payout-disabled", &[]);
+    }
+    translate(
+        lang,
+        "invalid-split-payout",
+        &[
+            ("min", min_amount.to_string()),
+            ("max", max_parts.to_string()),
+        ],
+    )
+}
+
+pub fn split_payout_amount(lang: Lang, amount: i64) -> String {
+    translate(
+        lang,
+        "split-payout-amount",
+        &[("amount", amount.to_string())],
+    )
+}
+
+pub fn payout_part_failed(lang: Lang, part: i64, amount: i64) -> String {
+    translate(
+        lang,
+        "payout-part-failed",
+        &[("part", part.to_string()), ("amount", amount.to_string())],
+    )
+}
+
+pub fn invalid_recurring(lang: Lang, max_repeats: i64) -> String {
+    translate(
+        lang,
+        "invalid-recurring",
+        &[("max", max_repeats.to_string())],
+    )
+}
+
+pub fn cant_edit_order_identity(lang: Lang) -> String {
+    translate(lang, "cant-edit-order-identity", &[])
+}
+
+pub fn release_confirmation(lang: Lang, minutes: i64) -> String {
+    translate(
+        lang,
+        "release-confirmation",
+        &[("minutes", minutes.to_string())],
+    )
+}
+
+pub fn buyer_invoice_not_received_canceled(lang: Lang) -> String {
+    translate(lang, "buyer-invoice-not-received-canceled", &[])
+}
+
+pub fn buyer_invoice_not_received_republished(lang: Lang) -> String {
+    translate(lang, "buyer-invoice-not-received-republished", &[])
+}
+
+pub fn cant_take_own_order(lang: Lang) -> String {
+    translate(lang, "cant-take-own-order", &[])
+}
+
+pub fn unsupported_payment_method(lang: Lang, payment_method: &str, methods: &[&str]) -> String {
+    translate(
+        lang,
+        "unsupported-payment-method",
+        &[
+            ("payment_method", payment_method.to_string()),
+            ("methods", methods.join(", ")),
+        ],
+    )
+}
+
+pub fn blocked_payment_method(lang: Lang, payment_method: &str, blocked: &[&str]) -> String {
+    translate(
+        lang,
+        "blocked-payment-method",
+        &[
+            ("payment_method", payment_method.to_string()),
+            ("methods", blocked.join(", ")),
+        ],
+    )
+}
+
+pub fn invalid_region(lang: Lang, region: &str) -> String {
+    translate(lang, "invalid-region", &[("region", region.to_string())])
+}
+
+pub fn invalid_trade_index(lang: Lang, index: &str) -> String {
+    translate(lang, "invalid-trade-index", &[("index", index.to_string())])
+}
+
+pub fn invalid_description(lang: Lang, max_length: usize) -> String {
+    translate(
+        lang,
+        "invalid-description",
+        &[("max", max_length.to_string())],
+    )
+}
+
+pub fn user_banned(lang: Lang) -> String {
+    translate(lang, "user-banned", &[])
+}
+
+pub fn invalid_rating(lang: Lang, min: u8, max: u8) -> String {
+    translate(
+        lang,
+        "invalid-rating",
+        &[("min", min.to_string()), ("max", max.to_string())],
+    )
+}
+
+pub fn already_rated(lang: Lang) -> String {
+    translate(lang, "already-rated", &[])
+}
+
+pub fn counterpart_reputation(lang: Lang, reputation: &UserReputation) -> String {
+    translate(
+        lang,
+        "counterpart-reputation",
+        &[
+            ("rating", format!("{:.1}", reputation.average_rating)),
+            ("total", reputation.total_ratings.to_string()),
+            ("days", reputation.days_active.to_string()),
+            ("trades", reputation.trades_completed.to_string()),
+            (
+                "completion",
+                format!("{:.0}", reputation.completion_rate * 100_f64),
+            ),
+            (
+                "release_minutes",
+                (reputation.average_release_time / 60).to_string(),
+            ),
+        ],
+    )
+}
+
+pub fn unsupported_currency(lang: Lang, fiat_code: &str, supported: &[&str]) -> String {
+    translate(
+        lang,
+        "unsupported-currency",
+        &[
+            ("fiat_code", fiat_code.to_string()),
+            ("currencies", supported.join(", ")),
+        ],
+    )
+}
+
+pub fn blocked_currency(lang: Lang, fiat_code: &str, blocked: &[String]) -> String {
+    translate(
+        lang,
+        "blocked-currency",
+        &[
+            ("fiat_code", fiat_code.to_string()),
+            ("currencies", blocked.join(", ")),
+        ],
+    )
+}
+
+pub fn new_user_limit(lang: Lang, max_amount: i64, trades_required: i64) -> String {
+    translate(
+        lang,
+        "new-user-limit",
+        &[
+            ("max", max_amount.to_string()),
+            ("trades", trades_required.to_string()),
+        ],
+    )
+}
+
+pub fn daily_volume_limit(lang: Lang, limit: i64) -> String {
+    translate(lang, "daily-volume-limit", &[("limit", limit.to_string())])
+}
+
+pub fn invalid_range(lang: Lang) -> String {
+    translate(lang, "invalid-range", &[])
+}
+
+pub fn out_of_range_amount(
+    lang: Lang,
+    min_amount: i64,
+    max_amount: i64,
     fiat_code: &str,
-    fiat_amount: &str,
+) -> String {
+    translate(
+        lang,
+        "out-of-range-amount",
+        &[
+            ("min", min_amount.to_string()),
+            ("max", max_amount.to_string()),
+            ("fiat_code", fiat_code.to_string()),
+        ],
+    )
+}
+
+/// Memo of the seller hold invoice, with the whole breakdown so sellers can
+/// tell in their wallet which trade the escrow belongs to
+pub fn hold_invoice_description(
+    lang: Lang,
+    mostro_pubkey: XOnlyPublicKey,
+    order: &Order,
+    seller_fee: i64,
+    escrow_amount: i64,
 ) -> Result<String> {
-    Ok(format!(
-        "{} - Escrow amount Order #{order_id}: SELL BTC for {fiat_code} {fiat_amount} - It WILL FREEZE IN WALLET. It will release once you release. It will return if buyer does not confirm the payment", mostro_pubkey.to_bech32()?
+    Ok(translate(
+        lang,
+        "hold-invoice-description",
+        &[
+            ("mostro", mostro_pubkey.to_bech32()?),
+            ("order_id", order.id.to_string()),
+            ("amount", order.amount.to_string()),
+            ("fiat_code", order.fiat_code.clone()),
+            ("fiat_amount", order.fiat_amount.to_string()),
+            ("premium", order.premium.to_string()),
+            ("fee", seller_fee.to_string()),
+            ("total", escrow_amount.to_string()),
+        ],
     ))
 }
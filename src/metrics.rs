@@ -0,0 +1,265 @@
+use crate::db;
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds in seconds of the buckets of the action latency
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Name, type and help of the metrics Mostro records
+const METRICS: &[(&str, &str, &str)] = &[
+    (
+        "mostro_actions_total",
+        "counter",
+        "Actions processed by action and outcome",
+    ),
+    (
+        "mostro_action_duration_seconds",
+        "histogram",
+        "Time taken to process an action",
+    ),
+    (
+        "mostro_dm_publish_failures_total",
+        "counter",
+        "DMs no relay took, kept in the outbox",
+    ),
+    (
+        "mostro_payments_total",
+        "counter",
+        "Payments to users finished by status",
+    ),
+    (
+        "mostro_routing_fees_paid_sats_total",
+        "counter",
+        "Routing fees paid in sats by the successful payments",
+    ),
+];
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// Values by metric name and labels
+    counters: BTreeMap<(&'static str, String), f64>,
+    histograms: BTreeMap<(&'static str, String), Histogram>,
+}
+
+impl Registry {
+    fn add(&mut self, name: &'static str, labels: String, value: f64) {
+        *self.counters.entry((name, labels)).or_default() += value;
+    }
+
+    fn observe(&mut self, name: &'static str, labels: String, value: f64) {
+        let histogram = self.histograms.entry((name, labels)).or_default();
+        for (index, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            if value <= *bound {
+                histogram.buckets[index] += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += value;
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for (name, kind, help) in METRICS {
+            let _ = writeln!(output, "# HELP {name} {help}\n# TYPE {name} {kind}");
+            for ((metric, labels), value) in self.counters.iter() {
+                if metric == name {
+                    let _ = writeln!(output, "{name}{} {value}", with_labels(labels, None));
+                }
+            }
+            for ((metric, labels), histogram) in self.histograms.iter() {
+                if metric != name {
+                    continue;
+                }
+                for (index, bound) in LATENCY_BUCKETS.iter().enumerate() {
+                    let le = format!("le=\"{bound}\"");
+                    let _ = writeln!(
+                        output,
+                        "{name}_bucket{} {}",
+                        with_labels(labels, Some(&le)),
+                        histogram.buckets[index]
+                    );
+                }
+                let le = "le=\"+Inf\"";
+                let _ = writeln!(
+                    output,
+                    "{name}_bucket{} {}",
+                    with_labels(labels, Some(le)),
+                    histogram.count
+                );
+                let _ = writeln!(
+                    output,
+                    "{name}_sum{} {}",
+                    with_labels(labels, None),
+                    histogram.sum
+                );
+                let _ = writeln!(
+                    output,
+                    "{name}_count{} {}",
+                    with_labels(labels, None),
+                    histogram.count
+                );
+            }
+        }
+
+        output
+    }
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    counters: BTreeMap::new(),
+    histograms: BTreeMap::new(),
+});
+
+/// Labels in the exposition format, `a="1",b="2"` without braces
+fn labels(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn with_labels(labels: &str, extra: Option<&str>) -> String {
+    let labels = [Some(labels), extra]
+        .into_iter()
+        .flatten()
+        .filter(|labels| !labels.is_empty())
+        .collect::<Vec<_>>()
+        .join(",");
+    match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{labels}}}"),
+    }
+}
+
+/// Add `value` to a counter
+pub fn add(name: &'static str, pairs: &[(&str, &str)], value: f64) {
+    REGISTRY.lock().unwrap().add(name, labels(pairs), value);
+}
+
+pub fn inc(name: &'static str, pairs: &[(&str, &str)]) {
+    add(name, pairs, 1.0);
+}
+
+/// Record a value in a histogram
+pub fn observe(name: &'static str, pairs: &[(&str, &str)], value: f64) {
+    REGISTRY.lock().unwrap().observe(name, labels(pairs), value);
+}
+
+/// Outcome and time taken by an action sent by a user
+pub fn record_action(action: &str, elapsed: Duration, ok: bool) {
+    let outcome = match ok {
+        true => "ok",
+        false => "error",
+    };
+    inc(
+        "mostro_actions_total",
+        &[("action", action), ("outcome", outcome)],
+    );
+    observe(
+        "mostro_action_duration_seconds",
+        &[("action", action)],
+        elapsed.as_secs_f64(),
+    );
+}
+
+/// Gauge read from the database when the metrics are scraped
+fn render_gauge(output: &mut String, name: &str, help: &str, samples: &[(String, f64)]) {
+    let _ = writeln!(output, "# HELP {name} {help}\n# TYPE {name} gauge");
+    for (labels, value) in samples {
+        let _ = writeln!(output, "{name}{} {value}", with_labels(labels, None));
+    }
+}
+
+/// Every metric in the Prometheus text format, the recorded ones and the
+/// orders and relays as saved now
+pub async fn gather(pool: &SqlitePool) -> Result<String> {
+    let mut output = REGISTRY.lock().unwrap().render();
+    let orders = db::count_orders_by_status(pool)
+        .await?
+        .into_iter()
+        .map(|(status, count)| (labels(&[("status", &status)]), count as f64))
+        .collect::<Vec<_>>();
+    render_gauge(
+        &mut output,
+        "mostro_orders",
+        "Orders by status, archived ones excluded",
+        &orders,
+    );
+    let relays = db::find_relays(pool).await?;
+    let up = relays
+        .iter()
+        .map(|relay| {
+            let value = match relay.status.as_str() {
+                "Online" => 1.0,
+                _ => 0.0,
+            };
+            (labels(&[("url", &relay.url)]), value)
+        })
+        .collect::<Vec<_>>();
+    render_gauge(
+        &mut output,
+        "mostro_relay_up",
+        "Whether the relay answered the last check",
+        &up,
+    );
+    let latency = relays
+        .iter()
+        .filter_map(|relay| {
+            relay
+                .latency
+                .map(|latency| (labels(&[("url", &relay.url)]), latency as f64 / 1000.0))
+        })
+        .collect::<Vec<_>>();
+    render_gauge(
+        &mut output,
+        "mostro_relay_latency_seconds",
+        "Time the relay took to answer the last check",
+        &latency,
+    );
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{labels, Registry};
+
+    #[test]
+    fn test_render() {
+        let mut registry = Registry::default();
+        let action = labels(&[("action", "Order"), ("outcome", "ok")]);
+        registry.add("mostro_actions_total", action.clone(), 1.0);
+        registry.add("mostro_actions_total", action, 1.0);
+        let action = labels(&[("action", "Order")]);
+        registry.observe("mostro_action_duration_seconds", action, 0.07);
+        let output = registry.render();
+        assert!(output.contains("mostro_actions_total{action=\"Order\",outcome=\"ok\"} 2\n"));
+        assert!(output
+            .contains("mostro_action_duration_seconds_bucket{action=\"Order\",le=\"0.05\"} 0\n"));
+        assert!(output
+            .contains("mostro_action_duration_seconds_bucket{action=\"Order\",le=\"0.1\"} 1\n"));
+        assert!(output
+            .contains("mostro_action_duration_seconds_bucket{action=\"Order\",le=\"+Inf\"} 1\n"));
+        assert!(output.contains("# TYPE mostro_dm_publish_failures_total counter\n"));
+        // Label values are escaped
+        assert_eq!("memo=\"a \\\"b\\\"\"", labels(&[("memo", "a \"b\"")]));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::db::MIGRATOR;
+use anyhow::{anyhow, Result};
+use sqlx::migrate::{Migrate, Migration};
+use sqlx::{Executor, SqlitePool};
+
+/// Migrations not applied yet in the order they are applied
+pub fn pending<'a>(migrations: &'a [Migration], applied: &[i64]) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect()
+}
+
+/// Down migrations taking the database back to the `target` version, newest
+/// first. Err with the first applied version above the target that can't be
+/// rolled back
+pub fn rollback_plan<'a>(
+    migrations: &'a [Migration],
+    applied: &[i64],
+    target: i64,
+) -> Result<Vec<&'a Migration>, i64> {
+    let mut versions = applied
+        .iter()
+        .copied()
+        .filter(|version| *version > target)
+        .collect::<Vec<_>>();
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    versions
+        .into_iter()
+        .map(|version| {
+            migrations
+                .iter()
+                .find(|migration| {
+                    migration.version == version && migration.migration_type.is_down_migration()
+                })
+                .ok_or(version)
+        })
+        .collect()
+}
+
+async fn applied_versions(pool: &SqlitePool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    Ok(applied
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect())
+}
+
+/// Run the migrations in a transaction that is rolled back, the output has
+/// the SQL of every migration and the first error found
+async fn try_migrations(pool: &SqlitePool, migrations: &[&Migration]) -> Result<String> {
+    let mut output = String::new();
+    let mut tx = pool.begin().await?;
+    for migration in migrations {
+        output.push_str(&format!(
+            "-- {} {}\n{}\n",
+            migration.version,
+            migration.description,
+            migration.sql.trim()
+        ));
+        if let Err(e) = tx.execute(&*migration.sql).await {
+            output.push_str(&format!("-- {} fails: {e}\n", migration.version));
+            break;
+        }
+        output.push_str(&format!("-- {} ok\n\n", migration.version));
+    }
+    tx.rollback().await?;
+
+    Ok(output)
+}
+
+/// Show the pending migrations and check they apply without changing the
+/// database
+pub async fn dry_run(pool: &SqlitePool) -> Result<String> {
+    let applied = applied_versions(pool).await?;
+    let pending = pending(&MIGRATOR.migrations, &applied);
+    if pending.is_empty() {
+        return Ok("No pending migrations".to_string());
+    }
+
+    try_migrations(pool, &pending).await
+}
+
+/// Revert the migrations applied after `target`, with `dry_run` they are
+/// only shown and checked. Nothing is reverted when one of them has no down
+/// migration, the database must be restored from a backup then
+pub async fn rollback(pool: &SqlitePool, target: i64, dry_run: bool) -> Result<String> {
+    let applied = applied_versions(pool).await?;
+    let plan = rollback_plan(&MIGRATOR.migrations, &applied, target).map_err(|version| {
+        anyhow!("Migration {version} can't be rolled back, restore a backup instead")
+    })?;
+    if plan.is_empty() {
+        return Ok(format!("No migrations applied after {target}"));
+    }
+    if dry_run {
+        return try_migrations(pool, &plan).await;
+    }
+    MIGRATOR.undo(pool, target).await?;
+
+    Ok(format!(
+        "Rolled back to {target}: {}",
+        plan.iter()
+            .map(|migration| migration.version.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pending, rollback_plan};
+    use sqlx::migrate::{Migration, MigrationType};
+    use std::borrow::Cow;
+
+    fn migration(version: i64, migration_type: MigrationType) -> Migration {
+        Migration::new(
+            version,
+            Cow::Borrowed("test"),
+            migration_type,
+            Cow::Borrowed(""),
+        )
+    }
+
+    #[test]
+    fn test_rollback_plan() {
+        let migrations = vec![
+            migration(1, MigrationType::Simple),
+            migration(2, MigrationType::Simple),
+            migration(2, MigrationType::ReversibleDown),
+            migration(3, MigrationType::Simple),
+            migration(3, MigrationType::ReversibleDown),
+        ];
+        let versions = |plan: Vec<&Migration>| plan.iter().map(|m| m.version).collect::<Vec<_>>();
+        assert_eq!(vec![3], versions(pending(&migrations, &[1, 2])));
+        assert_eq!(
+            Ok(vec![3, 2]),
+            rollback_plan(&migrations, &[1, 2, 3], 1).map(versions)
+        );
+        assert_eq!(
+            Ok(vec![]),
+            rollback_plan(&migrations, &[1, 2], 2).map(versions)
+        );
+        // The first migration has no down migration
+        assert_eq!(
+            Err(1),
+            rollback_plan(&migrations, &[1, 2, 3], 0).map(versions)
+        );
+    }
+}
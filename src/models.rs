@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Serialize, Deserialize)]
 pub struct Yadio {
@@ -14,3 +15,371 @@ pub struct Request {
     from: String,
     to: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Dispute {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    /// Initiated, InProgress once a solver takes it, Settled or SellerRefunded
+    pub status: String,
+    pub initiator_pubkey: String,
+    pub created_at: i64,
+    pub solver_pubkey: Option<String>,
+    pub resolution: Option<String>,
+    pub taken_at: i64,
+    pub resolved_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DisputeMessage {
+    pub id: i64,
+    pub dispute_id: Uuid,
+    pub sender_pubkey: String,
+    pub content: String,
+    pub created_at: i64,
+}
+
+/// Entry of the append-only audit log, `details` has the outcome of
+/// processed actions or what an admin command changed
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditLog {
+    pub id: i64,
+    pub pubkey: String,
+    pub action: String,
+    pub order_id: Option<Uuid>,
+    pub details: Option<String>,
+    pub created_at: i64,
+}
+
+/// Message exchanged with Mostro about an order, `direction` is `in` for
+/// messages sent to Mostro and `out` for the ones it sent
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderMessage {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub pubkey: String,
+    pub action: String,
+    pub direction: String,
+    pub payload_hash: String,
+    pub created_at: i64,
+    /// Nostr event that carried the message, empty for older messages
+    pub event_id: Option<String>,
+}
+
+/// Status transition of an order, `from_status` is empty for the first
+/// status and `trigger` says what caused it: user, admin, timeout, lightning
+/// or system
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderEvent {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub trigger: String,
+    pub created_at: i64,
+}
+
+/// Escalation state of an order stalled in its current status,
+/// `state_started_at` is 0 for statuses without their own timestamp
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct EscalationTimer {
+    pub id: Uuid,
+    pub state_started_at: i64,
+    pub started_at: Option<i64>,
+    pub reminders: i64,
+    pub escalated: bool,
+}
+
+/// Aggregated ratings of a user, days active since the user first made or
+/// took an order
+#[derive(Debug, Clone, Default, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UserReputation {
+    pub total_ratings: i64,
+    pub average_rating: f64,
+    pub days_active: i64,
+    pub trades_completed: i64,
+    /// Sats traded in completed trades
+    pub volume: i64,
+    /// Completed trades out of the ones where both parties committed, 0 to 1
+    pub completion_rate: f64,
+    /// Seconds the user takes as seller to release after the fiat is sent
+    pub average_release_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct User {
+    pub pubkey: String,
+    pub trades_completed: i64,
+    pub volume: i64,
+    pub disputes_opened: i64,
+    pub disputes_lost: i64,
+    pub is_banned: bool,
+    pub created_at: i64,
+    pub language: String,
+}
+
+/// Hold invoice locked by a party of the trade as a guarantee, status is one
+/// of Requested, Locked, Released, Forfeited or Paid. Forfeited bonds are
+/// paid to `payee_pubkey` minus the Mostro fee
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Bond {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub pubkey: String,
+    pub amount: i64,
+    pub hash: String,
+    pub preimage: String,
+    pub status: String,
+    pub created_at: i64,
+    pub payee_pubkey: Option<String>,
+    pub payout_amount: i64,
+}
+
+/// Amount owed by Mostro to a user out of the regular trade flow, paid to the
+/// invoice the user sends with AddInvoice, status is Pending or Paid
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Payout {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub pubkey: String,
+    pub amount: i64,
+    pub status: String,
+    pub created_at: i64,
+}
+
+/// Attempt to pay a user of an order, status is InFlight, Succeeded or
+/// Failed and `fee_paid` the routing fee in sats
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Payment {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub pubkey: String,
+    pub payment_hash: String,
+    pub amount: i64,
+    pub fee_paid: i64,
+    pub status: String,
+    pub failure_reason: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Order completed successfully, `completed_at` is when it reached Success
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Trade {
+    pub id: Uuid,
+    pub kind: String,
+    pub amount: i64,
+    pub fee: i64,
+    pub fiat_code: String,
+    pub fiat_amount: i64,
+    pub payment_method: String,
+    pub premium: i64,
+    pub created_at: i64,
+    pub completed_at: i64,
+}
+
+/// Setting changed by an admin at runtime
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Setting {
+    pub name: String,
+    pub value: String,
+    pub updated_by: String,
+    pub updated_at: i64,
+}
+
+/// Relay Mostro connects to, `latency` is the milliseconds the last check
+/// took to get an answer
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Relay {
+    pub url: String,
+    pub enabled: bool,
+    pub status: String,
+    pub latency: Option<i64>,
+    pub checked_at: Option<i64>,
+    pub updated_by: String,
+    pub created_at: i64,
+}
+
+/// Changes applied reloading the settings, the sections that need a restart
+/// keep their values until then
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReloadReport {
+    pub relays_added: Vec<String>,
+    pub relays_removed: Vec<String>,
+    pub restart_needed: Vec<String>,
+}
+
+/// Rows removed or anonymized purging the data of a user, in a dry run
+/// the rows that would be
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub dry_run: bool,
+    /// Orders, archived ones included, where the pubkey was replaced
+    pub orders: u64,
+    /// Messages about orders and disputes deleted
+    pub messages: u64,
+    /// Ratings received deleted and ratings given anonymized
+    pub ratings: u64,
+    /// Raw, processed and queued events deleted
+    pub events: u64,
+    /// Bonds, payouts, payments and disputes where the pubkey was replaced
+    pub payments: u64,
+    pub trade_indexes: u64,
+    pub user: bool,
+}
+
+/// Pending orders in a fiat currency and kind, market price orders add no sats
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OpenOrdersStats {
+    pub fiat_code: String,
+    pub kind: String,
+    pub orders: i64,
+    pub amount: i64,
+    pub fiat_amount: i64,
+}
+
+/// Trades completed in a fiat currency, with the average seconds from the
+/// take to the end of the trade
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CompletedStats {
+    pub fiat_code: String,
+    pub trades: i64,
+    pub volume: i64,
+    pub fiat_volume: i64,
+    pub fees: i64,
+    pub average_completion_secs: f64,
+}
+
+/// Activity of the last day sent to the admins in the daily summary, the
+/// disputes pending are counted whenever they were opened
+#[derive(Debug, Clone, Default, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Activity {
+    pub new_orders: i64,
+    pub fees: i64,
+    pub pending_disputes: i64,
+    pub failed_payouts: i64,
+}
+
+/// Fee earned by Mostro in an order, `paid_by` is seller, buyer, dispute or
+/// bond and `fiat_amount` its value in `fiat_code` at the trade price
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Fee {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub amount: i64,
+    pub paid_by: String,
+    pub created_at: i64,
+    pub fiat_code: Option<String>,
+    pub fiat_amount: Option<f64>,
+}
+
+/// Fees earned in a `YYYY-MM` month in a fiat currency
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MonthlyFees {
+    pub month: String,
+    pub fiat_code: String,
+    pub orders: i64,
+    pub amount: i64,
+    pub fiat_amount: f64,
+}
+
+/// Event waiting to reach the relays, a DM no relay took when it was sent
+/// or an order event saved with its new status. Status is Pending until it
+/// is delivered or Expired when it was tried too many times
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub event_id: String,
+    pub receiver_pubkey: String,
+    /// Signed event JSON, published as it is
+    pub event: String,
+    pub status: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// dm or order, order events have no receiver
+    pub kind: String,
+}
+
+/// Part of the buyer payout of an order split across several invoices, paid
+/// in order, status is Pending, Paid or Failed
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PayoutPart {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub part: i64,
+    pub payment_request: String,
+    pub amount: i64,
+    pub status: String,
+    pub payment_hash: Option<String>,
+    pub created_at: i64,
+}
+
+/// Details of an order which are not part of the order published by
+/// mostro-core, they travel in the tags of the order event
+#[derive(Debug, Default, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderExtras {
+    pub region: Option<String>,
+    pub description: Option<String>,
+    /// Side of the order fixed by the maker, `sats` or `fiat`
+    pub denomination: Option<String>,
+}
+
+/// Content of the WrongStatus message sent when an action is not allowed in
+/// the current status of the order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrongStatus {
+    pub status: String,
+    pub valid_actions: Vec<String>,
+    pub message: String,
+}
+
+/// Columns of an order its buyer and seller can see
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderDetail {
+    pub id: Uuid,
+    pub kind: String,
+    pub status: String,
+    pub amount: i64,
+    pub fee: i64,
+    pub fiat_code: String,
+    pub fiat_amount: i64,
+    pub payment_method: String,
+    pub premium: i64,
+    pub creator_pubkey: String,
+    pub buyer_pubkey: Option<String>,
+    pub seller_pubkey: Option<String>,
+    pub region: Option<String>,
+    pub description: Option<String>,
+    pub buyer_invoice_sent: bool,
+    pub created_at: i64,
+    pub taken_at: i64,
+    pub invoice_held_at: i64,
+    pub fiat_sent_at: i64,
+}
+
+/// Record of an order sent to its buyer or seller with GetOrder, so clients
+/// can resync after being offline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRecord {
+    #[serde(flatten)]
+    pub order: OrderDetail,
+    pub payouts: Vec<Payout>,
+    pub payout_parts: Vec<PayoutPart>,
+    /// Attempts to pay the user, newest last
+    pub payments: Vec<Payment>,
+    /// Status transitions of the order, oldest first
+    pub events: Vec<OrderEvent>,
+    /// Messages the user exchanged with Mostro about the order, oldest first
+    pub messages: Vec<OrderMessage>,
+}
+
+/// Page of the orders of a user, `total` is the number of orders in all pages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderPage {
+    pub page: i64,
+    pub page_size: i64,
+    pub total: i64,
+    pub orders: Vec<OrderDetail>,
+}
@@ -0,0 +1,40 @@
+use crate::settings::var;
+
+/// Statuses an order never leaves, only these orders are archived
+pub const ARCHIVED_STATUSES: &[&str] = &[
+    "Success",
+    "Canceled",
+    "CanceledByAdmin",
+    "CompletedByAdmin",
+    "CooperativelyCanceled",
+    "Expired",
+];
+
+/// Days an order stays in the orders table once created set on
+/// ARCHIVE_AFTER_DAYS, 0 disables the archival
+pub fn get_archive_after_days() -> i64 {
+    var("ARCHIVE_AFTER_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(0)
+}
+
+/// Orders created before the cutoff are archived, None when disabled
+pub fn archive_cutoff(now: i64, days: i64) -> Option<i64> {
+    match days {
+        0 => None,
+        days => Some(now - days * 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::archive_cutoff;
+
+    #[test]
+    fn test_archive_cutoff() {
+        assert_eq!(None, archive_cutoff(1_000_000, 0));
+        assert_eq!(Some(1_000_000 - 30 * 86_400), archive_cutoff(1_000_000, 30));
+    }
+}
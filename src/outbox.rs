@@ -0,0 +1,159 @@
+use crate::db;
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+/// Longest wait in seconds between two tries of an undelivered DM
+const MAX_RETRY_DELAY: i64 = 3600;
+
+/// Most times an undelivered DM is tried again before it expires,
+/// OUTBOX_MAX_ATTEMPTS with 0 trying forever
+pub fn get_max_attempts() -> i64 {
+    var("OUTBOX_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|attempts| attempts.parse::<i64>().ok())
+        .unwrap_or(100)
+}
+
+/// Whether a DM tried `attempts` times, the last one at `updated_at`, is due
+/// again at `now`, the wait doubles with every try from a minute to an hour
+pub fn is_retry_due(updated_at: i64, attempts: i64, now: i64) -> bool {
+    let delay = 60_i64
+        .checked_shl(attempts.clamp(0, 32) as u32)
+        .unwrap_or(MAX_RETRY_DELAY)
+        .min(MAX_RETRY_DELAY);
+
+    now >= updated_at + delay
+}
+
+/// Whether the client can reach at least one relay
+pub async fn is_connected(client: &Client) -> bool {
+    for relay in client.relays().await.values() {
+        if relay.status().await == RelayStatus::Connected {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Publish a signed DM or keep it in the outbox when no relay takes it, it
+/// is delivered later by the outbox job
+pub async fn send_or_queue(
+    client: &Client,
+    receiver_pubkey: &XOnlyPublicKey,
+    event: Event,
+) -> Result<()> {
+    let error = if is_connected(client).await {
+        match client.send_event(event.clone()).await {
+            Ok(_) => return Ok(()),
+            Err(e) => e.to_string(),
+        }
+    } else {
+        "no relay connected".to_string()
+    };
+    warn!(
+        "DM {} to {receiver_pubkey} not delivered, kept in the outbox: {error}",
+        event.id
+    );
+    crate::metrics::inc("mostro_dm_publish_failures_total", &[]);
+    let pool = db::connect().await?;
+    db::add_outbox_message(
+        &pool,
+        &event.id.to_hex(),
+        &receiver_pubkey.to_string(),
+        &event.as_json(),
+        &error,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Publish an event saved in the outbox, when no relay takes it the outbox
+/// job tries again
+pub async fn publish(
+    pool: &SqlitePool,
+    client: &Client,
+    outbox_id: i64,
+    event: Event,
+) -> Result<()> {
+    let error = if is_connected(client).await {
+        match client.send_event(event.clone()).await {
+            Ok(_) => {
+                db::update_outbox_message(pool, outbox_id, "Delivered", None).await?;
+                return Ok(());
+            }
+            Err(e) => e.to_string(),
+        }
+    } else {
+        "no relay connected".to_string()
+    };
+    warn!(
+        "Event {} not published, kept in the outbox: {error}",
+        event.id
+    );
+    db::update_outbox_message(pool, outbox_id, "Pending", Some(&error)).await?;
+
+    Ok(())
+}
+
+/// Publish the events waiting in the outbox which are due, the ones tried too
+/// many times are given up
+pub async fn deliver_outbox(client: &Client) -> Result<()> {
+    let pool = db::connect().await?;
+    let messages = db::find_pending_outbox_messages(&pool).await?;
+    if messages.is_empty() || !is_connected(client).await {
+        return Ok(());
+    }
+    let now = Timestamp::now().as_i64();
+    let max_attempts = get_max_attempts();
+    for message in messages.into_iter() {
+        if !is_retry_due(message.updated_at, message.attempts, now) {
+            continue;
+        }
+        let event = Event::from_json(&message.event)?;
+        match client.send_event(event).await {
+            Ok(_) => {
+                db::update_outbox_message(&pool, message.id, "Delivered", None).await?;
+                info!(
+                    "{} event {} delivered from the outbox",
+                    message.kind, message.event_id
+                );
+            }
+            Err(e) => {
+                let status = match max_attempts {
+                    0 => "Pending",
+                    max if message.attempts + 1 >= max => "Expired",
+                    _ => "Pending",
+                };
+                db::update_outbox_message(&pool, message.id, status, Some(&e.to_string())).await?;
+                warn!(
+                    "{} event {} still not delivered: {e}",
+                    message.kind, message.event_id
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_retry_due;
+
+    #[test]
+    fn test_is_retry_due() {
+        assert!(!is_retry_due(1000, 0, 1059));
+        assert!(is_retry_due(1000, 0, 1060));
+        // The wait doubles with every try
+        assert!(!is_retry_due(1000, 3, 1000 + 479));
+        assert!(is_retry_due(1000, 3, 1000 + 480));
+        // Up to an hour
+        assert!(is_retry_due(1000, 20, 1000 + 3600));
+        assert!(is_retry_due(1000, 1000, 1000 + 3600));
+    }
+}
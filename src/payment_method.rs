@@ -0,0 +1,169 @@
+use crate::settings::var;
+
+/// Canonical payment method, the names makers use for it and the currencies
+/// it is available for, any currency if empty
+pub struct PaymentMethod {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub currencies: &'static [&'static str],
+}
+
+pub const PAYMENT_METHODS: &[PaymentMethod] = &[
+    PaymentMethod {
+        name: "bank_transfer",
+        aliases: &["bank transfer", "bank", "transfer", "wire", "transferencia"],
+        currencies: &[],
+    },
+    PaymentMethod {
+        name: "cash",
+        aliases: &["cash", "face to face", "in person", "efectivo"],
+        currencies: &[],
+    },
+    PaymentMethod {
+        name: "revolut",
+        aliases: &["revolut"],
+        currencies: &[
+            "EUR", "GBP", "USD", "CHF", "PLN", "RON", "SEK", "NOK", "DKK",
+        ],
+    },
+    PaymentMethod {
+        name: "wise",
+        aliases: &["wise", "transferwise"],
+        currencies: &[],
+    },
+    PaymentMethod {
+        name: "paypal",
+        aliases: &["paypal"],
+        currencies: &[],
+    },
+    PaymentMethod {
+        name: "sepa",
+        aliases: &["sepa", "sepa instant"],
+        currencies: &["EUR"],
+    },
+    PaymentMethod {
+        name: "bizum",
+        aliases: &["bizum"],
+        currencies: &["EUR"],
+    },
+    PaymentMethod {
+        name: "zelle",
+        aliases: &["zelle"],
+        currencies: &["USD"],
+    },
+    PaymentMethod {
+        name: "pix",
+        aliases: &["pix"],
+        currencies: &["BRL"],
+    },
+    PaymentMethod {
+        name: "mercado_pago",
+        aliases: &["mercado pago", "mercadopago"],
+        currencies: &["ARS", "BRL", "CLP", "COP", "MXN", "PEN", "UYU"],
+    },
+    PaymentMethod {
+        name: "pago_movil",
+        aliases: &["pago movil", "pago móvil", "pagomovil"],
+        currencies: &["VES"],
+    },
+    PaymentMethod {
+        name: "sinpe_movil",
+        aliases: &["sinpe", "sinpe movil", "sinpe móvil"],
+        currencies: &["CRC"],
+    },
+    PaymentMethod {
+        name: "nequi",
+        aliases: &["nequi"],
+        currencies: &["COP"],
+    },
+];
+
+/// Canonical name of a payment method written by a maker
+pub fn normalize(method: &str) -> Option<&'static str> {
+    let method = method.trim().to_lowercase().replace(['-', '_'], " ");
+
+    PAYMENT_METHODS
+        .iter()
+        .find(|pm| pm.name.replace('_', " ") == method || pm.aliases.contains(&method.as_str()))
+        .map(|pm| pm.name)
+}
+
+/// Canonical names of the comma-separated payment methods in `block_list`
+pub fn blocked_methods(block_list: &str) -> Vec<&'static str> {
+    block_list.split(',').filter_map(normalize).collect()
+}
+
+/// Payment methods the operator doesn't accept set on BLOCKED_PAYMENT_METHODS,
+/// e.g. methods prone to chargebacks
+pub fn get_blocked_methods() -> Vec<&'static str> {
+    var("BLOCKED_PAYMENT_METHODS")
+        .map(|block_list| blocked_methods(&block_list))
+        .unwrap_or_default()
+}
+
+/// Payment methods accepted for `fiat_code`
+pub fn methods_for_currency(fiat_code: &str) -> Vec<&'static str> {
+    let blocked = get_blocked_methods();
+
+    PAYMENT_METHODS
+        .iter()
+        .filter(|pm| pm.currencies.is_empty() || pm.currencies.contains(&fiat_code))
+        .filter(|pm| !blocked.contains(&pm.name))
+        .map(|pm| pm.name)
+        .collect()
+}
+
+/// Canonical names of the comma-separated `payment_method` of an order, None
+/// if any of them is unknown or not available for `fiat_code`
+pub fn normalize_payment_methods(
+    payment_method: &str,
+    fiat_code: &str,
+) -> Option<Vec<&'static str>> {
+    let available = methods_for_currency(fiat_code);
+    let mut methods = vec![];
+    for method in payment_method.split(',').filter(|m| !m.trim().is_empty()) {
+        let name = normalize(method).filter(|name| available.contains(name))?;
+        if !methods.contains(&name) {
+            methods.push(name);
+        }
+    }
+    if methods.is_empty() {
+        return None;
+    }
+
+    Some(methods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blocked_methods, normalize, normalize_payment_methods};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Some("cash"), normalize("Face to Face"));
+        assert_eq!(Some("bank_transfer"), normalize(" bank-transfer "));
+        assert_eq!(Some("pago_movil"), normalize("Pago Móvil"));
+        assert_eq!(None, normalize("carrier pigeon"));
+    }
+
+    #[test]
+    fn test_blocked_methods() {
+        assert_eq!(
+            vec!["paypal", "revolut"],
+            blocked_methods("PayPal, revolut")
+        );
+        // Unknown methods can't be used anyway
+        assert_eq!(vec!["zelle"], blocked_methods("zelle,chargeback pay,"));
+    }
+
+    #[test]
+    fn test_normalize_payment_methods_by_currency() {
+        assert_eq!(
+            Some(vec!["sepa", "revolut"]),
+            normalize_payment_methods("SEPA, Revolut, sepa", "EUR")
+        );
+        assert_eq!(None, normalize_payment_methods("zelle", "EUR"));
+        assert_eq!(None, normalize_payment_methods("cash, unknown", "USD"));
+        assert_eq!(None, normalize_payment_methods("", "USD"));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::FeeSettings;
+use crate::fee::get_routing_fee_limit;
+use crate::lightning::invoice::is_valid_invoice;
+use crate::models::Payout;
+use crate::util::{get_user_lang, send_cant_do_msg, send_dm};
+use crate::webhook::{self, WebhookEvent};
+use crate::{db, lightning, messages, shutdown};
+use anyhow::Result;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::sync::mpsc::channel;
+use tonic_openssl_lnd::lnrpc::payment::PaymentStatus;
+use tonic_openssl_lnd::lnrpc::{Payment as LndPayment, PaymentFailureReason};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Split `amount` giving `buyer_percentage` percent to the buyer and the
+/// rest to the seller, returns (buyer, seller)
+pub fn split_amount(amount: i64, buyer_percentage: i64) -> (i64, i64) {
+    let buyer_amount = amount * buyer_percentage / 100;
+
+    (buyer_amount, amount - buyer_amount)
+}
+
+/// Invoices sent together in one payment request, separated by commas or
+/// whitespace
+pub fn split_payment_request(payment_request: &str) -> Vec<String> {
+    payment_request
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|invoice| !invoice.is_empty())
+        .map(|invoice| invoice.to_string())
+        .collect()
+}
+
+/// Keep track of an attempt to pay `pubkey`, called with every update of
+/// the payment so the attempt is opened when in flight and closed with its
+/// fee or failure reason
+pub async fn record_payment(
+    pool: &SqlitePool,
+    order_id: Uuid,
+    pubkey: &str,
+    payment: &LndPayment,
+) -> Result<()> {
+    let status = match PaymentStatus::from_i32(payment.status) {
+        Some(PaymentStatus::InFlight) => "InFlight",
+        Some(PaymentStatus::Succeeded) => "Succeeded",
+        Some(PaymentStatus::Failed) => "Failed",
+        _ => return Ok(()),
+    };
+    let hash = &payment.payment_hash;
+    if status == "InFlight" {
+        db::add_payment(pool, order_id, pubkey, hash, payment.value_sat, status).await?;
+        return Ok(());
+    }
+    crate::metrics::inc("mostro_payments_total", &[("status", status)]);
+    if status == "Succeeded" {
+        crate::metrics::add(
+            "mostro_routing_fees_paid_sats_total",
+            &[],
+            payment.fee_sat as f64,
+        );
+    }
+    let failure_reason = match status {
+        "Failed" => PaymentFailureReason::from_i32(payment.failure_reason)
+            .map(|reason| format!("{reason:?}")),
+        _ => None,
+    };
+    let failure_reason = failure_reason.as_deref();
+    if status == "Failed" {
+        webhook::notify(
+            WebhookEvent::PayoutFailed,
+            json!({
+                "order_id": order_id,
+                "pubkey": pubkey,
+                "amount": payment.value_sat,
+                "payment_hash": hash,
+                "failure_reason": failure_reason,
+            }),
+        );
+    }
+    if !db::update_payment(pool, hash, status, payment.fee_sat, failure_reason).await? {
+        // Updates in flight are not always sent, the attempt is opened now
+        db::add_payment(pool, order_id, pubkey, hash, payment.value_sat, "InFlight").await?;
+        db::update_payment(pool, hash, status, payment.fee_sat, failure_reason).await?;
+    }
+
+    Ok(())
+}
+
+/// Save a payout owed to `pubkey` and ask the user for an invoice
+pub async fn request_payout(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    order_id: Uuid,
+    pubkey: &str,
+    amount: i64,
+    text_message: String,
+) -> Result<Payout> {
+    let payout = db::add_payout(pool, order_id, pubkey, amount).await?;
+    info!("Order Id {order_id}: payout of {amount} sats owed to {pubkey}");
+    let message = Message::new(
+        0,
+        Some(order_id),
+        Action::AddInvoice,
+        Some(Content::TextMessage(text_message)),
+    );
+    let message = message.as_json()?;
+    let receiver_pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+    send_dm(client, my_keys, &receiver_pubkey, message).await?;
+
+    Ok(payout)
+}
+
+/// Pay a pending payout to the invoice sent by its receiver
+pub async fn pay_payout(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    fees: &FeeSettings,
+    payout: Payout,
+    payment_request: String,
+    receiver_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    if let Err(e) = is_valid_invoice(&payment_request, Some(payout.amount as u64)) {
+        send_cant_do_msg(
+            client,
+            my_keys,
+            Some(payout.order_id),
+            e.to_string(),
+            receiver_pubkey,
+        )
+        .await?;
+        return Ok(());
+    }
+    // The payout is marked as paid first so it can't be paid twice
+    db::update_payout_status(pool, payout.id, "Paid").await?;
+    let mut ln_client_payment = lightning::LndConnector::new().await;
+    let (tx, mut rx) = channel(100);
+    let amount = payout.amount;
+    let fee_limit = get_routing_fee_limit(fees, amount);
+    let payment_task = {
+        async move {
+            ln_client_payment
+                .send_payment(&payment_request, amount, fee_limit, tx)
+                .await;
+        }
+    };
+    tokio::spawn(payment_task);
+    let receiver_pubkey = *receiver_pubkey;
+    let payment = {
+        async move {
+            let pool = db::connect().await.unwrap();
+            while let Some(msg) = rx.recv().await {
+                if let Err(e) =
+                    record_payment(&pool, payout.order_id, &payout.pubkey, &msg.payment).await
+                {
+                    error!("Order Id {}: error saving payment: {e}", payout.order_id);
+                }
+                match PaymentStatus::from_i32(msg.payment.status) {
+                    Some(PaymentStatus::Succeeded) => {
+                        info!(
+                            "Order Id {}: payout of {amount} sats paid to {}",
+                            payout.order_id, payout.pubkey
+                        );
+                        let client = crate::util::connect_nostr().await.unwrap();
+                        let my_keys = crate::util::get_keys().unwrap();
+                        let message = Message::new(
+                            0,
+                            Some(payout.order_id),
+                            Action::PurchaseCompleted,
+                            Some(Content::TextMessage(messages::payout_paid(
+                                get_user_lang(&pool, &receiver_pubkey).await,
+                                amount,
+                            ))),
+                        );
+                        let message = message.as_json().unwrap();
+                        send_dm(&client, &my_keys, &receiver_pubkey, message)
+                            .await
+                            .unwrap();
+                    }
+                    Some(PaymentStatus::Failed) => {
+                        // The receiver can send another invoice
+                        error!("Order Id {}: payout payment failed", payout.order_id);
+                        db::update_payout_status(&pool, payout.id, "Pending")
+                            .await
+                            .unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+    shutdown::spawn_payment(payment);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_amount() {
+        assert_eq!((70_000, 30_000), split_amount(100_000, 70));
+        assert_eq!((0, 1000), split_amount(1000, 0));
+        // Nothing is lost rounding
+        let (buyer, seller) = split_amount(1001, 33);
+        assert_eq!(1001, buyer + seller);
+    }
+
+    #[test]
+    fn test_split_payment_request() {
+        assert_eq!(vec!["lnbc1"], split_payment_request("lnbc1"));
+        assert_eq!(
+            vec!["lnbc1", "lnbc2", "lnbc3"],
+            split_payment_request("lnbc1, lnbc2\nlnbc3,")
+        );
+        assert!(split_payment_request(" ").is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::error::MostroError;
+use crate::models::Yadio;
+use crate::settings::var;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Longest time a failing provider is skipped
+const MAX_BACKOFF_SECS: u64 = 3600;
+
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    /// Name used on PRICE_PROVIDERS to select the provider
+    fn name(&self) -> &'static str;
+    /// Price of one bitcoin in `fiat_code`
+    async fn btc_price(&self, fiat_code: &str) -> Result<f64>;
+}
+
+pub struct YadioProvider;
+
+#[async_trait]
+impl PriceProvider for YadioProvider {
+    fn name(&self) -> &'static str {
+        "yadio"
+    }
+
+    async fn btc_price(&self, fiat_code: &str) -> Result<f64> {
+        let req_string = format!("https://api.yadio.io/convert/1/BTC/{fiat_code}");
+        let req = reqwest::get(req_string)
+            .await
+            .context("Something went wrong with Yadio API request")?
+            .json::<Yadio>()
+            .await
+            .context("Wrong JSON parse of the Yadio answer, check the currency")?;
+
+        Ok(req.result)
+    }
+}
+
+pub struct CoinGeckoProvider;
+
+#[async_trait]
+impl PriceProvider for CoinGeckoProvider {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn btc_price(&self, fiat_code: &str) -> Result<f64> {
+        let fiat_code = fiat_code.to_lowercase();
+        let req_string = format!(
+            "https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies={fiat_code}"
+        );
+        let req = reqwest::get(req_string)
+            .await
+            .context("Something went wrong with CoinGecko API request")?
+            .json::<serde_json::Value>()
+            .await
+            .context("Wrong JSON parse of the CoinGecko answer")?;
+
+        req["bitcoin"][&fiat_code]
+            .as_f64()
+            .context("CoinGecko doesn't have a price for this currency")
+    }
+}
+
+pub struct BitfinexProvider;
+
+#[async_trait]
+impl PriceProvider for BitfinexProvider {
+    fn name(&self) -> &'static str {
+        "bitfinex"
+    }
+
+    async fn btc_price(&self, fiat_code: &str) -> Result<f64> {
+        let fiat_code = fiat_code.to_uppercase();
+        // Symbols with more than 3 letters are separated by a colon
+        let symbol = if fiat_code.len() == 3 {
+            format!("tBTC{fiat_code}")
+        } else {
+            format!("tBTC:{fiat_code}")
+        };
+        let req_string = format!("https://api-pub.bitfinex.com/v2/ticker/{symbol}");
+        let req = reqwest::get(req_string)
+            .await
+            .context("Something went wrong with Bitfinex API request")?
+            .json::<serde_json::Value>()
+            .await
+            .context("Wrong JSON parse of the Bitfinex answer")?;
+
+        // The last price is the 7th element of the ticker
+        req[6]
+            .as_f64()
+            .context("Bitfinex doesn't have a price for this currency")
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProviderHealth {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+static PROVIDERS_HEALTH: Mutex<BTreeMap<&'static str, ProviderHealth>> =
+    Mutex::new(BTreeMap::new());
+
+fn is_healthy(name: &'static str) -> bool {
+    let health = PROVIDERS_HEALTH.lock().unwrap();
+    match health.get(name).and_then(|h| h.retry_at) {
+        Some(retry_at) => Instant::now() >= retry_at,
+        None => true,
+    }
+}
+
+fn record_success(name: &'static str) {
+    let mut health = PROVIDERS_HEALTH.lock().unwrap();
+    health.remove(name);
+}
+
+/// Failing providers are skipped for a time that doubles on each failure
+fn record_failure(name: &'static str) {
+    let mut health = PROVIDERS_HEALTH.lock().unwrap();
+    let provider = health.entry(name).or_default();
+    provider.failures += 1;
+    let backoff = 60_u64
+        .saturating_mul(2_u64.saturating_pow(provider.failures - 1))
+        .min(MAX_BACKOFF_SECS);
+    provider.retry_at = Some(Instant::now() + Duration::from_secs(backoff));
+}
+
+static PRICES_CACHE: Mutex<BTreeMap<String, (f64, Instant)>> = Mutex::new(BTreeMap::new());
+
+/// Get a cached price only if it is younger than `max_age`
+fn get_cached_price(fiat_code: &str, max_age: Duration) -> Option<f64> {
+    let cache = PRICES_CACHE.lock().unwrap();
+    match cache.get(fiat_code) {
+        Some((price, fetched_at)) if fetched_at.elapsed() <= max_age => Some(*price),
+        _ => None,
+    }
+}
+
+fn set_cached_price(fiat_code: &str, price: f64) {
+    let mut cache = PRICES_CACHE.lock().unwrap();
+    cache.insert(fiat_code.to_string(), (price, Instant::now()));
+}
+
+fn get_duration_var(name: &str, default: u64) -> Duration {
+    let secs = var(name)
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(default);
+
+    Duration::from_secs(secs)
+}
+
+/// Price providers in the order set on PRICE_PROVIDERS
+pub fn get_providers() -> Vec<Box<dyn PriceProvider>> {
+    let providers =
+        var("PRICE_PROVIDERS").unwrap_or_else(|_| "yadio,coingecko,bitfinex".to_string());
+
+    providers
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter_map(|name| -> Option<Box<dyn PriceProvider>> {
+            match name.as_str() {
+                "yadio" => Some(Box::new(YadioProvider)),
+                "coingecko" => Some(Box::new(CoinGeckoProvider)),
+                "bitfinex" => Some(Box::new(BitfinexProvider)),
+                _ => {
+                    warn!("Unknown price provider {name}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Get the bitcoin price, prices younger than PRICE_CACHE_TTL seconds are
+/// taken from the cache, if no provider answers we fall back to a cached
+/// price only if it is younger than PRICE_MAX_AGE seconds
+pub async fn get_btc_price(fiat_code: &str) -> Result<f64> {
+    let fiat_code = fiat_code.to_uppercase();
+    let ttl = get_duration_var("PRICE_CACHE_TTL", 60);
+    if let Some(price) = get_cached_price(&fiat_code, ttl) {
+        return Ok(price);
+    }
+    match fetch_btc_price(&fiat_code).await {
+        Ok(price) => {
+            set_cached_price(&fiat_code, price);
+            Ok(price)
+        }
+        Err(e) => {
+            let max_age = get_duration_var("PRICE_MAX_AGE", 600);
+            match get_cached_price(&fiat_code, max_age) {
+                Some(price) => {
+                    warn!("Using cached price for {fiat_code}: {e}");
+                    Ok(price)
+                }
+                None => {
+                    warn!("{e}");
+                    Err(MostroError::PriceUnavailable.into())
+                }
+            }
+        }
+    }
+}
+
+/// Get the bitcoin price from the first provider that answers, healthy
+/// providers are tried first and failing ones only as last resort
+async fn fetch_btc_price(fiat_code: &str) -> Result<f64> {
+    let (healthy, failing): (Vec<_>, Vec<_>) = get_providers()
+        .into_iter()
+        .partition(|provider| is_healthy(provider.name()));
+
+    for provider in healthy.iter().chain(failing.iter()) {
+        match provider.btc_price(fiat_code).await {
+            Ok(price) if price > 0.0 => {
+                record_success(provider.name());
+                info!("BTC price from {}: {price} {fiat_code}", provider.name());
+                return Ok(price);
+            }
+            Ok(_) => {
+                record_failure(provider.name());
+                warn!("Price provider {} returned a wrong price", provider.name());
+            }
+            Err(e) => {
+                record_failure(provider.name());
+                warn!("Price provider {} failed: {e}", provider.name());
+            }
+        }
+    }
+
+    anyhow::bail!("No price provider could get the price for {fiat_code}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_cached_price, is_healthy, record_failure, record_success, set_cached_price};
+    use std::time::Duration;
+
+    #[test]
+    fn test_stale_cached_price_is_ignored() {
+        set_cached_price("XXX", 1000.0);
+        assert_eq!(
+            Some(1000.0),
+            get_cached_price("XXX", Duration::from_secs(60))
+        );
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(None, get_cached_price("XXX", Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_failing_provider_is_skipped_until_success() {
+        let name = "test_provider";
+        assert!(is_healthy(name));
+        record_failure(name);
+        assert!(!is_healthy(name));
+        record_success(name);
+        assert!(is_healthy(name));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::db;
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::info;
+
+/// Days incoming events are kept as received, RAW_EVENTS_RETENTION_DAYS.
+/// They are not kept by default, the users privacy comes first
+pub fn get_retention_days() -> i64 {
+    var("RAW_EVENTS_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .unwrap_or(0)
+        .max(0)
+}
+
+/// Events received before this unix time are purged, None when they are
+/// not kept
+pub fn retention_cutoff(now: i64, retention_days: i64) -> Option<i64> {
+    match retention_days {
+        0 => None,
+        days => Some(now - days * 86400),
+    }
+}
+
+/// Keep an incoming event still encrypted when the retention is enabled
+pub async fn store_event(pool: &SqlitePool, event: &Event) -> Result<()> {
+    if get_retention_days() == 0 {
+        return Ok(());
+    }
+    db::add_raw_event(
+        pool,
+        &event.id.to_hex(),
+        &event.pubkey.to_bech32()?,
+        &event.as_json(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Delete the events kept longer than RAW_EVENTS_RETENTION_DAYS, all of them
+/// once the retention is disabled
+pub async fn purge_events(pool: &SqlitePool) -> Result<()> {
+    let now = Timestamp::now().as_i64();
+    let before = retention_cutoff(now, get_retention_days()).unwrap_or(i64::MAX);
+    let purged = db::delete_raw_events(pool, before).await?;
+    if purged > 0 {
+        info!("{purged} raw events purged");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::retention_cutoff;
+
+    #[test]
+    fn test_retention_cutoff() {
+        assert_eq!(None, retention_cutoff(10 * 86400, 0));
+        assert_eq!(Some(3 * 86400), retention_cutoff(10 * 86400, 7));
+    }
+}
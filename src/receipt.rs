@@ -0,0 +1,164 @@
+use crate::db;
+use crate::util::send_dm;
+use anyhow::{anyhow, Result};
+use easy_hasher::easy_hasher::raw_sha256;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::nostr::secp256k1::{KeyPair, Message as SecpMessage, Secp256k1};
+use nostr_sdk::prelude::hex::ToHex;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tracing::info;
+use uuid::Uuid;
+
+/// Summary of a completed trade, its parties can present it signed by
+/// Mostro as proof of the trade
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub order_id: Uuid,
+    pub kind: String,
+    pub amount: i64,
+    pub fee: i64,
+    pub fiat_code: String,
+    pub fiat_amount: i64,
+    pub buyer_pubkey: String,
+    pub seller_pubkey: String,
+    /// Hash of the seller hold invoice
+    pub hold_invoice_hash: String,
+    /// Hash of the payment to the buyer
+    pub payment_hash: String,
+    pub created_at: i64,
+    pub taken_at: i64,
+    pub fiat_sent_at: i64,
+    pub completed_at: i64,
+}
+
+/// Receipt with its sha256 hash and the schnorr signature of the hash made
+/// with the Mostro keys, all hex encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    pub receipt: Receipt,
+    pub hash: String,
+    pub signature: String,
+    pub mostro_pubkey: String,
+}
+
+impl Receipt {
+    /// sha256 of the receipt JSON, fields are always serialized in the same order
+    pub fn hash(&self) -> Result<Vec<u8>> {
+        Ok(raw_sha256(serde_json::to_vec(self)?).to_vec())
+    }
+
+    pub fn sign(self, my_keys: &Keys) -> Result<SignedReceipt> {
+        let hash = self.hash()?;
+        let secp = Secp256k1::new();
+        let key_pair = KeyPair::from_secret_key(&secp, &my_keys.secret_key()?);
+        let signature = secp.sign_schnorr(&SecpMessage::from_slice(&hash)?, &key_pair);
+
+        Ok(SignedReceipt {
+            receipt: self,
+            hash: hash.to_hex(),
+            signature: signature.to_string(),
+            mostro_pubkey: my_keys.public_key().to_string(),
+        })
+    }
+}
+
+/// Sign the receipt of a successful order, store its hash with the order and
+/// send it to both parties
+pub async fn send_receipt(
+    pool: &SqlitePool,
+    client: &Client,
+    my_keys: &Keys,
+    order: &Order,
+    payment_hash: &str,
+) -> Result<()> {
+    let detail = db::find_order_detail(pool, order.id)
+        .await?
+        .ok_or_else(|| anyhow!("Order Id {} not found", order.id))?;
+    let (buyer_pubkey, seller_pubkey) = match (detail.buyer_pubkey, detail.seller_pubkey) {
+        (Some(buyer), Some(seller)) => (buyer, seller),
+        _ => return Err(anyhow!("Order Id {} without parties", order.id)),
+    };
+    let receipt = Receipt {
+        order_id: detail.id,
+        kind: detail.kind,
+        amount: detail.amount,
+        fee: detail.fee,
+        fiat_code: detail.fiat_code,
+        fiat_amount: detail.fiat_amount,
+        buyer_pubkey,
+        seller_pubkey,
+        hold_invoice_hash: order.hash.clone().unwrap_or_default(),
+        payment_hash: payment_hash.to_string(),
+        created_at: detail.created_at,
+        taken_at: detail.taken_at,
+        fiat_sent_at: detail.fiat_sent_at,
+        completed_at: Timestamp::now().as_i64(),
+    };
+    let signed = receipt.sign(my_keys)?;
+    db::update_order_receipt_hash(pool, order.id, &signed.hash).await?;
+    info!("Order Id {}: receipt {} signed", order.id, signed.hash);
+
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::PurchaseCompleted,
+        Some(Content::TextMessage(serde_json::to_string(&signed)?)),
+    );
+    let message = message.as_json()?;
+    for pubkey in [&signed.receipt.buyer_pubkey, &signed.receipt.seller_pubkey] {
+        let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+        send_dm(client, my_keys, &pubkey, message.clone()).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Receipt;
+    use nostr_sdk::nostr::secp256k1::schnorr::Signature;
+    use nostr_sdk::nostr::secp256k1::{Message, Secp256k1};
+    use nostr_sdk::prelude::hex::ToHex;
+    use nostr_sdk::prelude::*;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_signed_receipt() {
+        let receipt = Receipt {
+            order_id: Uuid::new_v4(),
+            kind: "Sell".to_string(),
+            amount: 100_000,
+            fee: 300,
+            fiat_code: "EUR".to_string(),
+            fiat_amount: 25,
+            buyer_pubkey: "buyer".to_string(),
+            seller_pubkey: "seller".to_string(),
+            hold_invoice_hash: "hold".to_string(),
+            payment_hash: "payment".to_string(),
+            created_at: 1,
+            taken_at: 2,
+            fiat_sent_at: 3,
+            completed_at: 4,
+        };
+        let hash = receipt.hash().unwrap();
+        assert_eq!(hash, receipt.clone().hash().unwrap());
+        let keys = Keys::generate();
+        let signed = receipt.clone().sign(&keys).unwrap();
+        assert_eq!(hash.to_hex(), signed.hash);
+        // Anyone with the Mostro pubkey can check the signature
+        let signature = Signature::from_str(&signed.signature).unwrap();
+        let pubkey = XOnlyPublicKey::from_str(&signed.mostro_pubkey).unwrap();
+        let message = Message::from_slice(&hash).unwrap();
+        assert!(Secp256k1::new()
+            .verify_schnorr(&signature, &message, &pubkey)
+            .is_ok());
+        // A changed receipt has another hash
+        let mut changed = receipt;
+        changed.amount += 1;
+        assert_ne!(hash, changed.hash().unwrap());
+    }
+}
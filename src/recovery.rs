@@ -0,0 +1,140 @@
+use crate::app::release::do_payment;
+use crate::util::{get_admin_pubkeys, get_user_lang, resubscribe_hold_invoices, send_dm};
+use crate::{db, messages};
+use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::{error, info};
+
+/// What to do on boot with an order left in the middle of a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// The hold invoice subscription reconciles the order with LND
+    FollowHoldInvoice,
+    /// The buyer payout is sent again or followed if it is in flight
+    ResumePayout,
+    /// The order can't go on by itself, the admins are told why
+    Flag(&'static str),
+    Nothing,
+}
+
+/// Recovery of an order from its status, if it has a hold invoice, if the
+/// buyer sent where to be paid and if its payout already failed and waits
+/// in the retry queue
+pub fn recovery_for(
+    status: &str,
+    has_hold_invoice: bool,
+    has_payout_invoice: bool,
+    payout_failed: bool,
+) -> Recovery {
+    match status {
+        "WaitingPayment" | "WaitingBuyerInvoice" | "Active" | "FiatSent" | "Dispute" => {
+            match (status, has_hold_invoice) {
+                (_, true) => Recovery::FollowHoldInvoice,
+                // Not paid yet, the escalation cancels it when its time is over
+                ("WaitingPayment", false) => Recovery::Nothing,
+                _ => Recovery::Flag("no hold invoice"),
+            }
+        }
+        "SettledHoldInvoice" => match (has_payout_invoice, payout_failed) {
+            (_, true) => Recovery::Nothing,
+            (true, false) => Recovery::ResumePayout,
+            (false, false) => Recovery::Flag("no buyer invoice to pay"),
+        },
+        _ => Recovery::Nothing,
+    }
+}
+
+/// Reconcile the orders in progress with LND after a restart, a crash in
+/// the middle of a trade would leave them stuck otherwise
+pub async fn recover_orders(pool: &SqlitePool) -> Result<()> {
+    resubscribe_hold_invoices(pool).await?;
+    for order in db::find_orders_in_progress(pool).await? {
+        let (failed_at, _, _) = db::find_payment_failure(pool, order.id).await?;
+        let has_payout_invoice = order.buyer_invoice.is_some()
+            || !db::find_payout_parts(pool, order.id).await?.is_empty();
+        let recovery = recovery_for(
+            &order.status,
+            order.hash.is_some(),
+            has_payout_invoice,
+            failed_at > 0,
+        );
+        match recovery {
+            Recovery::ResumePayout => {
+                info!("Order Id {}: resuming the buyer payout", order.id);
+                if let Err(e) = do_payment(order.clone()).await {
+                    error!("Order Id {}: payout could not be resumed: {e}", order.id);
+                }
+            }
+            Recovery::Flag(reason) => {
+                error!("Order Id {}: {} with {reason}", order.id, order.status);
+                alert_admins(pool, &order, reason).await?;
+            }
+            Recovery::FollowHoldInvoice | Recovery::Nothing => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn alert_admins(pool: &SqlitePool, order: &Order, reason: &str) -> Result<()> {
+    let client = crate::util::connect_nostr().await?;
+    let keys = crate::util::get_keys()?;
+    for admin_pubkey in get_admin_pubkeys() {
+        let text_message = messages::inconsistent_order_alert(
+            get_user_lang(pool, &admin_pubkey).await,
+            &order.id.to_string(),
+            &order.status,
+            reason,
+        );
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::CantDo,
+            Some(Content::TextMessage(text_message)),
+        );
+        send_dm(&client, &keys, &admin_pubkey, message.as_json()?).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recovery_for, Recovery};
+
+    #[test]
+    fn test_recovery_for() {
+        assert_eq!(
+            Recovery::FollowHoldInvoice,
+            recovery_for("FiatSent", true, false, false)
+        );
+        assert_eq!(
+            Recovery::Nothing,
+            recovery_for("WaitingPayment", false, false, false)
+        );
+        assert!(matches!(
+            recovery_for("Active", false, false, false),
+            Recovery::Flag(_)
+        ));
+        assert_eq!(
+            Recovery::ResumePayout,
+            recovery_for("SettledHoldInvoice", true, true, false)
+        );
+        // Failed payouts are already in the retry queue
+        assert_eq!(
+            Recovery::Nothing,
+            recovery_for("SettledHoldInvoice", true, true, true)
+        );
+        assert!(matches!(
+            recovery_for("SettledHoldInvoice", true, false, false),
+            Recovery::Flag(_)
+        ));
+        assert_eq!(
+            Recovery::Nothing,
+            recovery_for("Success", true, true, false)
+        );
+    }
+}
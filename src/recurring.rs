@@ -0,0 +1,94 @@
+use crate::db;
+use crate::denomination::Denomination;
+use crate::settings::var;
+use crate::util::{publish_order, send_dm, set_order_range};
+use anyhow::Result;
+use mostro_core::order::{NewOrder, Order};
+use mostro_core::{Action, Content, Kind as OrderKind, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use tracing::info;
+
+/// Maximum number of times a recurring order is published again set on
+/// MAX_ORDER_REPEATS
+pub fn get_max_repeats() -> i64 {
+    var("MAX_ORDER_REPEATS")
+        .ok()
+        .and_then(|max| max.parse::<i64>().ok())
+        .unwrap_or(10)
+}
+
+/// Makers flag an order as recurring tagging the message with
+/// `["recurring", <times>]`
+pub fn get_recurring_tag(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "recurring" => {
+            values.first().cloned()
+        }
+        _ => None,
+    })
+}
+
+/// Publish a fresh copy of a recurring order which is over, with a new id and
+/// the same terms
+pub async fn republish_order(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    order: &Order,
+) -> Result<()> {
+    let (remaining, max_amount) = match db::take_recurring_order(pool, order.id).await? {
+        Some((remaining, max_amount)) if remaining > 0 => (remaining, max_amount),
+        _ => return Ok(()),
+    };
+    // Market price orders get a new price when they are taken
+    let extras = db::find_order_extras(pool, order.id).await?;
+    let (amount, fiat_amount) = match extras.denomination.as_deref().map(Denomination::from_str) {
+        Some(Ok(Denomination::Fiat)) => (0, order.fiat_amount),
+        Some(Ok(Denomination::Sats)) => (order.amount, 0),
+        _ => (order.amount, order.fiat_amount),
+    };
+    let mut new_order = NewOrder::new(
+        None,
+        OrderKind::from_str(&order.kind)?,
+        Status::Pending,
+        amount,
+        order.fiat_code.to_owned(),
+        fiat_amount,
+        order.payment_method.to_owned(),
+        order.premium,
+        None,
+        None,
+    );
+    set_order_range(&mut new_order, order.min_amount, max_amount);
+    let new_order = publish_order(
+        pool,
+        client,
+        keys,
+        &new_order,
+        &order.creator_pubkey,
+        &extras,
+    )
+    .await?;
+    let order_id = match new_order.id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    db::add_recurring_order(pool, order_id, remaining - 1, max_amount).await?;
+    info!(
+        "Order Id {}: recurring order published again as {order_id}, {} times left",
+        order.id,
+        remaining - 1
+    );
+    // The maker gets the new order
+    let message = Message::new(
+        0,
+        Some(order_id),
+        Action::Order,
+        Some(Content::Order(new_order)),
+    );
+    let message = message.as_json()?;
+    let creator_pubkey = XOnlyPublicKey::from_bech32(&order.creator_pubkey)?;
+    send_dm(client, keys, &creator_pubkey, message).await
+}
@@ -0,0 +1,119 @@
+use nostr_sdk::prelude::*;
+
+/// ISO 3166-1 alpha-2 country codes
+pub const ISO_3166: &[&str] = &[
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW",
+];
+
+const MAX_CITY_LENGTH: usize = 50;
+
+/// Country and optionally city where a face-to-face trade takes place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub country: String,
+    pub city: Option<String>,
+}
+
+impl Region {
+    /// Parse a region written as `<country>` or `<country>/<city>`, the country
+    /// is an ISO 3166-1 alpha-2 code and the city only letters, spaces,
+    /// dots and dashes
+    pub fn parse(region: &str) -> Option<Self> {
+        let (country, city) = match region.split_once('/') {
+            Some((country, city)) => (country, Some(city.trim())),
+            None => (region, None),
+        };
+        let country = country.trim().to_uppercase();
+        if !ISO_3166.contains(&country.as_str()) {
+            return None;
+        }
+        let city = match city {
+            Some(city) => {
+                let valid = !city.is_empty()
+                    && city.chars().count() <= MAX_CITY_LENGTH
+                    && city
+                        .chars()
+                        .all(|c| c.is_alphabetic() || c == ' ' || c == '.' || c == '-');
+                if !valid {
+                    return None;
+                }
+                Some(city.to_string())
+            }
+            None => None,
+        };
+
+        Some(Self { country, city })
+    }
+
+    /// Tags added to the order event so clients can discover orders by place
+    pub fn tags(&self) -> Vec<Tag> {
+        let mut tags = vec![Tag::Generic(
+            TagKind::Custom("country".to_string()),
+            vec![self.country.clone()],
+        )];
+        if let Some(city) = &self.city {
+            tags.push(Tag::Generic(
+                TagKind::Custom("city".to_string()),
+                vec![city.to_lowercase()],
+            ));
+        }
+
+        tags
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.city {
+            Some(city) => write!(f, "{}/{}", self.country, city),
+            None => write!(f, "{}", self.country),
+        }
+    }
+}
+
+/// Makers set the region of an order tagging the message with
+/// `["region", <region>]`
+pub fn get_region_tag(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "region" => values.first().cloned(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Region;
+
+    #[test]
+    fn test_parse_region() {
+        let region = Region::parse("ve").unwrap();
+        assert_eq!("VE", region.country);
+        assert_eq!(None, region.city);
+        let region = Region::parse("VE/San Cristóbal").unwrap();
+        assert_eq!(Some("San Cristóbal".to_string()), region.city);
+        assert_eq!("VE/San Cristóbal", region.to_string());
+    }
+
+    #[test]
+    fn test_invalid_regions() {
+        assert_eq!(None, Region::parse("XX"));
+        assert_eq!(None, Region::parse("Venezuela"));
+        assert_eq!(None, Region::parse("VE/"));
+        assert_eq!(None, Region::parse("VE/<script>"));
+    }
+}
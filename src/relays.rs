@@ -0,0 +1,139 @@
+use crate::config::NostrSettings;
+use crate::db;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Seconds a relay has to answer a check before it is seen as unreachable
+const CHECK_TIMEOUT: u64 = 10;
+
+static ENABLED_RELAYS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Relays Mostro connects to, the enabled ones of the registry or RELAYS
+/// before the registry is loaded
+pub fn urls() -> Vec<String> {
+    urls_with(&crate::config::settings().nostr)
+}
+
+/// Enabled relays of the registry, the ones of `settings` when there is none
+pub fn urls_with(settings: &NostrSettings) -> Vec<String> {
+    let relays = ENABLED_RELAYS.read().unwrap().clone();
+    if !relays.is_empty() {
+        return relays;
+    }
+    settings.relays.clone()
+}
+
+/// Relay urls must be websocket ones
+pub fn is_valid_url(url: &str) -> bool {
+    match Url::parse(url) {
+        Ok(url) => matches!(url.scheme(), "ws" | "wss") && url.host_str().is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Fill the registry with RELAYS on the first start and keep the enabled
+/// relays for the clients created later
+pub async fn load(pool: &SqlitePool) -> Result<()> {
+    if db::find_relays(pool).await?.is_empty() {
+        for url in urls() {
+            db::add_relay(pool, &url, "RELAYS").await?;
+        }
+        info!("Relay registry created from RELAYS");
+    }
+    reload(pool).await
+}
+
+/// Read the enabled relays again after an admin changed the registry
+pub async fn reload(pool: &SqlitePool) -> Result<()> {
+    let relays = db::find_relays(pool)
+        .await?
+        .into_iter()
+        .filter(|relay| relay.enabled)
+        .map(|relay| relay.url)
+        .collect::<Vec<_>>();
+    if relays.is_empty() {
+        warn!("No relay enabled in the registry, using RELAYS");
+    }
+    *ENABLED_RELAYS.write().unwrap() = relays;
+
+    Ok(())
+}
+
+/// Make the running client follow a relay enabled or disabled in the
+/// registry
+pub async fn follow(client: &Client, my_keys: &Keys, url: &str, enabled: bool) -> Result<()> {
+    if enabled {
+        client.add_relay(url, None).await?;
+        client.connect().await;
+        let subscription = Filter::new()
+            .pubkey(my_keys.public_key())
+            .since(Timestamp::now());
+        client.subscribe(vec![subscription]).await;
+    } else if let Err(e) = client.remove_relay(url).await {
+        warn!("Relay {url} could not be removed from the client: {e}");
+    }
+
+    Ok(())
+}
+
+/// Connect to a single relay and time how long it takes to answer a query,
+/// None when it doesn't answer in CHECK_TIMEOUT seconds
+async fn probe(my_keys: &Keys, url: &str) -> Result<Option<i64>> {
+    let client = Client::new(my_keys);
+    client.add_relay(url, None).await?;
+    client.connect().await;
+    let started = Instant::now();
+    let filter = Filter::new().pubkey(my_keys.public_key()).limit(1);
+    let result = client
+        .get_events_of(vec![filter], Some(Duration::from_secs(CHECK_TIMEOUT)))
+        .await;
+    let elapsed = started.elapsed();
+    client.disconnect().await?;
+
+    Ok(match result {
+        Ok(_) if elapsed < Duration::from_secs(CHECK_TIMEOUT) => Some(elapsed.as_millis() as i64),
+        _ => None,
+    })
+}
+
+/// Check every relay of the registry and save its status and latency
+pub async fn check_relays(pool: &SqlitePool) -> Result<()> {
+    let my_keys = crate::util::get_keys()?;
+    for relay in db::find_relays(pool).await? {
+        if !relay.enabled {
+            db::update_relay_status(pool, &relay.url, "Disabled", None).await?;
+            continue;
+        }
+        let latency = match probe(&my_keys, &relay.url).await {
+            Ok(latency) => latency,
+            Err(e) => {
+                warn!("Relay {} check failed: {e}", relay.url);
+                None
+            }
+        };
+        let status = match latency {
+            Some(_) => "Online",
+            None => "Unreachable",
+        };
+        db::update_relay_status(pool, &relay.url, status, latency).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_url;
+
+    #[test]
+    fn test_is_valid_url() {
+        assert!(is_valid_url("wss://relay.damus.io"));
+        assert!(is_valid_url("ws://localhost:7000"));
+        assert!(!is_valid_url("https://relay.damus.io"));
+        assert!(!is_valid_url("relay.damus.io"));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::settings::var;
+use anyhow::{anyhow, Result};
+use nostr_sdk::prelude::Timestamp;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::time::Duration;
+use tracing::error;
+use uuid::Uuid;
+
+/// Seconds the error tracker has to take a report
+const REPORT_TIMEOUT: u64 = 5;
+
+/// Settings whose values never leave Mostro in a report
+const SECRET_VARS: [&str; 8] = [
+    "NSEC_PRIVKEY",
+    "DB_ENCRYPTION_KEY",
+    "SQLITE_PASSPHRASE",
+    "ADMIN_API_TOKEN",
+    "WEBHOOK_SECRET",
+    "TELEGRAM_BOT_TOKEN",
+    "MATRIX_ACCESS_TOKEN",
+    "SENTRY_DSN",
+];
+
+/// Action being handled when an error or a panic happens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub action: String,
+    pub order_id: Option<Uuid>,
+}
+
+tokio::task_local! {
+    static CONTEXT: Context;
+}
+
+/// Where the reports go, from a DSN like
+/// `https://<public key>@<host>[/<path>]/<project id>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dsn {
+    pub store_url: String,
+    pub public_key: String,
+}
+
+impl Dsn {
+    pub fn parse(dsn: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(dsn)?;
+        let public_key = url.username();
+        let mut path = url
+            .path_segments()
+            .map(|segments| segments.collect::<Vec<_>>())
+            .unwrap_or_default();
+        let project_id = path.pop().filter(|project_id| !project_id.is_empty());
+        let (host, project_id) = match (url.host_str(), project_id) {
+            (Some(host), Some(project_id)) if !public_key.is_empty() => (host, project_id),
+            _ => return Err(anyhow!("The DSN needs a public key, a host and a project")),
+        };
+        let port = url
+            .port()
+            .map(|port| format!(":{port}"))
+            .unwrap_or_default();
+        // Trackers served under a path keep it before the API
+        let path = path
+            .iter()
+            .map(|segment| format!("/{segment}"))
+            .collect::<String>();
+
+        Ok(Self {
+            store_url: format!(
+                "{}://{host}{port}{path}/api/{project_id}/store/",
+                url.scheme()
+            ),
+            public_key: public_key.to_string(),
+        })
+    }
+}
+
+/// DSN of the Sentry compatible error tracker set on SENTRY_DSN, reporting
+/// is off when it isn't set
+fn get_dsn() -> Option<Dsn> {
+    let dsn = var("SENTRY_DSN").ok().filter(|dsn| !dsn.is_empty())?;
+    match Dsn::parse(&dsn) {
+        Ok(dsn) => Some(dsn),
+        Err(e) => {
+            error!("SENTRY_DSN is not valid, errors are not reported: {e}");
+            None
+        }
+    }
+}
+
+/// Whether a word can be a secret: a nostr private key, a lightning invoice
+/// or 32 bytes in hex like a preimage
+fn is_secret_word(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    lower.starts_with("nsec1")
+        || (lower.len() > 20
+            && ["lnbc", "lntb", "lntbs", "lnbcrt"]
+                .iter()
+                .any(|hrp| lower.starts_with(hrp)))
+        || (word.len() == 64 && word.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Text without the secret settings, private keys, invoices and preimages
+pub fn scrub(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets.iter().filter(|secret| secret.len() >= 8) {
+        text = text.replace(secret.as_str(), "[redacted]");
+    }
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        match is_secret_word(&word) {
+            true => scrubbed.push_str("[redacted]"),
+            false => scrubbed.push_str(&word),
+        }
+        word.clear();
+        scrubbed.push(c);
+    }
+    scrubbed.pop();
+
+    scrubbed
+}
+
+fn secrets() -> Vec<String> {
+    SECRET_VARS
+        .iter()
+        .filter_map(|name| var(name).ok())
+        .collect()
+}
+
+/// Event in the format of the store endpoint, `chain` has the error and its
+/// causes, the error first
+fn event(level: &str, kind: &str, chain: &[String], context: Option<&Context>) -> Value {
+    let secrets = secrets();
+    let chain = chain
+        .iter()
+        .map(|message| scrub(message, &secrets))
+        .collect::<Vec<_>>();
+    // The tracker shows the innermost cause first
+    let exceptions = chain
+        .iter()
+        .rev()
+        .map(|message| json!({ "type": kind, "value": message }))
+        .collect::<Vec<_>>();
+    let mut event = json!({
+        "event_id": Uuid::new_v4().simple().to_string(),
+        "timestamp": Timestamp::now().as_i64(),
+        "level": level,
+        "platform": "rust",
+        "logger": "mostro",
+        "release": format!("mostro@{}", env!("CARGO_PKG_VERSION")),
+        "exception": { "values": exceptions },
+    });
+    if let Ok(environment) = var("SENTRY_ENVIRONMENT") {
+        event["environment"] = json!(environment);
+    }
+    if let Some(context) = context {
+        event["tags"] = json!({
+            "action": context.action,
+            "order_id": context.order_id.map(|order_id| order_id.to_string()),
+        });
+    }
+
+    event
+}
+
+async fn send(dsn: Dsn, event: Value) -> Result<()> {
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_client=mostro/{}, sentry_key={}",
+        env!("CARGO_PKG_VERSION"),
+        dsn.public_key
+    );
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(REPORT_TIMEOUT))
+        .build()?
+        .post(&dsn.store_url)
+        .header("X-Sentry-Auth", auth)
+        .json(&event)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Handle an action with its context, a panic while it runs is reported
+/// with it
+pub async fn with_context<F: Future>(context: Context, future: F) -> F::Output {
+    CONTEXT.scope(context, future).await
+}
+
+/// Report an error and its causes in the background
+pub fn capture_error(error: &anyhow::Error, context: &Context) {
+    let dsn = match get_dsn() {
+        Some(dsn) => dsn,
+        None => return,
+    };
+    let chain = error
+        .chain()
+        .map(|cause| cause.to_string())
+        .collect::<Vec<_>>();
+    let event = event("error", "Error", &chain, Some(context));
+    tokio::spawn(async move {
+        if let Err(e) = send(dsn, event).await {
+            error!("Error couldn't be reported: {e}");
+        }
+    });
+}
+
+/// Report the panics, with the context of the action being handled if any,
+/// before the default hook prints them. Nothing is installed without
+/// SENTRY_DSN
+pub fn init() {
+    if get_dsn().is_none() {
+        return;
+    }
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(dsn) = get_dsn() {
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "Box<dyn Any>".to_string()),
+            };
+            let message = match info.location() {
+                Some(location) => format!("{message} at {location}"),
+                None => message,
+            };
+            let context = CONTEXT.try_with(|context| context.clone()).ok();
+            let event = event("fatal", "Panic", &[message], context.as_ref());
+            // The runtime may be going away with the panic, the report is
+            // sent from its own thread before going on
+            let report = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build();
+                if let Ok(runtime) = runtime {
+                    let _ = runtime.block_on(send(dsn, event));
+                }
+            });
+            let _ = report.join();
+        }
+        default_hook(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scrub, Dsn};
+
+    #[test]
+    fn test_scrub() {
+        let secrets = vec!["s3cr3t-token".to_string()];
+        let preimage = "a".repeat(64);
+        assert_eq!(
+            "Bearer [redacted] failed for [redacted] with [redacted]: no route",
+            scrub(
+                &format!("Bearer s3cr3t-token failed for lnbc10u1pjabcdefghijklmnopqrstuv with {preimage}: no route"),
+                &secrets
+            )
+        );
+        assert_eq!(
+            "Order Id 6ceda69d-99e4-4263-84cd-157a673aa307 not found!",
+            scrub(
+                "Order Id 6ceda69d-99e4-4263-84cd-157a673aa307 not found!",
+                &secrets
+            )
+        );
+    }
+
+    #[test]
+    fn test_dsn_parse() {
+        let dsn = Dsn::parse("https://abc123@o42.ingest.sentry.io/7").unwrap();
+        assert_eq!("https://o42.ingest.sentry.io/api/7/store/", dsn.store_url);
+        assert_eq!("abc123", dsn.public_key);
+        let dsn = Dsn::parse("http://key@localhost:9000/errors/3").unwrap();
+        assert_eq!("http://localhost:9000/errors/api/3/store/", dsn.store_url);
+        assert!(Dsn::parse("https://o42.ingest.sentry.io/7").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::settings::var;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the sats of a market price order are rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Down, the buyer never gets more sats than the fiat is worth
+    Floor,
+    /// Up, the seller never gets less fiat than the sats are worth
+    Ceil,
+    /// To the closest value, halves away from zero
+    Nearest,
+    /// To the closest value, halves to the even one
+    Bankers,
+}
+
+impl Rounding {
+    fn round(&self, value: f64) -> f64 {
+        match self {
+            Self::Floor => value.floor(),
+            Self::Ceil => value.ceil(),
+            Self::Nearest => value.round(),
+            Self::Bankers if (value - value.trunc()).abs() == 0.5 => 2.0 * (value / 2.0).round(),
+            Self::Bankers => value.round(),
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Floor => write!(f, "floor"),
+            Self::Ceil => write!(f, "ceil"),
+            Self::Nearest => write!(f, "nearest"),
+            Self::Bankers => write!(f, "bankers"),
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = ();
+
+    fn from_str(rounding: &str) -> Result<Self, Self::Err> {
+        match rounding.trim().to_lowercase().as_str() {
+            "floor" => Ok(Self::Floor),
+            "ceil" => Ok(Self::Ceil),
+            "nearest" => Ok(Self::Nearest),
+            "bankers" => Ok(Self::Bankers),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Rounding of the sats converted from fiat, to a multiple of `granularity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundingPolicy {
+    pub rounding: Rounding,
+    pub granularity: i64,
+}
+
+impl Default for RoundingPolicy {
+    fn default() -> Self {
+        Self {
+            rounding: Rounding::Floor,
+            granularity: 1,
+        }
+    }
+}
+
+impl RoundingPolicy {
+    /// Parse `<rounding>` or `<rounding>,<granularity sats>`
+    pub fn parse(policy: &str) -> Option<Self> {
+        let mut parts = policy.split(',');
+        let rounding = Rounding::from_str(parts.next()?).ok()?;
+        let granularity = match parts.next() {
+            Some(granularity) => granularity.trim().parse::<i64>().ok()?,
+            None => 1,
+        };
+        if parts.next().is_some() || granularity < 1 {
+            return None;
+        }
+
+        Some(Self {
+            rounding,
+            granularity,
+        })
+    }
+
+    /// Policy of a currency set on SATS_ROUNDING_<CODE>, SATS_ROUNDING for
+    /// the rest, sats are floored by default
+    pub fn for_currency(fiat_code: &str) -> Self {
+        var(format!("SATS_ROUNDING_{}", fiat_code.to_uppercase()))
+            .or_else(|_| var("SATS_ROUNDING"))
+            .ok()
+            .and_then(|policy| Self::parse(&policy))
+            .unwrap_or_default()
+    }
+
+    pub fn apply(&self, sats: f64) -> i64 {
+        // Float noise like 35000.00000000001 must not be rounded up
+        let units = (sats / self.granularity as f64 * 1e6).round() / 1e6;
+        let units = self.rounding.round(units);
+
+        units as i64 * self.granularity
+    }
+}
+
+/// Sats of a fiat amount at market price, with every step so the parties
+/// can check the result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    pub btc_price: f64,
+    /// Sats with the premium before rounding
+    pub exact_sats: f64,
+    pub sats: i64,
+    pub policy: RoundingPolicy,
+}
+
+/// Sats of `fiat_amount` at `btc_price` plus `premium` percent, rounded
+/// following `policy`
+pub fn fiat_to_sats(
+    fiat_amount: i64,
+    btc_price: f64,
+    premium: i64,
+    policy: RoundingPolicy,
+) -> Conversion {
+    let sats = fiat_amount as f64 / btc_price * 100_000_000_f64;
+    let exact_sats = sats + premium as f64 / 100_f64 * sats;
+
+    Conversion {
+        btc_price,
+        exact_sats,
+        sats: policy.apply(exact_sats),
+        policy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fiat_to_sats, Rounding, RoundingPolicy};
+
+    #[test]
+    fn test_rounding_policy() {
+        let policy = |rounding, granularity| RoundingPolicy {
+            rounding,
+            granularity,
+        };
+        assert_eq!(1234, policy(Rounding::Floor, 1).apply(1234.9));
+        assert_eq!(1235, policy(Rounding::Ceil, 1).apply(1234.1));
+        assert_eq!(1200, policy(Rounding::Floor, 100).apply(1299.0));
+        assert_eq!(1300, policy(Rounding::Nearest, 100).apply(1250.0));
+        // Halves go to the even multiple
+        assert_eq!(1200, policy(Rounding::Bankers, 100).apply(1250.0));
+        assert_eq!(1400, policy(Rounding::Bankers, 100).apply(1350.0));
+        assert_eq!(
+            Some(policy(Rounding::Ceil, 1000)),
+            RoundingPolicy::parse("ceil, 1000")
+        );
+        assert_eq!(
+            Some(policy(Rounding::Bankers, 1)),
+            RoundingPolicy::parse("bankers")
+        );
+        assert_eq!(None, RoundingPolicy::parse("up,10"));
+        assert_eq!(None, RoundingPolicy::parse("floor,0"));
+    }
+
+    #[test]
+    fn test_fiat_to_sats() {
+        let conversion = fiat_to_sats(30, 30_000.0, 0, RoundingPolicy::default());
+        assert_eq!(100_000, conversion.sats);
+        let policy = RoundingPolicy::parse("ceil,1000").unwrap();
+        let conversion = fiat_to_sats(10, 30_000.0, 5, policy);
+        assert_eq!(35_000, conversion.exact_sats.round() as i64);
+        assert_eq!(35_000, conversion.sats);
+        let conversion = fiat_to_sats(7, 30_000.0, 0, policy);
+        assert_eq!(24_000, conversion.sats);
+    }
+}
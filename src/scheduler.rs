@@ -1,58 +1,886 @@
+use crate::app::dispute::open_dispute;
+use crate::config::{self, NostrSettings, Settings};
+use crate::escalation::{
+    current_timer, get_escalation, Escalation, EscalationAction, Step, ESCALATED_STATUSES,
+};
+use crate::i18n::Lang;
+use crate::lifecycle::Trigger;
+use crate::lightning::LndConnector;
+use crate::state::order_status;
 use anyhow::Result;
+use mostro_core::order::Order;
+use mostro_core::{Action, Content, Message, Status};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
 use std::error::Error;
 use tokio_cron_scheduler::{Job, JobScheduler};
-use tracing::{info, warn, Level};
-use tracing_subscriber::FmtSubscriber;
-
-pub async fn start_scheduler() -> Result<JobScheduler, Box<dyn Error>> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Setting default subscriber failed");
+use tracing::{error, info, warn};
+
+pub async fn start_scheduler(settings: &'static Settings) -> Result<JobScheduler, Box<dyn Error>> {
     info!("Creating scheduler");
     let sched = JobScheduler::new().await?;
-    cron_scheduler(&sched).await?;
+    cron_scheduler(&sched, settings).await?;
 
     Ok(sched)
 }
 
-pub async fn cron_scheduler(sched: &JobScheduler) -> Result<(), anyhow::Error> {
+/// Jobs publishing events use the nostr client and keys of `settings`
+pub async fn cron_scheduler(
+    sched: &JobScheduler,
+    settings: &'static Settings,
+) -> Result<(), anyhow::Error> {
     let job_older_orders_1m = Job::new_async("0 * * * * *", move |uuid, mut l| {
         Box::pin(async move {
-            info!("Create a pool to connect to db");
-            let pool = crate::db::connect().await;
-            // Connect to relays
-            let client = crate::util::connect_nostr().await;
-            let keys = crate::util::get_keys();
+            if let Err(e) = expire_pending_orders(&settings.nostr).await {
+                error!("Pending orders expiration job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for 1 minute is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
 
-            info!("I run async every minute id {:?}", uuid);
+    let job_cooperative_cancel_1m = Job::new_async("0 * * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = expire_cooperative_cancels(&settings.nostr).await {
+                error!("Cooperative cancel timeout job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for cooperative cancel check is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
 
-            let older_orders_list = crate::db::find_order_by_date(pool.as_ref().unwrap()).await;
+    let job_escalations_1m = Job::new_async("0 * * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = escalate_stalled_orders(&settings.nostr).await {
+                error!("Stalled orders escalation job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for stalled orders escalation is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
 
-            for order in older_orders_list.unwrap().iter() {
-                println!("Uid {} - created at {}", order.id, order.created_at);
-                // We update the order id with the new event_id
-                let _res = crate::util::update_order_event(
-                    pool.as_ref().unwrap(),
-                    client.as_ref().unwrap(),
-                    keys.as_ref().unwrap(),
-                    mostro_core::Status::Expired,
-                    order,
-                    None,
-                )
-                .await;
+    let job_payment_retries_1m = Job::new_async("0 * * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = retry_failed_payments().await {
+                error!("Failed payments retry job failed: {e}");
             }
             let next_tick = l.next_tick_for_job(uuid).await;
             match next_tick {
-                Ok(Some(ts)) => info!("Next time for 1 minute is {:?}", ts),
+                Ok(Some(ts)) => info!("Next time for failed payments retry is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
+
+    let job_outbox_1m = Job::new_async("30 * * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = deliver_outbox(&settings.nostr).await {
+                error!("Outbox delivery job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for outbox delivery is {:?}", ts),
                 _ => warn!("Could not get next tick for job"),
             }
         })
     })
     .unwrap();
 
-    // Add the task to the scheduler
+    // Add the tasks to the scheduler
     sched.add(job_older_orders_1m).await?;
+    sched.add(job_cooperative_cancel_1m).await?;
+    sched.add(job_escalations_1m).await?;
+    sched.add(job_payment_retries_1m).await?;
+    sched.add(job_outbox_1m).await?;
+    let job_archive_orders_1h = Job::new_async("0 0 * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = archive_finished_orders().await {
+                error!("Orders archival job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for orders archival is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
+    sched.add(job_archive_orders_1h).await?;
+    let job_prune_events_1h = Job::new_async("0 30 * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = prune_processed_events().await {
+                error!("Processed events pruning job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for processed events pruning is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
+    sched.add(job_prune_events_1h).await?;
+    let job_purge_raw_events_1h = Job::new_async("0 40 * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = purge_raw_events().await {
+                error!("Raw events purge job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for raw events purge is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
+    sched.add(job_purge_raw_events_1h).await?;
+    let job_check_relays_5m = Job::new_async("0 */5 * * * *", move |uuid, mut l| {
+        Box::pin(async move {
+            if let Err(e) = check_relays().await {
+                error!("Relay check job failed: {e}");
+            }
+            let next_tick = l.next_tick_for_job(uuid).await;
+            match next_tick {
+                Ok(Some(ts)) => info!("Next time for relay check is {:?}", ts),
+                _ => warn!("Could not get next tick for job"),
+            }
+        })
+    })
+    .unwrap();
+    sched.add(job_check_relays_5m).await?;
+    // Database maintenance tasks run on their own schedules
+    for task in crate::maintenance::TASKS {
+        let schedule = match task.schedule() {
+            Some(schedule) => schedule,
+            None => continue,
+        };
+        let job_maintenance = Job::new_async(schedule.as_str(), move |uuid, mut l| {
+            Box::pin(async move {
+                if let Err(e) = run_maintenance(task).await {
+                    error!("Database {task} job failed: {e}");
+                }
+                let next_tick = l.next_tick_for_job(uuid).await;
+                match next_tick {
+                    Ok(Some(ts)) => info!("Next time for database {task} is {:?}", ts),
+                    _ => warn!("Could not get next tick for job"),
+                }
+            })
+        })?;
+        sched.add(job_maintenance).await?;
+    }
+    // LND is watched to tell the webhooks when it disconnects
+    if !crate::webhook::get_webhook_urls().is_empty() {
+        let job_watch_lnd_1m = Job::new_async("15 * * * * *", move |uuid, mut l| {
+            Box::pin(async move {
+                crate::health::watch_lnd().await;
+                let next_tick = l.next_tick_for_job(uuid).await;
+                match next_tick {
+                    Ok(Some(ts)) => info!("Next time for LND watch is {:?}", ts),
+                    _ => warn!("Could not get next tick for job"),
+                }
+            })
+        })?;
+        sched.add(job_watch_lnd_1m).await?;
+    }
+    // Hold invoices about to expire and a node out of liquidity are pushed
+    // to Telegram or Matrix
+    if crate::alerts::is_configured(&config::mostro().alerts) {
+        let job_node_alerts_10m = Job::new_async("0 */10 * * * *", move |uuid, mut l| {
+            Box::pin(async move {
+                if let Err(e) = check_node_alerts().await {
+                    error!("Node alerts job failed: {e}");
+                }
+                let next_tick = l.next_tick_for_job(uuid).await;
+                match next_tick {
+                    Ok(Some(ts)) => info!("Next time for node alerts is {:?}", ts),
+                    _ => warn!("Could not get next tick for job"),
+                }
+            })
+        })?;
+        sched.add(job_node_alerts_10m).await?;
+    }
+    // The admins get a summary of the last day unless SUMMARY_SCHEDULE is empty
+    if let Some(schedule) = crate::summary::get_summary_schedule() {
+        let job_summary = Job::new_async(schedule.as_str(), move |uuid, mut l| {
+            Box::pin(async move {
+                if let Err(e) = send_daily_summary().await {
+                    error!("Daily summary job failed: {e}");
+                }
+                let next_tick = l.next_tick_for_job(uuid).await;
+                match next_tick {
+                    Ok(Some(ts)) => info!("Next time for daily summary is {:?}", ts),
+                    _ => warn!("Could not get next tick for job"),
+                }
+            })
+        })?;
+        sched.add(job_summary).await?;
+    }
+    // The database is backed up only when BACKUP_DIR is set
+    if let Some(backup_dir) = crate::backup::get_backup_dir() {
+        let schedule = crate::backup::get_backup_schedule();
+        let job_backups = Job::new_async(schedule.as_str(), move |uuid, mut l| {
+            let backup_dir = backup_dir.clone();
+            Box::pin(async move {
+                if let Err(e) = backup_database(&backup_dir).await {
+                    error!("Database backup job failed: {e}");
+                }
+                let next_tick = l.next_tick_for_job(uuid).await;
+                match next_tick {
+                    Ok(Some(ts)) => info!("Next time for database backup is {:?}", ts),
+                    _ => warn!("Could not get next tick for job"),
+                }
+            })
+        })?;
+        sched.add(job_backups).await?;
+    }
 
     Ok(())
 }
+
+/// Finished orders older than ARCHIVE_AFTER_DAYS leave the orders table,
+/// their history is still read from the archive
+async fn archive_finished_orders() -> Result<()> {
+    let days = crate::order_archive::get_archive_after_days();
+    let cutoff = match crate::order_archive::archive_cutoff(Timestamp::now().as_i64(), days) {
+        Some(cutoff) => cutoff,
+        None => return Ok(()),
+    };
+    let pool = crate::db::connect().await?;
+    let archived =
+        crate::db::archive_orders(&pool, cutoff, crate::order_archive::ARCHIVED_STATUSES).await?;
+    if archived > 0 {
+        info!("{archived} finished orders archived");
+    }
+
+    Ok(())
+}
+
+async fn backup_database(backup_dir: &std::path::Path) -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::backup::backup_database(&pool, backup_dir).await?;
+
+    Ok(())
+}
+
+/// Pending orders published longer than their timeout are expired and their
+/// makers notified
+async fn expire_pending_orders(settings: &NostrSettings) -> Result<()> {
+    let timeout = crate::escalation::get_pending_timeout(&config::mostro().expiration);
+    if timeout == 0 {
+        return Ok(());
+    }
+    let pool = crate::db::connect().await?;
+    let older_orders_list = crate::db::find_order_by_date(&pool, timeout).await?;
+    if older_orders_list.is_empty() {
+        return Ok(());
+    }
+    // Connect to relays
+    let client = crate::util::connect_nostr_with(settings).await?;
+    let keys = crate::util::get_keys_with(settings)?;
+
+    // An order that fails is tried again on the next run, the others go on
+    for order in older_orders_list.iter() {
+        if let Err(e) = expire_pending_order(&pool, &client, &keys, order).await {
+            error!("Order Id {}: couldn't be expired: {e}", order.id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn expire_pending_order(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    order: &Order,
+) -> Result<()> {
+    // The order could be taken since it was listed
+    let (order, version) = match crate::db::find_order_with_version(pool, order.id).await? {
+        Some((order, version)) if order_status(&order) == Ok(Status::Pending) => (order, version),
+        _ => return Ok(()),
+    };
+    let order = &order;
+    info!(
+        "Order Id {} created at {} has expired",
+        order.id, order.created_at
+    );
+    // We publish a new replaceable kind nostr event with the status updated
+    // and update on local database the status and new event id
+    crate::util::update_order_event(
+        pool,
+        client,
+        keys,
+        Status::Expired,
+        order,
+        version,
+        None,
+        Trigger::Timeout,
+    )
+    .await?;
+    // We let the maker know the order is not published anymore
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::Cancel,
+        Some(Content::TextMessage(crate::messages::order_expired(
+            crate::db::get_user_language(pool, &order.creator_pubkey).await?,
+        ))),
+    );
+    let message = message.as_json()?;
+    let creator_pubkey = XOnlyPublicKey::from_bech32(&order.creator_pubkey)?;
+    crate::util::send_dm(client, keys, &creator_pubkey, message).await?;
+
+    Ok(())
+}
+
+/// Buyer payments which failed are tried again every PAYMENT_RETRY_INTERVAL
+/// minutes up to PAYMENT_ATTEMPTS times
+async fn retry_failed_payments() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    let payout = config::mostro().payout.clone();
+    let failed_before = Timestamp::now().as_i64() - payout.payment_retry_interval * 60;
+    let orders =
+        crate::db::find_failed_payments(&pool, failed_before, payout.payment_attempts).await?;
+    for order in orders.into_iter() {
+        let order_id = order.id;
+        // The buyer could have sent a new invoice meanwhile
+        match crate::db::take_failed_payment(&pool, order_id).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                error!("Order Id {order_id}: failed payment couldn't be taken: {e}");
+                continue;
+            }
+        }
+        info!("Order Id {order_id}: trying the buyer payment again");
+        if let Err(e) = crate::app::release::do_payment(order).await {
+            error!("Order Id {order_id}: buyer payment retry failed: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_maintenance(task: crate::maintenance::Task) -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::maintenance::run_task(&pool, task).await
+}
+
+async fn check_node_alerts() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::alerts::check_node(&pool, &config::mostro().alerts).await
+}
+
+async fn send_daily_summary() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::summary::send_summary(&pool).await
+}
+
+/// Incoming events are kept RAW_EVENTS_RETENTION_DAYS days
+async fn purge_raw_events() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::raw_events::purge_events(&pool).await
+}
+
+/// Ids of handled events are kept PROCESSED_EVENTS_MAX_AGE days
+async fn prune_processed_events() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::dedup::prune_processed_events(&pool).await
+}
+
+/// Status and latency of the relays of the registry
+async fn check_relays() -> Result<()> {
+    let pool = crate::db::connect().await?;
+    crate::relays::check_relays(&pool).await
+}
+
+/// DMs no relay took are published again once the relays are back
+async fn deliver_outbox(settings: &NostrSettings) -> Result<()> {
+    let client = crate::util::connect_nostr_with(settings).await?;
+    crate::outbox::deliver_outbox(&client).await
+}
+
+/// Cooperative cancel requests not accepted by the counterparty on time are discarded
+async fn expire_cooperative_cancels(settings: &NostrSettings) -> Result<()> {
+    let timeout = config::mostro().expiration.cooperative_cancel_timeout;
+    let pool = crate::db::connect().await?;
+    let orders = crate::db::find_expired_cancel_requests(&pool, timeout).await?;
+    if orders.is_empty() {
+        return Ok(());
+    }
+    let client = crate::util::connect_nostr_with(settings).await?;
+    let keys = crate::util::get_keys_with(settings)?;
+
+    for order in orders.into_iter() {
+        let order_id = order.id;
+        if let Err(e) = expire_cooperative_cancel(&pool, &client, &keys, order).await {
+            error!("Order Id {order_id}: cooperative cancel request couldn't be expired: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn expire_cooperative_cancel(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    mut order: Order,
+) -> Result<()> {
+    info!("Order Id {}: cooperative cancel request expired", order.id);
+    order.cancel_initiator_pubkey = None;
+    order.buyer_cooperativecancel = false;
+    order.seller_cooperativecancel = false;
+    crate::db::init_cancel_order(pool, &order).await?;
+    // We let both parties know the request is not valid anymore
+    let parties = [order.buyer_pubkey.as_ref(), order.seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        let lang = crate::db::get_user_language(pool, pubkey).await?;
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::CooperativeCancelExpired,
+            Some(Content::TextMessage(
+                crate::messages::cooperative_cancel_expired(lang),
+            )),
+        );
+        let message = message.as_json()?;
+        let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+        crate::util::send_dm(client, keys, &pubkey, message).await?;
+    }
+
+    Ok(())
+}
+
+/// The seller didn't pay the hold invoice on time so we cancel it, orders
+/// taken by the seller go back to the book and orders created by the seller
+/// are canceled
+async fn cancel_unpaid_hold_invoice(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    mut order: Order,
+    version: i64,
+) -> Result<()> {
+    let mut ln_client = LndConnector::new().await;
+    let hash = order.hash.clone();
+    let seller_pubkey = order.seller_pubkey.clone();
+    let buyer_pubkey = order.buyer_pubkey.clone();
+    let seller_is_maker = seller_pubkey.as_ref() == Some(&order.creator_pubkey);
+    // We update the status before canceling the invoice, this way the
+    // invoice subscription knows this cancelation was done on purpose
+    let text_message: fn(Lang) -> String = if seller_is_maker {
+        crate::util::update_order_event(
+            pool,
+            client,
+            keys,
+            Status::Canceled,
+            &order,
+            version,
+            None,
+            Trigger::Timeout,
+        )
+        .await?;
+        info!(
+            "Order Id {}: seller didn't pay the hold invoice, order canceled",
+            order.id
+        );
+        crate::messages::hold_invoice_not_paid_canceled
+    } else {
+        crate::util::reset_market_amounts(pool, &mut order).await?;
+        crate::db::edit_seller_pubkey_order(pool, order.id, None).await?;
+        crate::db::update_order_to_initial_state(
+            pool,
+            order.id,
+            order.amount,
+            order.fee,
+            Trigger::Timeout,
+        )
+        .await?;
+        // Back to Pending, one status change after the version read
+        order.status = Status::Pending.to_string();
+        crate::util::update_order_event(
+            pool,
+            client,
+            keys,
+            Status::Pending,
+            &order,
+            version + 1,
+            None,
+            Trigger::Timeout,
+        )
+        .await?;
+        info!(
+            "Order Id {}: seller didn't pay the hold invoice, republishing order",
+            order.id
+        );
+        crate::messages::hold_invoice_not_paid_republished
+    };
+    if let Some(hash) = hash.as_ref() {
+        ln_client.cancel_hold_invoice(hash).await?;
+    }
+    // We let both parties know what happened
+    let parties = [buyer_pubkey.as_ref(), seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        let lang = crate::db::get_user_language(pool, pubkey).await?;
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::Cancel,
+            Some(Content::TextMessage(text_message(lang))),
+        );
+        let message = message.as_json()?;
+        let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+        crate::util::send_dm(client, keys, &pubkey, message).await?;
+    }
+
+    Ok(())
+}
+
+/// The buyer didn't send the invoice on time once the seller paid the hold
+/// invoice, the hold invoice is canceled and the order canceled if the buyer
+/// is the maker or published again if not
+async fn cancel_order_without_buyer_invoice(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    mut order: Order,
+    version: i64,
+) -> Result<()> {
+    let mut ln_client = LndConnector::new().await;
+    let hash = order.hash.clone();
+    let seller_pubkey = order.seller_pubkey.clone();
+    let buyer_pubkey = order.buyer_pubkey.clone();
+    let buyer_is_maker = buyer_pubkey.as_ref() == Some(&order.creator_pubkey);
+    // The buyer abandoned the trade so its bond goes to the seller
+    if let Some(seller) = seller_pubkey.as_ref() {
+        let fees = config::mostro().fees.clone();
+        crate::bond::forfeit_bond(pool, client, keys, &fees, order.id, seller).await?;
+    }
+    // We update the status before canceling the invoice, this way the
+    // invoice subscription knows this cancelation was done on purpose
+    let text_message: fn(Lang) -> String = if buyer_is_maker {
+        crate::util::update_order_event(
+            pool,
+            client,
+            keys,
+            Status::Canceled,
+            &order,
+            version,
+            None,
+            Trigger::Timeout,
+        )
+        .await?;
+        info!(
+            "Order Id {}: buyer didn't send the invoice, order canceled",
+            order.id
+        );
+        crate::messages::buyer_invoice_not_received_canceled
+    } else {
+        crate::util::reset_market_amounts(pool, &mut order).await?;
+        crate::db::edit_buyer_pubkey_order(pool, order.id, None).await?;
+        crate::db::update_order_to_initial_state(
+            pool,
+            order.id,
+            order.amount,
+            order.fee,
+            Trigger::Timeout,
+        )
+        .await?;
+        // Back to Pending, one status change after the version read
+        order.status = Status::Pending.to_string();
+        crate::util::update_order_event(
+            pool,
+            client,
+            keys,
+            Status::Pending,
+            &order,
+            version + 1,
+            None,
+            Trigger::Timeout,
+        )
+        .await?;
+        info!(
+            "Order Id {}: buyer didn't send the invoice, republishing order",
+            order.id
+        );
+        crate::messages::buyer_invoice_not_received_republished
+    };
+    if let Some(hash) = hash.as_ref() {
+        ln_client.cancel_hold_invoice(hash).await?;
+    }
+    // We let both parties know what happened
+    let parties = [buyer_pubkey.as_ref(), seller_pubkey.as_ref()];
+    for pubkey in parties.into_iter().flatten() {
+        let lang = crate::db::get_user_language(pool, pubkey).await?;
+        let message = Message::new(
+            0,
+            Some(order.id),
+            Action::Cancel,
+            Some(Content::TextMessage(text_message(lang))),
+        );
+        let message = message.as_json()?;
+        let pubkey = XOnlyPublicKey::from_bech32(pubkey)?;
+        crate::util::send_dm(client, keys, &pubkey, message).await?;
+    }
+
+    Ok(())
+}
+
+/// Orders stalled in a status go through the escalation ladder of the
+/// status: reminders to whoever has to act, a final warning and the
+/// automatic action once the window is over
+async fn escalate_stalled_orders(settings: &NostrSettings) -> Result<()> {
+    let pool = crate::db::connect().await?;
+    let now = Timestamp::now().as_i64();
+    let expiration = config::mostro().expiration.clone();
+    let mut due = vec![];
+    for (status, env_var) in ESCALATED_STATUSES.iter() {
+        let escalation = match get_escalation(&expiration, status, env_var) {
+            Some(escalation) => escalation,
+            None => continue,
+        };
+        let started_column = match status {
+            Status::WaitingPayment => Some("taken_at"),
+            Status::WaitingBuyerInvoice => Some("invoice_held_at"),
+            Status::FiatSent => Some("fiat_sent_at"),
+            _ => None,
+        };
+        for timer in crate::db::find_escalation_timers(&pool, status, started_column).await? {
+            let (started_at, reminders, escalated) = current_timer(
+                timer.state_started_at,
+                timer.started_at,
+                timer.reminders,
+                timer.escalated,
+            );
+            // Orders without a start time start counting now
+            if started_at == 0 {
+                if let Err(e) =
+                    crate::db::update_escalation(&pool, timer.id, status, now, 0, false).await
+                {
+                    error!("Order Id {}: escalation timer not started: {e}", timer.id);
+                }
+                continue;
+            }
+            if escalated {
+                continue;
+            }
+            let step = escalation.next_step(now - started_at, reminders);
+            if step != Step::Wait {
+                due.push((status, escalation, timer.id, started_at, reminders, step));
+            }
+        }
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+    let client = crate::util::connect_nostr_with(settings).await?;
+    let keys = crate::util::get_keys_with(settings)?;
+
+    for (status, escalation, order_id, started_at, reminders, step) in due.into_iter() {
+        let (order, version) = match crate::db::find_order_with_version(&pool, order_id).await {
+            Ok(Some(order)) => order,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Order Id {order_id}: couldn't be escalated: {e}");
+                continue;
+            }
+        };
+        let result = escalate_step(
+            &pool,
+            &client,
+            &keys,
+            order,
+            version,
+            status,
+            escalation,
+            started_at,
+            reminders,
+            step,
+            now - started_at,
+        )
+        .await;
+        // An order that fails is tried again on the next run, the others go on
+        if let Err(e) = result {
+            error!("Order Id {order_id}: stalled in {status}, escalation failed: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Send the reminder or the final warning of a stalled order, or run its
+/// automatic action
+#[allow(clippy::too_many_arguments)]
+async fn escalate_step(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    order: Order,
+    version: i64,
+    status: &Status,
+    escalation: Escalation,
+    started_at: i64,
+    reminders: i64,
+    step: Step,
+    elapsed: i64,
+) -> Result<()> {
+    if step == Step::Act {
+        info!(
+            "Order Id {}: stalled in {status} for {} minutes, {:?}",
+            order.id,
+            elapsed / 60,
+            escalation.action
+        );
+        crate::db::update_escalation(pool, order.id, status, started_at, reminders, true).await?;
+        return escalate_order(
+            pool,
+            client,
+            keys,
+            order,
+            version,
+            status,
+            &escalation,
+            elapsed,
+        )
+        .await;
+    }
+    let minutes_left = (escalation.window - elapsed) / 60;
+    for pubkey in stalled_parties(&order, status)? {
+        let lang = crate::util::get_user_lang(pool, &pubkey).await;
+        let text_message = match (status, step) {
+            (Status::FiatSent, Step::FinalWarning) => {
+                crate::messages::release_final_warning(lang, minutes_left)
+            }
+            (Status::FiatSent, _) => crate::messages::release_reminder(
+                lang,
+                reminders + 1,
+                elapsed / 60,
+                (escalation.window > 0).then_some(minutes_left),
+            ),
+            (_, Step::FinalWarning) => {
+                crate::messages::stalled_final_warning(lang, &status.to_string(), minutes_left)
+            }
+            _ => crate::messages::stalled_reminder(
+                lang,
+                reminders + 1,
+                &status.to_string(),
+                elapsed / 60,
+            ),
+        };
+        let message = Message::new(
+            0,
+            Some(order.id),
+            expected_action(status),
+            Some(Content::TextMessage(text_message)),
+        );
+        let message = message.as_json()?;
+        crate::util::send_dm(client, keys, &pubkey, message).await?;
+    }
+    crate::db::update_escalation(pool, order.id, status, started_at, reminders + 1, false).await?;
+
+    Ok(())
+}
+
+/// Action the order is waiting for in a stalled status
+fn expected_action(status: &Status) -> Action {
+    match status {
+        Status::WaitingPayment => Action::PayInvoice,
+        Status::WaitingBuyerInvoice => Action::AddInvoice,
+        Status::Dispute => Action::Dispute,
+        _ => Action::FiatSent,
+    }
+}
+
+/// Who has to act on an order stalled in `status`, the admins on disputes
+fn stalled_parties(order: &Order, status: &Status) -> Result<Vec<XOnlyPublicKey>> {
+    let pubkey = match status {
+        Status::WaitingPayment | Status::FiatSent => order.seller_pubkey.as_ref(),
+        Status::WaitingBuyerInvoice | Status::Active => order.buyer_pubkey.as_ref(),
+        _ => return Ok(crate::util::get_admin_pubkeys()),
+    };
+    match pubkey {
+        Some(pubkey) => Ok(vec![XOnlyPublicKey::from_bech32(pubkey)?]),
+        None => Ok(vec![]),
+    }
+}
+
+/// Run the automatic action of a stalled order
+#[allow(clippy::too_many_arguments)]
+async fn escalate_order(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    order: Order,
+    version: i64,
+    status: &Status,
+    escalation: &Escalation,
+    elapsed: i64,
+) -> Result<()> {
+    match (escalation.action, status) {
+        (EscalationAction::Cancel, Status::WaitingPayment) => {
+            cancel_unpaid_hold_invoice(pool, client, keys, order, version).await
+        }
+        (EscalationAction::Cancel, Status::WaitingBuyerInvoice) => {
+            cancel_order_without_buyer_invoice(pool, client, keys, order, version).await
+        }
+        (EscalationAction::Dispute, _) => {
+            // The dispute is opened on behalf of the party waiting
+            let initiator = match status {
+                Status::FiatSent => order.buyer_pubkey.clone(),
+                _ => order.seller_pubkey.clone(),
+            };
+            match initiator {
+                Some(initiator) => {
+                    open_dispute(
+                        pool,
+                        client,
+                        keys,
+                        &order,
+                        version,
+                        &initiator,
+                        Trigger::Timeout,
+                    )
+                    .await
+                }
+                None => {
+                    error!("Order Id {}: no party to open the dispute", order.id);
+                    Ok(())
+                }
+            }
+        }
+        _ => {
+            for admin_pubkey in crate::util::get_admin_pubkeys() {
+                let lang = crate::util::get_user_lang(pool, &admin_pubkey).await;
+                let text_message = crate::messages::stalled_order_alert(
+                    lang,
+                    &order.id.to_string(),
+                    &status.to_string(),
+                    elapsed / 60,
+                );
+                let message = Message::new(
+                    0,
+                    Some(order.id),
+                    Action::CantDo,
+                    Some(Content::TextMessage(text_message)),
+                );
+                let message = message.as_json()?;
+                crate::util::send_dm(client, keys, &admin_pubkey, message).await?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::db;
+use crate::lifecycle::Trigger;
+use crate::models::OrderExtras;
+use anyhow::{anyhow, Result};
+use mostro_core::order::NewOrder;
+use mostro_core::{Kind as OrderKind, Status};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use tracing::info;
+
+/// Order of the development data, made by one seeded user and taken by the
+/// next one when its status comes after Pending
+pub struct Fixture {
+    pub kind: OrderKind,
+    pub status: Status,
+    pub amount: i64,
+    pub fiat_code: &'static str,
+    pub fiat_amount: i64,
+    pub payment_method: &'static str,
+    pub premium: i64,
+    pub description: Option<&'static str>,
+}
+
+/// Orders in the statuses the admin and client features deal with, market
+/// price orders are only pending as taken ones always have their amount
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::Pending,
+        amount: 100_000,
+        fiat_code: "USD",
+        fiat_amount: 30,
+        payment_method: "bank transfer",
+        premium: 0,
+        description: Some("Fast release, US banks only"),
+    },
+    Fixture {
+        kind: OrderKind::Buy,
+        status: Status::Pending,
+        amount: 0,
+        fiat_code: "EUR",
+        fiat_amount: 50,
+        payment_method: "SEPA",
+        premium: 2,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::WaitingPayment,
+        amount: 50_000,
+        fiat_code: "ARS",
+        fiat_amount: 5_000,
+        payment_method: "mercadopago",
+        premium: 1,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Buy,
+        status: Status::WaitingBuyerInvoice,
+        amount: 20_000,
+        fiat_code: "VES",
+        fiat_amount: 200,
+        payment_method: "pago movil",
+        premium: 0,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::Active,
+        amount: 200_000,
+        fiat_code: "EUR",
+        fiat_amount: 55,
+        payment_method: "SEPA",
+        premium: -1,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Buy,
+        status: Status::FiatSent,
+        amount: 75_000,
+        fiat_code: "USD",
+        fiat_amount: 22,
+        payment_method: "zelle",
+        premium: 0,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::Dispute,
+        amount: 150_000,
+        fiat_code: "USD",
+        fiat_amount: 45,
+        payment_method: "cash app",
+        premium: 0,
+        description: Some("Send the payment with the order id as reference"),
+    },
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::Success,
+        amount: 300_000,
+        fiat_code: "EUR",
+        fiat_amount: 85,
+        payment_method: "revolut",
+        premium: 0,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Buy,
+        status: Status::Success,
+        amount: 40_000,
+        fiat_code: "ARS",
+        fiat_amount: 4_000,
+        payment_method: "bank transfer",
+        premium: 3,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Sell,
+        status: Status::Canceled,
+        amount: 60_000,
+        fiat_code: "USD",
+        fiat_amount: 18,
+        payment_method: "paypal",
+        premium: 0,
+        description: None,
+    },
+    Fixture {
+        kind: OrderKind::Buy,
+        status: Status::Expired,
+        amount: 10_000,
+        fiat_code: "EUR",
+        fiat_amount: 3,
+        payment_method: "SEPA",
+        premium: 0,
+        description: None,
+    },
+];
+
+/// Number of users created, the last one is banned
+pub const USERS: usize = 4;
+
+/// Maker and taker of the fixture at `index` among the seeded users, every
+/// user makes and takes orders of both kinds
+pub fn parties(index: usize) -> (usize, usize) {
+    let maker = index % (USERS - 1);
+
+    (maker, (maker + 1) % (USERS - 1))
+}
+
+/// Fill an empty database with users, orders in every status, a dispute,
+/// ratings and fees so contributors can work without real trades. Nothing
+/// is published and no invoice exists, returns the keys of the users
+pub async fn seed_dev_data(pool: &SqlitePool) -> Result<Vec<Keys>> {
+    if db::count_orders(pool).await? > 0 {
+        return Err(anyhow!(
+            "The database already has orders, the development data only goes in an empty one"
+        ));
+    }
+    let keys = (0..USERS).map(|_| Keys::generate()).collect::<Vec<_>>();
+    let mut pubkeys = Vec::new();
+    for user_keys in &keys {
+        let pubkey = user_keys.public_key().to_bech32()?;
+        db::add_user_if_not_exists(pool, &pubkey).await?;
+        pubkeys.push(pubkey);
+    }
+    db::update_user_banned(pool, &pubkeys[USERS - 1], true).await?;
+
+    for (index, fixture) in FIXTURES.iter().enumerate() {
+        let (maker, taker) = parties(index);
+        let new_order = NewOrder::new(
+            None,
+            fixture.kind,
+            Status::Pending,
+            fixture.amount,
+            fixture.fiat_code.to_string(),
+            fixture.fiat_amount,
+            fixture.payment_method.to_string(),
+            fixture.premium,
+            None,
+            None,
+        );
+        let extras = OrderExtras {
+            region: None,
+            description: fixture
+                .description
+                .map(|description| description.to_string()),
+            denomination: None,
+        };
+        let order = db::add_order(pool, &new_order, "", &pubkeys[maker], &extras).await?;
+        db::add_order_event(pool, order.id, None, "Pending", Trigger::User).await?;
+        if fixture.status == Status::Pending {
+            continue;
+        }
+        let status = fixture.status.to_string();
+        if matches!(fixture.status, Status::Canceled | Status::Expired) {
+            db::update_order_event_id_status(pool, order.id, &fixture.status, "", order.amount)
+                .await?;
+            let trigger = match fixture.status {
+                Status::Expired => Trigger::Timeout,
+                _ => Trigger::User,
+            };
+            db::add_order_event(pool, order.id, Some("Pending"), &status, trigger).await?;
+            continue;
+        }
+        // The order was just created, it is still at its first version
+        db::take_order(pool, order.id, &pubkeys[taker], 0).await?;
+        db::add_order_event(
+            pool,
+            order.id,
+            Some("Pending"),
+            "WaitingPayment",
+            Trigger::User,
+        )
+        .await?;
+        if fixture.status != Status::WaitingPayment {
+            db::update_order_event_id_status(pool, order.id, &fixture.status, "", order.amount)
+                .await?;
+            // Trades past the payment go through Active like real ones
+            let (to_status, then) = match fixture.status {
+                Status::WaitingBuyerInvoice | Status::Active => (status.as_str(), None),
+                _ => ("Active", Some(status.as_str())),
+            };
+            db::add_order_event(
+                pool,
+                order.id,
+                Some("WaitingPayment"),
+                to_status,
+                Trigger::Lightning,
+            )
+            .await?;
+            if let Some(then) = then {
+                db::add_order_event(pool, order.id, Some("Active"), then, Trigger::User).await?;
+            }
+        }
+        let (buyer, seller) = match fixture.kind {
+            OrderKind::Buy => (&pubkeys[maker], &pubkeys[taker]),
+            OrderKind::Sell => (&pubkeys[taker], &pubkeys[maker]),
+        };
+        match fixture.status {
+            Status::FiatSent => {
+                db::update_order_fiat_sent(pool, order.id, Timestamp::now().as_i64()).await?;
+            }
+            Status::Dispute => {
+                db::update_order_fiat_sent(pool, order.id, Timestamp::now().as_i64()).await?;
+                db::update_order_dispute(pool, order.id, true, false).await?;
+                let dispute = db::add_dispute(pool, order.id, buyer).await?;
+                db::add_dispute_message(
+                    pool,
+                    dispute.id,
+                    buyer,
+                    "I sent the fiat two hours ago and the seller doesn't answer",
+                )
+                .await?;
+                db::update_user_disputes(pool, buyer, 1, 0).await?;
+            }
+            Status::Success => {
+                db::update_order_fiat_sent(pool, order.id, Timestamp::now().as_i64()).await?;
+                for pubkey in [buyer, seller] {
+                    db::update_user_trade(pool, pubkey, order.amount).await?;
+                }
+                db::add_fee(pool, order.id, order.amount * 3 / 1000, "buyer").await?;
+                db::add_rating(pool, order.id, buyer, seller, 5).await?;
+                db::add_rating(pool, order.id, seller, buyer, 4).await?;
+            }
+            _ => {}
+        }
+    }
+    info!(
+        "Development data seeded: {} users and {} orders",
+        USERS,
+        FIXTURES.len()
+    );
+
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parties, FIXTURES, USERS};
+
+    #[test]
+    fn test_parties() {
+        for index in 0..FIXTURES.len() {
+            let (maker, taker) = parties(index);
+            assert_ne!(maker, taker);
+            // The banned user has no orders
+            assert!(maker < USERS - 1 && taker < USERS - 1);
+        }
+    }
+}
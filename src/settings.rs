@@ -0,0 +1,147 @@
+use crate::config::MostroSettings;
+use crate::models::ReloadReport;
+use crate::{config, db, relays};
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+
+/// Settings the admins can change while Mostro runs, the rest need a restart
+pub const RUNTIME_SETTINGS: &[&str] = &[
+    "FEE",
+    "MAKER_FEE_SHARE",
+    "DISPUTE_FEE",
+    "MIN_ORDER_AMOUNT",
+    "MAX_ORDER_AMOUNT",
+    "MIN_ORDER_FIAT_AMOUNT",
+    "MAX_ORDER_FIAT_AMOUNT",
+    "MAX_ACTIVE_ORDERS",
+    "NEW_USER_MAX_ORDER_AMOUNT",
+    "NEW_USER_TRADES",
+    "DAILY_VOLUME_LIMIT",
+    "MAINTENANCE_MODE",
+];
+
+/// Value of a setting without a typed field in `config::MostroSettings`,
+/// the environment one or else the one of the settings file
+pub fn var(name: &str) -> Result<String, dotenvy::Error> {
+    match dotenvy::var(name) {
+        Err(dotenvy::Error::EnvVar(std::env::VarError::NotPresent)) => {
+            crate::config::file_var(name)
+                .ok_or(dotenvy::Error::EnvVar(std::env::VarError::NotPresent))
+        }
+        result => result,
+    }
+}
+
+/// Whether `value` is valid for a runtime setting, checked as it is when
+/// the settings are read
+pub fn is_valid_value(name: &str, value: &str) -> bool {
+    RUNTIME_SETTINGS.contains(&name) && MostroSettings::default().set(name, value)
+}
+
+/// Apply the settings saved by the admins over the ones of the environment
+/// and the settings file
+pub async fn load(pool: &sqlx::SqlitePool) -> Result<()> {
+    let mut overrides = BTreeMap::new();
+    for setting in db::find_settings(pool).await? {
+        overrides.insert(setting.name, setting.value);
+    }
+    config::apply_overrides(&overrides);
+
+    Ok(())
+}
+
+/// Apply the settings changed in the `.env` file, the settings file or the
+/// database while Mostro runs. Fees, limits and timeouts take the new values
+/// on the next action and the client follows the new relays, LND isn't
+/// touched so the invoice subscriptions in progress go on
+pub async fn reload(pool: &SqlitePool, client: &Client, my_keys: &Keys) -> Result<ReloadReport> {
+    let new_settings = config::reload()?;
+    load(pool).await?;
+    // Relays new in the settings join the registry, removing one is left to
+    // the admins
+    let registry = db::find_relays(pool).await?;
+    for url in &new_settings.nostr.relays {
+        if !registry.iter().any(|relay| &relay.url == url) {
+            db::add_relay(pool, url, "RELAYS").await?;
+        }
+    }
+    let before = relays::urls();
+    relays::reload(pool).await?;
+    let after = relays::urls();
+    let mut report = ReloadReport {
+        restart_needed: config::restart_needed(config::settings(), &new_settings)
+            .into_iter()
+            .map(|section| section.to_string())
+            .collect(),
+        ..Default::default()
+    };
+    for url in after.iter().filter(|url| !before.contains(url)) {
+        relays::follow(client, my_keys, url, true).await?;
+        report.relays_added.push(url.clone());
+    }
+    for url in before.iter().filter(|url| !after.contains(url)) {
+        relays::follow(client, my_keys, url, false).await?;
+        report.relays_removed.push(url.clone());
+    }
+    if !report.restart_needed.is_empty() {
+        warn!(
+            "Settings changed in {} are applied on restart",
+            report.restart_needed.join(", ")
+        );
+    }
+    info!("Settings reloaded");
+
+    Ok(report)
+}
+
+/// Reload the settings every time the process gets a SIGHUP
+pub fn reload_on_hangup(pool: SqlitePool, client: Client, my_keys: Keys) -> Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if let Err(e) = reload(&pool, &client, &my_keys).await {
+                error!("Settings reload failed: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Save the value of a setting changed by an admin, with None the setting
+/// goes back to the environment value
+pub async fn set(
+    pool: &sqlx::SqlitePool,
+    name: &str,
+    value: Option<&str>,
+    admin_pubkey: &str,
+) -> Result<()> {
+    match value {
+        Some(value) => db::set_setting(pool, name, value, admin_pubkey).await?,
+        None => db::delete_setting(pool, name).await?,
+    }
+
+    load(pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_value;
+
+    #[test]
+    fn test_is_valid_value() {
+        assert!(is_valid_value("FEE", "0.003"));
+        assert!(!is_valid_value("FEE", "3"));
+        assert!(is_valid_value("MAINTENANCE_MODE", "true"));
+        assert!(!is_valid_value("MAINTENANCE_MODE", "yes"));
+        assert!(is_valid_value("MAX_ORDER_AMOUNT", "1000000"));
+        assert!(!is_valid_value("MAX_ORDER_AMOUNT", "-1"));
+        assert!(!is_valid_value("MAKER_FEE_SHARE", "half"));
+        // Only runtime settings can be changed
+        assert!(!is_valid_value("DATABASE_URL", "sqlite://other.db"));
+    }
+}
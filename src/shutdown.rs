@@ -0,0 +1,164 @@
+use crate::settings::var;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static REQUESTED: Notify = Notify::const_new();
+/// Payments being followed, a shutdown waits for them
+static PAYMENTS: AtomicUsize = AtomicUsize::new(0);
+/// Tasks handling the updates of an LND stream
+static FOLLOWERS: AtomicUsize = AtomicUsize::new(0);
+static STREAMS: Mutex<Vec<AbortHandle>> = Mutex::new(Vec::new());
+
+/// Seconds the work in progress has to finish once a shutdown starts,
+/// SHUTDOWN_TIMEOUT
+pub fn get_shutdown_timeout() -> u64 {
+    var("SHUTDOWN_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .unwrap_or(30)
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Payments being followed now
+pub fn payments_in_flight() -> usize {
+    PAYMENTS.load(Ordering::SeqCst)
+}
+
+/// LND streams whose updates are being handled now
+pub fn streams_followed() -> usize {
+    FOLLOWERS.load(Ordering::SeqCst)
+}
+
+/// Stop taking new events and finish the work in progress
+pub fn request() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+    REQUESTED.notify_waiters();
+}
+
+/// Wait until a shutdown is requested
+pub async fn requested() {
+    let notified = REQUESTED.notified();
+    if is_shutting_down() {
+        return;
+    }
+    notified.await;
+}
+
+/// Request a shutdown on SIGTERM or SIGINT, a second signal exits right away
+pub fn listen() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = terminate.recv() => {}
+                _ = interrupt.recv() => {}
+            }
+            if is_shutting_down() {
+                warn!("Second signal received, exiting without waiting");
+                std::process::exit(1);
+            }
+            info!("Shutting down, no new events are taken");
+            request();
+        }
+    });
+
+    Ok(())
+}
+
+/// Keeps a task counted while it runs
+struct Guard(&'static AtomicUsize);
+
+impl Guard {
+    fn new(counter: &'static AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Spawn the task following a payment, a shutdown waits for it
+pub fn spawn_payment(task: impl Future<Output = ()> + Send + 'static) {
+    let guard = Guard::new(&PAYMENTS);
+    tokio::spawn(async move {
+        let _guard = guard;
+        task.await
+    });
+}
+
+/// Spawn the task reading an LND stream and the one handling its updates, a
+/// shutdown closes the stream and lets the updates received be handled
+pub fn spawn_stream(
+    stream: impl Future<Output = ()> + Send + 'static,
+    follower: impl Future<Output = ()> + Send + 'static,
+) {
+    let handle = tokio::spawn(stream);
+    {
+        let mut streams = STREAMS.lock().unwrap();
+        streams.retain(|stream| !stream.is_finished());
+        streams.push(handle.abort_handle());
+    }
+    let guard = Guard::new(&FOLLOWERS);
+    tokio::spawn(async move {
+        let _guard = guard;
+        follower.await
+    });
+}
+
+/// Wait until no task is counted or the deadline is reached, returns the
+/// tasks left
+async fn drain(counter: &AtomicUsize, deadline: Instant) -> usize {
+    loop {
+        let left = counter.load(Ordering::SeqCst);
+        if left == 0 || Instant::now() >= deadline {
+            return left;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Finish the work in progress before exiting: the payments get up to
+/// SHUTDOWN_TIMEOUT seconds, the LND streams are closed once the updates
+/// received are handled, the DMs in the outbox are published and the
+/// relays and the database are closed
+pub async fn finish(pool: &SqlitePool, client: &Client) -> Result<()> {
+    let deadline = Instant::now() + Duration::from_secs(get_shutdown_timeout());
+    let payments = drain(&PAYMENTS, deadline).await;
+    if payments > 0 {
+        warn!("{payments} payments still in flight, they are recovered on the next start");
+    }
+    for stream in STREAMS.lock().unwrap().drain(..) {
+        stream.abort();
+    }
+    let followers = drain(&FOLLOWERS, deadline).await;
+    if followers > 0 {
+        warn!("{followers} LND updates not handled, they are recovered on the next start");
+    }
+    if let Err(e) = crate::outbox::deliver_outbox(client).await {
+        error!("Outbox could not be flushed: {e}");
+    }
+    client.disconnect().await?;
+    pool.close().await;
+    info!("Mostro stopped");
+
+    Ok(())
+}
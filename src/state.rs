@@ -0,0 +1,218 @@
+use crate::error::MostroError;
+use mostro_core::order::Order;
+use mostro_core::{Action, Status};
+use std::str::FromStr;
+
+/// Every action a user can send about an existing order
+pub const ORDER_ACTIONS: &[Action] = &[
+    Action::TakeSell,
+    Action::TakeBuy,
+    Action::Cancel,
+    Action::AddInvoice,
+    Action::FiatSent,
+    Action::Release,
+    Action::Dispute,
+    Action::AdminSettle,
+    Action::AdminCancel,
+    Action::RateUser,
+    Action::EditOrder,
+];
+
+pub fn order_status(order: &Order) -> Result<Status, MostroError> {
+    Status::from_str(&order.status).map_err(|_| MostroError::InvalidTransition)
+}
+
+/// Status an order of `kind` reaches when `action` is processed in `status`,
+/// None if the action is not allowed. A cancel ends in Canceled even when the
+/// handler still waits for the counterparty (cooperative cancel) or returns
+/// the order to the book because the taker canceled
+pub fn next_status(status: &Status, kind: &str, action: &Action) -> Option<Status> {
+    match (status, action) {
+        (Status::Pending, Action::TakeSell) if kind == "Sell" => Some(Status::WaitingPayment),
+        (Status::Pending, Action::TakeBuy) if kind == "Buy" => Some(Status::WaitingPayment),
+        (Status::Pending, Action::Cancel) => Some(Status::Canceled),
+        (Status::Pending, Action::EditOrder) => Some(Status::Pending),
+        (Status::WaitingPayment, Action::Cancel) if kind == "Buy" => Some(Status::Canceled),
+        (Status::WaitingBuyerInvoice, Action::AddInvoice) => Some(Status::Active),
+        (Status::WaitingBuyerInvoice, Action::Cancel) if kind == "Sell" => Some(Status::Canceled),
+        (Status::Active, Action::FiatSent) => Some(Status::FiatSent),
+        (Status::Active | Status::FiatSent, Action::Release) => Some(Status::SettledHoldInvoice),
+        (Status::Active | Status::FiatSent, Action::Dispute) => Some(Status::Dispute),
+        (Status::Active | Status::FiatSent | Status::Dispute, Action::Cancel) => {
+            Some(Status::Canceled)
+        }
+        (Status::Dispute, Action::AdminSettle) => Some(Status::CompletedByAdmin),
+        (Status::Dispute, Action::AdminCancel) => Some(Status::CanceledByAdmin),
+        (Status::Success, Action::RateUser) => Some(Status::Success),
+        _ => None,
+    }
+}
+
+/// Actions an order of `kind` in `status` accepts, sent to users whose
+/// action was rejected so they know what they can do next
+pub fn valid_actions(status: &Status, kind: &str) -> Vec<Action> {
+    ORDER_ACTIONS
+        .iter()
+        .filter(|action| next_status(status, kind, action).is_some())
+        .cloned()
+        .collect()
+}
+
+/// Validate that `action` can be processed for this order, every handler
+/// rejects invalid transitions through here
+pub fn transition(order: &Order, action: &Action) -> Result<Status, MostroError> {
+    let status = order_status(order)?;
+
+    next_status(&status, &order.kind, action).ok_or(MostroError::InvalidTransition)
+}
+
+/// The action was already processed for this order, a repeated message must
+/// get the same answer without running its side effects again
+pub fn is_repeated(order: &Order, action: &Action) -> bool {
+    match order_status(order) {
+        Ok(status) => is_repeated_status(&status, action),
+        Err(_) => false,
+    }
+}
+
+fn is_repeated_status(status: &Status, action: &Action) -> bool {
+    matches!(
+        (status, action),
+        (Status::FiatSent, Action::FiatSent)
+            | (
+                Status::SettledHoldInvoice | Status::Success,
+                Action::Release
+            )
+            | (
+                Status::Canceled | Status::CooperativelyCanceled,
+                Action::Cancel
+            )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_repeated_status, next_status, valid_actions, ORDER_ACTIONS};
+    use mostro_core::{Action, Status};
+
+    fn all_statuses() -> Vec<Status> {
+        vec![
+            Status::Active,
+            Status::Canceled,
+            Status::CanceledByAdmin,
+            Status::CompletedByAdmin,
+            Status::CooperativelyCanceled,
+            Status::Dispute,
+            Status::Expired,
+            Status::FiatSent,
+            Status::SettledHoldInvoice,
+            Status::Pending,
+            Status::Success,
+            Status::WaitingBuyerInvoice,
+            Status::WaitingPayment,
+        ]
+    }
+
+    /// Every allowed (status, kind, action) and the status reached
+    fn allowed() -> Vec<(Status, &'static str, &'static str, Status)> {
+        let mut allowed = vec![
+            (Status::Pending, "Sell", "TakeSell", Status::WaitingPayment),
+            (Status::Pending, "Buy", "TakeBuy", Status::WaitingPayment),
+            (Status::WaitingPayment, "Buy", "Cancel", Status::Canceled),
+            (
+                Status::WaitingBuyerInvoice,
+                "Sell",
+                "Cancel",
+                Status::Canceled,
+            ),
+        ];
+        for kind in ["Sell", "Buy"] {
+            allowed.extend([
+                (Status::Pending, kind, "Cancel", Status::Canceled),
+                (Status::Pending, kind, "EditOrder", Status::Pending),
+                (
+                    Status::WaitingBuyerInvoice,
+                    kind,
+                    "AddInvoice",
+                    Status::Active,
+                ),
+                (Status::Active, kind, "FiatSent", Status::FiatSent),
+                (Status::Active, kind, "Release", Status::SettledHoldInvoice),
+                (
+                    Status::FiatSent,
+                    kind,
+                    "Release",
+                    Status::SettledHoldInvoice,
+                ),
+                (Status::Active, kind, "Dispute", Status::Dispute),
+                (Status::FiatSent, kind, "Dispute", Status::Dispute),
+                (Status::Active, kind, "Cancel", Status::Canceled),
+                (Status::FiatSent, kind, "Cancel", Status::Canceled),
+                (Status::Dispute, kind, "Cancel", Status::Canceled),
+                (
+                    Status::Dispute,
+                    kind,
+                    "AdminSettle",
+                    Status::CompletedByAdmin,
+                ),
+                (
+                    Status::Dispute,
+                    kind,
+                    "AdminCancel",
+                    Status::CanceledByAdmin,
+                ),
+                (Status::Success, kind, "RateUser", Status::Success),
+            ]);
+        }
+        allowed
+    }
+
+    #[test]
+    fn test_repeated_actions() {
+        assert!(is_repeated_status(&Status::FiatSent, &Action::FiatSent));
+        assert!(is_repeated_status(&Status::Success, &Action::Release));
+        assert!(is_repeated_status(&Status::Canceled, &Action::Cancel));
+        assert!(!is_repeated_status(&Status::Active, &Action::FiatSent));
+        assert!(!is_repeated_status(&Status::FiatSent, &Action::Release));
+    }
+
+    #[test]
+    fn test_valid_actions() {
+        let names = |actions: Vec<Action>| {
+            actions
+                .iter()
+                .map(|action| format!("{action:?}"))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            vec!["TakeSell", "Cancel", "EditOrder"],
+            names(valid_actions(&Status::Pending, "Sell"))
+        );
+        assert_eq!(
+            vec!["Cancel", "FiatSent", "Release", "Dispute"],
+            names(valid_actions(&Status::Active, "Buy"))
+        );
+        assert!(valid_actions(&Status::Expired, "Buy").is_empty());
+    }
+
+    #[test]
+    fn test_all_transitions() {
+        let allowed = allowed();
+        for status in all_statuses() {
+            for kind in ["Sell", "Buy"] {
+                for action in ORDER_ACTIONS {
+                    let action_name = format!("{action:?}");
+                    let expected = allowed
+                        .iter()
+                        .find(|(s, k, a, _)| *s == status && *k == kind && *a == action_name)
+                        .map(|(_, _, _, next)| next.clone());
+                    assert_eq!(
+                        expected,
+                        next_status(&status, kind, action),
+                        "{status:?} {kind} {action_name}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::models::{CompletedStats, OpenOrdersStats};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use tokio::sync::OnceCell;
+
+/// Aggregates of the orders of a Mostro, completed trades since `since`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stats {
+    pub since: i64,
+    pub open_orders: Vec<OpenOrdersStats>,
+    pub completed: Vec<CompletedStats>,
+}
+
+static READ_POOL: OnceCell<SqlitePool> = OnceCell::const_new();
+
+/// Read only pool used by the reports, with a single connection the reports
+/// never take the connections of the trades
+async fn read_pool() -> Result<&'static SqlitePool> {
+    let pool = READ_POOL
+        .get_or_try_init(|| async {
+            let options = crate::db::connect_options()?.read_only(true);
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options)
+                .await
+        })
+        .await?;
+
+    Ok(pool)
+}
+
+pub async fn get_stats(since: i64) -> Result<Stats> {
+    let pool = read_pool().await?;
+    let open_orders = sqlx::query_as::<_, OpenOrdersStats>(
+        r#"
+          SELECT fiat_code, kind, COUNT(*) AS orders, SUM(amount) AS amount,
+          SUM(fiat_amount) AS fiat_amount
+          FROM orders
+          WHERE status = 'Pending'
+          GROUP BY fiat_code, kind
+          ORDER BY fiat_code, kind
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    let completed = sqlx::query_as::<_, CompletedStats>(
+        r#"
+          SELECT fiat_code, COUNT(*) AS trades, SUM(amount) AS volume,
+          SUM(fiat_amount) AS fiat_volume, SUM(fee) AS fees,
+          COALESCE(AVG(completed_at - taken_at), 0.0) AS average_completion_secs
+          FROM (
+            SELECT o.fiat_code, o.amount, o.fiat_amount, o.fee, o.taken_at,
+            COALESCE(
+              (SELECT MAX(e.created_at) FROM order_events e
+               WHERE e.order_id = o.id AND e.to_status = 'Success'),
+              NULLIF(o.fiat_sent_at, 0),
+              o.taken_at
+            ) AS completed_at
+            FROM all_orders o
+            WHERE o.status = 'Success'
+          )
+          WHERE completed_at >= ?1
+          GROUP BY fiat_code
+          ORDER BY fiat_code
+        "#,
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Stats {
+        since,
+        open_orders,
+        completed,
+    })
+}
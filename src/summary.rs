@@ -0,0 +1,133 @@
+use crate::lightning::LndConnector;
+use crate::models::{Activity, CompletedStats};
+use crate::settings::var;
+use crate::stats::get_stats;
+use crate::util::{get_admin_pubkeys, get_user_lang, send_dm};
+use crate::{db, messages};
+use anyhow::Result;
+use mostro_core::{Action, Content, Message};
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tracing::info;
+
+/// Seconds of activity a summary covers
+const SUMMARY_PERIOD: i64 = 24 * 60 * 60;
+/// Seconds LND has to answer before the summary goes without its liquidity
+const LND_TIMEOUT: u64 = 10;
+
+/// Channels of the node and the sats it can send and receive through them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liquidity {
+    pub active_channels: u32,
+    pub inactive_channels: u32,
+    pub outbound: u64,
+    pub inbound: u64,
+}
+
+/// What the admins get every day, the liquidity has the reason LND couldn't
+/// be reached instead
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub activity: Activity,
+    pub trades: i64,
+    pub volume: i64,
+    pub liquidity: Result<Liquidity, String>,
+}
+
+/// Cron expression of the daily summary set on SUMMARY_SCHEDULE, every day
+/// at 8:00 by default, an empty value disables it
+pub fn get_summary_schedule() -> Option<String> {
+    match var("SUMMARY_SCHEDULE") {
+        Ok(schedule) if schedule.trim().is_empty() => None,
+        Ok(schedule) => Some(schedule),
+        Err(_) => Some("0 0 8 * * *".to_string()),
+    }
+}
+
+/// Trades and sats traded in every fiat currency
+fn completed_totals(completed: &[CompletedStats]) -> (i64, i64) {
+    completed.iter().fold((0, 0), |(trades, volume), stats| {
+        (trades + stats.trades, volume + stats.volume)
+    })
+}
+
+/// Channels and liquidity of the node now
+pub async fn get_liquidity() -> Result<Liquidity> {
+    let mut lnd = LndConnector::try_connect(&crate::config::settings().lightning).await?;
+    let info = lnd.get_info().await?;
+    let balance = lnd.channel_balance().await?;
+
+    Ok(Liquidity {
+        active_channels: info.num_active_channels,
+        inactive_channels: info.num_inactive_channels,
+        outbound: balance.local_balance.map_or(0, |amount| amount.sat),
+        inbound: balance.remote_balance.map_or(0, |amount| amount.sat),
+    })
+}
+
+/// Activity of the last day and the liquidity of the node now
+pub async fn get_summary(pool: &SqlitePool) -> Result<Summary> {
+    let since = Timestamp::now().as_i64() - SUMMARY_PERIOD;
+    let activity = db::count_activity(pool, since).await?;
+    let (trades, volume) = completed_totals(&get_stats(since).await?.completed);
+    let liquidity =
+        match tokio::time::timeout(Duration::from_secs(LND_TIMEOUT), get_liquidity()).await {
+            Ok(Ok(liquidity)) => Ok(liquidity),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no answer in {LND_TIMEOUT} seconds")),
+        };
+
+    Ok(Summary {
+        activity,
+        trades,
+        volume,
+        liquidity,
+    })
+}
+
+/// Send every admin the summary of the last day in its language
+pub async fn send_summary(pool: &SqlitePool) -> Result<()> {
+    let admins = get_admin_pubkeys();
+    if admins.is_empty() {
+        return Ok(());
+    }
+    let summary = get_summary(pool).await?;
+    let client = crate::util::connect_nostr().await?;
+    let keys = crate::util::get_keys()?;
+    for admin_pubkey in admins {
+        let text_message =
+            messages::daily_summary(get_user_lang(pool, &admin_pubkey).await, &summary);
+        let message = Message::new(
+            0,
+            None,
+            Action::CantDo,
+            Some(Content::TextMessage(text_message)),
+        );
+        send_dm(&client, &keys, &admin_pubkey, message.as_json()?).await?;
+    }
+    info!("Daily summary sent to the admins");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::completed_totals;
+    use crate::models::CompletedStats;
+
+    #[test]
+    fn test_completed_totals() {
+        let stats = |fiat_code: &str, trades, volume| CompletedStats {
+            fiat_code: fiat_code.to_string(),
+            trades,
+            volume,
+            fiat_volume: 0,
+            fees: 0,
+            average_completion_secs: 0.0,
+        };
+        let completed = [stats("ARS", 3, 150_000), stats("USD", 2, 40_000)];
+        assert_eq!((5, 190_000), completed_totals(&completed));
+        assert_eq!((0, 0), completed_totals(&[]));
+    }
+}
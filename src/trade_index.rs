@@ -0,0 +1,51 @@
+use crate::db;
+use anyhow::Result;
+use nostr_sdk::prelude::*;
+use sqlx::SqlitePool;
+
+/// Clients deriving a new trade key per order from a seed send the index of
+/// the key tagging the message with `["trade_index", <index>]`
+pub fn get_trade_index_tag(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Generic(TagKind::Custom(kind), values) if kind == "trade_index" => {
+            values.first().cloned()
+        }
+        _ => None,
+    })
+}
+
+/// Indexes can't be reused, every order must use a higher index than the
+/// last one used by the same user
+pub fn is_next_index(index: i64, last_index: Option<i64>) -> bool {
+    match last_index {
+        Some(last_index) => index > last_index,
+        None => index >= 0,
+    }
+}
+
+/// Index sent by `pubkey` if it is a number higher than its last index
+pub async fn next_trade_index(pool: &SqlitePool, pubkey: &str, index: &str) -> Result<Option<i64>> {
+    let index = match index.trim().parse::<i64>() {
+        Ok(index) => index,
+        Err(_) => return Ok(None),
+    };
+    let last_index = db::find_last_trade_index(pool, pubkey).await?;
+
+    Ok(Some(index).filter(|index| is_next_index(*index, last_index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_next_index;
+
+    #[test]
+    fn test_is_next_index() {
+        assert!(is_next_index(0, None));
+        assert!(!is_next_index(-1, None));
+        assert!(is_next_index(4, Some(3)));
+        // Gaps are allowed, reused or older indexes are not
+        assert!(is_next_index(10, Some(3)));
+        assert!(!is_next_index(3, Some(3)));
+        assert!(!is_next_index(2, Some(3)));
+    }
+}
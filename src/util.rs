@@ -1,56 +1,63 @@
-use crate::models::Yadio;
-use crate::{db, flow};
-use anyhow::{Context, Result};
-use dotenvy::var;
-use log::{error, info};
+use crate::settings::var;
+use crate::{bond, db, fee, flow, payment_method, price, recurring};
+use anyhow::Result;
 use mostro_core::order::{NewOrder, Order, SmallOrder};
 use mostro_core::{Action, Content, Kind as OrderKind, Message, Status};
+use nostr_sdk::nostr::hashes::hex::FromHex;
 use nostr_sdk::prelude::hex::ToHex;
 use nostr_sdk::prelude::*;
 use sqlx::SqlitePool;
 use std::str::FromStr;
 use tonic_openssl_lnd::lnrpc::invoice::InvoiceState;
+use tracing::{error, info};
+use uuid::Uuid;
 
+use crate::config::NostrSettings;
+use crate::denomination::{fiat_from_sats, Denomination};
+use crate::error::MostroError;
+use crate::i18n::Lang;
+use crate::lifecycle::Trigger;
 use crate::lightning;
 use crate::messages;
+use crate::models::{OrderExtras, WrongStatus};
+use crate::region::Region;
+use crate::rounding::{fiat_to_sats, Conversion, RoundingPolicy};
+use crate::state::{order_status, valid_actions};
 use tokio::sync::mpsc::channel;
 
-/// Request market quote from Yadio to have sats amount at actual market price
-pub async fn get_market_quote(fiat_amount: &i64, fiat_code: &str, premium: &i64) -> Result<i64> {
-    // Add here check for market price
-    let req_string = format!(
-        "https://api.yadio.io/convert/{}/{}/BTC",
-        fiat_amount, fiat_code
-    );
-    let req = reqwest::get(req_string)
-        .await
-        .context("Something went wrong with API request, try again!")?
-        .json::<Yadio>()
-        .await
-        .context("Wrong JSON parse of the answer, check the currency")?;
-
-    let mut sats = req.result * 100_000_000_f64;
-
-    // Added premium value to have correct sats value
-    if *premium != 0 {
-        sats += (*premium as f64) / 100_f64 * sats;
-    }
+/// Request market quote from the price providers to have sats amount at actual market price
+/// Sats of a fiat amount at market price plus the premium, rounded with the
+/// policy of the currency
+pub async fn get_market_quote(
+    fiat_amount: &i64,
+    fiat_code: &str,
+    premium: &i64,
+) -> Result<Conversion> {
+    let btc_price = price::get_btc_price(fiat_code).await?;
 
-    Ok(sats as i64)
+    Ok(fiat_to_sats(
+        *fiat_amount,
+        btc_price,
+        *premium,
+        RoundingPolicy::for_currency(fiat_code),
+    ))
 }
 
+/// Save and publish a new order, the published order is returned with its id
 pub async fn publish_order(
     pool: &SqlitePool,
     client: &Client,
     keys: &Keys,
     new_order: &NewOrder,
     initiator_pubkey: &str,
-) -> Result<()> {
-    let order = crate::db::add_order(pool, new_order, "", initiator_pubkey).await?;
+    extras: &OrderExtras,
+) -> Result<NewOrder> {
+    let order = crate::db::add_order(pool, new_order, "", initiator_pubkey, extras).await?;
     let order_id = order.id;
     info!("New order saved Id: {}", order_id);
+    let (min_amount, max_amount) = (order.min_amount, order.max_amount);
     // Now we have the order id, we can create a new event adding this id to the Order object
-    let order = NewOrder::new(
+    let mut order = NewOrder::new(
         Some(order_id),
         OrderKind::from_str(&order.kind).unwrap(),
         Status::Pending,
@@ -62,13 +69,14 @@ pub async fn publish_order(
         None,
         Some(order.created_at),
     );
+    set_order_range(&mut order, min_amount, max_amount);
 
     let order_string = order.as_json().unwrap();
     info!("serialized order: {order_string}");
     // This tag (nip33) allows us to change this event in particular in the future
     let event_kind = 30000;
-    let d_tag = Tag::Generic(TagKind::Custom("d".to_string()), vec![order_id.to_string()]);
-    let event = EventBuilder::new(Kind::Custom(event_kind as u64), &order_string, &[d_tag])
+    let tags = order_event_tags(order_id, &order.payment_method, &order.fiat_code, extras);
+    let event = EventBuilder::new(Kind::Custom(event_kind as u64), &order_string, &tags)
         .to_event(keys)
         .unwrap();
     let event_id = event.id.to_string();
@@ -82,11 +90,148 @@ pub async fn publish_order(
         order.amount,
     )
     .await?;
-    client
-        .send_event(event)
-        .await
-        .map(|_s| ())
-        .map_err(|err| err.into())
+    db::add_order_event(
+        pool,
+        order_id,
+        None,
+        &Status::Pending.to_string(),
+        Trigger::User,
+    )
+    .await?;
+    client.send_event(event).await?;
+
+    Ok(order)
+}
+
+/// Tags of an order event, the d tag (nip33) allows us to replace the event,
+/// a `pm` tag for each canonical payment method and the region tags let
+/// clients filter orders, the description goes in its own tag
+fn order_event_tags(
+    order_id: Uuid,
+    payment_method: &str,
+    fiat_code: &str,
+    extras: &OrderExtras,
+) -> Vec<Tag> {
+    let mut tags = vec![Tag::Generic(
+        TagKind::Custom("d".to_string()),
+        vec![order_id.to_string()],
+    )];
+    let methods = payment_method::normalize_payment_methods(payment_method, fiat_code);
+    for method in methods.unwrap_or_default() {
+        tags.push(Tag::Generic(
+            TagKind::Custom("pm".to_string()),
+            vec![method.to_string()],
+        ));
+    }
+    if let Some(region) = extras.region.as_deref().and_then(Region::parse) {
+        tags.extend(region.tags());
+    }
+    if let Some(description) = &extras.description {
+        tags.push(Tag::Generic(
+            TagKind::Custom("description".to_string()),
+            vec![description.to_string()],
+        ));
+    }
+    if let Some(denomination) = &extras.denomination {
+        tags.push(Tag::Generic(
+            TagKind::Custom("denomination".to_string()),
+            vec![denomination.to_string()],
+        ));
+    }
+
+    tags
+}
+
+/// Range orders are published with the fiat range still available
+pub fn set_order_range(new_order: &mut NewOrder, min_amount: i64, max_amount: i64) {
+    if max_amount > 0 {
+        new_order.min_amount = Some(min_amount);
+        new_order.max_amount = Some(max_amount);
+    }
+}
+
+/// Range orders are not taken as a whole, we create a child order for the
+/// fiat amount requested by the taker and publish again the parent order
+/// with the remaining range. Several takers can take the same range at once,
+/// the child order is returned with its version, None when the amount was
+/// already taken by someone else
+pub async fn take_range_order(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    parent: &mut Order,
+    version: i64,
+    fiat_amount: i64,
+) -> Result<Option<(Order, i64)>> {
+    let (child, max_amount) = match db::add_range_child_order(pool, parent.id, fiat_amount).await? {
+        Some(taken) => taken,
+        None => return Ok(None),
+    };
+    parent.max_amount = max_amount;
+    db::add_order_event(pool, child.id, None, &child.status, Trigger::User).await?;
+    info!(
+        "Range order Id {}: child order Id {} created for {} {}",
+        parent.id, child.id, fiat_amount, parent.fiat_code
+    );
+    // If what is left is less than the minimum the whole range was taken,
+    // the parent is closed without being a trade of its own
+    let status = if parent.max_amount < parent.min_amount {
+        Status::Canceled
+    } else {
+        Status::Pending
+    };
+    update_order_event(
+        pool,
+        client,
+        keys,
+        status,
+        parent,
+        version,
+        None,
+        Trigger::User,
+    )
+    .await?;
+
+    // The child order was just created, it is still at its first version
+    Ok(Some((child, 0)))
+}
+
+/// Give back an order taken when the take can't go on, a child of a range
+/// order is removed and its amount goes back to the range of the parent,
+/// which is published again
+pub async fn release_taken_order(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    order: &Order,
+) -> Result<()> {
+    let (parent, version, exhausted) = match db::release_range_child_order(pool, order.id).await? {
+        Some(released) => released,
+        None => {
+            db::untake_order(pool, order.id).await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "Range order Id {}: child order Id {} released, {} {} back to the range",
+        parent.id, order.id, order.fiat_amount, parent.fiat_code
+    );
+    // A parent closed by this take is opened again
+    if order_status(&parent)? == Status::Pending || exhausted {
+        update_order_event(
+            pool,
+            client,
+            keys,
+            Status::Pending,
+            &parent,
+            version,
+            None,
+            Trigger::System,
+        )
+        .await?;
+    }
+
+    Ok(())
 }
 
 pub async fn send_dm(
@@ -96,33 +241,177 @@ pub async fn send_dm(
     content: String,
 ) -> Result<()> {
     info!("DM content: {content:#?}");
-    let event = EventBuilder::new_encrypted_direct_msg(sender_keys, *receiver_pubkey, content)?
+    let event = EventBuilder::new_encrypted_direct_msg(sender_keys, *receiver_pubkey, &content)?
         .to_event(sender_keys)?;
     info!("Sending event: {event:#?}");
+    let event_id = event.id;
+    crate::outbox::send_or_queue(client, receiver_pubkey, event).await?;
+    // Messages about an order are kept for the solvers
+    crate::archive::queue_sent_message(receiver_pubkey, &content, &event_id);
+
+    Ok(())
+}
+
+/// Send a CantDo message to a user with the reason why the action failed
+pub async fn send_cant_do_msg(
+    client: &Client,
+    sender_keys: &Keys,
+    order_id: Option<Uuid>,
+    text_message: String,
+    receiver_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    // We create a Message
+    let message = Message::new(
+        0,
+        order_id,
+        Action::CantDo,
+        Some(Content::TextMessage(text_message)),
+    );
+    let message = message.as_json()?;
+
+    send_dm(client, sender_keys, receiver_pubkey, message).await
+}
+
+/// Send a WrongStatus message to a user whose action is not allowed in the
+/// current status of the order, with the actions it can send instead
+pub async fn send_wrong_status_msg(
+    pool: &SqlitePool,
+    client: &Client,
+    sender_keys: &Keys,
+    order: &Order,
+    receiver_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    let status = order_status(order)?;
+    let valid_actions: Vec<String> = valid_actions(&status, &order.kind)
+        .iter()
+        .map(|action| format!("{action:?}"))
+        .collect();
+    let wrong_status = WrongStatus {
+        message: messages::wrong_status(
+            get_user_lang(pool, receiver_pubkey).await,
+            &order.status,
+            &valid_actions,
+        ),
+        status: order.status.clone(),
+        valid_actions,
+    };
+    let message = Message::new(
+        0,
+        Some(order.id),
+        Action::WrongStatus,
+        Some(Content::TextMessage(serde_json::to_string(&wrong_status)?)),
+    );
+    let message = message.as_json()?;
+
+    send_dm(client, sender_keys, receiver_pubkey, message).await
+}
+
+/// Publish the reputation of a user as a replaceable event (nip33) with the
+/// pubkey as d tag, the `z` tag tells it apart from the orders
+pub async fn publish_reputation(
+    pool: &SqlitePool,
+    client: &Client,
+    keys: &Keys,
+    pubkey: &str,
+) -> Result<()> {
+    let reputation = db::get_user_reputation(pool, pubkey).await?;
+    let tags = vec![
+        Tag::Generic(TagKind::Custom("d".to_string()), vec![pubkey.to_string()]),
+        Tag::Generic(TagKind::Custom("z".to_string()), vec!["rating".to_string()]),
+    ];
+    let content = serde_json::to_string(&reputation)?;
+    let event = EventBuilder::new(Kind::Custom(30000), &content, &tags).to_event(keys)?;
+    info!("Publishing reputation of {pubkey}: {content}");
     client.send_event(event).await?;
 
     Ok(())
 }
 
+/// Send to a party of the trade the reputation of the counterpart
+pub async fn send_counterpart_reputation(
+    pool: &SqlitePool,
+    client: &Client,
+    sender_keys: &Keys,
+    order_id: Uuid,
+    action: Action,
+    receiver_pubkey: &XOnlyPublicKey,
+    counterpart_pubkey: &XOnlyPublicKey,
+) -> Result<()> {
+    let reputation = db::get_user_reputation(pool, &counterpart_pubkey.to_bech32()?).await?;
+    let message = Message::new(
+        0,
+        Some(order_id),
+        action,
+        Some(Content::TextMessage(messages::counterpart_reputation(
+            get_user_lang(pool, receiver_pubkey).await,
+            &reputation,
+        ))),
+    );
+    let message = message.as_json()?;
+
+    send_dm(client, sender_keys, receiver_pubkey, message).await
+}
+
+/// Language of a user to talk to it, English if it can't be found
+pub async fn get_user_lang(pool: &SqlitePool, pubkey: &XOnlyPublicKey) -> Lang {
+    match pubkey.to_bech32() {
+        Ok(pubkey) => db::get_user_language(pool, &pubkey)
+            .await
+            .unwrap_or_default(),
+        Err(_) => Lang::default(),
+    }
+}
+
 pub fn get_keys() -> Result<Keys> {
-    // nostr private key
-    let nsec1privkey = var("NSEC_PRIVKEY").expect("NSEC_PRIVKEY is not set");
-    let my_keys = Keys::from_sk_str(&nsec1privkey)?;
+    get_keys_with(&crate::config::settings().nostr)
+}
+
+/// Mostro keys from the nostr settings
+pub fn get_keys_with(settings: &NostrSettings) -> Result<Keys> {
+    let my_keys = Keys::from_sk_str(&settings.nsec_privkey)?;
 
     Ok(my_keys)
 }
 
+/// Get the admins/solvers pubkeys set on ADMIN_PUBKEYS
+pub fn get_admin_pubkeys() -> Vec<XOnlyPublicKey> {
+    let admins = var("ADMIN_PUBKEYS").unwrap_or_default();
+
+    admins
+        .split(',')
+        .map(|pk| pk.trim())
+        .filter(|pk| !pk.is_empty())
+        .filter_map(|pk| {
+            XOnlyPublicKey::from_bech32(pk)
+                .ok()
+                .or_else(|| XOnlyPublicKey::from_str(pk).ok())
+        })
+        .collect()
+}
+
+/// Check if a pubkey belongs to one of the admins/solvers
+pub fn is_admin(pubkey: &XOnlyPublicKey) -> bool {
+    get_admin_pubkeys().iter().any(|admin| admin == pubkey)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn update_order_event(
     pool: &SqlitePool,
     client: &Client,
     keys: &Keys,
     status: Status,
     order: &Order,
+    version: i64,
     amount: Option<i64>,
+    trigger: Trigger,
 ) -> Result<()> {
+    // The order is only written if it is still at the status and `version`
+    // the handler read, when another handler changed it meanwhile this update
+    // fails
+    let from_status = &order.status;
     let kind = OrderKind::from_str(&order.kind).unwrap();
     let amount = amount.unwrap_or(order.amount);
-    let publish_order = NewOrder::new(
+    let mut publish_order = NewOrder::new(
         Some(order.id),
         kind,
         status,
@@ -134,38 +423,88 @@ pub async fn update_order_event(
         None,
         Some(order.created_at),
     );
+    set_order_range(&mut publish_order, order.min_amount, order.max_amount);
     let order_string = publish_order.as_json()?;
     // nip33 kind and d tag
     let event_kind = 30000;
-    let d_tag = Tag::Generic(TagKind::Custom("d".to_string()), vec![order.id.to_string()]);
-    let event =
-        EventBuilder::new(Kind::Custom(event_kind), &order_string, &[d_tag]).to_event(keys)?;
-    let event_id = event.id.to_string();
+    let extras = db::find_order_extras(pool, order.id).await?;
+    let tags = order_event_tags(order.id, &order.payment_method, &order.fiat_code, &extras);
+    let event = EventBuilder::new(Kind::Custom(event_kind), &order_string, &tags).to_event(keys)?;
     let status_str = status.to_string();
     info!("Sending replaceable event: {event:#?}");
-    // We update the order id with the new event_id
-    crate::db::update_order_event_id_status(pool, order.id, &status, &event_id, amount).await?;
+    // We update the order id with the new event_id, the event is published
+    // from the outbox
+    let outbox_id = db::swap_order_event_id_status(
+        pool,
+        order.id,
+        from_status,
+        version,
+        &status,
+        &event,
+        amount,
+        trigger,
+    )
+    .await?;
+    let outbox_id = match outbox_id {
+        Some(outbox_id) => outbox_id,
+        None => {
+            error!(
+                "Order Id {}: {status_str} not saved, the order changed meanwhile",
+                order.id
+            );
+            return Err(MostroError::StaleOrder.into());
+        }
+    };
     info!(
         "Order Id: {} updated Nostr new Status: {}",
         order.id, status_str
     );
+    // Recurring orders are published again once they are over
+    if matches!(status, Status::Success | Status::Expired) {
+        recurring::republish_order(pool, client, keys, order).await?;
+    }
+    // Bonds are returned once the trade is over or the taker is gone
+    if matches!(
+        status,
+        Status::Pending
+            | Status::Success
+            | Status::Canceled
+            | Status::CooperativelyCanceled
+            | Status::CanceledByAdmin
+            | Status::CompletedByAdmin
+            | Status::Expired
+    ) {
+        bond::release_bond(pool, order.id).await?;
+    }
 
-    client.send_event(event).await.map(|_s| ()).map_err(|err| {
-        error!("{}", err);
-        err.into()
-    })
+    crate::outbox::publish(pool, client, outbox_id, event).await
+}
+
+/// Ask relays to delete an order event (NIP-09) so clients stop showing the
+/// order, used when the maker pulls an offer from the book
+pub async fn delete_order_event(client: &Client, keys: &Keys, event_id: &str) -> Result<()> {
+    let event_id = EventId::from_hex(event_id)?;
+    let event =
+        EventBuilder::delete(vec![event_id], Some("Order canceled by the maker")).to_event(keys)?;
+    info!("Sending deletion event: {event:#?}");
+    client.send_event(event).await?;
+
+    Ok(())
 }
 
 pub async fn connect_nostr() -> Result<Client> {
-    let my_keys = crate::util::get_keys()?;
+    connect_nostr_with(&crate::config::settings().nostr).await
+}
+
+/// Nostr client of Mostro connected to the relays of the registry, those of
+/// the settings before it is loaded
+pub async fn connect_nostr_with(settings: &NostrSettings) -> Result<Client> {
+    let my_keys = get_keys_with(settings)?;
 
     // Create new client
     let client = Client::new(&my_keys);
-    let relays = var("RELAYS").expect("RELAYS is not set");
-    let relays = relays.split(',').collect::<Vec<&str>>();
-
     // Add relays
-    for r in relays.into_iter() {
+    for r in crate::relays::urls_with(settings).into_iter() {
         client.add_relay(r, None).await?;
     }
 
@@ -175,6 +514,7 @@ pub async fn connect_nostr() -> Result<Client> {
     Ok(client)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn show_hold_invoice(
     pool: &SqlitePool,
     client: &Client,
@@ -183,20 +523,30 @@ pub async fn show_hold_invoice(
     buyer_pubkey: &XOnlyPublicKey,
     seller_pubkey: &XOnlyPublicKey,
     order: &Order,
+    version: i64,
 ) -> anyhow::Result<()> {
     let mut ln_client = lightning::LndConnector::new().await;
+    // Mostro fee is charged on the sats amount of the order
+    let settings = crate::config::mostro();
+    let order_fee = fee::get_fee(&settings.fees, order.amount);
+    let trade_fees = fee::get_trade_fees(&settings.fees, order, order_fee);
+    let escrow_amount = fee::seller_escrow_amount(order, &trade_fees);
+    let payout_amount = fee::buyer_payout_amount(order, &trade_fees);
+    let seller_lang = get_user_lang(pool, seller_pubkey).await;
+    let description = messages::hold_invoice_description(
+        seller_lang,
+        my_keys.public_key(),
+        order,
+        trade_fees.seller_fee,
+        escrow_amount,
+    )?;
     // Now we generate the hold invoice that seller should pay
     let (invoice_response, preimage, hash) = ln_client
-        .create_hold_invoice(
-            &messages::hold_invoice_description(
-                my_keys.public_key(),
-                &order.id.to_string(),
-                &order.fiat_code,
-                &order.fiat_amount.to_string(),
-            )?,
-            order.amount,
-        )
+        .create_hold_invoice(&description, escrow_amount)
         .await?;
+    // The split is kept with the order, the payout doesn't change if
+    // MAKER_FEE_SHARE does before the trade is over
+    db::update_order_fee(pool, order.id, order_fee, &trade_fees).await?;
     if let Some(invoice) = payment_request {
         db::edit_buyer_invoice_order(pool, order.id, &invoice).await?;
     };
@@ -212,7 +562,17 @@ pub async fn show_hold_invoice(
     )
     .await?;
     // We need to publish a new event with the new status
-    update_order_event(pool, client, my_keys, Status::WaitingPayment, order, None).await?;
+    update_order_event(
+        pool,
+        client,
+        my_keys,
+        Status::WaitingPayment,
+        order,
+        version,
+        None,
+        Trigger::User,
+    )
+    .await?;
     let new_order = order.as_new_order();
     // We create a Message to send the hold invoice to seller
     let message = Message::new(
@@ -229,11 +589,30 @@ pub async fn show_hold_invoice(
     // We send the hold invoice to the seller
     send_dm(client, my_keys, seller_pubkey, message).await?;
 
-    let message = Message::new(0, Some(order.id), Action::WaitingSellerToPay, None);
+    // The buyer is told how much will receive after the fee
+    let fee_breakdown = if trade_fees.buyer_fee > 0 {
+        Some(Content::TextMessage(messages::buyer_fee_breakdown(
+            get_user_lang(pool, buyer_pubkey).await,
+            order.amount,
+            trade_fees.buyer_fee,
+            payout_amount,
+        )))
+    } else {
+        None
+    };
+    let message = Message::new(0, Some(order.id), Action::WaitingSellerToPay, fee_breakdown);
     let message = message.as_json()?;
 
     // We send a message to buyer to know that seller was requested to pay the invoice
     send_dm(client, my_keys, buyer_pubkey, message).await?;
+    subscribe_hold_invoice(hash).await;
+
+    Ok(())
+}
+
+/// Follow the state of a hold invoice, the order moves on when the seller
+/// pays it, when it is settled and when it is canceled inside or outside Mostro
+pub async fn subscribe_hold_invoice(hash: Vec<u8>) {
     let mut ln_client_invoices = lightning::LndConnector::new().await;
     let (tx, mut rx) = channel(100);
 
@@ -242,7 +621,6 @@ pub async fn show_hold_invoice(
             ln_client_invoices.subscribe_invoice(hash, tx).await;
         }
     };
-    tokio::spawn(invoice_task);
     let subs = {
         async move {
             // Receiving msgs from the invoice subscription.
@@ -251,14 +629,14 @@ pub async fn show_hold_invoice(
                 // If this invoice was paid by the seller
                 if msg.state == InvoiceState::Accepted {
                     flow::hold_invoice_paid(&hash).await;
-                    println!("Invoice with hash {hash} accepted!");
+                    info!("Invoice with hash {hash} accepted!");
                 } else if msg.state == InvoiceState::Settled {
                     // If the payment was released by the seller
-                    println!("Invoice with hash {hash} settled!");
+                    info!("Invoice with hash {hash} settled!");
                     flow::hold_invoice_settlement(&hash).await;
                 } else if msg.state == InvoiceState::Canceled {
                     // If the payment was canceled
-                    println!("Invoice with hash {hash} canceled!");
+                    info!("Invoice with hash {hash} canceled!");
                     flow::hold_invoice_canceled(&hash).await;
                 } else {
                     info!("Invoice with hash: {hash} subscribed!");
@@ -266,25 +644,56 @@ pub async fn show_hold_invoice(
             }
         }
     };
-    tokio::spawn(subs);
+    crate::shutdown::spawn_stream(invoice_task, subs);
+}
+
+/// Hold invoices are followed again after a restart, this way Mostro also
+/// finds out about escrows canceled while it was down
+pub async fn resubscribe_hold_invoices(pool: &SqlitePool) -> Result<()> {
+    for order in db::find_orders_in_escrow(pool).await? {
+        if let Some(hash) = order.hash.as_ref() {
+            subscribe_hold_invoice(Vec::<u8>::from_hex(hash)?).await;
+        }
+    }
 
     Ok(())
 }
 
-pub async fn set_market_order_sats_amount(
+/// Quote at market price the side of the order the maker left open, the
+/// buyer gets the new amounts and the taker how they were converted before
+/// the hold invoice is created
+pub async fn set_market_order_amounts(
+    pool: &SqlitePool,
     order: &mut Order,
     buyer_pubkey: XOnlyPublicKey,
+    taker_pubkey: XOnlyPublicKey,
+    action: Action,
     my_keys: &Keys,
-    pool: &SqlitePool,
     client: &Client,
-) -> Result<i64> {
-    // Update amount order
-    let new_sats_amout =
-        get_market_quote(&order.fiat_amount, &order.fiat_code, &order.premium).await?;
+) -> Result<()> {
+    let denomination = match Denomination::from_amounts(order.amount, order.fiat_amount) {
+        Some(denomination) => denomination,
+        None => return Ok(()),
+    };
+    let mut conversion = None;
+    match denomination {
+        // Update order with new sats value, it is saved with the next status update
+        Denomination::Fiat => {
+            let quote =
+                get_market_quote(&order.fiat_amount, &order.fiat_code, &order.premium).await?;
+            order.amount = quote.sats;
+            conversion = Some(quote);
+        }
+        Denomination::Sats => {
+            let btc_price = price::get_btc_price(&order.fiat_code).await?;
+            order.fiat_amount = fiat_from_sats(order.amount, btc_price, order.premium);
+            db::edit_order_fiat_amount(pool, order.id, order.fiat_amount).await?;
+        }
+    }
     // We send this data related to the order to the parties
     let order_data = SmallOrder::new(
         order.id,
-        new_sats_amout,
+        order.amount,
         order.fiat_code.clone(),
         order.fiat_amount,
         order.payment_method.clone(),
@@ -296,24 +705,59 @@ pub async fn set_market_order_sats_amount(
     let message = Message::new(
         0,
         Some(order.id),
-        Action::TakeSell,
+        action.clone(),
         Some(Content::SmallOrder(order_data)),
     );
     let message = message.as_json()?;
 
     send_dm(client, my_keys, &buyer_pubkey, message).await?;
 
-    // Update order with new sats value
-    order.amount = new_sats_amout;
-    update_order_event(
-        pool,
-        client,
-        my_keys,
-        Status::WaitingBuyerInvoice,
-        order,
-        None,
-    )
-    .await?;
+    // Both parties are told how the amount was converted
+    let maker_pubkey = XOnlyPublicKey::from_bech32(&order.creator_pubkey)?;
+    for pubkey in [taker_pubkey, maker_pubkey] {
+        let lang = get_user_lang(pool, &pubkey).await;
+        let mut text_message = messages::market_quote(
+            lang,
+            denomination,
+            order.amount,
+            &order.fiat_code,
+            order.fiat_amount,
+        );
+        if let Some(conversion) = conversion.as_ref() {
+            let derivation = messages::sats_conversion(
+                lang,
+                order.fiat_amount,
+                &order.fiat_code,
+                order.premium,
+                conversion,
+            );
+            text_message = format!("{text_message}. {derivation}");
+        }
+        let message = Message::new(
+            0,
+            Some(order.id),
+            action.clone(),
+            Some(Content::TextMessage(text_message)),
+        );
+        let message = message.as_json()?;
+        send_dm(client, my_keys, &pubkey, message).await?;
+    }
 
-    Ok(order.amount)
+    Ok(())
+}
+
+/// Amounts quoted at market price when the order was taken are cleared
+/// before it goes back to the book, the next taker gets a new quote
+pub async fn reset_market_amounts(pool: &SqlitePool, order: &mut Order) -> Result<()> {
+    if order.price_from_api {
+        order.amount = 0;
+        order.fee = 0;
+    }
+    let extras = db::find_order_extras(pool, order.id).await?;
+    if extras.denomination.as_deref().map(Denomination::from_str) == Some(Ok(Denomination::Sats)) {
+        order.fiat_amount = 0;
+        db::edit_order_fiat_amount(pool, order.id, 0).await?;
+    }
+
+    Ok(())
 }
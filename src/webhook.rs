@@ -0,0 +1,158 @@
+use crate::settings::var;
+use nostr_sdk::nostr::hashes::hex::ToHex;
+use nostr_sdk::nostr::hashes::hmac::{Hmac, HmacEngine};
+use nostr_sdk::nostr::hashes::{sha256, Hash, HashEngine};
+use nostr_sdk::prelude::Timestamp;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Seconds a webhook has to answer an attempt
+const WEBHOOK_TIMEOUT: u64 = 10;
+
+/// Events the operators can be told about with a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    DisputeOpened,
+    PayoutFailed,
+    LndDisconnected,
+    DatabaseError,
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DisputeOpened => write!(f, "dispute_opened"),
+            Self::PayoutFailed => write!(f, "payout_failed"),
+            Self::LndDisconnected => write!(f, "lnd_disconnected"),
+            Self::DatabaseError => write!(f, "database_error"),
+        }
+    }
+}
+
+/// URLs called on every event set on WEBHOOK_URLS, separated by commas
+pub fn get_webhook_urls() -> Vec<String> {
+    var("WEBHOOK_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim())
+        .filter(|url| !url.is_empty())
+        .map(|url| url.to_string())
+        .collect()
+}
+
+/// Attempts to deliver an event to a URL set on WEBHOOK_MAX_ATTEMPTS, 5 by
+/// default, waiting twice as long after every failed one
+fn get_max_attempts() -> u32 {
+    var("WEBHOOK_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|attempts| attempts.parse::<u32>().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(5)
+}
+
+/// Hex HMAC-SHA256 of the body with WEBHOOK_SECRET, receivers check it to
+/// know the call came from this Mostro
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+
+    Hmac::<sha256::Hash>::from_engine(engine).to_hex()
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    url: String,
+    event: WebhookEvent,
+    body: String,
+    signature: Option<String>,
+) {
+    let attempts = get_max_attempts();
+    for attempt in 1..=attempts {
+        let mut request = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-Mostro-Event", event.to_string())
+            .body(body.clone());
+        if let Some(signature) = signature.as_ref() {
+            request = request.header("X-Mostro-Signature", format!("sha256={signature}"));
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => warn!(
+                "Webhook {url} answered {} to {event}, attempt {attempt} of {attempts}",
+                response.status()
+            ),
+            Err(e) => {
+                warn!("Webhook {url} failed for {event}: {e}, attempt {attempt} of {attempts}")
+            }
+        }
+        if attempt < attempts {
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(10))).await;
+        }
+    }
+    error!("Webhook {url} couldn't be told about {event}");
+}
+
+/// Call every webhook in the background with the event and its data as
+/// JSON, signed when WEBHOOK_SECRET is set
+pub fn notify(event: WebhookEvent, data: Value) {
+    let urls = get_webhook_urls();
+    if urls.is_empty() {
+        return;
+    }
+    let body = json!({
+        "event": event.to_string(),
+        "created_at": Timestamp::now().as_i64(),
+        "data": data,
+    })
+    .to_string();
+    let signature = var("WEBHOOK_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .map(|secret| sign(&secret, body.as_bytes()));
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Webhooks couldn't be called: {e}");
+            return;
+        }
+    };
+    for url in urls {
+        tokio::spawn(deliver(
+            client.clone(),
+            url,
+            event,
+            body.clone(),
+            signature.clone(),
+        ));
+    }
+}
+
+/// Tell the webhooks about an error of the database, other errors are left
+/// to the logs
+pub fn notify_database_error(context: &str, error: &anyhow::Error) {
+    if error.downcast_ref::<sqlx::Error>().is_some() {
+        notify(
+            WebhookEvent::DatabaseError,
+            json!({ "context": context, "error": error.to_string() }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sign;
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8",
+            sign("key", b"The quick brown fox jumps over the lazy dog")
+        );
+    }
+}